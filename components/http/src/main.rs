@@ -14,10 +14,12 @@
 // limitations under the License.
 
 use clap::Parser;
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use dynamo_llm::{
     http::service::{
+        auth::api_key_watcher,
+        config::HttpServiceSettings,
         discovery::{model_watcher, ModelWatchState},
         service_v2::HttpService,
     },
@@ -30,13 +32,21 @@ use dynamo_runtime::{
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Host for the HTTP service
-    #[arg(long, default_value = "0.0.0.0")]
-    host: String,
+    /// Host for the HTTP service; overrides the config file
+    #[arg(long)]
+    host: Option<String>,
 
-    /// Port number for the HTTP service
-    #[arg(short, long, default_value = "8080")]
-    port: u16,
+    /// Port number for the HTTP service; overrides the config file [default: 8080]
+    #[arg(short, long)]
+    port: Option<u16>,
+
+    /// TOML or YAML config file for the HTTP service; reloaded when it changes
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Print the effective configuration as TOML and exit
+    #[arg(long)]
+    print_config: bool,
 
     /// Namespace for the distributed component
     #[arg(long, default_value = "public")]
@@ -49,20 +59,40 @@ struct Args {
 
 fn main() -> Result<()> {
     logging::init();
+
+    let args = Args::parse();
+    if args.print_config {
+        print!("{}", toml::to_string_pretty(&settings(&args)?.redacted())?);
+        return Ok(());
+    }
+
     let worker = Worker::from_settings()?;
-    worker.execute(app)
+    worker.execute(|runtime| app(runtime, args))
+}
+
+/// Effective settings: defaults, then the config file and `DYN_HTTP_` environment, then the CLI
+fn settings(args: &Args) -> Result<HttpServiceSettings> {
+    let mut settings = HttpServiceSettings::load(args.config.as_deref())?;
+
+    if let Some(host) = &args.host {
+        settings.listener.host = host.clone();
+    }
+    if let Some(port) = args.port {
+        settings.listener.port = port;
+    }
+    Ok(settings)
 }
 
-async fn app(runtime: Runtime) -> Result<()> {
+async fn app(runtime: Runtime, args: Args) -> Result<()> {
     let distributed = DistributedRuntime::from_settings(runtime.clone()).await?;
-    let args = Args::parse();
 
-    let http_service = HttpService::builder()
-        .port(args.port)
-        .host(args.host)
-        .build()?;
+    let http_service = HttpService::from_settings(settings(&args)?)?;
     let manager = http_service.model_manager().clone();
 
+    if let Some(path) = &args.config {
+        http_service.watch_config(path.clone(), runtime.child_token());
+    }
+
     // todo - use the IntoComponent trait to register the component
     // todo - start a service
     // todo - we want the service to create an entry and register component definition
@@ -82,12 +112,13 @@ async fn app(runtime: Runtime) -> Result<()> {
     for model_type in ModelType::all() {
         let etcd_path = format!("{}/models/{}/", etcd_root, model_type.as_str());
 
-        let state = Arc::new(ModelWatchState {
-            prefix: etcd_path.clone(),
+        let state = Arc::new(ModelWatchState::new(
+            etcd_path.clone(),
             model_type,
-            manager: manager.clone(),
-            drt: distributed.clone(),
-        });
+            manager.clone(),
+            distributed.clone(),
+        ));
+        http_service.add_model_watcher(state.clone());
 
         let etcd_client = distributed.etcd_client();
         let models_watcher: PrefixWatcher = etcd_client.kv_get_and_watch_prefix(etcd_path).await?;
@@ -97,6 +128,17 @@ async fn app(runtime: Runtime) -> Result<()> {
         watcher_tasks.push(watcher_task);
     }
 
+    // API keys may also be managed in etcd, one JSON `ApiKeyConfig` per key
+    if let Some(api_keys) = http_service.api_keys() {
+        let etcd_path = format!("{}/api_keys/", etcd_root);
+        let keys_watcher = distributed
+            .etcd_client()
+            .kv_get_and_watch_prefix(etcd_path)
+            .await?;
+        let (_prefix, _watcher, receiver) = keys_watcher.dissolve();
+        watcher_tasks.push(tokio::spawn(api_key_watcher(api_keys.clone(), receiver)));
+    }
+
     // Run the service
     http_service.run(runtime.child_token()).await
 }
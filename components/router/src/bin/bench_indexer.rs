@@ -0,0 +1,291 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Benchmarks the single and the sharded KV indexers under a synthetic workload.
+//!
+//! Each request is one of a few shared prefixes followed by a random suffix. The indexer is
+//! first populated with the blocks of requests stored on random workers, then matched against
+//! new requests while the workers keep storing blocks.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use clap::Parser;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use dynamo_llm::kv_router::{
+    indexer::{compute_sequence_hashes, KvIndexerInterface, RouterEvent},
+    protocols::{
+        KvCacheEvent, KvCacheEventData, KvCacheStoreData, KvCacheStoredBlockData, LocalBlockHash,
+    },
+    KvIndexerKind, KvRouterConfig,
+};
+use dynamo_runtime::{logging, raise, CancellationToken, Result};
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Shard counts of the sharded indexers to compare with the single indexer
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "2,4,8",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    shards: Vec<u64>,
+
+    /// Number of workers storing blocks
+    #[arg(long, default_value_t = 64)]
+    num_workers: i64,
+
+    /// Number of shared prefixes
+    #[arg(long, default_value_t = 16)]
+    num_prefixes: usize,
+
+    /// Blocks in each shared prefix
+    #[arg(long, default_value_t = 16)]
+    prefix_blocks: usize,
+
+    /// Blocks in the random suffix of each request
+    #[arg(long, default_value_t = 32)]
+    suffix_blocks: usize,
+
+    /// Requests stored before matching
+    #[arg(long, default_value_t = 20_000)]
+    populate_requests: usize,
+
+    /// Requests matched
+    #[arg(long, default_value_t = 20_000)]
+    match_requests: usize,
+
+    /// Concurrent matching tasks
+    #[arg(long, default_value_t = 16)]
+    concurrency: usize,
+
+    /// Seed of the workload
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+struct Workload {
+    prefixes: Vec<Vec<LocalBlockHash>>,
+    suffix_blocks: usize,
+    num_workers: i64,
+    rng: StdRng,
+}
+
+impl Workload {
+    fn new(args: &Args) -> Self {
+        let mut rng = StdRng::seed_from_u64(args.seed);
+        let prefixes = (0..args.num_prefixes)
+            .map(|_| Self::random_blocks(&mut rng, args.prefix_blocks))
+            .collect();
+        Self {
+            prefixes,
+            suffix_blocks: args.suffix_blocks,
+            num_workers: args.num_workers,
+            rng,
+        }
+    }
+
+    /// A workload with the same prefixes and workers, and different suffixes
+    fn fork(&mut self) -> Self {
+        Self {
+            prefixes: self.prefixes.clone(),
+            suffix_blocks: self.suffix_blocks,
+            num_workers: self.num_workers,
+            rng: StdRng::seed_from_u64(self.rng.random()),
+        }
+    }
+
+    fn random_blocks(rng: &mut StdRng, len: usize) -> Vec<LocalBlockHash> {
+        (0..len).map(|_| LocalBlockHash(rng.random())).collect()
+    }
+
+    fn request(&mut self) -> Vec<LocalBlockHash> {
+        let prefix = &self.prefixes[self.rng.random_range(0..self.prefixes.len())];
+        let mut sequence = prefix.clone();
+        sequence.extend(Self::random_blocks(&mut self.rng, self.suffix_blocks));
+        sequence
+    }
+
+    fn stored_event(&mut self, event_id: u64) -> RouterEvent {
+        let worker_id = self.rng.random_range(0..self.num_workers);
+        let local_hashes = self.request();
+        let blocks = local_hashes
+            .iter()
+            .zip(compute_sequence_hashes(&local_hashes))
+            .map(|(tokens_hash, block_hash)| KvCacheStoredBlockData {
+                block_hash,
+                tokens_hash: *tokens_hash,
+            })
+            .collect();
+        RouterEvent::new(
+            worker_id,
+            KvCacheEvent {
+                event_id,
+                data: KvCacheEventData::Stored(KvCacheStoreData {
+                    parent_hash: None,
+                    blocks,
+                }),
+            },
+        )
+    }
+}
+
+struct Report {
+    name: String,
+    populate: Duration,
+    populate_requests: usize,
+    match_latencies: Vec<Duration>,
+    match_elapsed: Duration,
+    num_blocks: usize,
+}
+
+impl Report {
+    fn percentile(&self, p: f64) -> Duration {
+        let index = (self.match_latencies.len().saturating_sub(1) as f64 * p).round() as usize;
+        self.match_latencies.get(index).copied().unwrap_or_default()
+    }
+
+    fn print(&self) {
+        println!("== {}", self.name);
+        println!(
+            "populate: {} requests accepted in {:.2?} ({:.0} requests/s)",
+            self.populate_requests,
+            self.populate,
+            self.populate_requests as f64 / self.populate.as_secs_f64()
+        );
+        println!(
+            "match: {} requests in {:.2?} ({:.0} requests/s), p50 {:.2?}, p99 {:.2?}, max {:.2?}",
+            self.match_latencies.len(),
+            self.match_elapsed,
+            self.match_latencies.len() as f64 / self.match_elapsed.as_secs_f64(),
+            self.percentile(0.5),
+            self.percentile(0.99),
+            self.percentile(1.0),
+        );
+        println!("blocks indexed: {}\n", self.num_blocks);
+    }
+}
+
+async fn bench(args: &Args, name: String, kind: KvIndexerKind) -> Result<Report> {
+    let token = CancellationToken::new();
+    let config = KvRouterConfig {
+        indexer: kind,
+        ..Default::default()
+    };
+    // the workload does not depend on the block size
+    let mut indexer = config.build_indexer(token.clone(), 1);
+    let mut workload = Workload::new(args);
+
+    let start = Instant::now();
+    for event_id in 0..args.populate_requests {
+        indexer
+            .apply_event(workload.stored_event(event_id as u64))
+            .await;
+    }
+    // the event channels are bounded, so this is close to the rate the indexer applies them
+    let populate = start.elapsed();
+
+    let indexer: Arc<dyn KvIndexerInterface + Send + Sync> = Arc::from(indexer);
+    let event_tx = indexer.event_sender();
+    let mut background_workload = workload.fork();
+    let stop = CancellationToken::new();
+    let background = tokio::spawn({
+        let stop = stop.clone();
+        let first_event_id = args.populate_requests as u64;
+        async move {
+            for event_id in first_event_id.. {
+                let event = background_workload.stored_event(event_id);
+                tokio::select! {
+                    _ = stop.cancelled() => break,
+                    sent = event_tx.send(event) => if sent.is_err() { break },
+                }
+            }
+        }
+    });
+
+    let requests_per_task = args.match_requests.div_ceil(args.concurrency);
+    let start = Instant::now();
+    let tasks: Vec<_> = (0..args.concurrency)
+        .map(|task| {
+            let indexer = indexer.clone();
+            let sequences: Vec<_> = (0..requests_per_task).map(|_| workload.request()).collect();
+            tokio::spawn(async move {
+                let mut latencies = Vec::with_capacity(sequences.len());
+                for sequence in sequences {
+                    let request_start = Instant::now();
+                    if let Err(e) = indexer.find_matches(sequence).await {
+                        tracing::warn!(task, "find_matches failed: {e}");
+                        continue;
+                    }
+                    latencies.push(request_start.elapsed());
+                }
+                latencies
+            })
+        })
+        .collect();
+
+    let mut match_latencies = Vec::with_capacity(args.match_requests);
+    for task in tasks {
+        match_latencies.extend(task.await?);
+    }
+    let match_elapsed = start.elapsed();
+    match_latencies.sort();
+
+    stop.cancel();
+    background.await?;
+    let num_blocks = indexer.stats().await?.num_blocks;
+    token.cancel();
+
+    Ok(Report {
+        name,
+        populate,
+        populate_requests: args.populate_requests,
+        match_latencies,
+        match_elapsed,
+        num_blocks,
+    })
+}
+
+fn main() -> Result<()> {
+    logging::init();
+    let args = Args::parse();
+    if args.concurrency == 0 || args.match_requests == 0 || args.num_prefixes == 0 {
+        raise!("--concurrency, --match-requests and --num-prefixes must be positive");
+    }
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+
+    let mut runs = vec![("single".to_string(), KvIndexerKind::Single)];
+    for &num_shards in &args.shards {
+        runs.push((
+            format!("sharded ({num_shards} shards)"),
+            KvIndexerKind::Sharded {
+                num_shards: num_shards as usize,
+            },
+        ));
+    }
+
+    for (name, kind) in runs {
+        runtime.block_on(bench(&args, name, kind))?.print();
+    }
+    Ok(())
+}
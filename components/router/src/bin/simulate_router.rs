@@ -0,0 +1,144 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Replays a recorded request trace against simulated workers and reports, for each selector,
+//! the prefix hit rate, the time to first token and the load imbalance.
+//!
+//! The trace holds one json request per line:
+//! `{"timestamp": <ms>, "token_ids": [...], "output_length": <tokens>}`
+
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+
+use dynamo_llm::kv_router::{
+    scheduler::DefaultWorkerSelector,
+    simulator::{
+        load_trace, LeastLoadedWorkerSelector, RoundRobinWorkerSelector, SimulatedWorkerConfig,
+        Simulation, SimulationConfig,
+    },
+    WorkerSelector,
+};
+use dynamo_runtime::{logging, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Selector {
+    /// The kv aware selector of the router
+    Default,
+    /// Send the requests to the workers in turn
+    RoundRobin,
+    /// Send each request to the worker with the fewest requests
+    LeastLoaded,
+}
+
+impl Selector {
+    fn build(self) -> Box<dyn WorkerSelector + Send + Sync> {
+        match self {
+            Selector::Default => Box::new(DefaultWorkerSelector),
+            Selector::RoundRobin => Box::new(RoundRobinWorkerSelector::default()),
+            Selector::LeastLoaded => Box::new(LeastLoadedWorkerSelector),
+        }
+    }
+}
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Trace to replay, one json request per line
+    trace: PathBuf,
+
+    /// Selectors to compare
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "default,round-robin,least-loaded"
+    )]
+    selectors: Vec<Selector>,
+
+    /// Block size of the kv cache
+    #[arg(long, default_value_t = 64, value_parser = clap::value_parser!(u64).range(1..))]
+    block_size: u64,
+
+    /// Number of simulated workers
+    #[arg(long, default_value_t = 4)]
+    num_workers: usize,
+
+    /// Number of kv blocks per worker
+    #[arg(
+        long,
+        default_value_t = SimulatedWorkerConfig::default().kv_total_blocks,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    kv_total_blocks: u64,
+
+    /// Number of requests run concurrently per worker
+    #[arg(long, default_value_t = SimulatedWorkerConfig::default().request_total_slots)]
+    request_total_slots: u64,
+
+    /// Prefill time of an uncached token, in milliseconds
+    #[arg(long, default_value_t = SimulatedWorkerConfig::default().prefill_ms_per_token)]
+    prefill_ms_per_token: f64,
+
+    /// Decode time of an output token, in milliseconds
+    #[arg(long, default_value_t = SimulatedWorkerConfig::default().decode_ms_per_token)]
+    decode_ms_per_token: f64,
+
+    /// Print the reports as json
+    #[arg(long)]
+    json: bool,
+}
+
+fn main() -> Result<()> {
+    logging::init();
+    let args = Args::parse();
+
+    let trace = load_trace(&args.trace)?;
+    let simulation = Simulation::new(SimulationConfig {
+        block_size: args.block_size as usize,
+        num_workers: args.num_workers,
+        worker: SimulatedWorkerConfig {
+            kv_total_blocks: args.kv_total_blocks,
+            request_total_slots: args.request_total_slots,
+            prefill_ms_per_token: args.prefill_ms_per_token,
+            decode_ms_per_token: args.decode_ms_per_token,
+        },
+    });
+
+    // the radix tree of a simulation is not Send, so it runs on the current thread
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    let mut reports = serde_json::Map::new();
+    for selector in args.selectors {
+        let name = selector
+            .to_possible_value()
+            .expect("selectors have a name")
+            .get_name()
+            .to_string();
+        let report = runtime.block_on(simulation.run(&trace, selector.build()))?;
+        if args.json {
+            reports.insert(name, serde_json::to_value(&report)?);
+        } else {
+            println!("== {name}\n{report}\n");
+        }
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    }
+    Ok(())
+}
@@ -20,13 +20,16 @@
 // 2. Update the backend component to produce a config in a standard location.
 // 3. Update the KvRouter to read the config from the backend component.
 
+use std::{num::NonZeroUsize, time::Duration};
+
 use clap::Parser;
 
 use dynamo_llm::kv_router::{
+    approx::ApproxIndexingConfig,
     protocols::WorkerSelectionResult,
     scheduler::{DefaultWorkerSelector, KvSchedulerError, SchedulingRequest},
     scoring::ProcessedEndpoints,
-    KvRouter, WorkerSelector,
+    KvIndexerKind, KvRouter, KvRouterConfig, WorkerSelector,
 };
 use dynamo_runtime::{
     logging, pipeline::network::Ingress, DistributedRuntime, Result, Runtime, Worker,
@@ -46,6 +49,30 @@ struct Args {
     /// Block size for the router
     #[arg(long)]
     block_size: usize,
+
+    /// Assume each worker caches the prompts routed to it, for engines which do not publish
+    /// KV cache events
+    #[arg(long)]
+    approximate: bool,
+
+    /// In approximate mode, seconds after which a block no request was routed to is assumed
+    /// evicted
+    #[arg(long, default_value_t = 120, value_parser = clap::value_parser!(u64).range(1..))]
+    approximate_ttl_secs: u64,
+
+    /// In approximate mode, number of blocks each worker is assumed to cache; defaults to the
+    /// KV cache size the workers report
+    #[arg(long)]
+    approximate_max_blocks: Option<u64>,
+
+    /// Split the KV index into this many shards, each holding a subset of the workers and
+    /// matched on its own thread; a single index is used when unset
+    #[arg(long)]
+    indexer_shards: Option<NonZeroUsize>,
+
+    /// Track how often the cached blocks were matched within this many milliseconds
+    #[arg(long)]
+    frequency_expiration_ms: Option<u64>,
 }
 
 fn main() -> Result<()> {
@@ -64,18 +91,34 @@ async fn app(runtime: Runtime) -> Result<()> {
 
     let selector = Box::new(CustomWorkerSelector::default());
 
-    let router = KvRouter::new(component.clone(), args.block_size, Some(selector)).await?;
-    let router = Ingress::for_engine(router)?;
+    let config = KvRouterConfig {
+        approximate: args.approximate.then(|| ApproxIndexingConfig {
+            ttl: Duration::from_secs(args.approximate_ttl_secs),
+            max_blocks_per_worker: args.approximate_max_blocks,
+        }),
+        indexer: match args.indexer_shards {
+            Some(num_shards) => KvIndexerKind::Sharded {
+                num_shards: num_shards.get(),
+            },
+            None => KvIndexerKind::Single,
+        },
+        frequency_expiration: args.frequency_expiration_ms.map(Duration::from_millis),
+    };
+
+    let router =
+        KvRouter::new_with_config(component.clone(), args.block_size, Some(selector), config)
+            .await?;
+    let component = component.service_builder().create().await?;
 
-    component
-        .service_builder()
-        .create()
-        .await?
+    let inspect = router.clone().create_inspect_endpoint(component.clone());
+    let generate = component
         .endpoint("generate")
         .endpoint_builder()
-        .handler(router)
-        .start()
-        .await
+        .handler(Ingress::for_engine(router)?)
+        .start();
+
+    tokio::try_join!(generate, inspect)?;
+    Ok(())
 }
 
 #[derive(Default)]
@@ -15,7 +15,6 @@
 
 
 import asyncio
-import os
 import sys
 
 from pydantic import BaseModel
@@ -99,28 +98,33 @@ class PrefillWorker:
 
     async def prefill_queue_handler(self):
         print("[DEBUG] prefill queue handler entered")
-        prefill_queue_nats_server = os.getenv("NATS_SERVER", "nats://localhost:4222")
         prefill_queue_stream_name = (
             self.engine_args.served_model_name
             if self.engine_args.served_model_name is not None
             else "vllm"
         )
-        print(f"Prefill queue: {prefill_queue_nats_server}:{prefill_queue_stream_name}")
+        print(f"Prefill queue: {prefill_queue_stream_name}")
         self.initialized = True
-        # TODO: integrate prefill_queue to a dynamo endpoint
-        async with PrefillQueue.get_instance(
-            nats_server=prefill_queue_nats_server,
-            stream_name=prefill_queue_stream_name,
-        ) as prefill_queue:
-            print("prefill queue handler started")
-            while True:
-                # TODO: this might add a small overhead to pull prefill from nats
-                # need to test and check how much overhead it is
-                prefill_request = await prefill_queue.dequeue_prefill_request()
-                if prefill_request is not None:
-                    print(f"Dequeued prefill request: {prefill_request.request_id}")
-                    async for _ in self.generate(prefill_request):
-                        pass
+        prefill_queue = PrefillQueue(
+            dynamo_context["runtime"], prefill_queue_stream_name
+        )
+        print("prefill queue handler started")
+        while True:
+            # TODO: this might add a small overhead to pull prefill from nats
+            # need to test and check how much overhead it is
+            dequeued = await prefill_queue.dequeue_prefill_request()
+            if dequeued is None:
+                continue
+            prefill_request, item = dequeued
+            print(f"Dequeued prefill request: {prefill_request.request_id}")
+            try:
+                async for _ in self.generate(prefill_request):
+                    pass
+            except Exception:
+                # another prefill worker retries the request
+                await item.nak()
+                raise
+            await item.ack()
 
     async def generate(self, request: RemotePrefillRequest):
         sampling_params = request.sampling_params
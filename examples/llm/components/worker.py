@@ -56,13 +56,8 @@ class VllmWorker:
             if self.engine_args.served_model_name is not None
             else "vllm"
         )
-        self._prefill_queue_nats_server = os.getenv(
-            "NATS_SERVER", "nats://localhost:4222"
-        )
         self._prefill_queue_stream_name = self.model_name
-        vllm_logger.info(
-            f"Prefill queue: {self._prefill_queue_nats_server}:{self._prefill_queue_stream_name}"
-        )
+        vllm_logger.info(f"Prefill queue: {self._prefill_queue_stream_name}")
 
         if self.engine_args.remote_prefill:
             if self.engine_args.enable_chunked_prefill is not False:
@@ -114,6 +109,7 @@ class VllmWorker:
             )
 
         runtime = dynamo_context["runtime"]
+        self.prefill_queue = PrefillQueue(runtime, self._prefill_queue_stream_name)
 
         if self.engine_args.remote_prefill:
             metadata = self.engine_client.nixl_metadata
@@ -136,13 +132,8 @@ class VllmWorker:
         await self.metrics_publisher.create_endpoint(component)
 
     def get_remote_prefill_request_callback(self):
-        # TODO: integrate prefill_queue to dynamo endpoint
         async def callback(request: RemotePrefillRequest):
-            async with PrefillQueue.get_instance(
-                nats_server=self._prefill_queue_nats_server,
-                stream_name=self._prefill_queue_stream_name,
-            ) as prefill_queue:
-                await prefill_queue.enqueue_prefill_request(request)
+            await self.prefill_queue.enqueue_prefill_request(request)
 
         return callback
 
@@ -151,11 +142,7 @@ class VllmWorker:
         # TODO: consider prefix hit when deciding prefill locally or remotely
 
         if self.disaggregated_router is not None:
-            async with PrefillQueue.get_instance(
-                nats_server=self._prefill_queue_nats_server,
-                stream_name=self._prefill_queue_stream_name,
-            ) as prefill_queue:
-                prefill_queue_size = await prefill_queue.get_queue_size()
+            prefill_queue_size = await self.prefill_queue.get_queue_size()
             disagg_router_decision = self.disaggregated_router.prefill_remote(
                 len(request.engine_prompt["prompt_token_ids"]),
                 request.prefix_hit_rate,
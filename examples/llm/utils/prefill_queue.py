@@ -14,43 +14,45 @@
 # limitations under the License.
 
 
-from typing import Optional
+from typing import Optional, Tuple
 
 import msgspec
-from utils.nats_queue import NATSQueue
 from vllm.remote_prefill import RemotePrefillRequest
 
+from dynamo.llm import PrefillQueue as _PrefillQueue
+from dynamo.llm import PrefillWorkItem
 
-class PrefillQueue(NATSQueue):
+
+class PrefillQueue:
     """
-    A wrapper of NATSQueue for PrefillRequest.
-    The stream name is forced to be "prefill_queue".
+    The durable queue of RemotePrefillRequests, shared by the decode and prefill
+    workers. A dequeued request is redelivered to another prefill worker unless it
+    is acknowledged.
     """
 
-    def __init__(
-        self,
-        stream_name="prefill_queue",
-        nats_server: str = "nats://localhost:4222",
-        dequeue_timeout: float = 1,
-    ):
-        super().__init__(
-            stream_name=stream_name,
-            nats_server=nats_server,
-            dequeue_timeout=dequeue_timeout,
-        )
+    def __init__(self, runtime, stream_name: str):
+        self._queue = _PrefillQueue(runtime, stream_name)
 
     async def enqueue_prefill_request(
         self, prefill_request: RemotePrefillRequest
     ) -> None:
         encoded_request = msgspec.json.encode(prefill_request)
-        await self.enqueue_task(encoded_request)
-
-    async def dequeue_prefill_request(self) -> Optional[RemotePrefillRequest]:
-        encoded_request = await self.dequeue_task()
-        if encoded_request is not None:
-            prefill_request = msgspec.json.decode(
-                encoded_request, type=RemotePrefillRequest
-            )
-            return prefill_request
-        else:
+        await self._queue.enqueue_prefill_request(
+            prefill_request.request_id, encoded_request
+        )
+
+    async def dequeue_prefill_request(
+        self,
+    ) -> Optional[Tuple[RemotePrefillRequest, PrefillWorkItem]]:
+        """
+        Returns the request with its work item, which must be acked once the prefill
+        is done
+        """
+        item = await self._queue.dequeue_prefill_request()
+        if item is None:
             return None
+        prefill_request = msgspec.json.decode(item.request, type=RemotePrefillRequest)
+        return prefill_request, item
+
+    async def get_queue_size(self) -> int:
+        return await self._queue.size()
@@ -16,6 +16,7 @@
 use dynamo_llm::{
     backend::Backend,
     http::service::discovery::ModelEntry,
+    model_card::model::TokenizerKind,
     model_type::ModelType,
     preprocessor::OpenAIPreprocessor,
     types::{
@@ -45,11 +46,11 @@ pub async fn run(
 
     let etcd_client = distributed.etcd_client();
 
-    let (ingress, service_name) = match engine_config {
+    let (ingress, service_name, card) = match engine_config {
         EngineConfig::StaticFull {
             service_name,
             engine,
-        } => (Ingress::for_engine(engine)?, service_name),
+        } => (Ingress::for_engine(engine)?, service_name, None),
         EngineConfig::StaticCore {
             service_name,
             engine: inner_engine,
@@ -73,7 +74,13 @@ pub async fn run(
                 .link(preprocessor.backward_edge())?
                 .link(frontend)?;
 
-            (Ingress::for_pipeline(pipeline)?, service_name)
+            // a tokenizer embedded from a GGUF file is too large to publish in etcd
+            let card = match card.tokenizer {
+                TokenizerKind::HfTokenizerJson(_) => Some(*card),
+                TokenizerKind::GGUF(_) => None,
+            };
+
+            (Ingress::for_pipeline(pipeline)?, service_name, card)
         }
         EngineConfig::Dynamic(_) => {
             anyhow::bail!("Cannot use endpoint for both in and out");
@@ -85,6 +92,8 @@ pub async fn run(
         name: service_name.to_string(),
         endpoint: endpoint_id.clone(),
         model_type: ModelType::Chat,
+        weight: 1,
+        card,
     };
 
     let component = distributed
@@ -105,6 +114,9 @@ pub async fn run(
         )
         .await?;
 
+    // finish in-flight requests when asked to shut down
+    distributed.enable_drain().await?;
+
     let rt_fut = endpoint.endpoint_builder().handler(ingress).start();
     tokio::select! {
         _ = rt_fut => {
@@ -56,12 +56,12 @@ pub async fn run(
             let network_prefix = component.service_name();
 
             // Listen for models registering themselves in etcd, add them to HTTP service
-            let state = Arc::new(discovery::ModelWatchState {
-                prefix: network_prefix.clone(),
-                model_type: ModelType::Chat,
-                manager: http_service.model_manager().clone(),
-                drt: distributed_runtime.clone(),
-            });
+            let state = Arc::new(discovery::ModelWatchState::new(
+                network_prefix.clone(),
+                ModelType::Chat,
+                http_service.model_manager().clone(),
+                distributed_runtime.clone(),
+            ));
             tracing::info!("Waiting for remote model at {network_prefix}");
             let etcd_client = distributed_runtime.etcd_client();
             let models_watcher = etcd_client.kv_get_and_watch_prefix(network_prefix).await?;
@@ -86,9 +86,11 @@ pub async fn run(
                 SingleIn<NvCreateChatCompletionRequest>,
                 ManyOut<Annotated<NvCreateChatCompletionStreamResponse>>,
             >::new();
-            let preprocessor = OpenAIPreprocessor::new(*card.clone())
-                .await?
-                .into_operator();
+            let preprocessor = OpenAIPreprocessor::new(*card.clone()).await?;
+            http_service
+                .model_manager()
+                .add_preprocessor(&service_name, preprocessor.clone());
+            let preprocessor = preprocessor.into_operator();
             let backend = Backend::from_mdc(*card.clone()).await?.into_operator();
             let engine = ServiceBackend::from_engine(inner_engine);
 
@@ -72,6 +72,7 @@ impl
                 let inner = deltas.create_choice(0, Some(c.to_string()), None, None);
                 let response = NvCreateChatCompletionStreamResponse {
                     inner,
+                    reasoning_content: Default::default(),
                 };
                 yield Annotated{ id: Some(id.to_string()), data: Some(response), event: None, comment: None };
                 id += 1;
@@ -80,6 +81,7 @@ impl
             let inner = deltas.create_choice(0, None, Some(async_openai::types::FinishReason::Stop), None);
             let response = NvCreateChatCompletionStreamResponse {
                 inner,
+                reasoning_content: Default::default(),
             };
             yield Annotated { id: Some(id.to_string()), data: Some(response), event: None, comment: None };
         };
@@ -16,10 +16,17 @@
 use clap::{Parser, Subcommand};
 use tracing as log;
 
-use dynamo_llm::{http::service::discovery::ModelEntry, model_type::ModelType};
+use dynamo_llm::{
+    http::service::discovery::{ModelAlias, ModelEntry, ModelRegistration},
+    kv_router::{
+        protocols::{RouterInspectRequest, RouterInspectResponse},
+        KV_INSPECT_ENDPOINT,
+    },
+    model_type::ModelType,
+};
 use dynamo_runtime::{
-    distributed::DistributedConfig, logging, protocols::Endpoint, raise, DistributedRuntime,
-    Result, Runtime, Worker,
+    distributed::DistributedConfig, logging, protocols::annotated::Annotated, protocols::Endpoint,
+    raise, stream::StreamExt, DistributedRuntime, Result, Runtime, Worker,
 };
 
 // Macro to define model types and associated commands
@@ -34,6 +41,24 @@ macro_rules! define_type_subcommands {
             )*
         }
 
+        #[derive(Subcommand)]
+        enum AliasCommands {
+            $(
+                #[doc = concat!("Alias a ", $primary_name, " model")]
+                #[command(name = $primary_name, aliases = [$($alias),*])]
+                $variant(AliasModelArgs),
+            )*
+        }
+
+        #[derive(Subcommand)]
+        enum SetWeightCommands {
+            $(
+                #[doc = concat!("Set the weight of a ", $primary_name, " model backend")]
+                #[command(name = $primary_name, aliases = [$($alias),*])]
+                $variant(SetWeightArgs),
+            )*
+        }
+
         #[derive(Subcommand)]
         enum ListCommands {
             $(
@@ -53,17 +78,33 @@ macro_rules! define_type_subcommands {
         }
 
         impl AddCommands {
-            fn into_parts(self) -> (ModelType, String, String) {
+            fn into_parts(self) -> (ModelType, AddModelArgs) {
+                match self {
+                    $(Self::$variant(args) => (ModelType::$variant, args)),*
+                }
+            }
+        }
+
+        impl AliasCommands {
+            fn into_parts(self) -> (ModelType, AliasModelArgs) {
                 match self {
-                    $(Self::$variant(args) => (ModelType::$variant, args.model_name, args.endpoint_name)),*
+                    $(Self::$variant(args) => (ModelType::$variant, args)),*
+                }
+            }
+        }
+
+        impl SetWeightCommands {
+            fn into_parts(self) -> (ModelType, SetWeightArgs) {
+                match self {
+                    $(Self::$variant(args) => (ModelType::$variant, args)),*
                 }
             }
         }
 
         impl RemoveCommands {
-            fn into_parts(self) -> (ModelType, String) {
+            fn into_parts(self) -> (ModelType, RemoveModelArgs) {
                 match self {
-                    $(Self::$variant(args) => (ModelType::$variant, args.model_name)),*
+                    $(Self::$variant(args) => (ModelType::$variant, args)),*
                 }
             }
         }
@@ -118,6 +159,51 @@ enum Commands {
         #[command(subcommand)]
         command: HttpCommands,
     },
+
+    /// Worker instance related commands
+    Worker {
+        #[command(subcommand)]
+        command: WorkerCommands,
+    },
+
+    /// KV aware router related commands
+    Router {
+        #[command(subcommand)]
+        command: RouterCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum RouterCommands {
+    /// Show what a KV aware router knows about its workers, without affecting routing
+    Inspect(InspectRouterArgs),
+}
+
+#[derive(Parser)]
+struct InspectRouterArgs {
+    /// Namespace of the router component
+    #[arg(name = "namespace")]
+    namespace: String,
+    /// Router component name
+    #[arg(long, default_value = "kv_aware_router")]
+    component: String,
+    /// Comma separated token ids of a prompt to show the overlap scores of
+    #[arg(long, value_delimiter = ',')]
+    tokens: Option<Vec<u32>>,
+    /// Print the full router state as json
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Subcommand)]
+enum WorkerCommands {
+    /// Gracefully drain a worker instance: stop routing new requests to it, let in-flight
+    /// requests finish, then shut it down
+    Drain {
+        /// Instance id (lease id in hex, as shown in the endpoint's etcd key)
+        #[arg(name = "instance-id")]
+        instance_id: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -134,11 +220,23 @@ enum HttpCommands {
         model_type: Option<ListCommands>,
     },
 
-    /// Remove models
+    /// Remove models, model backends or aliases
     Remove {
         #[command(subcommand)]
         model_type: RemoveCommands,
     },
+
+    /// Expose a model under an additional public name
+    Alias {
+        #[command(subcommand)]
+        model_type: AliasCommands,
+    },
+
+    /// Change the share of a model's traffic sent to one of its backends
+    SetWeight {
+        #[command(subcommand)]
+        model_type: SetWeightCommands,
+    },
 }
 
 #[derive(Parser)]
@@ -149,14 +247,45 @@ struct AddModelArgs {
     /// Endpoint name (format: component.endpoint or namespace.component.endpoint)
     #[arg(name = "endpoint-name")]
     endpoint_name: String,
+    /// Backend id; required to add a second backend, e.g. a canary, under the same model name
+    #[arg(long)]
+    backend: Option<String>,
+    /// Relative share of the model's traffic sent to this backend
+    #[arg(long, default_value_t = 1)]
+    weight: u32,
 }
 
 /// Common fields for removing any model type
 #[derive(Parser)]
 struct RemoveModelArgs {
-    /// Name of the model to remove
+    /// Name of the model or alias to remove
+    #[arg(name = "model-name")]
+    model_name: String,
+    /// Remove only this backend of the model
+    #[arg(long)]
+    backend: Option<String>,
+}
+
+#[derive(Parser)]
+struct AliasModelArgs {
+    /// Alias name (e.g. foo/latest)
+    #[arg(name = "alias-name")]
+    alias_name: String,
+    /// Name of the model the alias resolves to
+    #[arg(name = "target-name")]
+    target_name: String,
+}
+
+#[derive(Parser)]
+struct SetWeightArgs {
+    /// Model name
     #[arg(name = "model-name")]
     model_name: String,
+    /// New weight; 0 stops sending traffic to the backend without removing it
+    weight: u32,
+    /// Backend id, if the backend was added with one
+    #[arg(long)]
+    backend: Option<String>,
 }
 
 fn main() -> Result<()> {
@@ -178,15 +307,8 @@ async fn handle_command(runtime: Runtime, namespace: String, command: Commands)
         Commands::Http { command } => {
             match command {
                 HttpCommands::Add { model_type } => {
-                    let (model_type, model_name, endpoint_name) = model_type.into_parts();
-                    add_model(
-                        &distributed,
-                        namespace.to_string(),
-                        model_type,
-                        model_name,
-                        &endpoint_name,
-                    )
-                    .await?;
+                    let (model_type, args) = model_type.into_parts();
+                    add_model(&distributed, namespace.to_string(), model_type, args).await?;
                 }
                 HttpCommands::List { model_type } => {
                     match model_type {
@@ -205,23 +327,225 @@ async fn handle_command(runtime: Runtime, namespace: String, command: Commands)
                     }
                 }
                 HttpCommands::Remove { model_type } => {
-                    let (model_type, name) = model_type.into_parts();
-                    remove_model(&distributed, namespace.to_string(), model_type, &name).await?;
+                    let (model_type, args) = model_type.into_parts();
+                    remove_model(&distributed, namespace.to_string(), model_type, args).await?;
+                }
+                HttpCommands::Alias { model_type } => {
+                    let (model_type, args) = model_type.into_parts();
+                    add_alias(&distributed, namespace.to_string(), model_type, args).await?;
+                }
+                HttpCommands::SetWeight { model_type } => {
+                    let (model_type, args) = model_type.into_parts();
+                    set_weight(&distributed, namespace.to_string(), model_type, args).await?;
                 }
             }
         }
+        Commands::Worker { command } => match command {
+            WorkerCommands::Drain { instance_id } => {
+                drain_worker(&distributed, &instance_id).await?;
+            }
+        },
+        Commands::Router { command } => match command {
+            RouterCommands::Inspect(args) => {
+                inspect_router(&distributed, args).await?;
+            }
+        },
     }
     Ok(())
 }
 
+#[derive(tabled::Tabled)]
+struct RouterWorkerRow {
+    #[tabled(rename = "WORKER")]
+    worker_id: String,
+    #[tabled(rename = "CACHED BLOCKS")]
+    cached_blocks: usize,
+    #[tabled(rename = "ACTIVE BLOCKS")]
+    active_blocks: String,
+    #[tabled(rename = "ACTIVE SLOTS")]
+    active_slots: String,
+    #[tabled(rename = "WAITING")]
+    waiting: String,
+    #[tabled(rename = "OVERLAP")]
+    overlap: String,
+}
+
+#[derive(tabled::Tabled)]
+struct RouterDecisionRow {
+    #[tabled(rename = "TIME")]
+    timestamp: String,
+    #[tabled(rename = "ISL")]
+    isl_tokens: usize,
+    #[tabled(rename = "WORKER")]
+    worker_id: String,
+    #[tabled(rename = "OVERLAP")]
+    overlap: String,
+    #[tabled(rename = "LOGITS")]
+    logits: String,
+}
+
+async fn inspect_router(distributed: &DistributedRuntime, args: InspectRouterArgs) -> Result<()> {
+    let client = distributed
+        .namespace(&args.namespace)?
+        .component(&args.component)?
+        .endpoint(KV_INSPECT_ENDPOINT)
+        .client::<RouterInspectRequest, Annotated<RouterInspectResponse>>()
+        .await?;
+    client.wait_for_endpoints().await?;
+
+    let request = RouterInspectRequest {
+        tokens: args.tokens,
+    };
+    let mut stream = client.random(request.into()).await?;
+    let Some(response) = stream.next().await else {
+        raise!("Router {} returned no response", args.component);
+    };
+    let Some(state) = response.data else {
+        raise!(
+            "Router {} failed to report its state: {:?}",
+            args.component,
+            response
+        );
+    };
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&state)?);
+        return Ok(());
+    }
+
+    println!(
+        "Block size: {}, indexed blocks: {}, max depth: {}",
+        state.block_size, state.tree.num_blocks, state.tree.max_depth
+    );
+
+    let or_dash = |value: Option<u64>| value.map_or("-".to_string(), |v| v.to_string());
+    let workers: Vec<RouterWorkerRow> =
+        state
+            .workers
+            .iter()
+            .map(|worker| RouterWorkerRow {
+                worker_id: format!("{:x}", worker.worker_id),
+                cached_blocks: worker.cached_blocks,
+                active_blocks: match &worker.metrics {
+                    Some(m) => format!("{}/{}", m.kv_active_blocks, m.kv_total_blocks),
+                    None => "-".to_string(),
+                },
+                active_slots: match &worker.metrics {
+                    Some(m) => format!("{}/{}", m.request_active_slots, m.request_total_slots),
+                    None => "-".to_string(),
+                },
+                waiting: or_dash(worker.metrics.as_ref().map(|m| m.num_requests_waiting)),
+                overlap: or_dash(state.overlap.as_ref().map(|overlap| {
+                    overlap.scores.get(&worker.worker_id).copied().unwrap_or(0) as u64
+                })),
+            })
+            .collect();
+    if workers.is_empty() {
+        println!("No workers known to the router");
+    } else {
+        println!("{}", tabled::Table::new(workers));
+    }
+
+    let decisions: Vec<RouterDecisionRow> = state
+        .recent_decisions
+        .iter()
+        .map(|decision| {
+            let mut logits: Vec<_> = decision.logits.iter().collect();
+            logits.sort_by_key(|(worker_id, _)| **worker_id);
+            RouterDecisionRow {
+                timestamp: decision.timestamp.format("%H:%M:%S%.3f").to_string(),
+                isl_tokens: decision.isl_tokens,
+                worker_id: format!("{:x}", decision.worker_id),
+                overlap: format!("{}/{}", decision.overlap_blocks, decision.required_blocks),
+                logits: logits
+                    .iter()
+                    .map(|(worker_id, logit)| format!("{worker_id:x}={logit:.3}"))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            }
+        })
+        .collect();
+    if !decisions.is_empty() {
+        println!("Recent decisions:");
+        println!("{}", tabled::Table::new(decisions));
+    }
+    Ok(())
+}
+
+async fn drain_worker(distributed: &DistributedRuntime, instance_id: &str) -> Result<()> {
+    let Ok(id) = i64::from_str_radix(instance_id.trim_start_matches("0x"), 16) else {
+        raise!("Instance id '{}' is not a valid hex lease id", instance_id);
+    };
+
+    let response = distributed.request_drain(id).await?;
+    println!(
+        "Draining instance {:x}; {} request(s) in flight",
+        response.instance_id, response.inflight
+    );
+    Ok(())
+}
+
+/// etcd key of a model registration; additional backends of a model are stored as
+/// `{model_name}@{backend}`
+fn model_key(
+    distributed: &DistributedRuntime,
+    namespace: &str,
+    model_type: ModelType,
+    model_name: &str,
+    backend: Option<&str>,
+) -> Result<String> {
+    let component = distributed.namespace(namespace)?.component("http")?;
+    let mut key = format!(
+        "{}/models/{}/{}",
+        component.etcd_path(),
+        model_type.as_str(),
+        model_name
+    );
+    if let Some(backend) = backend {
+        key.push('@');
+        key.push_str(backend);
+    }
+    Ok(key)
+}
+
+/// Registrations of the given model type as `(etcd key, registration)` pairs
+async fn get_registrations(
+    distributed: &DistributedRuntime,
+    namespace: &str,
+    model_type: ModelType,
+) -> Result<Vec<(String, ModelRegistration)>> {
+    let component = distributed.namespace(namespace)?.component("http")?;
+    let prefix = format!("{}/models/{}/", component.etcd_path(), model_type.as_str());
+
+    let kvs = distributed.etcd_client().kv_get_prefix(&prefix).await?;
+    Ok(kvs
+        .iter()
+        .filter_map(|kv| {
+            match (
+                kv.key_str(),
+                serde_json::from_slice::<ModelRegistration>(kv.value()),
+            ) {
+                (Ok(key), Ok(registration)) => Some((key.to_string(), registration)),
+                _ => None,
+            }
+        })
+        .collect())
+}
+
 // Helper functions to handle the actual operations
 async fn add_model(
     distributed: &DistributedRuntime,
     namespace: String,
     model_type: ModelType,
-    model_name: String,
-    endpoint_name: &str,
+    args: AddModelArgs,
 ) -> Result<()> {
+    let AddModelArgs {
+        model_name,
+        endpoint_name,
+        backend,
+        weight,
+    } = args;
+
     log::debug!(
         "Adding model {} with endpoint {}",
         model_name,
@@ -231,6 +555,12 @@ async fn add_model(
     if model_name.starts_with('/') {
         raise!("Model name '{}' cannot start with a slash", model_name);
     }
+    if backend
+        .as_deref()
+        .is_some_and(|b| b.is_empty() || b.contains('/'))
+    {
+        raise!("Backend id must be non-empty and cannot contain a slash");
+    }
 
     let parts: Vec<&str> = endpoint_name.split('.').collect();
 
@@ -259,22 +589,35 @@ async fn add_model(
         name: model_name.to_string(),
         endpoint,
         model_type,
+        weight,
+        card: None,
     };
 
     // add model to etcd
-    let component = distributed.namespace(&namespace)?.component("http")?;
-    let path = format!(
-        "{}/models/{}/{}",
-        component.etcd_path(),
-        model_type.as_str(),
-        model_name
-    );
+    let path = model_key(
+        distributed,
+        &namespace,
+        model_type,
+        &model_name,
+        backend.as_deref(),
+    )?;
     let etcd_client = distributed.etcd_client();
 
-    // check if model already exists
-    let kvs = etcd_client.kv_get_prefix(&path).await?;
+    // check if the backend already exists, or the name is taken by an alias
+    let registrations = get_registrations(distributed, &namespace, model_type).await?;
+
+    if registrations
+        .iter()
+        .any(|(_, r)| matches!(r, ModelRegistration::Alias(a) if a.name == model_name))
+    {
+        raise!(
+            "{} model name {} is already used by an alias",
+            model_type.as_str(),
+            model_name
+        );
+    }
 
-    if !kvs.is_empty() {
+    if registrations.iter().any(|(key, _)| *key == path) {
         println!(
             "{} model {} already exists, please remove it before changing the endpoint.",
             model_type.as_str(),
@@ -292,12 +635,109 @@ async fn add_model(
     Ok(())
 }
 
+async fn add_alias(
+    distributed: &DistributedRuntime,
+    namespace: String,
+    model_type: ModelType,
+    args: AliasModelArgs,
+) -> Result<()> {
+    let AliasModelArgs {
+        alias_name,
+        target_name,
+    } = args;
+
+    if alias_name.starts_with('/') {
+        raise!("Alias name '{}' cannot start with a slash", alias_name);
+    }
+
+    let registrations = get_registrations(distributed, &namespace, model_type).await?;
+    if registrations.iter().any(|(_, r)| r.name() == alias_name) {
+        raise!(
+            "{} model or alias {} already exists, please remove it first",
+            model_type.as_str(),
+            alias_name
+        );
+    }
+    if !registrations.iter().any(|(_, r)| r.name() == target_name) {
+        println!(
+            "Warning: no {} model {} is registered yet",
+            model_type.as_str(),
+            target_name
+        );
+    }
+
+    let alias = ModelAlias {
+        name: alias_name.clone(),
+        target: target_name.clone(),
+        model_type,
+    };
+    let path = model_key(distributed, &namespace, model_type, &alias_name, None)?;
+    distributed
+        .etcd_client()
+        .kv_create(path, serde_json::to_vec_pretty(&alias)?, None)
+        .await?;
+
+    println!(
+        "Added {} alias {} -> {}",
+        model_type.as_str(),
+        alias_name,
+        target_name
+    );
+    Ok(())
+}
+
+async fn set_weight(
+    distributed: &DistributedRuntime,
+    namespace: String,
+    model_type: ModelType,
+    args: SetWeightArgs,
+) -> Result<()> {
+    let path = model_key(
+        distributed,
+        &namespace,
+        model_type,
+        &args.model_name,
+        args.backend.as_deref(),
+    )?;
+
+    let registrations = get_registrations(distributed, &namespace, model_type).await?;
+    let Some((_, registration)) = registrations.into_iter().find(|(key, _)| *key == path) else {
+        raise!(
+            "No {} model backend found for {}",
+            model_type.as_str(),
+            args.model_name
+        );
+    };
+    let ModelRegistration::Backend(mut model) = registration else {
+        raise!("{} is an alias and has no weight", args.model_name);
+    };
+
+    model.weight = args.weight;
+    distributed
+        .etcd_client()
+        .kv_put(path, serde_json::to_vec_pretty(&model)?, None)
+        .await?;
+
+    println!(
+        "Set the weight of {} model {} to {}",
+        model_type.as_str(),
+        args.model_name,
+        args.weight
+    );
+    list_single_model(distributed, namespace, model_type, args.model_name).await?;
+    Ok(())
+}
+
 #[derive(tabled::Tabled)]
 struct ModelRow {
     #[tabled(rename = "MODEL TYPE")]
     model_type: String,
     #[tabled(rename = "MODEL NAME")]
     name: String,
+    #[tabled(rename = "BACKEND")]
+    backend: String,
+    #[tabled(rename = "WEIGHT")]
+    weight: u32,
     #[tabled(rename = "NAMESPACE")]
     namespace: String,
     #[tabled(rename = "COMPONENT")]
@@ -306,38 +746,51 @@ struct ModelRow {
     endpoint: String,
 }
 
+#[derive(tabled::Tabled)]
+struct AliasRow {
+    #[tabled(rename = "MODEL TYPE")]
+    model_type: String,
+    #[tabled(rename = "ALIAS")]
+    name: String,
+    #[tabled(rename = "TARGET")]
+    target: String,
+}
+
+impl ModelRow {
+    fn new(key: &str, model_type: ModelType, model: ModelEntry) -> Self {
+        // backends added without an id are stored under the model name itself
+        let backend = match key.rfind(&format!("{}@", model.name)) {
+            Some(i) if !key.ends_with(&model.name) => key[i + model.name.len() + 1..].to_string(),
+            _ => "-".to_string(),
+        };
+        ModelRow {
+            model_type: model_type.as_str().to_string(),
+            name: model.name,
+            backend,
+            weight: model.weight,
+            namespace: model.endpoint.namespace,
+            component: model.endpoint.component,
+            endpoint: model.endpoint.name,
+        }
+    }
+}
+
 async fn list_single_model(
     distributed: &DistributedRuntime,
     namespace: String,
     model_type: ModelType,
     model_name: String,
 ) -> Result<()> {
-    let component = distributed.namespace(&namespace)?.component("http")?;
-    let path = format!(
-        "{}/models/{}/{}",
-        component.etcd_path(),
-        model_type.as_str(),
-        model_name
-    );
-
-    let mut models = Vec::new();
-    let etcd_client = distributed.etcd_client();
-    let kvs = etcd_client.kv_get_prefix(&path).await?;
-
-    for kv in kvs {
-        if let (Ok(_key), Ok(model)) = (
-            kv.key_str(),
-            serde_json::from_slice::<ModelEntry>(kv.value()),
-        ) {
-            models.push(ModelRow {
-                model_type: model_type.as_str().to_string(),
-                name: model_name.clone(),
-                namespace: model.endpoint.namespace,
-                component: model.endpoint.component,
-                endpoint: model.endpoint.name,
-            });
-        }
-    }
+    let models: Vec<ModelRow> = get_registrations(distributed, &namespace, model_type)
+        .await?
+        .into_iter()
+        .filter_map(|(key, registration)| match registration {
+            ModelRegistration::Backend(model) if model.name == model_name => {
+                Some(ModelRow::new(&key, model_type, model))
+            }
+            _ => None,
+        })
+        .collect();
 
     if models.is_empty() {
         println!("Something went wrong, no model was found.");
@@ -353,37 +806,27 @@ async fn list_models(
     namespace: String,
     model_type: Option<ModelType>,
 ) -> Result<()> {
-    let component = distributed.namespace(&namespace)?.component("http")?;
-
     let mut models = Vec::new();
+    let mut aliases = Vec::new();
     let model_types = match model_type {
         Some(mt) => vec![mt],
         None => ModelType::all(),
     };
 
     for mt in model_types {
-        let prefix = format!("{}/models/{}/", component.etcd_path(), mt.as_str(),);
-
-        let etcd_client = distributed.etcd_client();
-        let kvs = etcd_client.kv_get_prefix(&prefix).await?;
-
-        for kv in kvs {
-            if let (Ok(key), Ok(model)) = (
-                kv.key_str(),
-                serde_json::from_slice::<ModelEntry>(kv.value()),
-            ) {
-                models.push(ModelRow {
+        for (key, registration) in get_registrations(distributed, &namespace, mt).await? {
+            match registration {
+                ModelRegistration::Backend(model) => models.push(ModelRow::new(&key, mt, model)),
+                ModelRegistration::Alias(alias) => aliases.push(AliasRow {
                     model_type: mt.as_str().to_string(),
-                    name: key.trim_start_matches(&prefix).to_string(),
-                    namespace: model.endpoint.namespace,
-                    component: model.endpoint.component,
-                    endpoint: model.endpoint.name,
-                });
+                    name: alias.name,
+                    target: alias.target,
+                }),
             }
         }
     }
 
-    if models.is_empty() {
+    if models.is_empty() && aliases.is_empty() {
         match &model_type {
             Some(mt) => println!(
                 "No {} models found in the public namespace: {}",
@@ -393,7 +836,6 @@ async fn list_models(
             None => println!("No models found in the public namespace: {}", namespace),
         }
     } else {
-        let table = tabled::Table::new(models);
         match &model_type {
             Some(mt) => println!(
                 "Listing {} models in the public namespace: {}",
@@ -402,7 +844,12 @@ async fn list_models(
             ),
             None => println!("Listing all models in the public namespace: {}", namespace),
         }
-        println!("{}", table);
+        if !models.is_empty() {
+            println!("{}", tabled::Table::new(models));
+        }
+        if !aliases.is_empty() {
+            println!("{}", tabled::Table::new(aliases));
+        }
     }
     Ok(())
 }
@@ -411,15 +858,16 @@ async fn remove_model(
     distributed: &DistributedRuntime,
     namespace: String,
     model_type: ModelType,
-    name: &str,
+    args: RemoveModelArgs,
 ) -> Result<()> {
-    let component = distributed.namespace(&namespace)?.component("http")?;
-    let prefix = format!(
-        "{}/models/{}/{}",
-        component.etcd_path(),
-        model_type.as_str(),
-        name
-    );
+    let name = &args.model_name;
+    let prefix = model_key(
+        distributed,
+        &namespace,
+        model_type,
+        name,
+        args.backend.as_deref(),
+    )?;
 
     log::debug!("deleting key: {}", prefix);
 
@@ -62,6 +62,8 @@ fn _core(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<AsyncResponseStream>()?;
     m.add_class::<llm::kv::KvRouter>()?;
     m.add_class::<llm::disagg_router::DisaggregatedRouter>()?;
+    m.add_class::<llm::disagg_router::PrefillQueue>()?;
+    m.add_class::<llm::disagg_router::PrefillWorkItem>()?;
     m.add_class::<llm::kv::KvMetricsPublisher>()?;
     m.add_class::<llm::model_card::ModelDeploymentCard>()?;
     m.add_class::<llm::preprocessor::OAIChatPreprocessor>()?;
@@ -244,7 +246,10 @@ impl Endpoint {
         )?);
         let ingress = JsonServerStreamingIngress::for_engine(engine).map_err(to_pyerr)?;
         let builder = self.inner.endpoint_builder().handler(ingress);
+        let drt = self.inner.drt().clone();
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            // finish in-flight requests when asked to shut down
+            drt.enable_drain().await.map_err(to_pyerr)?;
             builder.start().await.map_err(to_pyerr)?;
             Ok(())
         })
@@ -16,8 +16,15 @@
 use super::*;
 
 use pyo3::exceptions::PyRuntimeError;
+use pyo3::types::PyBytes;
 use std::sync::Arc;
-use tokio::runtime::Runtime;
+use std::time::Duration;
+
+use dynamo_llm::disagg_router::prefill_queue;
+use dynamo_runtime::transports::nats::WorkQueueOptions;
+
+/// How often the router polls the size of the prefill queue
+const PREFILL_QUEUE_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 #[pyclass]
 pub struct DisaggregatedRouter {
@@ -27,27 +34,30 @@ pub struct DisaggregatedRouter {
 #[pymethods]
 impl DisaggregatedRouter {
     #[new]
-    #[pyo3(signature = (drt, model_name, default_max_local_prefill_length))]
+    #[pyo3(signature = (drt, model_name, default_max_local_prefill_length, prefill_component=None, decode_component=None, prefill_queue=None))]
     fn new(
         drt: PyObject,
         model_name: String,
         default_max_local_prefill_length: i32,
+        prefill_component: Option<Component>,
+        decode_component: Option<Component>,
+        prefill_queue: Option<PyRef<PrefillQueue>>,
     ) -> PyResult<Self> {
         let drt_arc = Python::with_gil(|py| {
             let drt_ref = drt.extract::<DistributedRuntime>(py)?;
             Ok::<_, PyErr>(Arc::new(drt_ref.inner))
         })?;
+        let cancel_token = drt_arc.primary_lease().primary_token();
 
-        // Create the runtime directly with the correct import
-        let runtime = Runtime::new().map_err(|e| {
-            PyRuntimeError::new_err(format!("Failed to create tokio runtime: {}", e))
-        })?;
-
+        // the router's watchers run on the shared runtime, so they outlive this call
+        let runtime = pyo3_async_runtimes::tokio::get_runtime();
         let router = runtime.block_on(async {
             dynamo_llm::disagg_router::DisaggregatedRouter::new_with_etcd_and_default(
                 drt_arc,
                 model_name,
                 default_max_local_prefill_length,
+                prefill_component.map(|component| component.inner),
+                decode_component.map(|component| component.inner),
             )
             .await
             .map_err(|e| {
@@ -55,6 +65,18 @@ impl DisaggregatedRouter {
             })
         })?;
 
+        let router = match prefill_queue {
+            Some(queue) => {
+                let _guard = runtime.enter();
+                router.with_prefill_queue(
+                    queue
+                        .inner
+                        .watch_size(PREFILL_QUEUE_POLL_INTERVAL, cancel_token),
+                )
+            }
+            None => router,
+        };
+
         Ok(DisaggregatedRouter {
             inner: Arc::new(router),
         })
@@ -68,3 +90,138 @@ impl DisaggregatedRouter {
         self.inner.get_model_name()
     }
 }
+
+/// The durable queue of remote prefill requests; the requests are json encoded
+#[pyclass]
+pub struct PrefillQueue {
+    inner: prefill_queue::PrefillQueue<serde_json::Value>,
+}
+
+#[pymethods]
+impl PrefillQueue {
+    /// Get or create the prefill queue `name`, e.g. one per model
+    #[new]
+    fn new(drt: DistributedRuntime, name: String) -> PyResult<Self> {
+        let runtime = pyo3_async_runtimes::tokio::get_runtime();
+        runtime.block_on(async {
+            let inner = prefill_queue::PrefillQueue::with_name(
+                &drt.inner.nats_client(),
+                &name,
+                WorkQueueOptions::default(),
+            )
+            .await
+            .map_err(to_pyerr)?;
+            Ok(Self { inner })
+        })
+    }
+
+    /// Enqueue the json encoded prefill `request` of `request_id`; returns False if it was
+    /// already enqueued
+    fn enqueue_prefill_request<'p>(
+        &self,
+        py: Python<'p>,
+        request_id: String,
+        request: &[u8],
+    ) -> PyResult<Bound<'p, PyAny>> {
+        let queue = self.inner.clone();
+        let request: serde_json::Value = serde_json::from_slice(request).map_err(to_pyerr)?;
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            queue
+                .enqueue_prefill_request(&request_id, &request)
+                .await
+                .map_err(to_pyerr)
+        })
+    }
+
+    /// Pull the next prefill request; None if the queue stayed empty
+    fn dequeue_prefill_request<'p>(&self, py: Python<'p>) -> PyResult<Bound<'p, PyAny>> {
+        let queue = self.inner.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let Some(item) = queue.dequeue_prefill_request().await.map_err(to_pyerr)? else {
+                return Ok(None);
+            };
+            let request = serde_json::to_vec(&item.request).map_err(to_pyerr)?;
+            Ok(Some(PrefillWorkItem {
+                request_id: item.request_id().map(str::to_string),
+                request,
+                item: Arc::new(tokio::sync::Mutex::new(Some(item))),
+            }))
+        })
+    }
+
+    /// Number of prefill requests waiting to be pulled
+    fn size<'p>(&self, py: Python<'p>) -> PyResult<Bound<'p, PyAny>> {
+        let queue = self.inner.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            queue.size().await.map_err(to_pyerr)
+        })
+    }
+}
+
+/// A prefill request pulled from a [PrefillQueue], which is redelivered unless acknowledged
+#[pyclass]
+pub struct PrefillWorkItem {
+    request_id: Option<String>,
+    request: Vec<u8>,
+    item: Arc<tokio::sync::Mutex<Option<prefill_queue::PrefillWorkItem<serde_json::Value>>>>,
+}
+
+impl PrefillWorkItem {
+    /// Takes the item to settle it; errors if it was already acknowledged or returned
+    async fn take(
+        item: &tokio::sync::Mutex<Option<prefill_queue::PrefillWorkItem<serde_json::Value>>>,
+    ) -> PyResult<prefill_queue::PrefillWorkItem<serde_json::Value>> {
+        item.lock()
+            .await
+            .take()
+            .ok_or_else(|| to_pyerr("prefill request was already acknowledged or returned"))
+    }
+}
+
+#[pymethods]
+impl PrefillWorkItem {
+    #[getter]
+    fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    /// The json encoded prefill request
+    #[getter]
+    fn request<'p>(&self, py: Python<'p>) -> Bound<'p, PyBytes> {
+        PyBytes::new(py, &self.request)
+    }
+
+    /// Acknowledge the prefill is done, removing the request from the queue
+    fn ack<'p>(&self, py: Python<'p>) -> PyResult<Bound<'p, PyAny>> {
+        let item = self.item.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            Self::take(&item).await?.ack().await.map_err(to_pyerr)
+        })
+    }
+
+    /// Return the request to the queue for another prefill worker, after `delay` seconds if any
+    #[pyo3(signature = (delay=None))]
+    fn nak<'p>(&self, py: Python<'p>, delay: Option<f64>) -> PyResult<Bound<'p, PyAny>> {
+        let item = self.item.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            Self::take(&item)
+                .await?
+                .nak(delay.map(Duration::from_secs_f64))
+                .await
+                .map_err(to_pyerr)
+        })
+    }
+
+    /// Report the prefill is still running, so the request is not redelivered
+    fn in_progress<'p>(&self, py: Python<'p>) -> PyResult<Bound<'p, PyAny>> {
+        let item = self.item.clone();
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            match item.lock().await.as_ref() {
+                Some(item) => item.in_progress().await.map_err(to_pyerr),
+                None => Err(to_pyerr(
+                    "prefill request was already acknowledged or returned",
+                )),
+            }
+        })
+    }
+}
@@ -21,6 +21,28 @@ use rs::traits::events::EventSubscriber;
 use tracing;
 
 use llm_rs::kv_router::{indexer::compute_block_hash_for_seq, protocols::*};
+use llm_rs::protocols::common::preprocessor::{MultiModalItem, PreprocessedRequest};
+
+/// The router request of a prompt, with the media given as the `multi_modal_data` of a
+/// preprocessed request, so that prompts with different media do not match
+fn router_request(
+    py: Python<'_>,
+    token_ids: Vec<u32>,
+    multi_modal_data: Option<PyObject>,
+) -> PyResult<RouterRequest> {
+    let multi_modal_data: Vec<MultiModalItem> = match multi_modal_data {
+        Some(data) => pythonize::depythonize(&data.into_bound(py))?,
+        None => Vec::new(),
+    };
+    let request = PreprocessedRequest::builder()
+        .token_ids(token_ids)
+        .multi_modal_data(multi_modal_data)
+        .stop_conditions(Default::default())
+        .sampling_options(Default::default())
+        .build()
+        .map_err(to_pyerr)?;
+    Ok(RouterRequest::from(&request))
+}
 
 #[pyclass]
 pub(crate) struct KvRouter {
@@ -41,18 +63,18 @@ impl KvRouter {
         })
     }
 
+    #[pyo3(signature = (token_ids, lora_id, multi_modal_data=None))]
     fn schedule<'p>(
         &self,
         py: Python<'p>,
         token_ids: Vec<u32>,
         lora_id: u64,
+        multi_modal_data: Option<PyObject>,
     ) -> PyResult<Bound<'p, PyAny>> {
+        let request = router_request(py, token_ids, multi_modal_data)?;
         let router = self.inner.clone();
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let worker_id = router
-                .schedule(&token_ids, lora_id)
-                .await
-                .map_err(to_pyerr)?;
+            let worker_id = router.schedule(&request, lora_id).await.map_err(to_pyerr)?;
             Ok(worker_id)
         })
     }
@@ -294,16 +316,19 @@ impl KvIndexer {
         self.inner.block_size()
     }
 
+    #[pyo3(signature = (token_ids, _lora_id, multi_modal_data=None))]
     fn find_matches_for_request<'p>(
         &self,
         py: Python<'p>,
         token_ids: Vec<u32>,
         _lora_id: u64,
+        multi_modal_data: Option<PyObject>,
     ) -> PyResult<Bound<'p, PyAny>> {
+        let request = router_request(py, token_ids, multi_modal_data)?;
         let indexer = self.inner.clone();
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
             let rs_overlap_scores = indexer
-                .find_matches_for_request(token_ids.as_slice())
+                .find_matches_for_request(&request)
                 .await
                 .map_err(to_pyerr)?;
             Ok(OverlapScores {
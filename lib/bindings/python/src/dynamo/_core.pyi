@@ -13,7 +13,7 @@
 # See the License for the specific language governing permissions and
 # limitations under the License.
 
-from typing import AsyncGenerator, AsyncIterator, Callable, Dict, List, Optional
+from typing import Any, AsyncGenerator, AsyncIterator, Callable, Dict, List, Optional
 
 def log_message(level: str, message: str, module: str, file: str, line: int) -> None:
     """
@@ -172,10 +172,16 @@ class KvRouter:
         Create a `KvRouter` object that is associated with the `component`
         """
 
-    def schedule(self, token_ids: List[int], lora_id: int) -> int:
+    def schedule(
+        self,
+        token_ids: List[int],
+        lora_id: int,
+        multi_modal_data: Optional[List[Dict[str, Any]]] = None,
+    ) -> int:
         """
         Return the worker id that should handle the given token ids,
         exception will be raised if there is no worker available.
+        `multi_modal_data` is the media of the preprocessed request, if any.
         """
         ...
 
@@ -190,6 +196,9 @@ class DisaggregatedRouter:
         drt: DistributedRuntime,
         model_name: str,
         default_max_local_prefill_length: int,
+        prefill_component: Optional[Component] = None,
+        decode_component: Optional[Component] = None,
+        prefill_queue: Optional[PrefillQueue] = None,
     ) -> None:
         """
         Create a `DisaggregatedRouter` object.
@@ -198,6 +207,12 @@ class DisaggregatedRouter:
             drt: The distributed runtime instance
             model_name: Name of the model
             default_max_local_prefill_length: Default maximum sequence length that can be processed locally
+            prefill_component: Component of the prefill workers, whose waiting requests keep
+                prefills local when the prefill workers are backed up
+            decode_component: Component of this decode worker, whose load must leave headroom
+                for a local prefill
+            prefill_queue: Queue of remote prefill requests, whose size counts towards the
+                requests waiting for the prefill workers
         """
         ...
 
@@ -232,6 +247,63 @@ class DisaggregatedRouter:
         """
         ...
 
+class PrefillQueue:
+    """
+    A durable queue of remote prefill requests, shared by the decode and prefill workers.
+    A dequeued request is redelivered to another prefill worker unless it is acknowledged.
+    """
+
+    def __init__(self, drt: DistributedRuntime, name: str) -> None:
+        """
+        Get or create the prefill queue `name`, e.g. one per model
+        """
+        ...
+
+    async def enqueue_prefill_request(self, request_id: str, request: bytes) -> bool:
+        """
+        Enqueue the json encoded prefill `request`. Returns False if `request_id` was
+        already enqueued.
+        """
+        ...
+
+    async def dequeue_prefill_request(self) -> Optional[PrefillWorkItem]:
+        """
+        Pull the next prefill request, or None if the queue stayed empty
+        """
+        ...
+
+    async def size(self) -> int:
+        """
+        Number of prefill requests waiting to be pulled
+        """
+        ...
+
+class PrefillWorkItem:
+    """
+    A prefill request pulled from a `PrefillQueue`
+    """
+
+    request_id: Optional[str]
+    request: bytes
+
+    async def ack(self) -> None:
+        """
+        Acknowledge the prefill is done, removing the request from the queue
+        """
+        ...
+
+    async def nak(self, delay: Optional[float] = None) -> None:
+        """
+        Return the request to the queue for another prefill worker, after `delay` seconds
+        """
+        ...
+
+    async def in_progress(self) -> None:
+        """
+        Report the prefill is still running, so the request is not redelivered
+        """
+        ...
+
 class KvMetricsPublisher:
     """
     A metrics publisher will provide KV metrics to the router.
@@ -317,10 +389,14 @@ class KvIndexer:
         """
 
     def find_matches_for_request(
-        self, token_ids: List[int], lora_id: int
+        self,
+        token_ids: List[int],
+        lora_id: int,
+        multi_modal_data: Optional[List[Dict[str, Any]]] = None,
     ) -> OverlapScores:
         """
         Return the overlapping scores of workers for the given token ids.
+        `multi_modal_data` is the media of the preprocessed request, if any.
         """
         ...
 
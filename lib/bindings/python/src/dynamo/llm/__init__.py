@@ -24,3 +24,5 @@ from dynamo._core import KvMetricsAggregator as KvMetricsAggregator
 from dynamo._core import KvMetricsPublisher as KvMetricsPublisher
 from dynamo._core import KvRouter as KvRouter
 from dynamo._core import OverlapScores as OverlapScores
+from dynamo._core import PrefillQueue as PrefillQueue
+from dynamo._core import PrefillWorkItem as PrefillWorkItem
@@ -214,6 +214,7 @@ impl
                     cum_log_probs: data.cum_log_probs,
                     log_probs: data.log_probs,
                     finish_reason: data.finish_reason,
+                    cached_tokens: data.cached_tokens,
                     //mdcsum: mdcsum.clone(),
                 })
             })
@@ -13,23 +13,54 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use prometheus::{IntCounterVec, Opts, Registry};
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 use tokio::sync::watch;
 use tracing;
 
+use dynamo_runtime::component::Component;
 use dynamo_runtime::transports::etcd::WatchEvent;
 use dynamo_runtime::DistributedRuntime;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+use crate::kv_router::{
+    metrics_aggregator::KvMetricsAggregator, protocols::ForwardPassMetrics,
+    scoring::ProcessedEndpoints,
+};
+
+pub mod prefill_queue;
+
+/// Value for the `decision` label of the decision counter for prefills run on the decode worker
+pub const DECISION_LOCAL: &str = "local";
+
+/// Value for the `decision` label of the decision counter for prefills sent to the prefill workers
+pub const DECISION_REMOTE: &str = "remote";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct DisaggRouterConf {
     pub max_local_prefill_length: i32,
+
+    /// Number of requests waiting across the prefill workers at which prefills longer than
+    /// `max_local_prefill_length` stay on the decode worker, if it has headroom; unset disables
+    /// the check
+    #[serde(default)]
+    pub max_prefill_queue_size: Option<u64>,
+
+    /// Load of the decode worker, from 0 to 1, under which it has headroom to prefill locally
+    #[serde(default = "default_max_local_decode_load")]
+    pub max_local_decode_load: f64,
+}
+
+fn default_max_local_decode_load() -> f64 {
+    0.8
 }
 
 impl Default for DisaggRouterConf {
     fn default() -> Self {
         Self {
             max_local_prefill_length: 1000,
+            max_prefill_queue_size: None,
+            max_local_decode_load: default_max_local_decode_load(),
         }
     }
 }
@@ -140,27 +171,212 @@ impl DisaggRouterConf {
     }
 }
 
+/// Load of the workers a prefill decision depends on
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DisaggLoad {
+    /// Number of prefill requests waiting in the prefill queue and across the prefill workers
+    pub prefill_queue_size: Option<u64>,
+
+    /// Load of the decode worker, from 0 to 1
+    pub decode_load: Option<f64>,
+}
+
+impl DisaggLoad {
+    /// Requests waiting across the workers, if any worker reports metrics
+    pub fn prefill_queue_size(endpoints: &ProcessedEndpoints) -> Option<u64> {
+        if endpoints.endpoints.is_empty() {
+            return None;
+        }
+        Some(
+            endpoints
+                .endpoints
+                .values()
+                .map(|endpoint| endpoint.data.num_requests_waiting)
+                .sum(),
+        )
+    }
+
+    /// The larger of the kv cache and request slot usage of a worker; None if it reports no
+    /// capacity
+    pub fn decode_load(metrics: &ForwardPassMetrics) -> Option<f64> {
+        let usage = |active: u64, total: u64| (total > 0).then(|| active as f64 / total as f64);
+        match (
+            usage(metrics.kv_active_blocks, metrics.kv_total_blocks),
+            usage(metrics.request_active_slots, metrics.request_total_slots),
+        ) {
+            (Some(kv), Some(slots)) => Some(kv.max(slots)),
+            (kv, slots) => kv.or(slots),
+        }
+    }
+}
+
+/// Where a prefill runs, and why
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefillDecision {
+    /// The prefill is short enough to run on the decode worker
+    ShortPrefill,
+
+    /// The prefill workers are backed up while the decode worker has headroom
+    PrefillQueueFull,
+
+    /// The prefill is long enough to run on the prefill workers
+    LongPrefill,
+}
+
+impl PrefillDecision {
+    pub fn new(
+        config: &DisaggRouterConf,
+        prefill_length: i32,
+        prefix_hit_length: i32,
+        load: &DisaggLoad,
+    ) -> Self {
+        // TODO: apply math models and compare local vs remote prefill TTFT
+        if prefill_length - prefix_hit_length <= config.max_local_prefill_length {
+            return PrefillDecision::ShortPrefill;
+        }
+
+        // without a decode load, the decode worker is assumed to have headroom
+        if let (Some(max_queue_size), Some(queue_size)) =
+            (config.max_prefill_queue_size, load.prefill_queue_size)
+        {
+            let decode_has_headroom = load
+                .decode_load
+                .is_none_or(|decode_load| decode_load < config.max_local_decode_load);
+            if queue_size >= max_queue_size && decode_has_headroom {
+                return PrefillDecision::PrefillQueueFull;
+            }
+        }
+
+        PrefillDecision::LongPrefill
+    }
+
+    pub fn is_remote(&self) -> bool {
+        matches!(self, PrefillDecision::LongPrefill)
+    }
+
+    /// Value of the `decision` label
+    pub fn decision(&self) -> &'static str {
+        if self.is_remote() {
+            DECISION_REMOTE
+        } else {
+            DECISION_LOCAL
+        }
+    }
+
+    /// Value of the `reason` label
+    pub fn reason(&self) -> &'static str {
+        match self {
+            PrefillDecision::ShortPrefill => "short_prefill",
+            PrefillDecision::PrefillQueueFull => "prefill_queue_full",
+            PrefillDecision::LongPrefill => "long_prefill",
+        }
+    }
+}
+
+pub struct DisaggRouterMetrics {
+    decision_counter: IntCounterVec,
+}
+
+impl Default for DisaggRouterMetrics {
+    fn default() -> Self {
+        Self::new("nv_llm")
+    }
+}
+
+impl DisaggRouterMetrics {
+    /// Create metrics with the given prefix
+    /// The following metrics will be created:
+    /// - `{prefix}_disagg_router_prefill_decisions_total` - IntCounterVec for the prefill
+    ///   decisions, labeled by model, decision (local or remote) and reason
+    pub fn new(prefix: &str) -> Self {
+        let decision_counter = IntCounterVec::new(
+            Opts::new(
+                format!("{}_disagg_router_prefill_decisions_total", prefix),
+                "Total number of local and remote prefill decisions",
+            ),
+            &["model", "decision", "reason"],
+        )
+        .unwrap();
+
+        Self { decision_counter }
+    }
+
+    /// Get the number of decisions for the given model with the decision and reason labels
+    pub fn get_decision_count(&self, model: &str, decision: &str, reason: &str) -> u64 {
+        self.decision_counter
+            .with_label_values(&[model, decision, reason])
+            .get()
+    }
+
+    fn inc_decision_counter(&self, model: &str, decision: PrefillDecision) {
+        self.decision_counter
+            .with_label_values(&[model, decision.decision(), decision.reason()])
+            .inc()
+    }
+
+    pub fn register(&self, registry: &Registry) -> Result<(), prometheus::Error> {
+        registry.register(Box::new(self.decision_counter.clone()))?;
+        Ok(())
+    }
+
+    /// The metrics shared by the routers of the process, registered with `registry` on first use
+    fn shared(registry: &Registry) -> Arc<Self> {
+        static METRICS: OnceLock<Arc<DisaggRouterMetrics>> = OnceLock::new();
+        METRICS
+            .get_or_init(|| {
+                let metrics = Arc::new(Self::default());
+                if let Err(e) = metrics.register(registry) {
+                    tracing::warn!("Failed to register the disagg router metrics: {}", e);
+                }
+                metrics
+            })
+            .clone()
+    }
+}
+
 #[derive(Clone)]
 pub struct DisaggregatedRouter {
-    max_local_prefill_length: Arc<Mutex<i32>>,
+    config: Arc<Mutex<DisaggRouterConf>>,
     model_name: String,
     config_watcher: Option<watch::Receiver<DisaggRouterConf>>,
+    prefill_endpoints: Option<watch::Receiver<ProcessedEndpoints>>,
+    prefill_queue: Option<watch::Receiver<Option<u64>>>,
+    decode_endpoints: Option<(watch::Receiver<ProcessedEndpoints>, i64)>,
+    metrics: Arc<DisaggRouterMetrics>,
 }
 
 impl DisaggregatedRouter {
     pub fn new(max_local_prefill_length: i32, model_name: String) -> Self {
         DisaggregatedRouter {
-            max_local_prefill_length: Arc::new(Mutex::new(max_local_prefill_length)),
+            config: Arc::new(Mutex::new(DisaggRouterConf {
+                max_local_prefill_length,
+                ..Default::default()
+            })),
             model_name,
             config_watcher: None,
+            prefill_endpoints: None,
+            prefill_queue: None,
+            decode_endpoints: None,
+            metrics: Arc::new(DisaggRouterMetrics::default()),
         }
     }
 
+    /// Create a router whose config is watched in etcd and whose decisions are counted in the
+    /// metrics of `drt`.
+    ///
+    /// The decisions consider the requests waiting across the workers of `prefill_component` and
+    /// the load of this process's worker among the workers of `decode_component`, if given.
     pub async fn new_with_etcd_and_default(
         drt: Arc<DistributedRuntime>,
         model_name: String,
         default_max_local_prefill_length: i32,
+        prefill_component: Option<Component>,
+        decode_component: Option<Component>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
+        let metrics = DisaggRouterMetrics::shared(drt.metrics().registry());
+        let cancellation_token = drt.primary_lease().primary_token();
+        let worker_id = drt.primary_lease().id();
+
         let (mut config, watcher) =
             DisaggRouterConf::from_etcd_with_watcher(drt, &model_name).await?;
 
@@ -169,44 +385,77 @@ impl DisaggregatedRouter {
             config.max_local_prefill_length = default_max_local_prefill_length;
         }
 
-        let router = Self {
-            max_local_prefill_length: Arc::new(Mutex::new(config.max_local_prefill_length)),
+        let mut router = Self {
+            config: Arc::new(Mutex::new(config)),
             model_name: model_name.clone(),
             config_watcher: Some(watcher),
+            prefill_endpoints: None,
+            prefill_queue: None,
+            decode_endpoints: None,
+            metrics,
         };
 
+        if let Some(component) = prefill_component {
+            let aggregator = KvMetricsAggregator::new(component, cancellation_token.clone()).await;
+            router = router.with_prefill_endpoints(aggregator.endpoints_watcher());
+        }
+        if let Some(component) = decode_component {
+            let aggregator = KvMetricsAggregator::new(component, cancellation_token).await;
+            router = router.with_decode_endpoints(aggregator.endpoints_watcher(), worker_id);
+        }
+
         // Start background task to watch for config updates
         router.start_config_watcher();
 
         Ok(router)
     }
 
+    /// Consider the requests waiting across the prefill workers, e.g. from the
+    /// [KvMetricsAggregator][crate::kv_router::metrics_aggregator::KvMetricsAggregator] of the
+    /// prefill component
+    pub fn with_prefill_endpoints(
+        mut self,
+        endpoints: watch::Receiver<ProcessedEndpoints>,
+    ) -> Self {
+        self.prefill_endpoints = Some(endpoints);
+        self
+    }
+
+    /// Consider the requests waiting in the prefill queue, e.g. from
+    /// [PrefillQueue::watch_size][prefill_queue::PrefillQueue::watch_size]
+    pub fn with_prefill_queue(mut self, size: watch::Receiver<Option<u64>>) -> Self {
+        self.prefill_queue = Some(size);
+        self
+    }
+
+    /// Consider the load of the decode worker `worker_id` among the endpoints of the decode
+    /// component
+    pub fn with_decode_endpoints(
+        mut self,
+        endpoints: watch::Receiver<ProcessedEndpoints>,
+        worker_id: i64,
+    ) -> Self {
+        self.decode_endpoints = Some((endpoints, worker_id));
+        self
+    }
+
+    pub fn metrics(&self) -> Arc<DisaggRouterMetrics> {
+        self.metrics.clone()
+    }
+
     fn start_config_watcher(&self) {
         if let Some(watcher) = self.config_watcher.clone() {
             let mut watcher = watcher;
             // Create a clone for the task
             let model_name = self.model_name.clone();
-            let max_local_prefill_length = self.max_local_prefill_length.clone();
+            let config = self.config.clone();
 
             tokio::spawn(async move {
                 tracing::info!("Starting config update watcher for model: {}", model_name);
 
                 while watcher.changed().await.is_ok() {
-                    let config = watcher.borrow().clone();
-                    let new_value = config.max_local_prefill_length;
-
-                    // Update the value using the mutex
-                    let mut current_value = max_local_prefill_length.lock().unwrap();
-                    let old_value = *current_value;
-                    if old_value != new_value {
-                        *current_value = new_value;
-                        tracing::info!(
-                            "Applied config update for model {}: max_local_prefill_length changed from {} to {}",
-                            model_name,
-                            old_value,
-                            new_value
-                        );
-                    }
+                    let new_config = watcher.borrow().clone();
+                    apply_config(&config, new_config, &model_name);
                 }
 
                 tracing::debug!("Config watcher closed for model: {}", model_name);
@@ -217,43 +466,220 @@ impl DisaggregatedRouter {
     pub fn check_for_updates(&self) {
         if let Some(watcher) = &self.config_watcher {
             if watcher.has_changed().unwrap_or(false) {
-                let config = watcher.borrow().clone();
-                let new_value = config.max_local_prefill_length;
-
-                // Update the value using the mutex
-                let mut current_value = self.max_local_prefill_length.lock().unwrap();
-                let old_value = *current_value;
-                if old_value != new_value {
-                    *current_value = new_value;
-                    tracing::info!(
-                        "Applied config update for model {}: max_local_prefill_length changed from {} to {}",
-                        self.model_name,
-                        old_value,
-                        new_value
-                    );
-                }
+                let new_config = watcher.borrow().clone();
+                apply_config(&self.config, new_config, &self.model_name);
             }
         }
     }
 
-    pub fn prefill_remote(&self, prefill_length: i32, prefix_hit_length: i32) -> bool {
+    /// The current load of the prefill workers and the decode worker
+    pub fn load(&self) -> DisaggLoad {
+        let waiting = self
+            .prefill_endpoints
+            .as_ref()
+            .and_then(|endpoints| DisaggLoad::prefill_queue_size(&endpoints.borrow()));
+        let queued = self.prefill_queue.as_ref().and_then(|size| *size.borrow());
+        let prefill_queue_size = match (queued, waiting) {
+            (None, None) => None,
+            (queued, waiting) => Some(queued.unwrap_or(0) + waiting.unwrap_or(0)),
+        };
+        let decode_load = self
+            .decode_endpoints
+            .as_ref()
+            .and_then(|(endpoints, worker_id)| {
+                let endpoints = endpoints.borrow();
+                endpoints
+                    .endpoints
+                    .get(worker_id)
+                    .and_then(|endpoint| DisaggLoad::decode_load(&endpoint.data))
+            });
+
+        DisaggLoad {
+            prefill_queue_size,
+            decode_load,
+        }
+    }
+
+    pub fn prefill_decision(&self, prefill_length: i32, prefix_hit_length: i32) -> PrefillDecision {
         // Check for updates before making the decision
         self.check_for_updates();
 
-        // Get the current value from the mutex
-        let max_local_prefill_length = *self.max_local_prefill_length.lock().unwrap();
+        let load = self.load();
+        let config = self.config.lock().unwrap().clone();
+        let decision = PrefillDecision::new(&config, prefill_length, prefix_hit_length, &load);
+        tracing::debug!(
+            model = self.model_name,
+            prefill_length,
+            prefix_hit_length,
+            ?load,
+            ?decision,
+            "prefill decision"
+        );
+
+        self.metrics
+            .inc_decision_counter(&self.model_name, decision);
+        decision
+    }
 
-        // schedule the request purely based on the prefill length
-        // TODO: apply math models and compare local vs remote prefill TTFT
-        prefill_length - prefix_hit_length > max_local_prefill_length
+    pub fn prefill_remote(&self, prefill_length: i32, prefix_hit_length: i32) -> bool {
+        self.prefill_decision(prefill_length, prefix_hit_length)
+            .is_remote()
     }
 
     pub fn update_value(&self, max_local_prefill_length: i32) {
-        let mut current = self.max_local_prefill_length.lock().unwrap();
-        *current = max_local_prefill_length;
+        let mut current = self.config.lock().unwrap();
+        current.max_local_prefill_length = max_local_prefill_length;
     }
 
     pub fn get_model_name(&self) -> &str {
         &self.model_name
     }
 }
+
+fn apply_config(config: &Mutex<DisaggRouterConf>, new_config: DisaggRouterConf, model_name: &str) {
+    let mut current = config.lock().unwrap();
+    if *current != new_config {
+        tracing::info!(
+            "Applied config update for model {}: {:?} changed to {:?}",
+            model_name,
+            *current,
+            new_config
+        );
+        *current = new_config;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(max_prefill_queue_size: Option<u64>) -> DisaggRouterConf {
+        DisaggRouterConf {
+            max_local_prefill_length: 100,
+            max_prefill_queue_size,
+            ..Default::default()
+        }
+    }
+
+    fn load(prefill_queue_size: u64, decode_load: f64) -> DisaggLoad {
+        DisaggLoad {
+            prefill_queue_size: Some(prefill_queue_size),
+            decode_load: Some(decode_load),
+        }
+    }
+
+    #[test]
+    fn test_conf_defaults() {
+        let config: DisaggRouterConf =
+            serde_json::from_str(r#"{"max_local_prefill_length": 500}"#).unwrap();
+        assert_eq!(config.max_local_prefill_length, 500);
+        assert_eq!(config.max_prefill_queue_size, None);
+        assert_eq!(config.max_local_decode_load, 0.8);
+    }
+
+    #[test]
+    fn test_prefill_length_decision() {
+        // without a queue size threshold only the prefill length matters
+        let config = config(None);
+        let busy = load(1000, 0.0);
+        assert_eq!(
+            PrefillDecision::new(&config, 150, 60, &busy),
+            PrefillDecision::ShortPrefill
+        );
+        assert_eq!(
+            PrefillDecision::new(&config, 150, 40, &busy),
+            PrefillDecision::LongPrefill
+        );
+    }
+
+    #[test]
+    fn test_queue_aware_decision() {
+        let config = config(Some(8));
+
+        // the prefill workers keep up
+        assert_eq!(
+            PrefillDecision::new(&config, 1000, 0, &load(7, 0.1)),
+            PrefillDecision::LongPrefill
+        );
+
+        // the prefill workers are backed up and the decode worker is idle
+        let decision = PrefillDecision::new(&config, 1000, 0, &load(8, 0.1));
+        assert_eq!(decision, PrefillDecision::PrefillQueueFull);
+        assert!(!decision.is_remote());
+
+        // the decode worker is busy as well
+        assert_eq!(
+            PrefillDecision::new(&config, 1000, 0, &load(8, 0.9)),
+            PrefillDecision::LongPrefill
+        );
+
+        // the prefill workers report no metrics
+        assert_eq!(
+            PrefillDecision::new(&config, 1000, 0, &DisaggLoad::default()),
+            PrefillDecision::LongPrefill
+        );
+    }
+
+    #[test]
+    fn test_decode_load() {
+        let metrics = ForwardPassMetrics {
+            request_active_slots: 2,
+            request_total_slots: 8,
+            kv_active_blocks: 50,
+            kv_total_blocks: 100,
+            ..Default::default()
+        };
+        assert_eq!(DisaggLoad::decode_load(&metrics), Some(0.5));
+        assert_eq!(
+            DisaggLoad::decode_load(&ForwardPassMetrics::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_load_with_prefill_queue() {
+        let (queue_tx, queue_rx) = watch::channel(None);
+        let router =
+            DisaggregatedRouter::new(100, "model".to_string()).with_prefill_queue(queue_rx);
+        assert_eq!(router.load().prefill_queue_size, None);
+
+        queue_tx.send(Some(3)).unwrap();
+        assert_eq!(router.load().prefill_queue_size, Some(3));
+    }
+
+    #[test]
+    fn test_decision_metrics() {
+        let router = DisaggregatedRouter::new(100, "model".to_string());
+        assert!(!router.prefill_remote(50, 0));
+        assert!(router.prefill_remote(500, 0));
+        assert!(router.prefill_remote(500, 0));
+
+        let metrics = router.metrics();
+        assert_eq!(
+            metrics.get_decision_count("model", DECISION_LOCAL, "short_prefill"),
+            1
+        );
+        assert_eq!(
+            metrics.get_decision_count("model", DECISION_REMOTE, "long_prefill"),
+            2
+        );
+    }
+
+    #[test]
+    fn test_shared_metrics() {
+        let registry = Registry::new();
+        let metrics = DisaggRouterMetrics::shared(&registry);
+        assert!(Arc::ptr_eq(
+            &metrics,
+            &DisaggRouterMetrics::shared(&registry)
+        ));
+
+        metrics.inc_decision_counter("shared", PrefillDecision::ShortPrefill);
+        let families = registry.gather();
+        assert_eq!(families.len(), 1);
+        assert_eq!(
+            families[0].get_name(),
+            "nv_llm_disagg_router_prefill_decisions_total"
+        );
+    }
+}
@@ -0,0 +1,163 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Durable queue of remote prefill requests
+//!
+//! Decode workers enqueue the prefills the [DisaggregatedRouter][super::DisaggregatedRouter]
+//! sends remote; prefill workers pull them and acknowledge each one once its kv cache has been
+//! transferred. A request that is not acknowledged, e.g. because its prefill worker restarted,
+//! is redelivered to another prefill worker.
+
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::watch;
+
+use dynamo_runtime::transports::nats::{self, WorkItem, WorkQueue, WorkQueueOptions};
+use dynamo_runtime::{error, CancellationToken, Result};
+
+/// Name of the prefill queue shared by the decode and prefill workers
+pub const PREFILL_QUEUE_NAME: &str = "prefill_queue";
+
+/// A [WorkQueue] of prefill requests `T`, encoded as json
+pub struct PrefillQueue<T> {
+    queue: WorkQueue,
+    _request: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for PrefillQueue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+            _request: PhantomData,
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> PrefillQueue<T> {
+    /// Get or create the shared prefill queue
+    pub async fn new(client: &nats::Client, options: WorkQueueOptions) -> Result<Self> {
+        Self::with_name(client, PREFILL_QUEUE_NAME, options).await
+    }
+
+    /// Get or create the prefill queue `name`, e.g. one per model
+    pub async fn with_name(
+        client: &nats::Client,
+        name: &str,
+        options: WorkQueueOptions,
+    ) -> Result<Self> {
+        let queue = client.get_or_create_work_queue(name, options).await?;
+        Ok(Self {
+            queue,
+            _request: PhantomData,
+        })
+    }
+
+    /// Enqueue the prefill of `request_id`; returns false if it was already enqueued
+    pub async fn enqueue_prefill_request(&self, request_id: &str, request: &T) -> Result<bool> {
+        let payload = serde_json::to_vec(request)?;
+        self.queue.enqueue_with_id(request_id, payload.into()).await
+    }
+
+    /// Pull the next prefill request; None if the queue stayed empty for its dequeue timeout
+    ///
+    /// A request that cannot be decoded is removed from the queue and reported as an error.
+    pub async fn dequeue_prefill_request(&self) -> Result<Option<PrefillWorkItem<T>>> {
+        let Some(item) = self.queue.dequeue().await? else {
+            return Ok(None);
+        };
+
+        match serde_json::from_slice(item.payload()) {
+            Ok(request) => Ok(Some(PrefillWorkItem { request, item })),
+            Err(e) => {
+                let id = item.id().unwrap_or_default().to_string();
+                item.term().await?;
+                Err(error!("dropped undecodable prefill request {id}: {e}"))
+            }
+        }
+    }
+
+    /// Number of prefill requests waiting to be pulled
+    pub async fn size(&self) -> Result<u64> {
+        self.queue.depth().await
+    }
+
+    /// Poll the size of the queue every `interval` until `cancel` is cancelled; the value is None
+    /// until the first poll succeeds, and after a poll fails
+    pub fn watch_size(
+        &self,
+        interval: Duration,
+        cancel: CancellationToken,
+    ) -> watch::Receiver<Option<u64>> {
+        let (tx, rx) = watch::channel(None);
+        let queue = self.queue.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = tx.closed() => break,
+                    _ = interval.tick() => {}
+                }
+
+                let size = match queue.depth().await {
+                    Ok(size) => Some(size),
+                    Err(e) => {
+                        tracing::warn!("failed to get the size of {}: {e}", queue.name());
+                        None
+                    }
+                };
+                tx.send_replace(size);
+            }
+        });
+
+        rx
+    }
+}
+
+/// A prefill request pulled from a [PrefillQueue]
+pub struct PrefillWorkItem<T> {
+    pub request: T,
+    item: WorkItem,
+}
+
+impl<T> PrefillWorkItem<T> {
+    /// Id the request was enqueued under
+    pub fn request_id(&self) -> Option<&str> {
+        self.item.id()
+    }
+
+    /// Number of times the request has been delivered, including this delivery
+    pub fn delivered(&self) -> Result<u64> {
+        self.item.delivered()
+    }
+
+    /// Acknowledge the prefill is done, removing the request from the queue
+    pub async fn ack(self) -> Result<()> {
+        self.item.ack().await
+    }
+
+    /// Return the request to the queue for another prefill worker, after `delay` if any
+    pub async fn nak(self, delay: Option<Duration>) -> Result<()> {
+        self.item.nak(delay).await
+    }
+
+    /// Report the prefill is still running, so the request is not redelivered
+    pub async fn in_progress(&self) -> Result<()> {
+        self.item.in_progress().await
+    }
+}
@@ -35,13 +35,16 @@ use llama_cpp_2::{
     sampling::LlamaSampler,
     token::LlamaToken,
 };
+use rand::Rng;
 
 use crate::backend::ExecutionContext;
 use crate::protocols::common::llm_backend::{BackendInput, LLMEngineOutput};
 use crate::protocols::common::preprocessor::PreprocessedRequest;
+use crate::protocols::common::SamplingOptions;
 
-/// If user does not provide a max_tokens limit prompt+output to this many
-const DEFAULT_MAX_TOKENS: u32 = 8192;
+/// How many of the most recent output tokens the repetition, frequency and presence penalties
+/// look at
+const PENALTY_LAST_N: i32 = 256;
 
 // I'm not entirely sure what this is. The model context size surely comes from the GGUF??
 const CONTEXT_SIZE: u32 = 8192;
@@ -208,6 +211,66 @@ async fn worker(
     }
 }
 
+/// Build the llama.cpp sampler chain for the request's sampling options, in the order llama.cpp
+/// applies them: penalties, top_k, top_p, min_p, temperature, then a seeded random pick.
+/// A missing or zero temperature samples greedily.
+fn make_sampler(options: &SamplingOptions) -> LlamaSampler {
+    let mut samplers = Vec::new();
+
+    let repetition_penalty = options.repetition_penalty.unwrap_or(1.0);
+    let frequency_penalty = options.frequency_penalty.unwrap_or(0.0);
+    let presence_penalty = options.presence_penalty.unwrap_or(0.0);
+    if repetition_penalty != 1.0 || frequency_penalty != 0.0 || presence_penalty != 0.0 {
+        samplers.push(LlamaSampler::penalties(
+            PENALTY_LAST_N,
+            repetition_penalty,
+            frequency_penalty,
+            presence_penalty,
+        ));
+    }
+
+    let temperature = options.temperature.unwrap_or(0.0);
+    if temperature <= 0.0 {
+        samplers.push(LlamaSampler::greedy());
+        return LlamaSampler::chain_simple(samplers);
+    }
+
+    if let Some(top_k) = options.top_k.filter(|top_k| *top_k > 0) {
+        samplers.push(LlamaSampler::top_k(top_k));
+    }
+    if let Some(top_p) = options.top_p.filter(|top_p| *top_p < 1.0) {
+        samplers.push(LlamaSampler::top_p(top_p, 1));
+    }
+    if let Some(min_p) = options.min_p.filter(|min_p| *min_p > 0.0) {
+        samplers.push(LlamaSampler::min_p(min_p, 1));
+    }
+    samplers.push(LlamaSampler::temp(temperature));
+
+    // llama.cpp seeds are 32 bits, so only the low bits of the request's seed are used
+    let seed = match options.seed {
+        Some(seed) => seed as u32,
+        None => rand::rng().random(),
+    };
+    samplers.push(LlamaSampler::dist(seed));
+
+    LlamaSampler::chain_simple(samplers)
+}
+
+/// Log probability of `token` in the model's output distribution, before sampling
+fn token_log_prob(logits: &[f32], token: LlamaToken) -> f64 {
+    let max_logit = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max) as f64;
+    let sum_exp: f64 = logits
+        .iter()
+        .map(|logit| (*logit as f64 - max_logit).exp())
+        .sum();
+    logits[token.0 as usize] as f64 - max_logit - sum_exp.ln()
+}
+
+/// Whether the end of generation tokens must be masked out before sampling the next token
+fn suppress_eog(ignore_eos: bool, min_tokens: u32, used_output_tokens: u32) -> bool {
+    ignore_eos || used_output_tokens < min_tokens
+}
+
 fn run_request(
     cancel_token: CancellationToken,
     work_request: WorkRequest,
@@ -220,15 +283,28 @@ fn run_request(
         .map(|u| LlamaToken::new(u as i32))
         .collect();
 
-    let limit = DEFAULT_MAX_TOKENS; // - prompt_tokens;
-    let max_output_tokens = std::cmp::min(
-        work_request
-            .request
-            .stop_conditions
-            .max_tokens
-            .unwrap_or(limit),
-        limit,
-    );
+    // prompt and output must fit in the context
+    let context_size = llama_context.0.n_ctx();
+    let Some(limit) = context_size.checked_sub(tokens_list.len() as u32) else {
+        let err_msg = format!(
+            "prompt of {} tokens does not fit in the context of {context_size} tokens",
+            tokens_list.len()
+        );
+        let _ = work_request
+            .response_channel
+            .blocking_send(Annotated::from_data(LLMEngineOutput::error(err_msg)));
+        return Ok(());
+    };
+    let stop_conditions = &work_request.request.stop_conditions;
+    let max_output_tokens = std::cmp::min(stop_conditions.max_tokens.unwrap_or(limit), limit);
+    let ignore_eos = stop_conditions.ignore_eos.unwrap_or(false);
+    let min_tokens = stop_conditions.min_tokens.unwrap_or(0);
+    if max_output_tokens == 0 {
+        let _ = work_request
+            .response_channel
+            .blocking_send(Annotated::from_data(LLMEngineOutput::length()));
+        return Ok(());
+    }
 
     // create a llama_batch with size 512
     // we use this object to submit token data for decoding
@@ -248,19 +324,32 @@ fn run_request(
         .decode(&mut batch)
         .with_context(|| "llama_decode failed on first pass")?;
 
-    let mut sampler = LlamaSampler::greedy();
+    // This is probably safe for concurrent access
+    let model = LLAMA_MODEL.get().unwrap();
+    let mut sampler = make_sampler(&work_request.request.sampling_options);
     let mut n_cur = batch.n_tokens() as u32;
 
     let mut used_output_tokens = 0;
+    let mut cum_log_prob = 0.0;
     while !cancel_token.is_cancelled() {
         // sample the next token
-        let n_tokens = batch.n_tokens();
-        let token = sampler.sample(&llama_context.0, n_tokens - 1);
+        let last_index = batch.n_tokens() - 1;
+        let mut candidates = llama_context.0.token_data_array_ith(last_index);
+        if suppress_eog(ignore_eos, min_tokens, used_output_tokens) {
+            for candidate in candidates.data.iter_mut() {
+                if model.is_eog_token(candidate.id()) {
+                    candidate.set_logit(f32::NEG_INFINITY);
+                }
+            }
+        }
+        candidates.apply_sampler(&sampler);
+        let token = candidates
+            .selected_token()
+            .with_context(|| "sampler did not select a token")?;
         sampler.accept(token);
 
         // is it an end of stream?
-        // This is probably safe for concurrent access
-        if LLAMA_MODEL.get().unwrap().is_eog_token(token) {
+        if model.is_eog_token(token) {
             work_request
                 .response_channel
                 .blocking_send(Annotated::from_data(LLMEngineOutput::stop()))
@@ -268,15 +357,19 @@ fn run_request(
             break;
         }
 
+        let log_prob = token_log_prob(llama_context.0.get_logits_ith(last_index), token);
+        cum_log_prob += log_prob;
+
         let engine_out = LLMEngineOutput {
             // todo - propagate mdcsum
             token_ids: vec![token.0 as u32],
             tokens: None,
             text: None,
             //text: if output.text.is_empty() { None } else { Some(output.text) },
-            cum_log_probs: None, // TODO output.cumulative_logprob.map(|v| v as f64),
-            log_probs: None,     // TODO  output.logprobs
+            cum_log_probs: Some(cum_log_prob),
+            log_probs: Some(vec![log_prob]),
             finish_reason: None,
+            cached_tokens: None,
         };
         work_request
             .response_channel
@@ -297,7 +390,7 @@ fn run_request(
         n_cur += 1;
 
         used_output_tokens += 1;
-        if used_output_tokens > max_output_tokens {
+        if used_output_tokens >= max_output_tokens {
             let _ = work_request
                 .response_channel
                 .blocking_send(Annotated::from_data(LLMEngineOutput::length()));
@@ -321,3 +414,36 @@ fn run_request(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_log_prob() {
+        let logits = [1.0, 2.0, 3.0];
+        let log_probs: Vec<f64> = (0..3)
+            .map(|token| token_log_prob(&logits, LlamaToken::new(token)))
+            .collect();
+
+        let total: f64 = log_probs.iter().map(|log_prob| log_prob.exp()).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        assert!(log_probs[0] < log_probs[1] && log_probs[1] < log_probs[2]);
+        assert!((log_probs[2] - log_probs[1] - 1.0).abs() < 1e-9);
+
+        // large logits do not overflow
+        let log_prob = token_log_prob(&[1000.0, 1000.0], LlamaToken::new(0));
+        assert!((log_prob - 0.5_f64.ln()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_suppress_eog() {
+        assert!(!suppress_eog(false, 0, 0));
+        assert!(suppress_eog(true, 0, 10));
+
+        // the end of generation can only be sampled once min_tokens were generated
+        assert!(suppress_eog(false, 2, 0));
+        assert!(suppress_eog(false, 2, 1));
+        assert!(!suppress_eog(false, 2, 2));
+    }
+}
@@ -314,7 +314,7 @@ impl
                             system_fingerprint: Some(c.system_fingerprint),
                             service_tier: None,
                         };
-                        let delta = NvCreateChatCompletionStreamResponse{inner};
+                        let delta = NvCreateChatCompletionStreamResponse{inner, reasoning_content: Default::default()};
                         let ann = Annotated{
                             id: None,
                             data: Some(delta),
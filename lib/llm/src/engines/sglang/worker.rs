@@ -751,6 +751,10 @@ async fn output_loop(
                         cum_log_probs: None,
                         log_probs: None,
                         finish_reason: sglang_finish_reason.map(|x| x.into()),
+                        cached_tokens: req_out
+                            .cached_tokens
+                            .get(idx)
+                            .map(|&cached| cached.max(0) as u32),
                     };
                     active.num_output_tokens_so_far = Some(next_total_toks);
                     let _ = active.tx.send(Annotated::from_data(out)).await;
@@ -77,6 +77,7 @@ impl From<Output> for common::llm_backend::LLMEngineOutput {
             cum_log_probs: output.cum_log_prob,
             log_probs: None,
             finish_reason,
+            cached_tokens: None,
         }
     }
 }
@@ -565,7 +565,11 @@ async fn metrics_loop(cancel_token: CancellationToken, mut socket: async_zmq::Pu
     }
 }
 
-fn from_vllm(output: CompletionOutput, previous_total_toks: usize) -> LLMEngineOutput {
+fn from_vllm(
+    output: CompletionOutput,
+    previous_total_toks: usize,
+    cached_tokens: Option<u32>,
+) -> LLMEngineOutput {
     let finish_reason = match output.finish_reason.as_deref() {
         Some("stop") => Some(FinishReason::Stop),
         Some("abort") => Some(FinishReason::Cancelled),
@@ -586,6 +590,7 @@ fn from_vllm(output: CompletionOutput, previous_total_toks: usize) -> LLMEngineO
         cum_log_probs: output.cumulative_logprob.map(|v| v as f64),
         log_probs: None, // TODO  output.logprobs
         finish_reason,
+        cached_tokens,
     }
 }
 
@@ -745,7 +750,11 @@ async fn output_loop(
 
             match active_requests.lock().await.get_mut(&req_out.request_id) {
                 Some(active) => {
-                    let out = from_vllm(vllm_output, active.num_output_tokens_so_far);
+                    let out = from_vllm(
+                        vllm_output,
+                        active.num_output_tokens_so_far,
+                        req_out.num_cached_tokens,
+                    );
                     active.num_output_tokens_so_far = next_total_toks;
                     let _ = active.tx.send(Annotated::from_data(out)).await;
                 }
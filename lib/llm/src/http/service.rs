@@ -32,6 +32,11 @@
 
 mod openai;
 
+pub mod admin;
+pub mod admission;
+pub mod audit;
+pub mod auth;
+pub mod config;
 pub mod discovery;
 pub mod error;
 pub mod metrics;
@@ -45,13 +50,18 @@ pub use axum;
 pub use error::ServiceHttpError;
 pub use metrics::Metrics;
 
+use crate::model_type::ModelType;
+use crate::preprocessor::OpenAIPreprocessor;
 use crate::types::openai::{
     chat_completions::OpenAIChatCompletionsStreamingEngine,
     completions::OpenAICompletionsStreamingEngine,
 };
+use admission::AdmissionController;
+use audit::AuditLog;
+use config::{HttpServiceSettings, ReloadableSettings};
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, RwLock},
     time::Duration,
 };
 
@@ -68,7 +78,12 @@ impl Default for ModelManager {
 
 impl ModelManager {
     pub fn new() -> Self {
-        let state = Arc::new(DeploymentState::new());
+        Self::with_settings(&HttpServiceSettings::default())
+    }
+
+    /// Create a [`ModelManager`] whose metrics and per-request behavior follow `settings`
+    pub fn with_settings(settings: &HttpServiceSettings) -> Self {
+        let state = Arc::new(DeploymentState::new(settings));
         Self { state }
     }
 
@@ -117,13 +132,172 @@ impl ModelManager {
     }
 
     pub fn remove_completions_model(&self, model: &str) -> Result<(), ServiceHttpError> {
-        let mut clients = self.state.completion_engines.lock().unwrap();
-        clients.remove(model)
+        self.state
+            .completion_engines
+            .lock()
+            .unwrap()
+            .remove(model)?;
+        self.prune_preprocessor(model);
+        Ok(())
     }
 
     pub fn remove_chat_completions_model(&self, model: &str) -> Result<(), ServiceHttpError> {
+        self.state
+            .chat_completion_engines
+            .lock()
+            .unwrap()
+            .remove(model)?;
+        self.prune_preprocessor(model);
+        Ok(())
+    }
+
+    /// Serve `/tokenize` and `/detokenize` for `model` with the preprocessor of its engine. The
+    /// preprocessor is dropped with the last engine serving the model.
+    pub fn add_preprocessor(&self, model: &str, preprocessor: Arc<OpenAIPreprocessor>) {
+        self.state
+            .preprocessors
+            .write()
+            .unwrap()
+            .insert(model.to_string(), preprocessor);
+    }
+
+    pub fn remove_preprocessor(&self, model: &str) -> Result<(), ServiceHttpError> {
+        match self.state.preprocessors.write().unwrap().remove(model) {
+            Some(_) => Ok(()),
+            None => Err(ServiceHttpError::ModelNotFound(model.to_string())),
+        }
+    }
+
+    /// Drop the preprocessor of a model which no longer has an engine of either type
+    fn prune_preprocessor(&self, model: &str) {
+        if !self.has_model_any(model) {
+            self.state.preprocessors.write().unwrap().remove(model);
+        }
+    }
+
+    /// Add a weighted backend for `model`; several backends may serve the same public model name,
+    /// e.g. to canary a new deployment. `backend_id` must be unique per model.
+    pub fn add_completions_backend(
+        &self,
+        model: &str,
+        backend_id: &str,
+        weight: u32,
+        engine: OpenAICompletionsStreamingEngine,
+    ) -> Result<(), ServiceHttpError> {
+        let mut clients = self.state.completion_engines.lock().unwrap();
+        clients.add_backend(model, backend_id, weight, engine)
+    }
+
+    /// Add a weighted backend for `model`; see [`ModelManager::add_completions_backend`]
+    pub fn add_chat_completions_backend(
+        &self,
+        model: &str,
+        backend_id: &str,
+        weight: u32,
+        engine: OpenAIChatCompletionsStreamingEngine,
+    ) -> Result<(), ServiceHttpError> {
         let mut clients = self.state.chat_completion_engines.lock().unwrap();
-        clients.remove(model)
+        clients.add_backend(model, backend_id, weight, engine)
+    }
+
+    pub fn remove_completions_backend(
+        &self,
+        model: &str,
+        backend_id: &str,
+    ) -> Result<(), ServiceHttpError> {
+        self.state
+            .completion_engines
+            .lock()
+            .unwrap()
+            .remove_backend(model, backend_id)?;
+        self.prune_preprocessor(model);
+        Ok(())
+    }
+
+    pub fn remove_chat_completions_backend(
+        &self,
+        model: &str,
+        backend_id: &str,
+    ) -> Result<(), ServiceHttpError> {
+        self.state
+            .chat_completion_engines
+            .lock()
+            .unwrap()
+            .remove_backend(model, backend_id)?;
+        self.prune_preprocessor(model);
+        Ok(())
+    }
+
+    /// Serve `target` under the additional public name `alias`. The target is resolved per
+    /// request, so it need not exist yet.
+    pub fn add_alias(
+        &self,
+        model_type: ModelType,
+        alias: &str,
+        target: &str,
+    ) -> Result<(), ServiceHttpError> {
+        match model_type {
+            ModelType::Chat => self
+                .state
+                .chat_completion_engines
+                .lock()
+                .unwrap()
+                .add_alias(alias, target),
+            ModelType::Completion => self
+                .state
+                .completion_engines
+                .lock()
+                .unwrap()
+                .add_alias(alias, target),
+        }
+    }
+
+    pub fn remove_alias(&self, model_type: ModelType, alias: &str) -> Result<(), ServiceHttpError> {
+        match model_type {
+            ModelType::Chat => self
+                .state
+                .chat_completion_engines
+                .lock()
+                .unwrap()
+                .remove_alias(alias),
+            ModelType::Completion => self
+                .state
+                .completion_engines
+                .lock()
+                .unwrap()
+                .remove_alias(alias),
+        }
+    }
+
+    /// `(backend id, weight)` pairs of a model of the given type
+    pub fn list_backends(&self, model_type: ModelType, model: &str) -> Vec<(String, u32)> {
+        match model_type {
+            ModelType::Chat => self
+                .state
+                .chat_completion_engines
+                .lock()
+                .unwrap()
+                .list_backends(model),
+            ModelType::Completion => self
+                .state
+                .completion_engines
+                .lock()
+                .unwrap()
+                .list_backends(model),
+        }
+    }
+
+    /// `(alias, target)` pairs for the given model type
+    pub fn list_aliases(&self, model_type: ModelType) -> Vec<(String, String)> {
+        match model_type {
+            ModelType::Chat => self
+                .state
+                .chat_completion_engines
+                .lock()
+                .unwrap()
+                .list_aliases(),
+            ModelType::Completion => self.state.completion_engines.lock().unwrap().list_aliases(),
+        }
     }
 
     /// Get the Prometheus [`Metrics`] object which tracks request counts and inflight requests
@@ -132,10 +306,20 @@ impl ModelManager {
     }
 }
 
+/// A single deployment serving a public model name; requests are split across the backends of a
+/// model in proportion to their weights
+struct Backend<E> {
+    id: String,
+    weight: u32,
+    engine: E,
+}
+
 struct ModelEngines<E> {
     /// Optional default model name
     default: Option<String>,
-    engines: HashMap<String, E>,
+    engines: HashMap<String, Vec<Backend<E>>>,
+    /// Alias name to target model name
+    aliases: HashMap<String, String>,
 }
 
 impl<E> Default for ModelEngines<E> {
@@ -143,11 +327,12 @@ impl<E> Default for ModelEngines<E> {
         Self {
             default: None,
             engines: HashMap::new(),
+            aliases: HashMap::new(),
         }
     }
 }
 
-impl<E> ModelEngines<E> {
+impl<E: Clone> ModelEngines<E> {
     #[allow(dead_code)]
     fn set_default(&mut self, model: &str) {
         self.default = Some(model.to_string());
@@ -158,14 +343,39 @@ impl<E> ModelEngines<E> {
         self.default = None;
     }
 
+    /// Add a model served by a single backend whose id is the model name
     fn add(&mut self, model: &str, engine: E) -> Result<(), ServiceHttpError> {
-        if self.engines.contains_key(model) {
+        if self.contains(model) {
+            return Err(ServiceHttpError::ModelAlreadyExists(model.to_string()));
+        }
+        self.add_backend(model, model, 1, engine)
+    }
+
+    fn add_backend(
+        &mut self,
+        model: &str,
+        backend_id: &str,
+        weight: u32,
+        engine: E,
+    ) -> Result<(), ServiceHttpError> {
+        if self.aliases.contains_key(model) {
             return Err(ServiceHttpError::ModelAlreadyExists(model.to_string()));
         }
-        self.engines.insert(model.to_string(), engine);
+        let backends = self.engines.entry(model.to_string()).or_default();
+        if backends.iter().any(|b| b.id == backend_id) {
+            return Err(ServiceHttpError::ModelAlreadyExists(format!(
+                "{model} (backend {backend_id})"
+            )));
+        }
+        backends.push(Backend {
+            id: backend_id.to_string(),
+            weight,
+            engine,
+        });
         Ok(())
     }
 
+    /// Remove a model and all of its backends
     fn remove(&mut self, model: &str) -> Result<(), ServiceHttpError> {
         if self.engines.remove(model).is_none() {
             return Err(ServiceHttpError::ModelNotFound(model.to_string()));
@@ -173,17 +383,90 @@ impl<E> ModelEngines<E> {
         Ok(())
     }
 
-    fn get(&self, model: &str) -> Option<&E> {
-        self.engines.get(model)
+    /// Remove a single backend; the model is removed with its last backend
+    fn remove_backend(&mut self, model: &str, backend_id: &str) -> Result<(), ServiceHttpError> {
+        let Some(backends) = self.engines.get_mut(model) else {
+            return Err(ServiceHttpError::ModelNotFound(model.to_string()));
+        };
+        let count = backends.len();
+        backends.retain(|b| b.id != backend_id);
+        if backends.len() == count {
+            return Err(ServiceHttpError::ModelNotFound(format!(
+                "{model} (backend {backend_id})"
+            )));
+        }
+        if backends.is_empty() {
+            self.engines.remove(model);
+        }
+        Ok(())
+    }
+
+    fn add_alias(&mut self, alias: &str, target: &str) -> Result<(), ServiceHttpError> {
+        if self.contains(alias) {
+            return Err(ServiceHttpError::ModelAlreadyExists(alias.to_string()));
+        }
+        self.aliases.insert(alias.to_string(), target.to_string());
+        Ok(())
+    }
+
+    fn remove_alias(&mut self, alias: &str) -> Result<(), ServiceHttpError> {
+        if self.aliases.remove(alias).is_none() {
+            return Err(ServiceHttpError::ModelNotFound(alias.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Resolve aliases and pick a backend at random in proportion to the backend weights.
+    /// Backends with a weight of zero stay registered but receive no traffic.
+    fn get(&self, model: &str) -> Option<E> {
+        let model = self.aliases.get(model).map(String::as_str).unwrap_or(model);
+        let backends = self.engines.get(model)?;
+        let total: u64 = backends.iter().map(|b| b.weight as u64).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = rand::random_range(0..total);
+        backends
+            .iter()
+            .find(|b| {
+                if pick < b.weight as u64 {
+                    return true;
+                }
+                pick -= b.weight as u64;
+                false
+            })
+            .map(|b| b.engine.clone())
     }
 
     fn contains(&self, model: &str) -> bool {
-        self.engines.contains_key(model)
+        self.engines.contains_key(model) || self.aliases.contains_key(model)
     }
 
+    /// Model names, not including aliases
     fn list(&self) -> Vec<String> {
         self.engines.keys().map(|k| k.to_owned()).collect()
     }
+
+    /// `(alias, target)` pairs
+    fn list_aliases(&self) -> Vec<(String, String)> {
+        self.aliases
+            .iter()
+            .map(|(alias, target)| (alias.clone(), target.clone()))
+            .collect()
+    }
+
+    /// `(backend id, weight)` pairs of a model
+    fn list_backends(&self, model: &str) -> Vec<(String, u32)> {
+        self.engines
+            .get(model)
+            .map(|backends| backends.iter().map(|b| (b.id.clone(), b.weight)).collect())
+            .unwrap_or_default()
+    }
+
+    /// The target of `model` if it is an alias
+    fn alias_target(&self, model: &str) -> Option<String> {
+        self.aliases.get(model).cloned()
+    }
 }
 
 /// The DeploymentState is a global state that is shared across all the workers
@@ -193,16 +476,133 @@ pub struct DeploymentState {
     chat_completion_engines: Arc<Mutex<ModelEngines<OpenAIChatCompletionsStreamingEngine>>>,
     metrics: Arc<Metrics>,
     sse_keep_alive: Option<Duration>,
+    reloadable: RwLock<Arc<ReloadableSettings>>,
+    /// Public model names which reject new requests with a 503
+    maintenance: RwLock<HashSet<String>>,
+    admission: AdmissionController,
+    audit: Option<AuditLog>,
+    /// Preprocessors of the models served by a local pipeline, for tokenization requests
+    preprocessors: RwLock<HashMap<String, Arc<OpenAIPreprocessor>>>,
 }
 
 impl DeploymentState {
-    fn new() -> Self {
+    fn new(settings: &HttpServiceSettings) -> Self {
+        let metrics = Arc::new(Metrics::new(&settings.metrics.prefix));
         Self {
             completion_engines: Arc::new(Mutex::new(ModelEngines::default())),
             chat_completion_engines: Arc::new(Mutex::new(ModelEngines::default())),
-            metrics: Arc::new(Metrics::default()),
-            sse_keep_alive: None,
+            admission: AdmissionController::new(settings.admission.clone(), metrics.clone()),
+            metrics,
+            sse_keep_alive: settings
+                .listener
+                .sse_keep_alive_secs
+                .map(Duration::from_secs),
+            reloadable: RwLock::new(Arc::new(ReloadableSettings::from(settings))),
+            maintenance: RwLock::new(HashSet::new()),
+            audit: settings
+                .audit
+                .enabled
+                .then(|| AuditLog::new(&settings.audit)),
+            preprocessors: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// The settings currently applied to each request
+    pub fn reloadable_settings(&self) -> Arc<ReloadableSettings> {
+        self.reloadable.read().unwrap().clone()
+    }
+
+    /// Replace the settings applied to each request; in-flight requests are unaffected
+    pub fn set_reloadable_settings(&self, settings: ReloadableSettings) {
+        *self.reloadable.write().unwrap() = Arc::new(settings);
+    }
+
+    /// Set the model served for requests without a model name, until the next config reload
+    pub fn set_default_model(&self, model: Option<String>) {
+        let mut reloadable = self.reloadable.write().unwrap();
+        let mut settings = ReloadableSettings::clone(&reloadable);
+        settings.default_model = model;
+        *reloadable = Arc::new(settings);
+    }
+
+    /// While in maintenance, requests for `model` and its aliases are rejected with a 503;
+    /// in-flight requests are unaffected
+    pub fn set_maintenance(&self, model: &str, enabled: bool) {
+        let mut maintenance = self.maintenance.write().unwrap();
+        if enabled {
+            maintenance.insert(model.to_string());
+        } else {
+            maintenance.remove(model);
+        }
+    }
+
+    /// Per-model in-flight limits and the admission queue
+    pub fn admission(&self) -> &AdmissionController {
+        &self.admission
+    }
+
+    /// Models currently in maintenance
+    pub fn maintenance_models(&self) -> Vec<String> {
+        self.maintenance.read().unwrap().iter().cloned().collect()
+    }
+
+    /// Whether `model`, or the model it is an alias of, is in maintenance
+    pub fn in_maintenance(&self, model: &str) -> bool {
+        let maintenance = self.maintenance.read().unwrap();
+        if maintenance.is_empty() {
+            return false;
+        }
+        if maintenance.contains(model) {
+            return true;
         }
+        let chat_target = self
+            .chat_completion_engines
+            .lock()
+            .unwrap()
+            .alias_target(model);
+        let cmpl_target = self.completion_engines.lock().unwrap().alias_target(model);
+        chat_target
+            .into_iter()
+            .chain(cmpl_target)
+            .any(|target| maintenance.contains(&target))
+    }
+
+    /// The completions model serving requests for `model`: its alias target, or `model` itself
+    pub fn completions_model(&self, model: &str) -> String {
+        self.completion_engines
+            .lock()
+            .unwrap()
+            .alias_target(model)
+            .unwrap_or_else(|| model.to_string())
+    }
+
+    /// The chat completions model serving requests for `model`: its alias target, or `model`
+    /// itself
+    pub fn chat_completions_model(&self, model: &str) -> String {
+        self.chat_completion_engines
+            .lock()
+            .unwrap()
+            .alias_target(model)
+            .unwrap_or_else(|| model.to_string())
+    }
+
+    /// The preprocessor of `model`, resolving aliases
+    fn get_preprocessor(&self, model: &str) -> Result<Arc<OpenAIPreprocessor>, ServiceHttpError> {
+        let preprocessors = self.preprocessors.read().unwrap();
+        if let Some(preprocessor) = preprocessors.get(model) {
+            return Ok(preprocessor.clone());
+        }
+        let chat_target = self
+            .chat_completion_engines
+            .lock()
+            .unwrap()
+            .alias_target(model);
+        let cmpl_target = self.completion_engines.lock().unwrap().alias_target(model);
+        chat_target
+            .into_iter()
+            .chain(cmpl_target)
+            .find_map(|target| preprocessors.get(&target).cloned())
+            .ok_or(ServiceHttpError::ModelNotFound(model.to_string()))
     }
 
     fn get_completions_engine(
@@ -213,7 +613,6 @@ impl DeploymentState {
             .lock()
             .unwrap()
             .get(model)
-            .cloned()
             .ok_or(ServiceHttpError::ModelNotFound(model.to_string()))
     }
 
@@ -225,7 +624,6 @@ impl DeploymentState {
             .lock()
             .unwrap()
             .get(model)
-            .cloned()
             .ok_or(ServiceHttpError::ModelNotFound(model.to_string()))
     }
 }
@@ -0,0 +1,303 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Admin API for managing the models served by a frontend at runtime.
+//!
+//! The routes are served under `/admin` when `[admin] enabled = true` and require
+//! `Authorization: Bearer <admin.api_key>`:
+//!
+//! - `GET /admin/models`: models with their backends and instance counts, aliases, the default
+//!   model and the models in maintenance
+//! - `POST /admin/models[?backend=<id>]`: register a [`ModelRegistration`] (a backend or an alias)
+//! - `DELETE /admin/models/{model_type}/{name}[?backend=<id>]`: remove a backend or alias
+//! - `PUT /admin/default_model`: `{"model": "<name>"}` or `{"model": null}`
+//! - `PUT /admin/maintenance/{name}`: `{"enabled": true}` rejects new requests for the model with a
+//!   503 until disabled again
+//!
+//! Registrations use the same [`ModelWatchState::register`] and [`ModelWatchState::deregister`]
+//! code path as models discovered in etcd and are keyed the same way, so a later etcd update of the
+//! same key replaces them. They are held only by this frontend; use `llmctl http` to register
+//! models with every frontend.
+
+use std::sync::{Arc, RwLock};
+
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::{delete, get, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+use super::{
+    discovery::{ModelAlias, ModelRegistration, ModelWatchState},
+    error::ServiceHttpError,
+    openai::ErrorResponse,
+    DeploymentState, ModelManager, RouteDoc,
+};
+use crate::model_type::ModelType;
+
+/// The [`ModelWatchState`]s of a frontend, one per discovered model type. Watchers are added after
+/// the service is built, once the distributed runtime is available.
+#[derive(Clone, Default)]
+pub struct ModelWatchers(Arc<RwLock<Vec<Arc<ModelWatchState>>>>);
+
+impl ModelWatchers {
+    pub fn add(&self, state: Arc<ModelWatchState>) {
+        self.0.write().unwrap().push(state);
+    }
+
+    pub fn get(&self, model_type: ModelType) -> Option<Arc<ModelWatchState>> {
+        self.0
+            .read()
+            .unwrap()
+            .iter()
+            .find(|w| w.model_type == model_type)
+            .cloned()
+    }
+}
+
+/// State of the admin routes
+#[derive(Clone)]
+pub struct AdminState {
+    pub manager: ModelManager,
+    pub watchers: ModelWatchers,
+    pub api_key: String,
+}
+
+impl AdminState {
+    fn deployment(&self) -> Arc<DeploymentState> {
+        self.manager.state()
+    }
+}
+
+/// Response of `GET /admin/models`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminModels {
+    pub default_model: Option<String>,
+    pub models: Vec<AdminModel>,
+    pub aliases: Vec<ModelAlias>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminModel {
+    pub name: String,
+    pub model_type: ModelType,
+    pub maintenance: bool,
+    pub backends: Vec<AdminBackend>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminBackend {
+    pub id: String,
+    pub weight: u32,
+    /// Live instances of the backend's endpoint; `None` for engines not discovered through etcd
+    pub instances: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct BackendQuery {
+    backend: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DefaultModelRequest {
+    model: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MaintenanceRequest {
+    enabled: bool,
+}
+
+/// Require the admin bearer token
+async fn admin_auth(State(state): State<AdminState>, request: Request, next: Next) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|token| bool::from(token.trim().as_bytes().ct_eq(state.api_key.as_bytes())));
+
+    if !authorized {
+        return ErrorResponse::unauthorized().into_response();
+    }
+    next.run(request).await
+}
+
+fn parse_model_type(model_type: &str) -> Result<ModelType, (StatusCode, Json<ErrorResponse>)> {
+    ModelType::all()
+        .into_iter()
+        .find(|t| t.as_str() == model_type)
+        .ok_or_else(|| ErrorResponse::bad_request(&format!("Unknown model type: {model_type}")))
+}
+
+fn watcher(
+    state: &AdminState,
+    model_type: ModelType,
+) -> Result<Arc<ModelWatchState>, (StatusCode, Json<ErrorResponse>)> {
+    state.watchers.get(model_type).ok_or_else(|| {
+        ErrorResponse::bad_request(&format!(
+            "Model discovery is not enabled for {model_type} models"
+        ))
+    })
+}
+
+async fn list_models(State(state): State<AdminState>) -> Json<AdminModels> {
+    let deployment = state.deployment();
+    let maintenance = deployment.maintenance_models();
+
+    let mut models = Vec::new();
+    let mut aliases = Vec::new();
+
+    for model_type in ModelType::all() {
+        let names = match model_type {
+            ModelType::Chat => state.manager.list_chat_completions_models(),
+            ModelType::Completion => state.manager.list_completions_models(),
+        };
+        let watcher = state.watchers.get(model_type);
+
+        for name in names {
+            let backends = state
+                .manager
+                .list_backends(model_type, &name)
+                .into_iter()
+                .map(|(id, weight)| AdminBackend {
+                    instances: watcher.as_ref().and_then(|w| w.instance_count(&id)),
+                    id,
+                    weight,
+                })
+                .collect();
+            models.push(AdminModel {
+                maintenance: maintenance.contains(&name),
+                name,
+                model_type,
+                backends,
+            });
+        }
+
+        aliases.extend(
+            state
+                .manager
+                .list_aliases(model_type)
+                .into_iter()
+                .map(|(name, target)| ModelAlias {
+                    name,
+                    target,
+                    model_type,
+                }),
+        );
+    }
+
+    Json(AdminModels {
+        default_model: deployment.reloadable_settings().default_model.clone(),
+        models,
+        aliases,
+    })
+}
+
+async fn add_model(
+    State(state): State<AdminState>,
+    Query(query): Query<BackendQuery>,
+    Json(registration): Json<ModelRegistration>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    let watcher = watcher(&state, registration.model_type())?;
+    let key = watcher.registration_key(registration.name(), query.backend.as_deref());
+
+    tracing::info!(key, "admin: registering {}", registration.name());
+
+    watcher.register(&key, registration).await.map_err(|e| {
+        match e.downcast_ref::<ServiceHttpError>() {
+            Some(ServiceHttpError::ModelAlreadyExists(model)) => {
+                ErrorResponse::conflict(&format!("Model already exists: {model}"))
+            }
+            _ => ErrorResponse::bad_request(&e.to_string()),
+        }
+    })?;
+
+    Ok((StatusCode::CREATED, Json(serde_json::json!({ "key": key }))).into_response())
+}
+
+async fn remove_model(
+    State(state): State<AdminState>,
+    Path((model_type, name)): Path<(String, String)>,
+    Query(query): Query<BackendQuery>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let watcher = watcher(&state, parse_model_type(&model_type)?)?;
+    let key = watcher.registration_key(&name, query.backend.as_deref());
+
+    tracing::info!(key, "admin: removing {}", name);
+
+    watcher
+        .deregister(&key)
+        .map_err(|_| ErrorResponse::model_not_found())?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn set_default_model(
+    State(state): State<AdminState>,
+    Json(request): Json<DefaultModelRequest>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    if let Some(model) = &request.model {
+        if !state.manager.has_model_any(model) {
+            return Err(ErrorResponse::model_not_found());
+        }
+    }
+
+    tracing::info!("admin: default model set to {:?}", request.model);
+    state.deployment().set_default_model(request.model);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn set_maintenance(
+    State(state): State<AdminState>,
+    Path(name): Path<String>,
+    Json(request): Json<MaintenanceRequest>,
+) -> StatusCode {
+    tracing::info!("admin: maintenance for {} set to {}", name, request.enabled);
+    state.deployment().set_maintenance(&name, request.enabled);
+    StatusCode::NO_CONTENT
+}
+
+/// Create an Axum [`Router`] for the admin API
+pub fn admin_router(state: AdminState) -> (Vec<RouteDoc>, Router) {
+    let docs = vec![
+        RouteDoc::new(axum::http::Method::GET, "/admin/models"),
+        RouteDoc::new(axum::http::Method::POST, "/admin/models"),
+        RouteDoc::new(
+            axum::http::Method::DELETE,
+            "/admin/models/{model_type}/{name}",
+        ),
+        RouteDoc::new(axum::http::Method::PUT, "/admin/default_model"),
+        RouteDoc::new(axum::http::Method::PUT, "/admin/maintenance/{name}"),
+    ];
+
+    let router = Router::new()
+        .route("/admin/models", get(list_models).post(add_model))
+        .route("/admin/models/{model_type}/{*name}", delete(remove_model))
+        .route("/admin/default_model", put(set_default_model))
+        .route("/admin/maintenance/{*name}", put(set_maintenance))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            admin_auth,
+        ))
+        .with_state(state);
+
+    (docs, router)
+}
@@ -0,0 +1,445 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Admission control for the OpenAI routes.
+//!
+//! Each model may limit its number of in-flight requests. Requests beyond the limit wait in a
+//! bounded per-model queue ordered by [`Priority`], taken from the [`PRIORITY_HEADER`] header or
+//! the `nvext.priority` field, and then by arrival. When the queue is full, a request displaces the
+//! newest queued request of a lower priority, or is rejected with a 429 if there is none. Requests
+//! which wait longer than the queue timeout are rejected with a 503. Both rejections carry a
+//! `Retry-After` header.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+};
+use tokio::sync::oneshot;
+
+use super::{config::AdmissionSettings, openai::ErrorResponse, Metrics};
+
+pub use crate::protocols::openai::nvext::Priority;
+
+/// Request header selecting the [`Priority`] of a request; takes precedence over `nvext.priority`
+pub const PRIORITY_HEADER: &str = "x-dynamo-priority";
+
+/// Value of the `reason` label when the queue is full
+pub const REJECTED_QUEUE_FULL: &str = "queue_full";
+
+/// Value of the `reason` label when a queued request is displaced by a higher priority request
+pub const REJECTED_DISPLACED: &str = "displaced";
+
+/// Value of the `reason` label when a request waited longer than the queue timeout
+pub const REJECTED_QUEUE_TIMEOUT: &str = "queue_timeout";
+
+/// Queue position: higher priorities first, then arrival order
+type WaitKey = (Reverse<Priority>, u64);
+
+#[derive(Default)]
+struct ModelQueue {
+    inflight: usize,
+    waiting: BTreeMap<WaitKey, oneshot::Sender<()>>,
+}
+
+struct Inner {
+    settings: AdmissionSettings,
+    metrics: Arc<Metrics>,
+    queues: Mutex<HashMap<String, ModelQueue>>,
+    next_id: AtomicU64,
+}
+
+/// Per-model in-flight limits with a bounded priority queue
+#[derive(Clone)]
+pub struct AdmissionController {
+    inner: Arc<Inner>,
+}
+
+/// Reason a request was not admitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// The queue was full of requests of the same or a higher priority
+    QueueFull,
+    /// The request was queued, then displaced by a request of a higher priority
+    Displaced,
+    /// The request waited longer than the queue timeout
+    QueueTimeout,
+}
+
+impl Rejection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rejection::QueueFull => REJECTED_QUEUE_FULL,
+            Rejection::Displaced => REJECTED_DISPLACED,
+            Rejection::QueueTimeout => REJECTED_QUEUE_TIMEOUT,
+        }
+    }
+}
+
+/// A rejection together with the `Retry-After` hint sent to the client
+pub struct RejectionResponse {
+    pub rejection: Rejection,
+    pub retry_after: Duration,
+}
+
+impl IntoResponse for RejectionResponse {
+    fn into_response(self) -> Response {
+        let (status, body) = match self.rejection {
+            Rejection::QueueFull | Rejection::Displaced => {
+                ErrorResponse::too_many_requests("Too many requests for this model")
+            }
+            Rejection::QueueTimeout => {
+                ErrorResponse::service_unavailable("Timed out waiting for capacity")
+            }
+        };
+        (
+            status,
+            [(
+                header::RETRY_AFTER,
+                self.retry_after.as_secs().max(1).to_string(),
+            )],
+            body,
+        )
+            .into_response()
+    }
+}
+
+/// Holds one of a model's in-flight slots; dropping it admits the next queued request
+pub struct AdmissionPermit {
+    slot: Option<(Arc<Inner>, String)>,
+}
+
+impl Drop for AdmissionPermit {
+    fn drop(&mut self) {
+        if let Some((inner, model)) = self.slot.take() {
+            inner.release(&model);
+        }
+    }
+}
+
+impl AdmissionController {
+    pub fn new(settings: AdmissionSettings, metrics: Arc<Metrics>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                settings,
+                metrics,
+                queues: Mutex::new(HashMap::new()),
+                next_id: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Number of in-flight requests holding a permit for `model`
+    pub fn inflight(&self, model: &str) -> usize {
+        let queues = self.inner.queues.lock().unwrap();
+        queues.get(model).map_or(0, |q| q.inflight)
+    }
+
+    /// Wait until `model` has a free in-flight slot. Returns immediately for models without a
+    /// limit. Dropping the returned future leaves the queue.
+    pub async fn admit(
+        &self,
+        model: &str,
+        priority: Priority,
+    ) -> Result<AdmissionPermit, RejectionResponse> {
+        let inner = &self.inner;
+        let Some(limit) = inner.settings.max_inflight(model) else {
+            return Ok(AdmissionPermit { slot: None });
+        };
+
+        let (key, rx) = {
+            let mut queues = inner.queues.lock().unwrap();
+            let queue = queues.entry(model.to_string()).or_default();
+
+            if queue.inflight < limit && queue.waiting.is_empty() {
+                queue.inflight += 1;
+                return Ok(self.permit(model));
+            }
+
+            if queue.waiting.len() >= inner.settings.max_queued {
+                // displace the newest request of the lowest priority, if it is lower than ours
+                match queue.waiting.last_key_value() {
+                    Some(((Reverse(lowest), _), _)) if *lowest < priority => {
+                        let ((Reverse(lowest), _), _displaced) = queue.waiting.pop_last().unwrap();
+                        inner.metrics.dec_queued_gauge(model, lowest.as_str());
+                    }
+                    _ => return Err(inner.reject(model, Rejection::QueueFull)),
+                }
+            }
+
+            let key = (
+                Reverse(priority),
+                inner.next_id.fetch_add(1, Ordering::Relaxed),
+            );
+            let (tx, rx) = oneshot::channel();
+            queue.waiting.insert(key, tx);
+            inner.metrics.inc_queued_gauge(model, priority.as_str());
+            (key, rx)
+        };
+
+        let mut waiter = Waiter {
+            inner: inner.clone(),
+            model,
+            key,
+            rx,
+            done: false,
+        };
+
+        let start = Instant::now();
+        let timeout = Duration::from_millis(inner.settings.queue_timeout_ms);
+        match tokio::time::timeout(timeout, &mut waiter.rx).await {
+            Ok(Ok(())) => {
+                waiter.done = true;
+                inner.metrics.observe_queue_duration(
+                    model,
+                    priority.as_str(),
+                    start.elapsed().as_secs_f64(),
+                );
+                Ok(self.permit(model))
+            }
+            Ok(Err(_)) => {
+                // the sender was dropped when the request was displaced
+                waiter.done = true;
+                Err(inner.reject(model, Rejection::Displaced))
+            }
+            Err(_) => {
+                drop(waiter);
+                Err(inner.reject(model, Rejection::QueueTimeout))
+            }
+        }
+    }
+
+    fn permit(&self, model: &str) -> AdmissionPermit {
+        AdmissionPermit {
+            slot: Some((self.inner.clone(), model.to_string())),
+        }
+    }
+}
+
+impl Inner {
+    fn reject(&self, model: &str, rejection: Rejection) -> RejectionResponse {
+        tracing::debug!(model, "request rejected: {}", rejection.as_str());
+        self.metrics.inc_rejected_counter(model, rejection.as_str());
+        RejectionResponse {
+            rejection,
+            retry_after: Duration::from_secs(self.settings.retry_after_secs),
+        }
+    }
+
+    /// Hand the slot to the first queued request still waiting, or free it
+    fn release(&self, model: &str) {
+        let mut queues = self.queues.lock().unwrap();
+        let Some(queue) = queues.get_mut(model) else {
+            return;
+        };
+
+        while let Some(((Reverse(priority), _), tx)) = queue.waiting.pop_first() {
+            self.metrics.dec_queued_gauge(model, priority.as_str());
+            if tx.send(()).is_ok() {
+                return;
+            }
+        }
+
+        queue.inflight -= 1;
+        if queue.inflight == 0 {
+            queues.remove(model);
+        }
+    }
+}
+
+/// A queued request; leaves the queue when dropped before it was admitted or displaced
+struct Waiter<'a> {
+    inner: Arc<Inner>,
+    model: &'a str,
+    key: WaitKey,
+    rx: oneshot::Receiver<()>,
+    done: bool,
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+
+        let removed = {
+            let mut queues = self.inner.queues.lock().unwrap();
+            queues
+                .get_mut(self.model)
+                .and_then(|q| q.waiting.remove(&self.key))
+                .is_some()
+        };
+
+        if removed {
+            let Reverse(priority) = self.key.0;
+            self.inner
+                .metrics
+                .dec_queued_gauge(self.model, priority.as_str());
+        } else if self.rx.try_recv().is_ok() {
+            // admitted concurrently with the timeout or cancellation; pass the slot on
+            self.inner.release(self.model);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller(max_inflight: usize, max_queued: usize, timeout_ms: u64) -> AdmissionController {
+        let settings = AdmissionSettings {
+            max_inflight: Some(max_inflight),
+            max_queued,
+            queue_timeout_ms: timeout_ms,
+            ..Default::default()
+        };
+        AdmissionController::new(settings, Arc::new(Metrics::default()))
+    }
+
+    #[tokio::test]
+    async fn test_unlimited() {
+        let controller =
+            AdmissionController::new(AdmissionSettings::default(), Arc::new(Metrics::default()));
+        let _permits: Vec<_> =
+            futures::future::join_all((0..10).map(|_| controller.admit("foo", Priority::Normal)))
+                .await;
+        assert_eq!(controller.inflight("foo"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_priority_order_and_release() {
+        let controller = controller(1, 8, 10_000);
+        let first = controller
+            .admit("foo", Priority::Normal)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(controller.inflight("foo"), 1);
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut tasks = Vec::new();
+        for (name, priority) in [
+            ("batch", Priority::Batch),
+            ("normal", Priority::Normal),
+            ("interactive", Priority::Interactive),
+        ] {
+            let controller = controller.clone();
+            let tx = tx.clone();
+            tasks.push(tokio::spawn(async move {
+                let permit = controller.admit("foo", priority).await.ok().unwrap();
+                tx.send(name).unwrap();
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                drop(permit);
+            }));
+            // make the arrival order deterministic
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(controller.inner.metrics.get_queued_count("foo", "batch"), 1);
+
+        drop(first);
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let order: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        assert_eq!(order, vec!["interactive", "normal", "batch"]);
+        assert_eq!(controller.inflight("foo"), 0);
+        assert_eq!(controller.inner.metrics.get_queued_count("foo", "batch"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_queue_full_and_displacement() {
+        let controller = controller(1, 1, 10_000);
+        let first = controller
+            .admit("foo", Priority::Normal)
+            .await
+            .ok()
+            .unwrap();
+
+        let queued = {
+            let controller = controller.clone();
+            tokio::spawn(async move { controller.admit("foo", Priority::Batch).await.err() })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        // the queue is full of requests of the same or a higher priority
+        let rejected = controller
+            .admit("foo", Priority::Batch)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(rejected.rejection, Rejection::QueueFull);
+
+        // a higher priority request displaces the queued batch request
+        let interactive = {
+            let controller = controller.clone();
+            tokio::spawn(
+                async move { controller.admit("foo", Priority::Interactive).await.is_ok() },
+            )
+        };
+        let displaced = queued.await.unwrap().unwrap();
+        assert_eq!(displaced.rejection, Rejection::Displaced);
+
+        drop(first);
+        assert!(interactive.await.unwrap());
+        let metrics = &controller.inner.metrics;
+        assert_eq!(metrics.get_rejected_count("foo", REJECTED_QUEUE_FULL), 1);
+        assert_eq!(metrics.get_rejected_count("foo", REJECTED_DISPLACED), 1);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_and_cancellation() {
+        let controller = controller(1, 4, 20);
+        let first = controller
+            .admit("foo", Priority::Normal)
+            .await
+            .ok()
+            .unwrap();
+
+        let rejected = controller
+            .admit("foo", Priority::Normal)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(rejected.rejection, Rejection::QueueTimeout);
+        assert_eq!(rejected.retry_after, Duration::from_secs(1));
+
+        // a cancelled waiter leaves the queue
+        let waiting = {
+            let controller = controller.clone();
+            tokio::spawn(async move { controller.admit("foo", Priority::Normal).await.is_ok() })
+        };
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        waiting.abort();
+        let _ = waiting.await;
+        assert_eq!(
+            controller.inner.metrics.get_queued_count("foo", "normal"),
+            0
+        );
+
+        drop(first);
+        assert_eq!(controller.inflight("foo"), 0);
+        assert!(controller.admit("foo", Priority::Normal).await.is_ok());
+    }
+}
@@ -0,0 +1,691 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Audit log of the requests served by the OpenAI routes.
+//!
+//! When `[audit] enabled = true`, one [`AuditRecord`] per sampled request is appended as a JSON line
+//! to `audit.path` once its response has completed, failed or been cancelled. Streamed responses
+//! are aggregated from their chunks with the same aggregators used for non-streaming requests.
+//!
+//! Prompts and responses are recorded according to `audit.redaction`: verbatim, as a BLAKE3 hash,
+//! or not at all. Records are written by a background thread; before the file would grow beyond
+//! `audit.max_file_bytes` it is renamed to `<path>.1`, older files shifting to `<path>.2` and so
+//! on up to `audit.max_files`.
+//!
+//! Requests rejected before they reach an engine (unknown model, maintenance, admission) are not
+//! recorded; they are counted by the service [`super::Metrics`].
+
+use std::{
+    fs::{self, File, OpenOptions},
+    future::Future,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::mpsc,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use async_stream::stream;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    config::{AuditSettings, Redaction},
+    metrics::Endpoint,
+    DeploymentState,
+};
+use crate::protocols::{
+    openai::{chat_completions::NvCreateChatCompletionResponse, completions::CompletionResponse},
+    Annotated,
+};
+use crate::types::openai::{
+    chat_completions::NvCreateChatCompletionRequest, completions::CompletionRequest,
+};
+
+type DataStream<T> = Pin<Box<dyn Stream<Item = T> + Send + Sync>>;
+
+/// One line of the audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Unix time at which the request was issued to the engine, in milliseconds
+    pub timestamp_ms: u64,
+    pub request_id: String,
+    pub endpoint: String,
+    pub model: String,
+    /// Id of the API key the request was made with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    pub streaming: bool,
+    /// Messages of a chat request or prompt of a completions request, if not redacted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_hash: Option<String>,
+    pub sampling: SamplingParams,
+    pub choices: Vec<AuditChoice>,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    /// Time from issuing the request to the first response chunk, in milliseconds
+    pub first_chunk_ms: Option<u64>,
+    pub duration_ms: u64,
+    pub status: AuditStatus,
+    pub error: Option<String>,
+}
+
+/// Sampling parameters of a request, after the per-model defaults were applied
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SamplingParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<async_openai::types::Stop>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub n: Option<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditChoice {
+    pub index: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_hash: Option<String>,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditStatus {
+    Success,
+    Error,
+    /// The client disconnected before the response completed
+    Cancelled,
+}
+
+/// Requests whose prompt and sampling parameters are recorded in the audit log
+pub trait AuditRequest {
+    fn model(&self) -> &str;
+    fn prompt(&self) -> serde_json::Value;
+    fn sampling(&self) -> SamplingParams;
+}
+
+/// Aggregated responses whose choices and token counts are recorded in the audit log
+pub trait AuditResponse {
+    /// Text and finish reason of each choice, in index order
+    fn choices(&self) -> Vec<(String, Option<String>)>;
+
+    /// Prompt and completion token counts
+    fn usage(&self) -> Option<(u32, u32)>;
+}
+
+impl AuditRequest for NvCreateChatCompletionRequest {
+    fn model(&self) -> &str {
+        &self.inner.model
+    }
+
+    fn prompt(&self) -> serde_json::Value {
+        serde_json::to_value(&self.inner.messages).unwrap_or_default()
+    }
+
+    #[allow(deprecated)]
+    fn sampling(&self) -> SamplingParams {
+        let inner = &self.inner;
+        SamplingParams {
+            max_tokens: inner.max_completion_tokens.or(inner.max_tokens),
+            temperature: inner.temperature,
+            top_p: inner.top_p,
+            frequency_penalty: inner.frequency_penalty,
+            presence_penalty: inner.presence_penalty,
+            stop: inner.stop.clone(),
+            seed: inner.seed,
+            n: inner.n,
+        }
+    }
+}
+
+impl AuditRequest for CompletionRequest {
+    fn model(&self) -> &str {
+        &self.inner.model
+    }
+
+    fn prompt(&self) -> serde_json::Value {
+        serde_json::to_value(&self.inner.prompt).unwrap_or_default()
+    }
+
+    fn sampling(&self) -> SamplingParams {
+        let inner = &self.inner;
+        SamplingParams {
+            max_tokens: inner.max_tokens,
+            temperature: inner.temperature,
+            top_p: inner.top_p,
+            frequency_penalty: inner.frequency_penalty,
+            presence_penalty: inner.presence_penalty,
+            stop: inner.stop.clone(),
+            seed: inner.seed,
+            n: inner.n,
+        }
+    }
+}
+
+impl AuditResponse for NvCreateChatCompletionResponse {
+    fn choices(&self) -> Vec<(String, Option<String>)> {
+        self.inner
+            .choices
+            .iter()
+            .map(|choice| {
+                // finish reasons serialize to their OpenAI names
+                let finish_reason = choice
+                    .finish_reason
+                    .as_ref()
+                    .and_then(|reason| serde_json::to_value(reason).ok())
+                    .and_then(|reason| reason.as_str().map(str::to_string));
+                (
+                    choice.message.content.clone().unwrap_or_default(),
+                    finish_reason,
+                )
+            })
+            .collect()
+    }
+
+    fn usage(&self) -> Option<(u32, u32)> {
+        let usage = self.inner.usage.as_ref()?;
+        Some((usage.prompt_tokens, usage.completion_tokens))
+    }
+}
+
+impl AuditResponse for CompletionResponse {
+    fn choices(&self) -> Vec<(String, Option<String>)> {
+        self.choices
+            .iter()
+            .map(|choice| (choice.text.clone(), choice.finish_reason.clone()))
+            .collect()
+    }
+
+    fn usage(&self) -> Option<(u32, u32)> {
+        let usage = self.usage.as_ref()?;
+        Some((
+            usage.prompt_tokens.max(0) as u32,
+            usage.completion_tokens.max(0) as u32,
+        ))
+    }
+}
+
+/// Sends the audit records of sampled requests to a background writer thread
+pub struct AuditLog {
+    redaction: Redaction,
+    sample_rate: f64,
+    tx: mpsc::Sender<AuditRecord>,
+}
+
+impl AuditLog {
+    pub fn new(settings: &AuditSettings) -> Self {
+        let (tx, rx) = mpsc::channel::<AuditRecord>();
+        let mut file = RotatingFile::new(
+            settings.path.clone(),
+            settings.max_file_bytes,
+            settings.max_files,
+        );
+
+        // the thread exits once the log and all its entries are dropped
+        std::thread::Builder::new()
+            .name("audit-log".to_string())
+            .spawn(move || {
+                for record in rx {
+                    if let Err(e) = file.write_record(&record) {
+                        tracing::error!(
+                            path = %file.path.display(),
+                            request_id = %record.request_id,
+                            "Failed to write audit record: {e}"
+                        );
+                    }
+                }
+            })
+            .expect("failed to spawn the audit log writer");
+
+        Self {
+            redaction: settings.redaction,
+            sample_rate: settings.sample_rate.clamp(0.0, 1.0),
+            tx,
+        }
+    }
+
+    /// Start the audit record of a request; the entry is disabled if the request is not sampled
+    pub fn start<R: AuditRequest>(
+        &self,
+        request_id: &str,
+        endpoint: Endpoint,
+        request: &R,
+        key_id: Option<&str>,
+        streaming: bool,
+    ) -> AuditEntry {
+        if self.sample_rate < 1.0 && !rand::random_bool(self.sample_rate) {
+            return AuditEntry::disabled();
+        }
+
+        let prompt = request.prompt();
+        let (prompt, prompt_hash) = match self.redaction {
+            Redaction::None => (Some(prompt), None),
+            Redaction::Hash => (None, Some(hash(&prompt.to_string()))),
+            Redaction::Omit => (None, None),
+        };
+
+        AuditEntry(Some(PendingRecord {
+            tx: self.tx.clone(),
+            redaction: self.redaction,
+            start: Instant::now(),
+            completed: false,
+            record: AuditRecord {
+                timestamp_ms: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64,
+                request_id: request_id.to_string(),
+                endpoint: endpoint.as_str().to_string(),
+                model: request.model().to_string(),
+                key_id: key_id.map(str::to_string),
+                streaming,
+                prompt,
+                prompt_hash,
+                sampling: request.sampling(),
+                choices: Vec::new(),
+                prompt_tokens: None,
+                completion_tokens: None,
+                first_chunk_ms: None,
+                duration_ms: 0,
+                status: AuditStatus::Cancelled,
+                error: None,
+            },
+        }))
+    }
+}
+
+impl DeploymentState {
+    /// Start the audit record of a request; the entry is disabled if the audit log is disabled or
+    /// the request is not sampled
+    pub fn audit_entry<R: AuditRequest>(
+        &self,
+        request_id: &str,
+        endpoint: Endpoint,
+        request: &R,
+        key_id: Option<&str>,
+        streaming: bool,
+    ) -> AuditEntry {
+        match &self.audit {
+            Some(audit) => audit.start(request_id, endpoint, request, key_id, streaming),
+            None => AuditEntry::disabled(),
+        }
+    }
+}
+
+/// The audit record of a request; written to the audit log when dropped
+pub struct AuditEntry(Option<PendingRecord>);
+
+struct PendingRecord {
+    tx: mpsc::Sender<AuditRecord>,
+    redaction: Redaction,
+    start: Instant,
+    completed: bool,
+    record: AuditRecord,
+}
+
+impl AuditEntry {
+    /// An entry which records nothing
+    pub fn disabled() -> Self {
+        AuditEntry(None)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Record the aggregated response; the request completed successfully unless an error was set
+    pub fn set_response<R: AuditResponse>(&mut self, response: &R) {
+        let Some(pending) = &mut self.0 else {
+            return;
+        };
+        pending.completed = true;
+
+        let record = &mut pending.record;
+        record.choices = response
+            .choices()
+            .into_iter()
+            .enumerate()
+            .map(|(index, (text, finish_reason))| {
+                let (text, text_hash) = match pending.redaction {
+                    Redaction::None => (Some(text), None),
+                    Redaction::Hash => (None, Some(hash(&text))),
+                    Redaction::Omit => (None, None),
+                };
+                AuditChoice {
+                    index: index as u32,
+                    text,
+                    text_hash,
+                    finish_reason,
+                }
+            })
+            .collect();
+
+        if let Some((prompt_tokens, completion_tokens)) = response.usage() {
+            record.prompt_tokens = Some(prompt_tokens);
+            record.completion_tokens = Some(completion_tokens);
+        }
+    }
+
+    pub fn set_error(&mut self, error: impl std::fmt::Display) {
+        if let Some(pending) = &mut self.0 {
+            pending.completed = true;
+            pending.record.error = Some(error.to_string());
+        }
+    }
+
+    fn first_chunk(&mut self) {
+        if let Some(pending) = &mut self.0 {
+            if pending.record.first_chunk_ms.is_none() {
+                pending.record.first_chunk_ms = Some(pending.start.elapsed().as_millis() as u64);
+            }
+        }
+    }
+}
+
+impl Drop for AuditEntry {
+    fn drop(&mut self) {
+        let Some(mut pending) = self.0.take() else {
+            return;
+        };
+
+        let record = &mut pending.record;
+        record.duration_ms = pending.start.elapsed().as_millis() as u64;
+        record.status = match (&record.error, pending.completed) {
+            (Some(_), _) => AuditStatus::Error,
+            (None, true) => AuditStatus::Success,
+            (None, false) => AuditStatus::Cancelled,
+        };
+
+        // the writer only stops once every sender is dropped
+        let _ = pending.tx.send(pending.record);
+    }
+}
+
+/// Record the time to the first chunk of a response stream and, once the stream is exhausted,
+/// the response `aggregate`d from its chunks. The entry is written when the returned stream is
+/// dropped, as cancelled if the stream was not exhausted.
+pub fn audit_stream<T, R, E, F, Fut>(
+    stream: impl Stream<Item = Annotated<T>> + Send + 'static,
+    mut entry: AuditEntry,
+    aggregate: F,
+) -> Pin<Box<dyn Stream<Item = Annotated<T>> + Send>>
+where
+    T: Clone + Send + Sync + 'static,
+    R: AuditResponse + 'static,
+    E: std::fmt::Display + 'static,
+    F: FnOnce(DataStream<Annotated<T>>) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<R, E>> + Send + 'static,
+{
+    if !entry.is_enabled() {
+        return Box::pin(stream);
+    }
+
+    Box::pin(stream! {
+        let mut stream = Box::pin(stream);
+        let mut chunks = Vec::new();
+
+        while let Some(chunk) = stream.next().await {
+            entry.first_chunk();
+            chunks.push(chunk.clone());
+            yield chunk;
+        }
+
+        match aggregate(Box::pin(futures::stream::iter(chunks))).await {
+            Ok(response) => entry.set_response(&response),
+            Err(e) => entry.set_error(e),
+        }
+    })
+}
+
+/// Hash of redacted content
+fn hash(content: &str) -> String {
+    blake3::hash(content.as_bytes()).to_hex().to_string()
+}
+
+/// Append-only file which is rotated before it would grow beyond `max_bytes`
+struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: Option<(File, u64)>,
+}
+
+impl RotatingFile {
+    fn new(path: PathBuf, max_bytes: u64, max_files: usize) -> Self {
+        Self {
+            path,
+            max_bytes,
+            max_files,
+            file: None,
+        }
+    }
+
+    fn write_record(&mut self, record: &AuditRecord) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let result = self.write_line(&line);
+        if result.is_err() {
+            // reopen the file for the next record
+            self.file = None;
+        }
+        result
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let (mut file, mut size) = match self.file.take() {
+            Some(file) => file,
+            None => open(&self.path)?,
+        };
+
+        if size > 0 && size + line.len() as u64 > self.max_bytes {
+            drop(file);
+            self.rotate()?;
+            (file, size) = open(&self.path)?;
+        }
+
+        file.write_all(line)?;
+        self.file = Some((file, size + line.len() as u64));
+        Ok(())
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        if self.max_files == 0 {
+            return fs::remove_file(&self.path);
+        }
+
+        for n in (1..self.max_files).rev() {
+            match fs::rename(self.rotated(n), self.rotated(n + 1)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        fs::rename(&self.path, self.rotated(1))
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{n}"));
+        path.into()
+    }
+}
+
+fn open(path: &Path) -> io::Result<(File, u64)> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok((file, size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start_entry(redaction: Redaction) -> (AuditEntry, mpsc::Receiver<AuditRecord>) {
+        let (tx, rx) = mpsc::channel();
+        let log = AuditLog {
+            redaction,
+            sample_rate: 1.0,
+            tx,
+        };
+        let request: CompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "foo",
+            "prompt": "hello",
+            "max_tokens": 16,
+            "temperature": 0.5,
+        }))
+        .unwrap();
+        let entry = log.start("id", Endpoint::Completions, &request, Some("key"), false);
+        (entry, rx)
+    }
+
+    fn response(text: &str) -> CompletionResponse {
+        serde_json::from_value(serde_json::json!({
+            "id": "cmpl",
+            "created": 0,
+            "model": "foo",
+            "object": "text_completion",
+            "choices": [{ "text": text, "index": 0, "finish_reason": "stop" }],
+            "usage": { "prompt_tokens": 1, "completion_tokens": 2, "total_tokens": 3 },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_entry_records_response() {
+        let (mut entry, rx) = start_entry(Redaction::None);
+        entry.set_response(&response("world"));
+        drop(entry);
+
+        let record = rx.try_recv().unwrap();
+        assert_eq!(record.status, AuditStatus::Success);
+        assert_eq!(record.key_id.as_deref(), Some("key"));
+        assert_eq!(record.prompt, Some(serde_json::json!("hello")));
+        assert_eq!(record.sampling.max_tokens, Some(16));
+        assert_eq!(record.sampling.temperature, Some(0.5));
+        assert_eq!(record.choices[0].text.as_deref(), Some("world"));
+        assert_eq!(record.choices[0].finish_reason.as_deref(), Some("stop"));
+        assert_eq!(record.prompt_tokens, Some(1));
+        assert_eq!(record.completion_tokens, Some(2));
+    }
+
+    #[test]
+    fn test_entry_status() {
+        let (entry, rx) = start_entry(Redaction::None);
+        drop(entry);
+        assert_eq!(rx.try_recv().unwrap().status, AuditStatus::Cancelled);
+
+        let (mut entry, rx) = start_entry(Redaction::None);
+        entry.set_error("engine failed");
+        drop(entry);
+        let record = rx.try_recv().unwrap();
+        assert_eq!(record.status, AuditStatus::Error);
+        assert_eq!(record.error.as_deref(), Some("engine failed"));
+    }
+
+    #[test]
+    fn test_redaction() {
+        let (mut entry, rx) = start_entry(Redaction::Hash);
+        entry.set_response(&response("world"));
+        drop(entry);
+        let record = rx.try_recv().unwrap();
+        assert_eq!(record.prompt, None);
+        assert_eq!(
+            record.prompt_hash.unwrap(),
+            blake3::hash(b"\"hello\"").to_hex().to_string()
+        );
+        assert_eq!(record.choices[0].text, None);
+        assert_eq!(
+            record.choices[0].text_hash.as_deref().unwrap(),
+            blake3::hash(b"world").to_hex().as_str()
+        );
+
+        let (mut entry, rx) = start_entry(Redaction::Omit);
+        entry.set_response(&response("world"));
+        drop(entry);
+        let record = rx.try_recv().unwrap();
+        assert_eq!((record.prompt, record.prompt_hash), (None, None));
+        assert_eq!(record.choices[0].text, None);
+        assert_eq!(record.choices[0].text_hash, None);
+        assert_eq!(record.choices[0].finish_reason.as_deref(), Some("stop"));
+    }
+
+    #[test]
+    fn test_sampling_disabled() {
+        let (tx, rx) = mpsc::channel();
+        let log = AuditLog {
+            redaction: Redaction::None,
+            sample_rate: 0.0,
+            tx,
+        };
+        let request: CompletionRequest =
+            serde_json::from_value(serde_json::json!({ "model": "foo", "prompt": "hello" }))
+                .unwrap();
+        let entry = log.start("id", Endpoint::Completions, &request, None, false);
+        assert!(!entry.is_enabled());
+        drop(entry);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_audit_stream() {
+        let (entry, rx) = start_entry(Redaction::None);
+        let chunks = ["hel", "lo"].map(|text| Annotated::from_data(response(text)));
+        let stream = audit_stream(
+            futures::stream::iter(chunks),
+            entry,
+            CompletionResponse::from_annotated_stream,
+        );
+        assert_eq!(stream.collect::<Vec<_>>().await.len(), 2);
+
+        let record = rx.try_recv().unwrap();
+        assert_eq!(record.status, AuditStatus::Success);
+        assert_eq!(record.choices[0].text.as_deref(), Some("hello"));
+        assert!(record.first_chunk_ms.is_some());
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let mut file = RotatingFile::new(path.clone(), 10, 2);
+
+        for line in ["aaaaaa\n", "bbbbbb\n", "cccccc\n", "dddddd\n"] {
+            file.write_line(line.as_bytes()).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "dddddd\n");
+        assert_eq!(fs::read_to_string(file.rotated(1)).unwrap(), "cccccc\n");
+        assert_eq!(fs::read_to_string(file.rotated(2)).unwrap(), "bbbbbb\n");
+        assert!(!file.rotated(3).exists());
+    }
+}
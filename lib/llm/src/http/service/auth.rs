@@ -0,0 +1,581 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! API key authentication and per-key rate limiting for the OpenAI routes.
+//!
+//! Clients authenticate with `Authorization: Bearer <key>`. Keys come from the `[auth]` section
+//! of the [`super::config::HttpServiceSettings`] and, optionally, from an etcd prefix watched by
+//! [`api_key_watcher`]; each etcd value is a JSON [`ApiKeyConfig`].
+//!
+//! Each key may limit requests per second and tokens per minute using token buckets. Tokens are
+//! charged after the response completes: the `usage` of unary responses, or one token per streamed
+//! chunk. A key whose token bucket is in deficit is rejected until it refills.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+
+use dynamo_runtime::transports::etcd::WatchEvent;
+
+use super::DeploymentState;
+
+/// Value of the `api_key` metrics label for unauthenticated requests
+pub const ANONYMOUS_KEY_ID: &str = "anonymous";
+
+/// Configuration of a single API key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKeyConfig {
+    /// Identifier used in logs and metrics; never the secret itself
+    pub id: String,
+
+    /// The secret presented by clients as a bearer token
+    pub key: String,
+
+    /// Sustained requests per second; the burst allowance is the same number of requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_second: Option<f64>,
+
+    /// Prompt and completion tokens per minute
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_minute: Option<u64>,
+
+    /// Models the key may use; all models if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub models: Option<Vec<String>>,
+}
+
+impl ApiKeyConfig {
+    /// Reject rate limits which cannot refill
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Some(rps) = self.requests_per_second {
+            if !rps.is_finite() || rps <= 0.0 {
+                anyhow::bail!(
+                    "api key {}: requests_per_second must be a positive number, got {rps}",
+                    self.id
+                );
+            }
+        }
+        if self.tokens_per_minute == Some(0) {
+            anyhow::bail!("api key {}: tokens_per_minute must be positive", self.id);
+        }
+        Ok(())
+    }
+}
+
+/// Longest wait reported to a rate limited client
+const MAX_WAIT: Duration = Duration::from_secs(3600);
+
+/// Refilling token bucket; the balance may go negative when charged after the fact
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    available: f64,
+    refill_per_sec: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: f64, refill_per_sec: f64, now: Instant) -> Self {
+        Self {
+            capacity,
+            available: capacity,
+            refill_per_sec,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * self.refill_per_sec).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Time until `amount` is available, at most [`MAX_WAIT`]
+    fn wait_time(&self, amount: f64) -> Duration {
+        let secs = ((amount - self.available) / self.refill_per_sec).max(0.0);
+        Duration::try_from_secs_f64(secs)
+            .unwrap_or(MAX_WAIT)
+            .min(MAX_WAIT)
+    }
+
+    /// Take `amount` if available, otherwise return the time until it will be
+    fn try_take(&mut self, amount: f64, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+        if self.available >= amount {
+            self.available -= amount;
+            Ok(())
+        } else {
+            Err(self.wait_time(amount))
+        }
+    }
+
+    /// Ok if the bucket is not in deficit, otherwise the time until it will be
+    fn check(&mut self, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+        if self.available > 0.0 {
+            Ok(())
+        } else {
+            Err(self.wait_time(f64::MIN_POSITIVE))
+        }
+    }
+
+    fn charge(&mut self, amount: f64, now: Instant) {
+        self.refill(now);
+        self.available -= amount;
+    }
+}
+
+/// The limit a request exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKind {
+    Requests,
+    Tokens,
+}
+
+impl RateLimitKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RateLimitKind::Requests => "requests",
+            RateLimitKind::Tokens => "tokens",
+        }
+    }
+}
+
+/// An API key and its rate limit state
+#[derive(Debug)]
+pub struct ApiKey {
+    id: String,
+    models: Option<HashSet<String>>,
+    requests: Option<Mutex<TokenBucket>>,
+    tokens: Option<Mutex<TokenBucket>>,
+}
+
+impl ApiKey {
+    fn new(config: &ApiKeyConfig) -> Self {
+        let now = Instant::now();
+        Self {
+            id: config.id.clone(),
+            models: config
+                .models
+                .as_ref()
+                .map(|models| models.iter().cloned().collect()),
+            requests: config
+                .requests_per_second
+                .map(|rps| Mutex::new(TokenBucket::new(rps.max(1.0), rps, now))),
+            tokens: config
+                .tokens_per_minute
+                .map(|tpm| Mutex::new(TokenBucket::new(tpm as f64, tpm as f64 / 60.0, now))),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// True if the key limits the tokens per minute, so its requests are charged their usage
+    pub fn limits_tokens(&self) -> bool {
+        self.tokens.is_some()
+    }
+
+    /// True if the key may use `model`
+    pub fn allows_model(&self, model: &str) -> bool {
+        self.models
+            .as_ref()
+            .is_none_or(|models| models.contains(model))
+    }
+
+    /// Admit a request against the key's limits
+    pub fn admit(&self) -> Result<(), (RateLimitKind, Duration)> {
+        let now = Instant::now();
+        if let Some(tokens) = &self.tokens {
+            tokens
+                .lock()
+                .unwrap()
+                .check(now)
+                .map_err(|wait| (RateLimitKind::Tokens, wait))?;
+        }
+        if let Some(requests) = &self.requests {
+            requests
+                .lock()
+                .unwrap()
+                .try_take(1.0, now)
+                .map_err(|wait| (RateLimitKind::Requests, wait))?;
+        }
+        Ok(())
+    }
+
+    /// Charge tokens consumed by a completed request
+    pub fn record_tokens(&self, tokens: u64) {
+        if let Some(bucket) = &self.tokens {
+            bucket.lock().unwrap().charge(tokens as f64, Instant::now());
+        }
+    }
+}
+
+#[derive(Default)]
+struct KeyTable {
+    /// keys by secret
+    by_secret: HashMap<String, Arc<ApiKey>>,
+
+    /// configs by source, e.g. the etcd key which defined them
+    by_source: HashMap<String, ApiKeyConfig>,
+}
+
+/// The set of valid API keys
+#[derive(Clone, Default)]
+pub struct ApiKeyStore {
+    inner: Arc<RwLock<KeyTable>>,
+}
+
+impl ApiKeyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace the key defined by `source`. An unchanged key keeps its rate limit state,
+    /// so re-putting the same config does not refill its buckets.
+    pub fn upsert(&self, source: &str, config: &ApiKeyConfig) -> anyhow::Result<()> {
+        config.validate()?;
+        let mut table = self.inner.write().unwrap();
+        if table.by_source.get(source) == Some(config) {
+            return Ok(());
+        }
+        if let Some(previous) = table.by_source.remove(source) {
+            table.by_secret.remove(&previous.key);
+        }
+        table
+            .by_secret
+            .insert(config.key.clone(), Arc::new(ApiKey::new(config)));
+        table.by_source.insert(source.to_string(), config.clone());
+        Ok(())
+    }
+
+    /// Remove the key defined by `source`; returns the removed key's id
+    pub fn remove(&self, source: &str) -> Option<String> {
+        let mut table = self.inner.write().unwrap();
+        let config = table.by_source.remove(source)?;
+        table
+            .by_secret
+            .remove(&config.key)
+            .map(|key| key.id.clone())
+    }
+
+    /// Look up a key by its secret
+    pub fn get(&self, secret: &str) -> Option<Arc<ApiKey>> {
+        self.inner.read().unwrap().by_secret.get(secret).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().by_secret.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Watch an etcd prefix of [`ApiKeyConfig`] values and keep the [`ApiKeyStore`] in sync
+pub async fn api_key_watcher(store: ApiKeyStore, mut events_rx: Receiver<WatchEvent>) {
+    tracing::debug!("api key watcher started");
+
+    while let Some(event) = events_rx.recv().await {
+        match event {
+            WatchEvent::Put(kv) => {
+                let source = kv.key_str().unwrap_or_default().to_string();
+                let added = serde_json::from_slice::<ApiKeyConfig>(kv.value())
+                    .map_err(anyhow::Error::from)
+                    .and_then(|config| store.upsert(&source, &config).map(|_| config));
+                match added {
+                    Ok(config) => {
+                        tracing::info!("added api key: {}", config.id);
+                    }
+                    Err(e) => {
+                        tracing::error!("error adding api key from {source}: {e}");
+                    }
+                }
+            }
+            WatchEvent::Delete(kv) => {
+                let source = kv.key_str().unwrap_or_default();
+                if let Some(id) = store.remove(source) {
+                    tracing::info!("removed api key: {}", id);
+                }
+            }
+        }
+    }
+
+    tracing::debug!("api key watcher stopped");
+}
+
+/// OpenAI-shaped error body
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OpenAIError {
+    pub error: OpenAIErrorDetail,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OpenAIErrorDetail {
+    pub message: String,
+    #[serde(rename = "type")]
+    pub error_type: String,
+    pub param: Option<String>,
+    pub code: Option<String>,
+}
+
+impl OpenAIError {
+    fn response(status: StatusCode, message: String, error_type: &str, code: &str) -> Response {
+        let body = OpenAIError {
+            error: OpenAIErrorDetail {
+                message,
+                error_type: error_type.to_string(),
+                param: None,
+                code: Some(code.to_string()),
+            },
+        };
+        (status, Json(body)).into_response()
+    }
+
+    fn unauthorized(message: &str) -> Response {
+        Self::response(
+            StatusCode::UNAUTHORIZED,
+            message.to_string(),
+            "invalid_request_error",
+            "invalid_api_key",
+        )
+    }
+
+    fn rate_limited(key: &ApiKey, kind: RateLimitKind, wait: Duration) -> Response {
+        let message = format!(
+            "Rate limit reached for {} on key {}. Please try again in {:.3}s.",
+            kind.as_str(),
+            key.id(),
+            wait.as_secs_f64()
+        );
+        let mut response = Self::response(
+            StatusCode::TOO_MANY_REQUESTS,
+            message,
+            kind.as_str(),
+            "rate_limit_exceeded",
+        );
+        let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        response
+    }
+
+    fn bad_request(message: &str) -> Response {
+        Self::response(
+            StatusCode::BAD_REQUEST,
+            message.to_string(),
+            "invalid_request_error",
+            "invalid_request",
+        )
+    }
+
+    fn model_not_found(model: &str) -> Response {
+        Self::response(
+            StatusCode::NOT_FOUND,
+            format!("The model `{model}` does not exist or you do not have access to it."),
+            "invalid_request_error",
+            "model_not_found",
+        )
+    }
+}
+
+/// State of the [`auth_middleware`]
+#[derive(Clone)]
+pub struct AuthState {
+    pub keys: ApiKeyStore,
+    pub deployment: Arc<DeploymentState>,
+    pub max_body_bytes: usize,
+}
+
+#[derive(Deserialize)]
+struct RequestModel {
+    #[serde(default)]
+    model: String,
+}
+
+/// Authenticate the request, enforce the key's limits and model allow-list, and attach the
+/// [`ApiKey`] to the request extensions for the handlers
+pub async fn auth_middleware(
+    State(state): State<AuthState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let secret = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim);
+
+    let Some(secret) = secret else {
+        return OpenAIError::unauthorized(
+            "You didn't provide an API key. Provide it in the Authorization header using Bearer auth.",
+        );
+    };
+
+    let Some(key) = state.keys.get(secret) else {
+        return OpenAIError::unauthorized("Incorrect API key provided.");
+    };
+
+    if let Err((kind, wait)) = key.admit() {
+        tracing::debug!(api_key = key.id(), "rate limited on {}", kind.as_str());
+        return OpenAIError::rate_limited(&key, kind, wait);
+    }
+
+    let (parts, body) = request.into_parts();
+
+    // the model allow-list needs the model named in the body
+    let request = if key.models.is_some() && parts.method == axum::http::Method::POST {
+        let bytes = match axum::body::to_bytes(body, state.max_body_bytes).await {
+            Ok(bytes) => bytes,
+            Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+        };
+        // a body the model cannot be read from is denied rather than let past the allow-list
+        let Ok(RequestModel { model }) = serde_json::from_slice(&bytes) else {
+            return OpenAIError::bad_request("The request body is not a JSON object.");
+        };
+        let model = state.deployment.reloadable_settings().resolve_model(&model);
+        if !key.allows_model(&model) {
+            return OpenAIError::model_not_found(&model);
+        }
+        Request::from_parts(parts, Body::from(bytes))
+    } else {
+        Request::from_parts(parts, body)
+    };
+
+    let mut request = request;
+    request.extensions_mut().insert(key);
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(id: &str) -> ApiKeyConfig {
+        ApiKeyConfig {
+            id: id.to_string(),
+            key: format!("sk-{id}"),
+            requests_per_second: None,
+            tokens_per_minute: None,
+            models: None,
+        }
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2.0, 1.0, start);
+
+        assert!(bucket.try_take(1.0, start).is_ok());
+        assert!(bucket.try_take(1.0, start).is_ok());
+        assert_eq!(bucket.try_take(1.0, start), Err(Duration::from_secs(1)));
+
+        // refills at 1/s up to capacity
+        assert!(bucket.try_take(1.0, start + Duration::from_secs(1)).is_ok());
+        bucket.refill(start + Duration::from_secs(10));
+        assert_eq!(bucket.available, 2.0);
+
+        // charging after the fact can leave the bucket in deficit
+        bucket.charge(4.0, start + Duration::from_secs(10));
+        assert!(bucket.check(start + Duration::from_secs(10)).is_err());
+        assert!(bucket.check(start + Duration::from_secs(11)).is_err());
+        assert!(bucket.check(start + Duration::from_millis(12_500)).is_ok());
+    }
+
+    #[test]
+    fn test_api_key_limits() {
+        let mut config = config("team-a");
+        config.requests_per_second = Some(2.0);
+        config.tokens_per_minute = Some(100);
+        config.models = Some(vec!["llama".to_string()]);
+        let key = ApiKey::new(&config);
+
+        assert!(key.allows_model("llama"));
+        assert!(!key.allows_model("mistral"));
+
+        assert!(key.admit().is_ok());
+        assert!(key.admit().is_ok());
+        assert_eq!(key.admit().unwrap_err().0, RateLimitKind::Requests);
+
+        let key = ApiKey::new(&config);
+        key.record_tokens(150);
+        assert_eq!(key.admit().unwrap_err().0, RateLimitKind::Tokens);
+    }
+
+    #[test]
+    fn test_invalid_limits() {
+        let mut bucket = TokenBucket::new(1.0, 0.0, Instant::now());
+        bucket.available = 0.0;
+        assert_eq!(bucket.wait_time(1.0), MAX_WAIT);
+
+        let store = ApiKeyStore::new();
+        for rps in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let mut config = config("a");
+            config.requests_per_second = Some(rps);
+            assert!(store.upsert("config/0", &config).is_err());
+        }
+        let mut config = config("a");
+        config.tokens_per_minute = Some(0);
+        assert!(store.upsert("config/0", &config).is_err());
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_api_key_store() {
+        let store = ApiKeyStore::new();
+        store.upsert("config/0", &config("a")).unwrap();
+        store.upsert("/keys/b", &config("b")).unwrap();
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.get("sk-a").unwrap().id(), "a");
+
+        // rotating the secret behind a source replaces the old one
+        let mut rotated = config("b");
+        rotated.key = "sk-b2".to_string();
+        store.upsert("/keys/b", &rotated).unwrap();
+        assert!(store.get("sk-b").is_none());
+        assert_eq!(store.get("sk-b2").unwrap().id(), "b");
+
+        // re-putting the same config keeps the rate limit state
+        let mut limited = config("c");
+        limited.requests_per_second = Some(1.0);
+        store.upsert("/keys/c", &limited).unwrap();
+        assert!(store.get("sk-c").unwrap().admit().is_ok());
+        store.upsert("/keys/c", &limited).unwrap();
+        assert!(store.get("sk-c").unwrap().admit().is_err());
+        assert_eq!(store.remove("/keys/c"), Some("c".to_string()));
+
+        assert_eq!(store.remove("/keys/b"), Some("b".to_string()));
+        assert_eq!(store.remove("/keys/b"), None);
+        assert_eq!(store.len(), 1);
+    }
+}
@@ -0,0 +1,732 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Typed configuration for the [`super::service_v2::HttpService`].
+//!
+//! Settings are loaded through figment in the following order, where the last has the highest priority:
+//! 1. Built-in defaults
+//! 2. An optional TOML (`.toml`) or YAML (`.yaml`/`.yml`) file
+//! 3. Environment variables prefixed with `DYN_HTTP_`; nested keys are separated by `__`,
+//!    e.g. `DYN_HTTP_LISTENER__PORT=8080`
+//!
+//! Example TOML:
+//!
+//! ```toml
+//! default_model = "llama"
+//!
+//! [listener]
+//! port = 8080
+//! cors_allowed_origins = ["*"]
+//!
+//! [limits]
+//! request_timeout_secs = 30
+//!
+//! [models.llama]
+//! max_tokens = 512
+//! temperature = 0.7
+//! chat_template = """
+//! {% for message in messages %}<|{{ message.role }}|>{{ message.content }}{% endfor %}<|assistant|>
+//! """
+//! ```
+//!
+//! The [`ReloadableSettings`] subset (default model, per-model defaults, request timeout and
+//! request logging) can be changed while the service is running; see [`watch`]. Changes to the
+//! listener, enabled endpoints, CORS, body limit, metrics prefix, static API keys or the audit log
+//! require a restart.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use figment::{
+    providers::{Env, Format, Serialized, Toml, Yaml},
+    Figment,
+};
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use super::{auth::ApiKeyConfig, DeploymentState};
+use crate::protocols::openai::nvext::NvExt;
+use crate::types::openai::{
+    chat_completions::NvCreateChatCompletionRequest, completions::CompletionRequest,
+};
+
+/// Environment variable prefix for [`HttpServiceSettings`]
+pub const ENV_PREFIX: &str = "DYN_HTTP_";
+
+/// Top-level keys of [`HttpServiceSettings`]; other environment variables with the
+/// [`ENV_PREFIX`] are ignored rather than rejected as unknown fields
+const SETTINGS_KEYS: &[&str] = &[
+    "listener",
+    "endpoints",
+    "limits",
+    "metrics",
+    "logging",
+    "auth",
+    "admin",
+    "admission",
+    "audit",
+    "default_model",
+    "models",
+];
+
+/// Replaces secrets in [`HttpServiceSettings::redacted`]
+pub const REDACTED: &str = "<redacted>";
+
+/// How often [`watch`] checks the configuration file for changes
+pub const RELOAD_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Configuration for the [`super::service_v2::HttpService`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpServiceSettings {
+    pub listener: ListenerSettings,
+    pub endpoints: EndpointSettings,
+    pub limits: LimitSettings,
+    pub metrics: MetricsSettings,
+    pub logging: LoggingSettings,
+    pub auth: AuthSettings,
+    pub admin: AdminSettings,
+    pub admission: AdmissionSettings,
+    pub audit: AuditSettings,
+
+    /// Model used when a request does not name one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_model: Option<String>,
+
+    /// Per-model request defaults, keyed by model name
+    pub models: HashMap<String, ModelDefaults>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListenerSettings {
+    pub host: String,
+    pub port: u16,
+
+    /// Origins allowed to make cross-origin requests; `"*"` allows any origin.
+    /// CORS is disabled when empty.
+    pub cors_allowed_origins: Vec<String>,
+
+    /// Interval between SSE keep-alive comments on streaming responses
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sse_keep_alive_secs: Option<u64>,
+}
+
+impl Default for ListenerSettings {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+            cors_allowed_origins: Vec::new(),
+            sse_keep_alive_secs: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EndpointSettings {
+    /// Serve `/v1/chat/completions`
+    pub chat: bool,
+
+    /// Serve `/v1/completions`
+    pub completions: bool,
+
+    /// Serve `/v1/responses`, which is backed by the chat completions engines
+    pub responses: bool,
+
+    /// Serve `/tokenize` and `/detokenize` for models registered with a preprocessor
+    pub tokenize: bool,
+}
+
+impl Default for EndpointSettings {
+    fn default() -> Self {
+        Self {
+            chat: true,
+            completions: true,
+            responses: true,
+            tokenize: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitSettings {
+    /// Maximum size of a request body in bytes
+    pub max_body_bytes: usize,
+
+    /// Maximum time to wait for an engine to accept a request; unbounded if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_timeout_secs: Option<u64>,
+}
+
+impl Default for LimitSettings {
+    fn default() -> Self {
+        Self {
+            // matches the axum default
+            max_body_bytes: 2 * 1024 * 1024,
+            request_timeout_secs: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdmissionSettings {
+    /// Maximum number of in-flight requests per model; unlimited if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_inflight: Option<usize>,
+
+    /// Per-model overrides of `max_inflight`, keyed by model name
+    pub model_max_inflight: HashMap<String, usize>,
+
+    /// Maximum number of requests waiting for admission per model; beyond this, requests are
+    /// rejected with a 429 unless they displace a queued request of a lower priority
+    pub max_queued: usize,
+
+    /// Maximum time a request waits for admission before it is rejected with a 503
+    pub queue_timeout_ms: u64,
+
+    /// Value of the `Retry-After` header sent with rejections
+    pub retry_after_secs: u64,
+}
+
+impl Default for AdmissionSettings {
+    fn default() -> Self {
+        Self {
+            max_inflight: None,
+            model_max_inflight: HashMap::new(),
+            max_queued: 128,
+            queue_timeout_ms: 30_000,
+            retry_after_secs: 1,
+        }
+    }
+}
+
+impl AdmissionSettings {
+    /// The in-flight limit of `model`, if any
+    pub fn max_inflight(&self, model: &str) -> Option<usize> {
+        self.model_max_inflight
+            .get(model)
+            .copied()
+            .or(self.max_inflight)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditSettings {
+    /// Write a JSONL audit record for every sampled request; see [`super::audit`]
+    pub enabled: bool,
+
+    /// Audit log file; rotated files are suffixed with `.1`, `.2`, ...
+    pub path: PathBuf,
+
+    /// How prompts and responses are recorded
+    pub redaction: Redaction,
+
+    /// Fraction of requests recorded, between 0 and 1
+    pub sample_rate: f64,
+
+    /// Size at which the audit log is rotated
+    pub max_file_bytes: u64,
+
+    /// Number of rotated files kept besides the current one
+    pub max_files: usize,
+}
+
+impl Default for AuditSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: PathBuf::from("dynamo-audit.jsonl"),
+            redaction: Redaction::default(),
+            sample_rate: 1.0,
+            max_file_bytes: 100 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+/// How message content is recorded in the audit log
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Redaction {
+    /// Prompts and responses are recorded verbatim
+    None,
+    /// Only a BLAKE3 hash of prompts and responses is recorded
+    #[default]
+    Hash,
+    /// Prompts and responses are not recorded
+    Omit,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsSettings {
+    /// Prefix of the Prometheus metric names
+    pub prefix: String,
+}
+
+impl Default for MetricsSettings {
+    fn default() -> Self {
+        Self {
+            prefix: "nv_llm".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingSettings {
+    /// Log the model and id of every request at `info` level
+    pub log_requests: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthSettings {
+    /// Require an API key on the OpenAI routes
+    pub enabled: bool,
+
+    /// Static API keys; more keys may be added at runtime through etcd
+    pub keys: Vec<ApiKeyConfig>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminSettings {
+    /// Serve the admin API under `/admin`
+    pub enabled: bool,
+
+    /// Bearer token required by the admin API; must be set when the admin API is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+}
+
+/// Defaults applied to requests for a model when the request leaves the field unset
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModelDefaults {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repetition_penalty: Option<f64>,
+
+    /// Bypass the model's prompt template and tokenize the prompt as-is
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_raw_prompt: Option<bool>,
+
+    /// Jinja chat template to render the prompt with instead of the model's own template. It is
+    /// handed to the preprocessor in the request context, so it applies to models preprocessed
+    /// by this process.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chat_template: Option<String>,
+}
+
+/// The subset of [`HttpServiceSettings`] which is applied per request and may be hot reloaded
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReloadableSettings {
+    pub default_model: Option<String>,
+    pub models: HashMap<String, ModelDefaults>,
+    pub request_timeout: Option<Duration>,
+    pub log_requests: bool,
+}
+
+impl From<&HttpServiceSettings> for ReloadableSettings {
+    fn from(settings: &HttpServiceSettings) -> Self {
+        Self {
+            default_model: settings.default_model.clone(),
+            models: settings.models.clone(),
+            request_timeout: settings
+                .limits
+                .request_timeout_secs
+                .map(Duration::from_secs),
+            log_requests: settings.logging.log_requests,
+        }
+    }
+}
+
+impl HttpServiceSettings {
+    /// Figment stack for the settings on top of `defaults`; `path` is read as TOML or YAML
+    /// depending on its extension
+    pub fn figment(defaults: &Self, path: Option<&Path>) -> Result<Figment> {
+        let mut figment = Figment::new().merge(Serialized::defaults(defaults));
+
+        if let Some(path) = path {
+            figment = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("toml") => figment.merge(Toml::file(path)),
+                Some("yaml") | Some("yml") => figment.merge(Yaml::file(path)),
+                _ => anyhow::bail!(
+                    "Unsupported config file {}; expected .toml, .yaml or .yml",
+                    path.display()
+                ),
+            };
+        }
+
+        let env = Env::prefixed(ENV_PREFIX)
+            .filter(|key| {
+                let section = key.as_str().split("__").next().unwrap_or_default();
+                SETTINGS_KEYS
+                    .iter()
+                    .any(|known| known.eq_ignore_ascii_case(section))
+            })
+            .split("__");
+        Ok(figment.merge(env))
+    }
+
+    /// Load the settings from defaults, the optional file at `path` and the environment
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        Ok(Self::figment(&Self::default(), path)?.extract()?)
+    }
+
+    /// A copy of the settings with the API keys and the admin token replaced by [`REDACTED`],
+    /// e.g. to print them
+    pub fn redacted(&self) -> Self {
+        let mut settings = self.clone();
+        for key in &mut settings.auth.keys {
+            key.key = REDACTED.to_string();
+        }
+        if let Some(api_key) = &mut settings.admin.api_key {
+            *api_key = REDACTED.to_string();
+        }
+        settings
+    }
+
+    /// Settings which changed between `self` and `other` but cannot be applied without a restart
+    pub fn restart_required(&self, other: &Self) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.listener != other.listener {
+            changed.push("listener");
+        }
+        if self.endpoints != other.endpoints {
+            changed.push("endpoints");
+        }
+        if self.limits.max_body_bytes != other.limits.max_body_bytes {
+            changed.push("limits.max_body_bytes");
+        }
+        if self.metrics != other.metrics {
+            changed.push("metrics");
+        }
+        if self.auth != other.auth {
+            changed.push("auth");
+        }
+        if self.admin != other.admin {
+            changed.push("admin");
+        }
+        if self.admission != other.admission {
+            changed.push("admission");
+        }
+        if self.audit != other.audit {
+            changed.push("audit");
+        }
+        changed
+    }
+}
+
+impl ReloadableSettings {
+    /// The model to serve a request for; an empty model name selects the default model
+    pub fn resolve_model(&self, model: &str) -> String {
+        match (&self.default_model, model.is_empty()) {
+            (Some(default), true) => default.clone(),
+            _ => model.to_string(),
+        }
+    }
+
+    /// Chat template configured to replace the model's template, if any. It is passed to the
+    /// preprocessor in the request context; clients cannot set it.
+    pub fn chat_template(&self, model: &str) -> Option<String> {
+        self.models.get(model)?.chat_template.clone()
+    }
+
+    #[allow(deprecated)]
+    pub fn apply_chat_defaults(&self, request: &mut NvCreateChatCompletionRequest) {
+        let Some(defaults) = self.models.get(&request.inner.model) else {
+            return;
+        };
+        let inner = &mut request.inner;
+        if inner.max_completion_tokens.is_none() && inner.max_tokens.is_none() {
+            inner.max_tokens = defaults.max_tokens;
+        }
+        inner.temperature = inner.temperature.or(defaults.temperature);
+        inner.top_p = inner.top_p.or(defaults.top_p);
+        inner.frequency_penalty = inner.frequency_penalty.or(defaults.frequency_penalty);
+        inner.presence_penalty = inner.presence_penalty.or(defaults.presence_penalty);
+        defaults.apply_nvext(&mut request.nvext);
+    }
+
+    pub fn apply_completion_defaults(&self, request: &mut CompletionRequest) {
+        let Some(defaults) = self.models.get(&request.inner.model) else {
+            return;
+        };
+        let inner = &mut request.inner;
+        inner.max_tokens = inner.max_tokens.or(defaults.max_tokens);
+        inner.temperature = inner.temperature.or(defaults.temperature);
+        inner.top_p = inner.top_p.or(defaults.top_p);
+        inner.frequency_penalty = inner.frequency_penalty.or(defaults.frequency_penalty);
+        inner.presence_penalty = inner.presence_penalty.or(defaults.presence_penalty);
+        defaults.apply_nvext(&mut request.nvext);
+    }
+}
+
+impl ModelDefaults {
+    fn apply_nvext(&self, nvext: &mut Option<NvExt>) {
+        if self.top_k.is_none()
+            && self.repetition_penalty.is_none()
+            && self.use_raw_prompt.is_none()
+        {
+            return;
+        }
+        let nvext = nvext.get_or_insert_with(NvExt::default);
+        nvext.top_k = nvext.top_k.or(self.top_k);
+        nvext.repetition_penalty = nvext.repetition_penalty.or(self.repetition_penalty);
+        nvext.use_raw_prompt = nvext.use_raw_prompt.or(self.use_raw_prompt);
+    }
+}
+
+/// Watch the configuration file at `path` and apply changes to the [`ReloadableSettings`] of the
+/// service until `cancel_token` is triggered.
+///
+/// Files which fail to load are logged and ignored; the previous settings stay in effect.
+pub fn watch(
+    path: PathBuf,
+    state: Arc<DeploymentState>,
+    cancel_token: CancellationToken,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut previous = HttpServiceSettings::load(Some(&path)).ok();
+        let mut last_modified = modified(&path);
+        let mut interval = tokio::time::interval(RELOAD_POLL_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = cancel_token.cancelled() => break,
+            }
+
+            let modified = modified(&path);
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            let settings = match HttpServiceSettings::load(Some(&path)) {
+                Ok(settings) => settings,
+                Err(e) => {
+                    tracing::warn!(path = %path.display(), "Failed to reload config; keeping previous settings: {e}");
+                    continue;
+                }
+            };
+
+            let restart_required = previous
+                .as_ref()
+                .map(|previous| previous.restart_required(&settings))
+                .unwrap_or_default();
+            if !restart_required.is_empty() {
+                tracing::warn!(
+                    "Changes to {} require a restart and were not applied",
+                    restart_required.join(", ")
+                );
+            }
+
+            state.set_reloadable_settings(ReloadableSettings::from(&settings));
+            tracing::info!(path = %path.display(), "Reloaded config");
+            previous = Some(settings);
+        }
+    })
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write_config(suffix: &str, contents: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::Builder::new().suffix(suffix).tempfile().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_defaults() {
+        let settings = HttpServiceSettings::load(None).unwrap();
+        assert_eq!(settings, HttpServiceSettings::default());
+        assert_eq!(settings.listener.port, 8080);
+        assert!(settings.endpoints.chat);
+    }
+
+    #[test]
+    fn test_load_toml_and_yaml() {
+        let toml = write_config(
+            ".toml",
+            r#"
+            default_model = "llama"
+
+            [listener]
+            port = 9000
+
+            [models.llama]
+            max_tokens = 64
+            top_k = 5
+            "#,
+        );
+        let yaml = write_config(
+            ".yaml",
+            "default_model: llama\nlistener:\n  port: 9000\nmodels:\n  llama:\n    max_tokens: 64\n    top_k: 5\n",
+        );
+
+        let from_toml = HttpServiceSettings::load(Some(toml.path())).unwrap();
+        let from_yaml = HttpServiceSettings::load(Some(yaml.path())).unwrap();
+        assert_eq!(from_toml, from_yaml);
+        assert_eq!(from_toml.listener.port, 9000);
+        assert_eq!(from_toml.listener.host, "0.0.0.0");
+        assert_eq!(from_toml.models["llama"].max_tokens, Some(64));
+    }
+
+    #[test]
+    fn test_unknown_fields_rejected() {
+        let toml = write_config(".toml", "[listener]\nprot = 9000\n");
+        assert!(HttpServiceSettings::load(Some(toml.path())).is_err());
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let toml = write_config(".toml", "[listener]\nport = 9000\n");
+        figment::Jail::expect_with(|jail| {
+            jail.set_env("DYN_HTTP_LISTENER__PORT", "9001");
+            let settings = HttpServiceSettings::load(Some(toml.path())).unwrap();
+            assert_eq!(settings.listener.port, 9001);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_unrelated_env_ignored() {
+        figment::Jail::expect_with(|jail| {
+            jail.set_env("DYN_HTTP_PROXY", "http://proxy:3128");
+            let settings = HttpServiceSettings::load(None).unwrap();
+            assert_eq!(settings, HttpServiceSettings::default());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_redacted() {
+        let toml = write_config(
+            ".toml",
+            r#"
+            [auth]
+            enabled = true
+            keys = [{ id = "team-a", key = "sk-secret" }]
+
+            [admin]
+            enabled = true
+            api_key = "admin-secret"
+            "#,
+        );
+        let settings = HttpServiceSettings::load(Some(toml.path())).unwrap();
+        let printed = serde_json::to_string(&settings.redacted()).unwrap();
+        assert!(!printed.contains("secret"));
+        assert!(printed.contains("team-a"));
+        assert_eq!(settings.auth.keys[0].key, "sk-secret");
+    }
+
+    #[test]
+    fn test_restart_required() {
+        let current = HttpServiceSettings::default();
+        let mut next = current.clone();
+        next.default_model = Some("llama".to_string());
+        next.limits.request_timeout_secs = Some(10);
+        assert!(current.restart_required(&next).is_empty());
+
+        next.listener.port = 1;
+        next.limits.max_body_bytes = 1;
+        assert_eq!(
+            current.restart_required(&next),
+            vec!["listener", "limits.max_body_bytes"]
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_apply_chat_defaults() {
+        let mut settings = HttpServiceSettings::default();
+        settings.models.insert(
+            "llama".to_string(),
+            ModelDefaults {
+                max_tokens: Some(64),
+                temperature: Some(0.5),
+                top_k: Some(5),
+                chat_template: Some("{{ messages[0].content }}".to_string()),
+                ..Default::default()
+            },
+        );
+        let reloadable = ReloadableSettings::from(&settings);
+
+        let mut request: NvCreateChatCompletionRequest =
+            serde_json::from_value(serde_json::json!({
+                "model": "llama",
+                "messages": [{"role": "user", "content": "hi"}],
+                "temperature": 0.1
+            }))
+            .unwrap();
+        reloadable.apply_chat_defaults(&mut request);
+
+        assert_eq!(request.inner.max_tokens, Some(64));
+        assert_eq!(request.inner.temperature, Some(0.1));
+        let nvext = request.nvext.unwrap();
+        assert_eq!(nvext.top_k, Some(5));
+        assert_eq!(
+            reloadable.chat_template("llama").as_deref(),
+            Some("{{ messages[0].content }}")
+        );
+        assert_eq!(reloadable.chat_template("mistral"), None);
+    }
+
+    #[test]
+    fn test_resolve_model() {
+        let mut reloadable = ReloadableSettings::default();
+        assert_eq!(reloadable.resolve_model(""), "");
+        reloadable.default_model = Some("llama".to_string());
+        assert_eq!(reloadable.resolve_model(""), "llama");
+        assert_eq!(reloadable.resolve_model("mistral"), "mistral");
+    }
+}
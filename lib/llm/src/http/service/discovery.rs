@@ -13,12 +13,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
 use serde::{Deserialize, Serialize};
-use tokio::sync::mpsc::Receiver;
+use tokio::sync::{mpsc::Receiver, watch};
 
 use dynamo_runtime::{
+    component::Client,
     protocols::{self, annotated::Annotated},
     raise,
     transports::etcd::{KeyValue, WatchEvent},
@@ -26,7 +30,9 @@ use dynamo_runtime::{
 };
 
 use super::ModelManager;
+use crate::model_card::model::ModelDeploymentCard;
 use crate::model_type::ModelType;
+use crate::preprocessor::OpenAIPreprocessor;
 use crate::protocols::openai::chat_completions::{
     NvCreateChatCompletionRequest, NvCreateChatCompletionStreamResponse,
 };
@@ -34,7 +40,10 @@ use crate::protocols::openai::completions::{CompletionRequest, CompletionRespons
 use tracing;
 /// [ModelEntry] is a struct that contains the information for the HTTP service to discover models
 /// from the etcd cluster.
-#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+///
+/// Several entries may share the same public `name`; each becomes a backend of that model and
+/// requests are split across the backends in proportion to their `weight`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelEntry {
     /// Public name of the model
     /// This will be used to identify the model in the HTTP service and the value used in an
@@ -46,6 +55,77 @@ pub struct ModelEntry {
 
     /// Specifies whether the model is a chat or completion model.s
     pub model_type: ModelType,
+
+    /// Relative share of the model's traffic sent to this backend; `0` keeps the backend
+    /// registered without sending it requests.
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+
+    /// Deployment card of the model, from which the HTTP service builds the preprocessor serving
+    /// `/tokenize` and `/detokenize`. The files it refers to must be readable by the service.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card: Option<ModelDeploymentCard>,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// [ModelAlias] exposes the model `target` under the additional public name `name`.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ModelAlias {
+    /// Public name of the alias
+    pub name: String,
+
+    /// Public name of the model the alias resolves to
+    pub target: String,
+
+    /// Specifies whether the alias refers to a chat or completion model.
+    pub model_type: ModelType,
+}
+
+/// The value of a key under a model watch prefix: either a backend or an alias
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ModelRegistration {
+    Backend(ModelEntry),
+    Alias(ModelAlias),
+}
+
+impl ModelRegistration {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Backend(entry) => &entry.name,
+            Self::Alias(alias) => &alias.name,
+        }
+    }
+
+    pub fn model_type(&self) -> ModelType {
+        match self {
+            Self::Backend(entry) => entry.model_type,
+            Self::Alias(alias) => alias.model_type,
+        }
+    }
+}
+
+/// A registration ready to be applied, with the backend's client already built
+enum Prepared {
+    Alias(ModelAlias),
+    Chat(
+        Client<NvCreateChatCompletionRequest, Annotated<NvCreateChatCompletionStreamResponse>>,
+        u32,
+    ),
+    Completion(
+        Client<CompletionRequest, Annotated<CompletionResponse>>,
+        u32,
+    ),
+}
+
+/// A registration applied by a [`ModelWatchState`]
+struct Registered {
+    registration: ModelRegistration,
+    /// Instance ids of the backend's endpoint; `None` for aliases
+    instances: Option<watch::Receiver<Vec<i64>>>,
 }
 
 pub struct ModelWatchState {
@@ -53,6 +133,229 @@ pub struct ModelWatchState {
     pub model_type: ModelType,
     pub manager: ModelManager,
     pub drt: DistributedRuntime,
+    /// etcd key to the registration added for it, so deletes can be applied
+    registrations: Mutex<HashMap<String, Registered>>,
+}
+
+impl ModelWatchState {
+    pub fn new(
+        prefix: String,
+        model_type: ModelType,
+        manager: ModelManager,
+        drt: DistributedRuntime,
+    ) -> Self {
+        Self {
+            prefix,
+            model_type,
+            manager,
+            drt,
+            registrations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The key a registration named `name` is stored under, matching the layout used by `llmctl`:
+    /// additional backends of a model are stored as `{name}@{backend}`
+    pub fn registration_key(&self, name: &str, backend: Option<&str>) -> String {
+        match backend {
+            Some(backend) => format!("{}{}@{}", self.prefix, name, backend),
+            None => format!("{}{}", self.prefix, name),
+        }
+    }
+
+    /// Number of live instances behind the backend registered under `key`
+    pub fn instance_count(&self, key: &str) -> Option<usize> {
+        let registrations = self.registrations.lock().unwrap();
+        let instances = registrations.get(key)?.instances.as_ref()?;
+        let count = instances.borrow().len();
+        Some(count)
+    }
+
+    /// Add a model backend or alias under `key`, replacing any previous registration of the key.
+    /// This is the code path for both etcd PUT events and the admin API.
+    ///
+    /// The new registration is checked and its client built before the previous one is removed,
+    /// so a registration which cannot be applied leaves the previous one serving.
+    pub async fn register(&self, key: &str, registration: ModelRegistration) -> Result<()> {
+        if registration.model_type() != self.model_type {
+            raise!(
+                "model type mismatch: {} != {}",
+                registration.model_type(),
+                self.model_type
+            );
+        }
+        self.check_conflicts(key, &registration)?;
+
+        let service_name = registration.name().to_string();
+
+        // the model is served without `/tokenize` rather than not at all
+        let preprocessor = match &registration {
+            ModelRegistration::Backend(ModelEntry {
+                card: Some(card), ..
+            }) => OpenAIPreprocessor::new(card.clone())
+                .await
+                .inspect_err(|e| {
+                    tracing::warn!(
+                        model = %service_name,
+                        "failed to build the preprocessor from the deployment card: {}",
+                        e
+                    )
+                })
+                .ok(),
+            _ => None,
+        };
+
+        let prepared = match &registration {
+            ModelRegistration::Alias(alias) => Prepared::Alias(alias.clone()),
+            ModelRegistration::Backend(model_entry) => {
+                let endpoint = model_entry.endpoint.clone();
+                let endpoint = self
+                    .drt
+                    .namespace(endpoint.namespace)?
+                    .component(endpoint.component)?
+                    .endpoint(endpoint.name);
+                match self.model_type {
+                    ModelType::Chat => Prepared::Chat(
+                        endpoint
+                            .client::<NvCreateChatCompletionRequest, Annotated<NvCreateChatCompletionStreamResponse>>()
+                            .await?,
+                        model_entry.weight,
+                    ),
+                    ModelType::Completion => Prepared::Completion(
+                        endpoint
+                            .client::<CompletionRequest, Annotated<CompletionResponse>>()
+                            .await?,
+                        model_entry.weight,
+                    ),
+                }
+            }
+        };
+
+        if self.registrations.lock().unwrap().contains_key(key) {
+            self.deregister(key)?;
+        }
+
+        let instances = match prepared {
+            Prepared::Alias(alias) => {
+                self.manager
+                    .add_alias(self.model_type, &alias.name, &alias.target)?;
+                None
+            }
+            Prepared::Chat(client, weight) => {
+                let instances = client.endpoint_ids().clone();
+                self.manager.add_chat_completions_backend(
+                    &service_name,
+                    key,
+                    weight,
+                    Arc::new(client),
+                )?;
+                Some(instances)
+            }
+            Prepared::Completion(client, weight) => {
+                let instances = client.endpoint_ids().clone();
+                self.manager.add_completions_backend(
+                    &service_name,
+                    key,
+                    weight,
+                    Arc::new(client),
+                )?;
+                Some(instances)
+            }
+        };
+
+        if let Some(preprocessor) = preprocessor {
+            self.manager.add_preprocessor(&service_name, preprocessor);
+        }
+
+        self.registrations.lock().unwrap().insert(
+            key.to_string(),
+            Registered {
+                registration,
+                instances,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Fail if `registration` would clash with a model or alias registered under another key.
+    /// Names held only by the registration under `key` are free, since it is replaced.
+    fn check_conflicts(&self, key: &str, registration: &ModelRegistration) -> Result<()> {
+        let name = registration.name();
+        let registrations = self.registrations.lock().unwrap();
+        let previous = registrations.get(key).map(|r| &r.registration);
+        let is_alias = self
+            .manager
+            .list_aliases(self.model_type)
+            .iter()
+            .any(|(alias, _)| alias == name);
+        let previous_is_alias =
+            matches!(previous, Some(ModelRegistration::Alias(alias)) if alias.name == name);
+
+        match registration {
+            ModelRegistration::Alias(_) => {
+                let backends = self.manager.list_backends(self.model_type, name);
+                let only_previous_backend = matches!(
+                    previous,
+                    Some(ModelRegistration::Backend(entry)) if entry.name == name
+                ) && backends.len() == 1;
+                if (is_alias && !previous_is_alias)
+                    || (!backends.is_empty() && !only_previous_backend)
+                {
+                    raise!("model {} already exists", name);
+                }
+            }
+            ModelRegistration::Backend(_) => {
+                if is_alias && !previous_is_alias {
+                    raise!("model {} already exists as an alias", name);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the model backend or alias registered under `key`.
+    /// This is the code path for both etcd DELETE events and the admin API.
+    pub fn deregister(&self, key: &str) -> Result<ModelRegistration> {
+        let Some(Registered { registration, .. }) = self.registrations.lock().unwrap().remove(key)
+        else {
+            raise!("no model registered for key {}", key);
+        };
+
+        match &registration {
+            ModelRegistration::Backend(entry) => {
+                match self.model_type {
+                    ModelType::Chat => self
+                        .manager
+                        .remove_chat_completions_backend(&entry.name, key)?,
+                    ModelType::Completion => {
+                        self.manager.remove_completions_backend(&entry.name, key)?
+                    }
+                }
+                // the preprocessor is kept while another backend of the model carries a card,
+                // and is already gone if this was the model's last backend
+                if entry.card.is_some() && !self.has_card(&entry.name) {
+                    let _ = self.manager.remove_preprocessor(&entry.name);
+                }
+            }
+            ModelRegistration::Alias(alias) => {
+                self.manager.remove_alias(self.model_type, &alias.name)?
+            }
+        };
+
+        Ok(registration)
+    }
+
+    /// True if a backend of the model `name` is registered with a deployment card
+    fn has_card(&self, name: &str) -> bool {
+        self.registrations
+            .lock()
+            .unwrap()
+            .values()
+            .any(|registered| match &registered.registration {
+                ModelRegistration::Backend(entry) => entry.name == name && entry.card.is_some(),
+                ModelRegistration::Alias(_) => false,
+            })
+    }
 }
 
 pub async fn model_watcher(state: Arc<ModelWatchState>, mut events_rx: Receiver<WatchEvent>) {
@@ -80,66 +383,63 @@ pub async fn model_watcher(state: Arc<ModelWatchState>, mut events_rx: Receiver<
     }
 }
 
-async fn handle_delete(kv: &KeyValue, state: Arc<ModelWatchState>) -> Result<(&str, ModelType)> {
+async fn handle_delete(kv: &KeyValue, state: Arc<ModelWatchState>) -> Result<(String, ModelType)> {
     let key = kv.key_str()?;
     tracing::debug!(key, "removing model");
 
-    let model_name = key.trim_start_matches(&state.prefix);
+    let registration = state.deregister(key)?;
 
-    match state.model_type {
-        ModelType::Chat => state.manager.remove_chat_completions_model(model_name)?,
-        ModelType::Completion => state.manager.remove_completions_model(model_name)?,
-    };
-
-    Ok((model_name, state.model_type))
+    Ok((registration.name().to_string(), state.model_type))
 }
 
-// Handles a PUT event from etcd, this usually means adding a new model to the list of served
-// models.
+// Handles a PUT event from etcd, this usually means adding a new model backend or alias to the
+// list of served models. The etcd key identifies the backend, so a PUT to an existing key replaces
+// the previous registration, e.g. to change a backend's weight.
 //
 // If this method errors, for the near term, we will delete the offending key.
 async fn handle_put(kv: &KeyValue, state: Arc<ModelWatchState>) -> Result<(String, ModelType)> {
     let key = kv.key_str()?;
     tracing::debug!(key, "adding model");
 
-    // model_entry.name is the service name (e.g. "Llama-3.2-3B-Instruct")
-    let model_entry = serde_json::from_slice::<ModelEntry>(kv.value())?;
-    let service_name = model_entry.name.clone();
+    // the name is the service name (e.g. "Llama-3.2-3B-Instruct")
+    let registration = serde_json::from_slice::<ModelRegistration>(kv.value())?;
+    let service_name = registration.name().to_string();
 
-    if model_entry.model_type != state.model_type {
-        raise!(
-            "model type mismatch: {} != {}",
-            model_entry.model_type,
-            state.model_type
-        );
-    }
-
-    match state.model_type {
-        ModelType::Chat => {
-            let client = state
-                .drt
-                .namespace(model_entry.endpoint.namespace)?
-                .component(model_entry.endpoint.component)?
-                .endpoint(model_entry.endpoint.name)
-                .client::<NvCreateChatCompletionRequest, Annotated<NvCreateChatCompletionStreamResponse>>()
-                .await?;
-            state
-                .manager
-                .add_chat_completions_model(&service_name, Arc::new(client))?;
-        }
-        ModelType::Completion => {
-            let client = state
-                .drt
-                .namespace(model_entry.endpoint.namespace)?
-                .component(model_entry.endpoint.component)?
-                .endpoint(model_entry.endpoint.name)
-                .client::<CompletionRequest, Annotated<CompletionResponse>>()
-                .await?;
-            state
-                .manager
-                .add_completions_model(&service_name, Arc::new(client))?;
-        }
-    }
+    state.register(key, registration).await?;
 
     Ok((service_name, state.model_type))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_registration_serde() {
+        // entries written before weights existed default to a weight of 1
+        let registration: ModelRegistration = serde_json::from_str(
+            r#"{"name":"foo","endpoint":{"namespace":"ns","component":"vllm","name":"generate"},"model_type":"Chat"}"#,
+        )
+        .unwrap();
+        let ModelRegistration::Backend(entry) = &registration else {
+            panic!("expected a backend: {registration:?}");
+        };
+        assert_eq!(entry.weight, 1);
+
+        assert!(entry.card.is_none());
+
+        let registration: ModelRegistration =
+            serde_json::from_str(r#"{"name":"llama","target":"foo","model_type":"Chat"}"#).unwrap();
+        let ModelRegistration::Alias(alias) = registration else {
+            panic!("expected an alias: {registration:?}");
+        };
+        assert_eq!(
+            alias,
+            ModelAlias {
+                name: "llama".to_string(),
+                target: "foo".to_string(),
+                model_type: ModelType::Chat,
+            }
+        );
+    }
+}
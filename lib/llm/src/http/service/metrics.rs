@@ -19,7 +19,11 @@ use std::{sync::Arc, time::Instant};
 
 pub use prometheus::Registry;
 
-use super::{DeploymentState, RouteDoc};
+use super::{
+    admission::AdmissionPermit,
+    auth::{ApiKey, ANONYMOUS_KEY_ID},
+    DeploymentState, RouteDoc,
+};
 
 /// Value for the `status` label in the request counter for successful requests
 pub const REQUEST_STATUS_SUCCESS: &str = "success";
@@ -37,6 +41,9 @@ pub struct Metrics {
     request_counter: IntCounterVec,
     inflight_gauge: IntGaugeVec,
     request_duration: HistogramVec,
+    queued_gauge: IntGaugeVec,
+    queue_duration: HistogramVec,
+    rejected_counter: IntCounterVec,
 }
 
 /// RAII object for inflight gauge and request counters
@@ -50,6 +57,10 @@ pub struct InflightGuard {
     request_type: RequestType,
     status: Status,
     timer: Instant,
+    api_key: Option<Arc<ApiKey>>,
+    tokens: u64,
+    /// Released when the request completes
+    _permit: Option<AdmissionPermit>,
 }
 
 /// Requests will be logged by the type of endpoint hit
@@ -60,6 +71,9 @@ pub enum Endpoint {
 
     /// OAI Chat Completions
     ChatCompletions,
+
+    /// OAI Responses
+    Responses,
 }
 
 /// Metrics for the HTTP service
@@ -86,16 +100,23 @@ impl Default for Metrics {
 impl Metrics {
     /// Create Metrics with the given prefix
     /// The following metrics will be created:
-    /// - `{prefix}_http_service_requests_total` - IntCounterVec for the total number of requests processed
+    /// - `{prefix}_http_service_requests_total` - IntCounterVec for the total number of requests processed,
+    ///   labeled by the id of the API key which made the request
     /// - `{prefix}_http_service_inflight_requests` - IntGaugeVec for the number of inflight requests
     /// - `{prefix}_http_service_request_duration_seconds` - HistogramVec for the duration of requests
+    /// - `{prefix}_http_service_queued_requests` - IntGaugeVec for the number of requests waiting
+    ///   for admission, labeled by priority
+    /// - `{prefix}_http_service_queue_duration_seconds` - HistogramVec for the time admitted
+    ///   requests waited, labeled by priority
+    /// - `{prefix}_http_service_rejected_requests_total` - IntCounterVec for the requests rejected
+    ///   by admission control, labeled by reason
     pub fn new(prefix: &str) -> Self {
         let request_counter = IntCounterVec::new(
             Opts::new(
                 format!("{}_http_service_requests_total", prefix),
                 "Total number of LLM requests processed",
             ),
-            &["model", "endpoint", "request_type", "status"],
+            &["model", "endpoint", "request_type", "status", "api_key"],
         )
         .unwrap();
 
@@ -120,10 +141,43 @@ impl Metrics {
         )
         .unwrap();
 
+        let queued_gauge = IntGaugeVec::new(
+            Opts::new(
+                format!("{}_http_service_queued_requests", prefix),
+                "Number of requests waiting for admission",
+            ),
+            &["model", "priority"],
+        )
+        .unwrap();
+
+        let queue_duration = HistogramVec::new(
+            HistogramOpts::new(
+                format!("{}_http_service_queue_duration_seconds", prefix),
+                "Time requests waited for admission",
+            )
+            .buckets(vec![
+                0.001, 0.005, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+            ]),
+            &["model", "priority"],
+        )
+        .unwrap();
+
+        let rejected_counter = IntCounterVec::new(
+            Opts::new(
+                format!("{}_http_service_rejected_requests_total", prefix),
+                "Total number of requests rejected by admission control",
+            ),
+            &["model", "reason"],
+        )
+        .unwrap();
+
         Metrics {
             request_counter,
             inflight_gauge,
             request_duration,
+            queued_gauge,
+            queue_duration,
+            rejected_counter,
         }
     }
 
@@ -138,6 +192,19 @@ impl Metrics {
         endpoint: &Endpoint,
         request_type: &RequestType,
         status: &Status,
+    ) -> u64 {
+        self.get_request_counter_for_key(ANONYMOUS_KEY_ID, model, endpoint, request_type, status)
+    }
+
+    /// Get the number of requests made with the API key `api_key` for the given dimensions;
+    /// unauthenticated requests are counted under [`ANONYMOUS_KEY_ID`]
+    pub fn get_request_counter_for_key(
+        &self,
+        api_key: &str,
+        model: &str,
+        endpoint: &Endpoint,
+        request_type: &RequestType,
+        status: &Status,
     ) -> u64 {
         self.request_counter
             .with_label_values(&[
@@ -145,6 +212,7 @@ impl Metrics {
                 endpoint.as_str(),
                 request_type.as_str(),
                 status.as_str(),
+                api_key,
             ])
             .get()
     }
@@ -160,6 +228,7 @@ impl Metrics {
         endpoint: &Endpoint,
         request_type: &RequestType,
         status: &Status,
+        api_key: &str,
     ) {
         self.request_counter
             .with_label_values(&[
@@ -167,6 +236,7 @@ impl Metrics {
                 endpoint.as_str(),
                 request_type.as_str(),
                 status.as_str(),
+                api_key,
             ])
             .inc()
     }
@@ -184,10 +254,51 @@ impl Metrics {
         self.inflight_gauge.with_label_values(&[model]).dec()
     }
 
+    /// Get the number of requests for the given model and priority waiting for admission
+    pub fn get_queued_count(&self, model: &str, priority: &str) -> i64 {
+        self.queued_gauge
+            .with_label_values(&[model, priority])
+            .get()
+    }
+
+    pub(crate) fn inc_queued_gauge(&self, model: &str, priority: &str) {
+        self.queued_gauge
+            .with_label_values(&[model, priority])
+            .inc()
+    }
+
+    pub(crate) fn dec_queued_gauge(&self, model: &str, priority: &str) {
+        self.queued_gauge
+            .with_label_values(&[model, priority])
+            .dec()
+    }
+
+    pub(crate) fn observe_queue_duration(&self, model: &str, priority: &str, seconds: f64) {
+        self.queue_duration
+            .with_label_values(&[model, priority])
+            .observe(seconds)
+    }
+
+    /// Get the number of requests for the given model rejected by admission control for `reason`
+    pub fn get_rejected_count(&self, model: &str, reason: &str) -> u64 {
+        self.rejected_counter
+            .with_label_values(&[model, reason])
+            .get()
+    }
+
+    pub(crate) fn inc_rejected_counter(&self, model: &str, reason: &str) {
+        self.rejected_counter
+            .with_label_values(&[model, reason])
+            .inc()
+    }
+
     pub fn register(&self, registry: &Registry) -> Result<(), prometheus::Error> {
         registry.register(Box::new(self.request_counter.clone()))?;
         registry.register(Box::new(self.inflight_gauge.clone()))?;
         registry.register(Box::new(self.request_duration.clone()))?;
+        registry.register(Box::new(self.queued_gauge.clone()))?;
+        registry.register(Box::new(self.queue_duration.clone()))?;
+        registry.register(Box::new(self.rejected_counter.clone()))?;
         Ok(())
     }
 }
@@ -240,12 +351,30 @@ impl InflightGuard {
             request_type,
             status: Status::Error,
             timer,
+            api_key: None,
+            tokens: 0,
+            _permit: None,
         }
     }
 
     pub(crate) fn mark_ok(&mut self) {
         self.status = Status::Success;
     }
+
+    /// Attribute the request to an API key; the key is charged for the recorded tokens on drop
+    pub(crate) fn set_api_key(&mut self, api_key: Option<Arc<ApiKey>>) {
+        self.api_key = api_key;
+    }
+
+    /// Hold the request's admission slot until the request completes
+    pub(crate) fn set_admission_permit(&mut self, permit: AdmissionPermit) {
+        self._permit = Some(permit);
+    }
+
+    /// Record tokens consumed by the request
+    pub(crate) fn add_tokens(&mut self, tokens: u64) {
+        self.tokens += tokens;
+    }
 }
 
 impl Drop for InflightGuard {
@@ -256,13 +385,22 @@ impl Drop for InflightGuard {
         // the frequency on incrementing the full request counter is relatively low
         // if we were incrementing the counter on every forward pass, we'd use static CounterVec or
         // discrete counter object without the more costly lookup required for the following calls
+        let api_key = self
+            .api_key
+            .as_ref()
+            .map_or(ANONYMOUS_KEY_ID, |key| key.id());
         self.metrics.inc_request_counter(
             &self.model,
             &self.endpoint,
             &self.request_type,
             &self.status,
+            api_key,
         );
 
+        if let Some(key) = &self.api_key {
+            key.record_tokens(self.tokens);
+        }
+
         // Record the duration of the request
         self.metrics
             .request_duration
@@ -276,6 +414,7 @@ impl std::fmt::Display for Endpoint {
         match self {
             Endpoint::Completions => write!(f, "completions"),
             Endpoint::ChatCompletions => write!(f, "chat_completions"),
+            Endpoint::Responses => write!(f, "responses"),
         }
     }
 }
@@ -285,6 +424,7 @@ impl Endpoint {
         match self {
             Endpoint::Completions => "completions",
             Endpoint::ChatCompletions => "chat_completions",
+            Endpoint::Responses => "responses",
         }
     }
 }
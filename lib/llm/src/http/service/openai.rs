@@ -13,9 +13,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use async_openai::types::ChatCompletionStreamOptions;
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::{Extension, State},
+    http::{HeaderMap, StatusCode},
     response::{
         sse::{Event, KeepAlive, Sse},
         IntoResponse, Response,
@@ -28,26 +29,38 @@ use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
     pin::Pin,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
     time::{SystemTime, UNIX_EPOCH},
 };
 use tokio_stream::wrappers::ReceiverStream;
 
 use super::DeploymentState;
 use super::{
+    admission::{Priority, PRIORITY_HEADER},
+    audit::audit_stream,
+    auth::ApiKey,
     error::HttpError,
     metrics::{Endpoint, InflightGuard},
     RouteDoc,
 };
 
+use crate::preprocessor::{media::MediaError, CONTEXT_CHAT_TEMPLATE};
 use crate::protocols::openai::{
-    chat_completions::NvCreateChatCompletionResponse, completions::CompletionResponse,
+    chat_completions::{NvCreateChatCompletionResponse, NvCreateChatCompletionStreamResponse},
+    completions::CompletionResponse,
+    nvext::NvExt,
+    responses::{NvCreateResponseRequest, ResponseEventGenerator, ResponseStreamEvent},
+    tokenize::{DetokenizeRequest, DetokenizeResponse, TokenizeRequest, TokenizeResponse},
 };
 use crate::types::{
     openai::{chat_completions::NvCreateChatCompletionRequest, completions::CompletionRequest},
     Annotated,
 };
 
+use dynamo_runtime::logging::TraceParent;
 use dynamo_runtime::pipeline::{AsyncEngineContext, Context};
 
 #[derive(Serialize, Deserialize)]
@@ -77,6 +90,68 @@ impl ErrorResponse {
         )
     }
 
+    /// Service Unavailable
+    /// This is returned for models which an operator put into maintenance.
+    pub fn model_in_maintenance(model: &str) -> (StatusCode, Json<ErrorResponse>) {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse {
+                error: format!("Model {model} is under maintenance"),
+            }),
+        )
+    }
+
+    /// Too Many Requests
+    pub fn too_many_requests(msg: &str) -> (StatusCode, Json<ErrorResponse>) {
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(ErrorResponse {
+                error: msg.to_string(),
+            }),
+        )
+    }
+
+    /// Service Unavailable
+    /// This is returned when a request could not be admitted in time.
+    pub fn service_unavailable(msg: &str) -> (StatusCode, Json<ErrorResponse>) {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse {
+                error: msg.to_string(),
+            }),
+        )
+    }
+
+    /// Unauthorized
+    pub fn unauthorized() -> (StatusCode, Json<ErrorResponse>) {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse {
+                error: "Unauthorized".to_string(),
+            }),
+        )
+    }
+
+    /// Conflict
+    pub fn conflict(msg: &str) -> (StatusCode, Json<ErrorResponse>) {
+        (
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: msg.to_string(),
+            }),
+        )
+    }
+
+    /// Bad Request
+    pub fn bad_request(msg: &str) -> (StatusCode, Json<ErrorResponse>) {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: msg.to_string(),
+            }),
+        )
+    }
+
     /// Internal Service Error
     /// Return this error when the service encounters an internal error.
     /// We should return a generic message to the client instead of the real error.
@@ -95,9 +170,14 @@ impl ErrorResponse {
     /// an [`anyhow::Error`]. This method will convert the [`anyhow::Error`] into an [`HttpError`].
     /// If successful, it will return the [`HttpError`] as an [`ErrorResponse::internal_server_error`]
     /// with the details of the error.
+    /// Invalid media of a multimodal request, a [`MediaError`], is a bad request.
     pub fn from_anyhow(err: anyhow::Error, alt_msg: &str) -> (StatusCode, Json<ErrorResponse>) {
-        match err.downcast::<HttpError>() {
-            Ok(http_error) => ErrorResponse::from_http_error(http_error),
+        let err = match err.downcast::<HttpError>() {
+            Ok(http_error) => return ErrorResponse::from_http_error(http_error),
+            Err(err) => err,
+        };
+        match err.downcast::<MediaError>() {
+            Ok(media_error) => ErrorResponse::bad_request(&media_error.to_string()),
             Err(err) => ErrorResponse::internal_server_error(&format!("{alt_msg}: {err}")),
         }
     }
@@ -131,46 +211,91 @@ impl From<HttpError> for ErrorResponse {
 #[tracing::instrument(skip_all)]
 async fn completions(
     State(state): State<Arc<DeploymentState>>,
+    api_key: Option<Extension<Arc<ApiKey>>>,
+    headers: HeaderMap,
     Json(request): Json<CompletionRequest>,
 ) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     // return a 503 if the service is not ready
     check_ready(&state)?;
 
-    // todo - extract distributed tracing id and context id from headers
+    // todo - extract context id from headers
+    let trace_parent = trace_parent_from_headers(&headers);
     let request_id = uuid::Uuid::new_v4().to_string();
 
     // todo - decide on default
     let streaming = request.inner.stream.unwrap_or(false);
 
+    // the priority is read before nvext is dropped from the request
+    let priority = request_priority(&headers, request.nvext.as_ref())?;
+
+    let include_usage = include_usage(request.inner.stream_options.as_ref());
+    let charge_tokens = charges_tokens(api_key.as_ref());
+
     // update the request to always stream
     let inner = async_openai::types::CreateCompletionRequest {
         stream: Some(true),
+        stream_options: engine_stream_options(
+            streaming,
+            charge_tokens,
+            request.inner.stream_options,
+        ),
         ..request.inner
     };
 
-    let request = CompletionRequest { inner, nvext: None };
+    let mut request = CompletionRequest { inner, nvext: None };
+
+    // an empty model name selects the configured default model
+    let settings = state.reloadable_settings();
+    request.inner.model = settings.resolve_model(&request.inner.model);
+    settings.apply_completion_defaults(&mut request);
 
-    // todo - make the protocols be optional for model name
-    // todo - when optional, if none, apply a default
     let model = &request.inner.model;
 
+    if settings.log_requests {
+        tracing::info!(%request_id, %model, "completions request");
+    }
+
+    // aliases share the maintenance state, in-flight limit and metrics of the model they name
+    let served_model = state.completions_model(model);
+    if state.in_maintenance(&served_model) {
+        return Err(ErrorResponse::model_in_maintenance(model));
+    }
+
     // todo - error handling should be more robust
     let engine = state
         .get_completions_engine(model)
         .map_err(|_| ErrorResponse::model_not_found())?;
 
+    // wait for a free slot if the model is at its in-flight limit
+    let permit = match state.admission().admit(&served_model, priority).await {
+        Ok(permit) => permit,
+        Err(rejection) => return Ok(rejection.into_response()),
+    };
+
     // this will increment the inflight gauge for the model
-    let mut inflight = state.create_inflight_guard(model, Endpoint::Completions, streaming);
+    let mut inflight = state.create_inflight_guard(&served_model, Endpoint::Completions, streaming);
+    let api_key = api_key.map(|Extension(key)| key);
+    let mut audit = state.audit_entry(
+        &request_id,
+        Endpoint::Completions,
+        &request,
+        api_key.as_deref().map(ApiKey::id),
+        streaming,
+    );
+    inflight.set_api_key(api_key);
+    inflight.set_admission_permit(permit);
 
     // setup context
-    // todo - inherit request_id from distributed trace details
-    let request = Context::with_id(request, request_id.clone());
+    let chat_template = settings.chat_template(model);
+    let request = request_context(request, request_id.clone(), trace_parent, chat_template);
 
     // issue the generate call on the engine
-    let stream = engine
-        .generate(request)
+    let stream = with_request_timeout(settings.request_timeout, engine.generate(request))
         .await
-        .map_err(|e| ErrorResponse::from_anyhow(e, "Failed to generate completions"))?;
+        .and_then(|stream| {
+            stream.map_err(|e| ErrorResponse::from_anyhow(e, "Failed to generate completions"))
+        })
+        .inspect_err(|(_, Json(e))| audit.set_error(&e.error))?;
 
     // capture the context to cancel the stream if the client disconnects
     let ctx = stream.context();
@@ -179,8 +304,11 @@ async fn completions(
     // note - we might do this as part of the post processing set to make it more generic
 
     if streaming {
+        let tokens = Arc::new(AtomicU64::new(0));
+        let stream = audit_stream(stream, audit, CompletionResponse::from_annotated_stream);
+        let stream = charge_usage(stream, tokens.clone(), include_usage);
         let stream = stream.map(|response| Event::try_from(EventConverter::from(response)));
-        let stream = monitor_for_disconnects(stream.boxed(), ctx, inflight).await;
+        let stream = monitor_for_disconnects(stream.boxed(), ctx, inflight, tokens).await;
 
         let mut sse_stream = Sse::new(stream);
 
@@ -193,6 +321,7 @@ async fn completions(
         let response = CompletionResponse::from_annotated_stream(stream.into())
             .await
             .map_err(|e| {
+                audit.set_error(&e);
                 tracing::error!(
                     "Failed to fold completions stream for {}: {:?}",
                     request_id,
@@ -201,6 +330,10 @@ async fn completions(
                 ErrorResponse::internal_server_error("Failed to fold completions stream")
             })?;
 
+        if let Some(usage) = &response.usage {
+            inflight.add_tokens(usage.total_tokens.max(0) as u64);
+        }
+        audit.set_response(&response);
         inflight.mark_ok();
         Ok(Json(response).into_response())
     }
@@ -217,52 +350,106 @@ async fn completions(
 #[tracing::instrument(skip_all)]
 async fn chat_completions(
     State(state): State<Arc<DeploymentState>>,
+    api_key: Option<Extension<Arc<ApiKey>>>,
+    headers: HeaderMap,
     Json(request): Json<NvCreateChatCompletionRequest>,
 ) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     // return a 503 if the service is not ready
     check_ready(&state)?;
 
-    // todo - extract distributed tracing id and context id from headers
+    // todo - extract context id from headers
+    let trace_parent = trace_parent_from_headers(&headers);
     let request_id = uuid::Uuid::new_v4().to_string();
 
     // todo - decide on default
     let streaming = request.inner.stream.unwrap_or(false);
 
+    let priority = request_priority(&headers, request.nvext.as_ref())?;
+
+    let include_usage = include_usage(request.inner.stream_options.as_ref());
+    let charge_tokens = charges_tokens(api_key.as_ref());
+
     // update the request to always stream
     let inner_request = async_openai::types::CreateChatCompletionRequest {
         stream: Some(true),
+        stream_options: engine_stream_options(
+            streaming,
+            charge_tokens,
+            request.inner.stream_options,
+        ),
         ..request.inner
     };
-    let request = NvCreateChatCompletionRequest {
+    // only the reasoning parser selection is forwarded to the preprocessor; the other nvext
+    // fields are not client settable
+    let nvext = request
+        .nvext
+        .and_then(|nvext| nvext.reasoning_parser)
+        .map(|reasoning_parser| NvExt {
+            reasoning_parser: Some(reasoning_parser),
+            ..Default::default()
+        });
+    let mut request = NvCreateChatCompletionRequest {
         inner: inner_request,
-        nvext: None,
+        nvext,
     };
 
-    // todo - make the protocols be optional for model name
-    // todo - when optional, if none, apply a default
+    // an empty model name selects the configured default model
+    let settings = state.reloadable_settings();
+    request.inner.model = settings.resolve_model(&request.inner.model);
+    settings.apply_chat_defaults(&mut request);
+
     let model = &request.inner.model;
 
+    if settings.log_requests {
+        tracing::info!(%request_id, %model, "chat completions request");
+    }
+
     // todo - determine the proper error code for when a request model is not present
     tracing::trace!("Getting chat completions engine for model: {}", model);
 
+    // aliases share the maintenance state, in-flight limit and metrics of the model they name
+    let served_model = state.chat_completions_model(model);
+    if state.in_maintenance(&served_model) {
+        return Err(ErrorResponse::model_in_maintenance(model));
+    }
+
     let engine = state
         .get_chat_completions_engine(model)
         .map_err(|_| ErrorResponse::model_not_found())?;
 
+    // wait for a free slot if the model is at its in-flight limit
+    let permit = match state.admission().admit(&served_model, priority).await {
+        Ok(permit) => permit,
+        Err(rejection) => return Ok(rejection.into_response()),
+    };
+
     // this will increment the inflight gauge for the model
-    let mut inflight = state.create_inflight_guard(model, Endpoint::ChatCompletions, streaming);
+    let mut inflight =
+        state.create_inflight_guard(&served_model, Endpoint::ChatCompletions, streaming);
+    let api_key = api_key.map(|Extension(key)| key);
+    let mut audit = state.audit_entry(
+        &request_id,
+        Endpoint::ChatCompletions,
+        &request,
+        api_key.as_deref().map(ApiKey::id),
+        streaming,
+    );
+    inflight.set_api_key(api_key);
+    inflight.set_admission_permit(permit);
 
     // setup context
-    // todo - inherit request_id from distributed trace details
-    let request = Context::with_id(request, request_id.clone());
+    let chat_template = settings.chat_template(model);
+    let request = request_context(request, request_id.clone(), trace_parent, chat_template);
 
     tracing::trace!("Issuing generate call for chat completions");
 
     // issue the generate call on the engine
-    let stream = engine
-        .generate(request)
+    let stream = with_request_timeout(settings.request_timeout, engine.generate(request))
         .await
-        .map_err(|e| ErrorResponse::from_anyhow(e, "Failed to generate completions"))?;
+        .and_then(|stream| {
+            stream.map_err(|e| ErrorResponse::from_anyhow(e, "Failed to generate completions"))
+        })
+        .inspect_err(|(_, Json(e))| audit.set_error(&e.error))?;
 
     // capture the context to cancel the stream if the client disconnects
     let ctx = stream.context();
@@ -271,8 +458,15 @@ async fn chat_completions(
     // note - we might do this as part of the post processing set to make it more generic
 
     if streaming {
+        let tokens = Arc::new(AtomicU64::new(0));
+        let stream = audit_stream(
+            stream,
+            audit,
+            NvCreateChatCompletionResponse::from_annotated_stream,
+        );
+        let stream = charge_usage(stream, tokens.clone(), include_usage);
         let stream = stream.map(|response| Event::try_from(EventConverter::from(response)));
-        let stream = monitor_for_disconnects(stream.boxed(), ctx, inflight).await;
+        let stream = monitor_for_disconnects(stream.boxed(), ctx, inflight, tokens).await;
 
         let mut sse_stream = Sse::new(stream);
 
@@ -285,6 +479,7 @@ async fn chat_completions(
         let response = NvCreateChatCompletionResponse::from_annotated_stream(stream.into())
             .await
             .map_err(|e| {
+                audit.set_error(&e);
                 tracing::error!(
                     request_id,
                     "Failed to fold chat completions stream for: {:?}",
@@ -296,11 +491,258 @@ async fn chat_completions(
                 ))
             })?;
 
+        if let Some(usage) = &response.inner.usage {
+            inflight.add_tokens(usage.total_tokens as u64);
+        }
+        audit.set_response(&response);
+        inflight.mark_ok();
+        Ok(Json(response).into_response())
+    }
+}
+
+/// Serve the Responses API with the chat completions engine of the model. The request is
+/// translated into a chat completions request and the chat stream is mapped back into Responses
+/// events; non-streaming requests return the response object built from the same events.
+async fn responses(
+    State(state): State<Arc<DeploymentState>>,
+    api_key: Option<Extension<Arc<ApiKey>>>,
+    headers: HeaderMap,
+    Json(mut request): Json<NvCreateResponseRequest>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    // return a 503 if the service is not ready
+    check_ready(&state)?;
+
+    let trace_parent = trace_parent_from_headers(&headers);
+    let request_id = uuid::Uuid::new_v4().to_string();
+
+    let streaming = request.stream.unwrap_or(false);
+
+    let priority = request_priority(&headers, request.nvext.as_ref())?;
+
+    // an empty model name selects the configured default model
+    let settings = state.reloadable_settings();
+    request.model = settings.resolve_model(&request.model);
+
+    let mut generator = ResponseEventGenerator::new(&request);
+
+    // the chat request always streams and reports usage in its final chunk
+    let mut request = NvCreateChatCompletionRequest::try_from(request)
+        .map_err(|e| ErrorResponse::bad_request(&e.to_string()))?;
+    settings.apply_chat_defaults(&mut request);
+
+    let model = &request.inner.model;
+
+    if settings.log_requests {
+        tracing::info!(%request_id, %model, "responses request");
+    }
+
+    // aliases share the maintenance state, in-flight limit and metrics of the model they name
+    let served_model = state.chat_completions_model(model);
+    if state.in_maintenance(&served_model) {
+        return Err(ErrorResponse::model_in_maintenance(model));
+    }
+
+    let engine = state
+        .get_chat_completions_engine(model)
+        .map_err(|_| ErrorResponse::model_not_found())?;
+
+    // wait for a free slot if the model is at its in-flight limit
+    let permit = match state.admission().admit(&served_model, priority).await {
+        Ok(permit) => permit,
+        Err(rejection) => return Ok(rejection.into_response()),
+    };
+
+    // this will increment the inflight gauge for the model
+    let mut inflight = state.create_inflight_guard(&served_model, Endpoint::Responses, streaming);
+    let api_key = api_key.map(|Extension(key)| key);
+    // the audit log records the translated chat completions request and response
+    let mut audit = state.audit_entry(
+        &request_id,
+        Endpoint::Responses,
+        &request,
+        api_key.as_deref().map(ApiKey::id),
+        streaming,
+    );
+    inflight.set_api_key(api_key);
+    inflight.set_admission_permit(permit);
+
+    // setup context
+    let chat_template = settings.chat_template(model);
+    let request = request_context(request, request_id.clone(), trace_parent, chat_template);
+
+    tracing::trace!("Issuing generate call for responses");
+
+    // issue the generate call on the engine
+    let stream = with_request_timeout(settings.request_timeout, engine.generate(request))
+        .await
+        .and_then(|stream| {
+            stream.map_err(|e| ErrorResponse::from_anyhow(e, "Failed to generate response"))
+        })
+        .inspect_err(|(_, Json(e))| audit.set_error(&e.error))?;
+
+    // capture the context to cancel the stream if the client disconnects
+    let ctx = stream.context();
+
+    let mut stream = audit_stream(
+        stream,
+        audit,
+        NvCreateChatCompletionResponse::from_annotated_stream,
+    );
+
+    if streaming {
+        let tokens = Arc::new(AtomicU64::new(0));
+        let usage = tokens.clone();
+        let events = async_stream::stream! {
+            for event in generator.start() {
+                yield event;
+            }
+            // the chat stream is drained after an error so that the audit log sees all of it
+            let mut error = None;
+            while let Some(chunk) = stream.next().await {
+                match chunk.ok() {
+                    Ok(chunk) if error.is_none() => {
+                        if let Some(data) = chunk.data {
+                            for event in generator.push(&data) {
+                                yield event;
+                            }
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        error.get_or_insert(e);
+                    }
+                }
+            }
+            let events = match error {
+                Some(error) => generator.fail(&error),
+                None => {
+                    let events = generator.finish();
+                    if let Some(response_usage) = &generator.response().usage {
+                        usage.store(response_usage.total_tokens as u64, Ordering::Relaxed);
+                    }
+                    events
+                }
+            };
+            for event in events {
+                yield event;
+            }
+        };
+        let stream = events.map(response_event);
+        let stream = monitor_for_disconnects(stream.boxed(), ctx, inflight, tokens).await;
+
+        let mut sse_stream = Sse::new(stream);
+
+        if let Some(keep_alive) = state.sse_keep_alive {
+            sse_stream = sse_stream.keep_alive(KeepAlive::default().interval(keep_alive));
+        }
+
+        Ok(sse_stream.into_response())
+    } else {
+        generator.start();
+        let mut error = None;
+        while let Some(chunk) = stream.next().await {
+            match chunk.ok() {
+                Ok(chunk) if error.is_none() => {
+                    if let Some(data) = chunk.data {
+                        generator.push(&data);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        if let Some(error) = error {
+            tracing::error!(request_id, "Failed to generate response: {}", error);
+            return Err(ErrorResponse::internal_server_error(&format!(
+                "Failed to generate response: {}",
+                error
+            )));
+        }
+        generator.finish();
+
+        let response = generator.into_response();
+        if let Some(usage) = &response.usage {
+            inflight.add_tokens(usage.total_tokens as u64);
+        }
         inflight.mark_ok();
         Ok(Json(response).into_response())
     }
 }
 
+/// Convert a Responses event into a server-sent event named after its type
+fn response_event(event: ResponseStreamEvent) -> Result<Event, axum::Error> {
+    Event::default()
+        .event(event.event.event_type())
+        .json_data(event)
+}
+
+/// Extract the W3C trace context from the `traceparent` and `tracestate` headers
+fn trace_parent_from_headers(headers: &HeaderMap) -> Option<TraceParent> {
+    let traceparent = headers.get("traceparent")?.to_str().ok()?;
+    let tracestate = headers.get("tracestate").and_then(|v| v.to_str().ok());
+    TraceParent::new(traceparent, tracestate)
+}
+
+/// Create the request [`Context`] and link the handler span into the caller's trace. The model's
+/// configured chat template override, if any, is passed to the preprocessor through the context.
+///
+/// The trace context propagated downstream is that of the current handler span when spans are
+/// being exported; otherwise the caller's trace context is forwarded unchanged.
+fn request_context<T: Send + Sync + 'static>(
+    request: T,
+    request_id: String,
+    trace_parent: Option<TraceParent>,
+    chat_template: Option<String>,
+) -> Context<T> {
+    if let Some(trace_parent) = &trace_parent {
+        trace_parent.attach(&tracing::Span::current());
+    }
+
+    let mut request = Context::with_id(request, request_id);
+    if let Some(trace_parent) = TraceParent::current().or(trace_parent) {
+        request.set_trace_parent(trace_parent);
+    }
+    if let Some(chat_template) = chat_template {
+        request.insert(CONTEXT_CHAT_TEMPLATE, chat_template);
+    }
+    request
+}
+
+/// Bound the time an engine may take to accept a request; returns a 504 on expiry
+async fn with_request_timeout<F: std::future::Future>(
+    timeout: Option<std::time::Duration>,
+    future: F,
+) -> Result<F::Output, (StatusCode, Json<ErrorResponse>)> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future).await.map_err(|_| {
+            (
+                StatusCode::GATEWAY_TIMEOUT,
+                Json(ErrorResponse {
+                    error: format!("Request timed out after {}s", timeout.as_secs_f64()),
+                }),
+            )
+        }),
+        None => Ok(future.await),
+    }
+}
+
+/// Priority class of a request: the [`PRIORITY_HEADER`] header, then `nvext.priority`
+fn request_priority(
+    headers: &HeaderMap,
+    nvext: Option<&NvExt>,
+) -> Result<Priority, (StatusCode, Json<ErrorResponse>)> {
+    match headers.get(PRIORITY_HEADER) {
+        Some(value) => value
+            .to_str()
+            .map_err(|e| e.to_string())
+            .and_then(str::parse)
+            .map_err(|e| ErrorResponse::bad_request(&format!("Invalid {PRIORITY_HEADER}: {e}"))),
+        None => Ok(nvext.and_then(|ext| ext.priority).unwrap_or_default()),
+    }
+}
+
 // todo - abstract this to the top level lib.rs to be reused
 // todo - move the service_observer to its own state/arc
 fn check_ready(_state: &Arc<DeploymentState>) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
@@ -310,34 +752,40 @@ fn check_ready(_state: &Arc<DeploymentState>) -> Result<(), (StatusCode, Json<Er
     Ok(())
 }
 
+/// True if the caller's API key, if any, may use `model`
+fn key_allows(api_key: Option<&Extension<Arc<ApiKey>>>, model: &str) -> bool {
+    api_key.is_none_or(|Extension(key)| key.allows_model(model))
+}
+
 /// list models handler, non-standard format
 async fn list_models_custom(
     State(state): State<Arc<DeploymentState>>,
+    api_key: Option<Extension<Arc<ApiKey>>>,
 ) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     check_ready(&state)?;
+    let api_key = api_key.as_ref();
     let mut models = HashMap::new();
 
-    let chat_models = state
-        .chat_completion_engines
-        .lock()
-        .unwrap()
-        .engines
-        .keys()
-        .cloned()
-        .collect::<Vec<String>>();
-
-    let completion_models = state
-        .completion_engines
-        .lock()
-        .unwrap()
-        .engines
-        .keys()
-        .cloned()
-        .collect::<Vec<String>>();
+    let mut chat_models = state.chat_completion_engines.lock().unwrap().list();
+    chat_models.retain(|model| key_allows(api_key, model));
+    let mut completion_models = state.completion_engines.lock().unwrap().list();
+    completion_models.retain(|model| key_allows(api_key, model));
 
     models.insert("chat_completion_models", chat_models);
     models.insert("completion_models", completion_models);
 
+    // aliases are reported as `alias -> target`
+    let mut aliases = state.chat_completion_engines.lock().unwrap().list_aliases();
+    aliases.extend(state.completion_engines.lock().unwrap().list_aliases());
+    models.insert(
+        "aliases",
+        aliases
+            .into_iter()
+            .filter(|(alias, _)| key_allows(api_key, alias))
+            .map(|(alias, target)| format!("{alias} -> {target}"))
+            .collect(),
+    );
+
     Ok(Json(models).into_response())
 }
 
@@ -356,8 +804,10 @@ async fn list_models_custom(
 /// }
 async fn list_models_openai(
     State(state): State<Arc<DeploymentState>>,
+    api_key: Option<Extension<Arc<ApiKey>>>,
 ) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
     check_ready(&state)?;
+    let api_key = api_key.as_ref();
 
     let created = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -369,10 +819,10 @@ async fn list_models_openai(
         .chat_completion_engines
         .lock()
         .unwrap()
-        .engines
-        .keys()
-        .chain(state.completion_engines.lock().unwrap().engines.keys())
-        .cloned()
+        .list()
+        .into_iter()
+        .chain(state.completion_engines.lock().unwrap().list())
+        .filter(|model| key_allows(api_key, model))
         .collect();
 
     for model_id in models {
@@ -381,6 +831,28 @@ async fn list_models_openai(
             object: "object",
             created,                        // Where would this come from? The GGUF?
             owned_by: "nvidia".to_string(), // Get organization from GGUF
+            parent: None,
+        });
+    }
+
+    // aliases are listed as models whose parent is the alias target
+    let aliases: HashMap<String, String> = state
+        .chat_completion_engines
+        .lock()
+        .unwrap()
+        .list_aliases()
+        .into_iter()
+        .chain(state.completion_engines.lock().unwrap().list_aliases())
+        .filter(|(alias, _)| key_allows(api_key, alias))
+        .collect();
+
+    for (alias, target) in aliases {
+        data.push(ModelListing {
+            id: alias,
+            object: "object",
+            created,
+            owned_by: "nvidia".to_string(),
+            parent: Some(target),
         });
     }
 
@@ -403,6 +875,9 @@ struct ModelListing {
     object: &'static str, // always "object"
     created: u64,         //  Seconds since epoch
     owned_by: String,
+    /// Target model of an alias
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent: Option<String>,
 }
 
 /// This method will consume a stream of SSE events and forward them to a new stream defined by a tokio channel.
@@ -410,35 +885,46 @@ struct ModelListing {
 /// how we can monitor for disconnects and stop the generation of completions.
 ///
 /// If a disconnect is detected, then the context will issue a `stop_generating` call to the context which will
-/// propagate the cancellation signal to the backend.
+/// propagate the cancellation signal to the backend. The rest of the stream is still consumed so that the usage
+/// it reports is charged.
+///
+/// Once the stream ends, the `tokens` recorded by the stream are charged against the caller's API key.
 async fn monitor_for_disconnects(
     stream: Pin<
         Box<dyn Stream<Item = Result<axum::response::sse::Event, axum::Error>> + std::marker::Send>,
     >,
     context: Arc<dyn AsyncEngineContext>,
     inflight: InflightGuard,
+    tokens: Arc<AtomicU64>,
 ) -> ReceiverStream<Result<Event, axum::Error>> {
     let (tx, rx) = tokio::sync::mpsc::channel(8);
 
     tokio::spawn(async move {
         let mut inflight = inflight;
         let mut stream = stream;
+        let mut disconnected = false;
         while let Some(event) = stream.next().await {
+            if disconnected {
+                continue;
+            }
+
             let event = match event {
                 Ok(event) => Ok(event),
                 Err(err) => Ok(Event::default().event("error").comment(err.to_string())),
             };
 
             if (tx.send(event).await).is_err() {
-                tracing::trace!("Forwarding SSE stream was dropped; breaking loop");
+                tracing::trace!("Forwarding SSE stream was dropped; stopping generation");
                 context.stop_generating();
-                break;
+                disconnected = true;
             }
         }
 
+        inflight.add_tokens(tokens.load(Ordering::Relaxed));
+
         // the stream completed successfully - mark as ok
         // this will increment the request counter with an "success" status
-        if tx.send(Ok(Event::default().data("[DONE]"))).await.is_ok() {
+        if !disconnected && tx.send(Ok(Event::default().data("[DONE]"))).await.is_ok() {
             inflight.mark_ok();
         }
     });
@@ -446,6 +932,90 @@ async fn monitor_for_disconnects(
     ReceiverStream::new(rx)
 }
 
+/// True if the client asked for the usage chunk at the end of a stream
+fn include_usage(stream_options: Option<&ChatCompletionStreamOptions>) -> bool {
+    stream_options.is_some_and(|options| options.include_usage)
+}
+
+/// True if the request is charged to an API key with a token limit
+fn charges_tokens(api_key: Option<&Extension<Arc<ApiKey>>>) -> bool {
+    api_key.is_some_and(|Extension(key)| key.limits_tokens())
+}
+
+/// The stream options of the request sent to the engine. Non-streaming responses are aggregated
+/// with the usage reported by the final chunk, and requests charged to a key with a token limit
+/// are charged from it; see [`charge_usage`]. Otherwise the client's options are kept.
+fn engine_stream_options(
+    streaming: bool,
+    charge_tokens: bool,
+    requested: Option<ChatCompletionStreamOptions>,
+) -> Option<ChatCompletionStreamOptions> {
+    if streaming && !charge_tokens {
+        requested
+    } else {
+        Some(ChatCompletionStreamOptions {
+            include_usage: true,
+        })
+    }
+}
+
+/// Streamed responses whose final chunk reports the token usage of the request
+trait StreamUsage {
+    /// Total tokens of the usage reported by the chunk, if any
+    fn total_tokens(&self) -> Option<u64>;
+
+    /// Remove the usage from the chunk; returns false if nothing else is left to send
+    fn strip_usage(&mut self) -> bool;
+}
+
+impl StreamUsage for CompletionResponse {
+    fn total_tokens(&self) -> Option<u64> {
+        self.usage
+            .as_ref()
+            .map(|usage| usage.total_tokens.max(0) as u64)
+    }
+
+    fn strip_usage(&mut self) -> bool {
+        self.usage = None;
+        !self.choices.is_empty()
+    }
+}
+
+impl StreamUsage for NvCreateChatCompletionStreamResponse {
+    fn total_tokens(&self) -> Option<u64> {
+        self.inner
+            .usage
+            .as_ref()
+            .map(|usage| usage.total_tokens as u64)
+    }
+
+    fn strip_usage(&mut self) -> bool {
+        self.inner.usage = None;
+        !self.inner.choices.is_empty()
+    }
+}
+
+/// Record the usage reported by a response stream in `tokens` for [`monitor_for_disconnects`] to
+/// charge. The engine may be asked for usage the client did not ask for; the usage is then
+/// removed from the stream and a chunk which only carried the usage is dropped.
+fn charge_usage<T: StreamUsage>(
+    stream: impl Stream<Item = Annotated<T>>,
+    tokens: Arc<AtomicU64>,
+    include_usage: bool,
+) -> impl Stream<Item = Annotated<T>> {
+    stream.filter_map(move |mut annotated| {
+        if let Some(data) = annotated.data.as_mut() {
+            if let Some(total_tokens) = data.total_tokens() {
+                tokens.fetch_add(total_tokens, Ordering::Relaxed);
+                if !include_usage && !data.strip_usage() {
+                    return futures::future::ready(None);
+                }
+            }
+        }
+        futures::future::ready(Some(annotated))
+    })
+}
+
 struct EventConverter<T>(Annotated<T>);
 
 impl<T> From<Annotated<T>> for EventConverter<T> {
@@ -518,6 +1088,104 @@ pub fn chat_completions_router(
     (vec![doc], router)
 }
 
+/// Create an Axum [`Router`] for the OpenAI API Responses endpoint
+/// If not path is provided, the default path is `/v1/responses`
+pub fn responses_router(
+    state: Arc<DeploymentState>,
+    path: Option<String>,
+) -> (Vec<RouteDoc>, Router) {
+    let path = path.unwrap_or("/v1/responses".to_string());
+    let doc = RouteDoc::new(axum::http::Method::POST, &path);
+    let router = Router::new()
+        .route(&path, post(responses))
+        .with_state(state);
+    (vec![doc], router)
+}
+
+/// Tokenize a prompt or chat messages with the tokenizer and chat template of a model. No
+/// inference is run, so these requests are neither admitted nor counted in the request metrics.
+async fn tokenize(
+    State(state): State<Arc<DeploymentState>>,
+    Json(mut request): Json<TokenizeRequest>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    check_ready(&state)?;
+
+    request.model = state.reloadable_settings().resolve_model(&request.model);
+    let preprocessor = state
+        .get_preprocessor(&request.model)
+        .map_err(|_| ErrorResponse::model_not_found())?;
+
+    if request.prompt.is_some() == request.messages.is_some() {
+        return Err(ErrorResponse::bad_request(
+            "Exactly one of prompt or messages is required",
+        ));
+    }
+
+    let formatted_prompt = if request.apply_chat_template() {
+        preprocessor.render(&request).map_err(|e| {
+            ErrorResponse::bad_request(&format!("Failed to apply the chat template: {e}"))
+        })?
+    } else if let Some(prompt) = &request.prompt {
+        prompt.clone()
+    } else {
+        return Err(ErrorResponse::bad_request(
+            "Messages can only be tokenized with the chat template applied",
+        ));
+    };
+
+    let encoding = preprocessor
+        .tokenize(&formatted_prompt)
+        .map_err(|e| ErrorResponse::internal_server_error(&format!("Failed to tokenize: {e}")))?;
+
+    Ok(Json(TokenizeResponse {
+        model: request.model,
+        count: encoding.token_ids.len(),
+        token_ids: encoding.token_ids,
+        tokens: encoding.tokens,
+        formatted_prompt,
+        context_length: preprocessor.context_length(),
+    })
+    .into_response())
+}
+
+/// Decode token ids with the tokenizer of a model
+async fn detokenize(
+    State(state): State<Arc<DeploymentState>>,
+    Json(mut request): Json<DetokenizeRequest>,
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    check_ready(&state)?;
+
+    request.model = state.reloadable_settings().resolve_model(&request.model);
+    let preprocessor = state
+        .get_preprocessor(&request.model)
+        .map_err(|_| ErrorResponse::model_not_found())?;
+
+    // unknown token ids are a client error
+    let text = preprocessor
+        .detokenize(&request.token_ids, request.skip_special_tokens)
+        .map_err(|e| ErrorResponse::bad_request(&format!("Failed to detokenize: {e}")))?;
+
+    Ok(Json(DetokenizeResponse {
+        model: request.model,
+        text,
+        context_length: preprocessor.context_length(),
+    })
+    .into_response())
+}
+
+/// Create an Axum [`Router`] for `/tokenize` and `/detokenize`
+pub fn tokenize_router(state: Arc<DeploymentState>) -> (Vec<RouteDoc>, Router) {
+    let docs = vec![
+        RouteDoc::new(axum::http::Method::POST, "/tokenize"),
+        RouteDoc::new(axum::http::Method::POST, "/detokenize"),
+    ];
+    let router = Router::new()
+        .route("/tokenize", post(tokenize))
+        .route("/detokenize", post(detokenize))
+        .with_state(state);
+    (docs, router)
+}
+
 /// List Models
 pub fn list_models_router(
     state: Arc<DeploymentState>,
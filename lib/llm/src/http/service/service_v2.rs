@@ -13,12 +13,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::{path::PathBuf, sync::Arc};
+
+use super::admin::{admin_router, AdminState, ModelWatchers};
+use super::auth::{auth_middleware, ApiKeyStore, AuthState};
+use super::config::{self, HttpServiceSettings};
+use super::discovery::ModelWatchState;
 use super::metrics;
 use super::ModelManager;
 use anyhow::Result;
+use axum::{extract::DefaultBodyLimit, http::HeaderValue};
 use derive_builder::Builder;
 use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 
 #[derive(Clone)]
 pub struct HttpService {
@@ -26,24 +34,33 @@ pub struct HttpService {
     router: axum::Router,
     port: u16,
     host: String,
+    settings: HttpServiceSettings,
+    api_keys: Option<ApiKeyStore>,
+    model_watchers: ModelWatchers,
 }
 
 #[derive(Clone, Builder)]
 #[builder(pattern = "owned", build_fn(private, name = "build_internal"))]
 pub struct HttpServiceConfig {
-    #[builder(default = "8787")]
-    port: u16,
+    /// Overrides the port of the [`HttpServiceSettings`] listener
+    #[builder(default, setter(strip_option))]
+    port: Option<u16>,
 
-    #[builder(setter(into), default = "String::from(\"0.0.0.0\")")]
-    host: String,
+    /// Overrides the host of the [`HttpServiceSettings`] listener
+    #[builder(default, setter(into, strip_option))]
+    host: Option<String>,
 
     // #[builder(default)]
     // custom: Vec<axum::Router>
-    #[builder(default = "true")]
-    enable_chat_endpoints: bool,
+    #[builder(default, setter(strip_option))]
+    enable_chat_endpoints: Option<bool>,
 
-    #[builder(default = "true")]
-    enable_cmpl_endpoints: bool,
+    #[builder(default, setter(strip_option))]
+    enable_cmpl_endpoints: Option<bool>,
+
+    /// Remaining settings; the listener and endpoint toggles above take precedence when set
+    #[builder(default)]
+    settings: HttpServiceSettings,
 }
 
 impl HttpService {
@@ -51,10 +68,35 @@ impl HttpService {
         HttpServiceConfigBuilder::default()
     }
 
+    /// Build the service from [`HttpServiceSettings`]
+    pub fn from_settings(settings: HttpServiceSettings) -> Result<HttpService> {
+        HttpService::builder().settings(settings).build()
+    }
+
     pub fn model_manager(&self) -> &ModelManager {
         &self.models
     }
 
+    /// The API keys accepted by the service; `None` if authentication is disabled
+    pub fn api_keys(&self) -> Option<&ApiKeyStore> {
+        self.api_keys.as_ref()
+    }
+
+    /// Make the models discovered by `state` manageable through the admin API
+    pub fn add_model_watcher(&self, state: Arc<ModelWatchState>) {
+        self.model_watchers.add(state);
+    }
+
+    /// The settings the service was started with
+    pub fn settings(&self) -> &HttpServiceSettings {
+        &self.settings
+    }
+
+    /// Reload the hot-reloadable settings whenever the config file at `path` changes
+    pub fn watch_config(&self, path: PathBuf, cancel_token: CancellationToken) -> JoinHandle<()> {
+        config::watch(path, self.models.state(), cancel_token)
+    }
+
     pub async fn spawn(&self, cancel_token: CancellationToken) -> JoinHandle<Result<()>> {
         let this = self.clone();
         tokio::spawn(async move { this.run(cancel_token).await })
@@ -84,7 +126,22 @@ impl HttpServiceConfigBuilder {
     pub fn build(self) -> Result<HttpService, anyhow::Error> {
         let config = self.build_internal()?;
 
-        let model_manager = ModelManager::new();
+        // the listener and endpoint toggles set on the builder take precedence over the settings
+        let mut settings = config.settings;
+        if let Some(port) = config.port {
+            settings.listener.port = port;
+        }
+        if let Some(host) = config.host {
+            settings.listener.host = host;
+        }
+        if let Some(enable) = config.enable_chat_endpoints {
+            settings.endpoints.chat = enable;
+        }
+        if let Some(enable) = config.enable_cmpl_endpoints {
+            settings.endpoints.completions = enable;
+        }
+
+        let model_manager = ModelManager::with_settings(&settings);
 
         // enable prometheus metrics
         let registry = metrics::Registry::new();
@@ -93,40 +150,122 @@ impl HttpServiceConfigBuilder {
         let mut router = axum::Router::new();
         let mut all_docs = Vec::new();
 
-        let mut routes = vec![
-            metrics::router(registry, None),
-            super::openai::list_models_router(model_manager.state(), None),
-        ];
+        // the metrics route is never behind authentication
+        let (route_docs, route) = metrics::router(registry, None);
+        router = router.merge(route);
+        all_docs.extend(route_docs);
 
-        if config.enable_chat_endpoints {
+        let mut routes = vec![super::openai::list_models_router(
+            model_manager.state(),
+            None,
+        )];
+
+        if settings.endpoints.chat {
             routes.push(super::openai::chat_completions_router(
                 model_manager.state(),
                 None,
             ));
         }
 
-        if config.enable_cmpl_endpoints {
+        if settings.endpoints.completions {
             routes.push(super::openai::completions_router(
                 model_manager.state(),
                 None,
             ));
         }
 
+        if settings.endpoints.chat && settings.endpoints.responses {
+            routes.push(super::openai::responses_router(model_manager.state(), None));
+        }
+
+        if settings.endpoints.tokenize {
+            routes.push(super::openai::tokenize_router(model_manager.state()));
+        }
+
         // for (route_docs, route) in routes.into_iter().chain(self.routes.into_iter()) {
         //     router = router.merge(route);
         //     all_docs.extend(route_docs);
         // }
 
+        let mut openai_router = axum::Router::new();
         for (route_docs, route) in routes.into_iter() {
+            openai_router = openai_router.merge(route);
+            all_docs.extend(route_docs);
+        }
+
+        let api_keys = if settings.auth.enabled {
+            let keys = ApiKeyStore::new();
+            for (i, key) in settings.auth.keys.iter().enumerate() {
+                keys.upsert(&format!("config/{i}"), key)?;
+            }
+            let state = AuthState {
+                keys: keys.clone(),
+                deployment: model_manager.state(),
+                max_body_bytes: settings.limits.max_body_bytes,
+            };
+            openai_router =
+                openai_router.layer(axum::middleware::from_fn_with_state(state, auth_middleware));
+            Some(keys)
+        } else {
+            None
+        };
+
+        router = router.merge(openai_router);
+
+        // the admin API has its own bearer token, independent of the OpenAI API keys
+        let model_watchers = ModelWatchers::default();
+        if settings.admin.enabled {
+            let Some(api_key) = settings.admin.api_key.clone() else {
+                anyhow::bail!("admin.api_key is required when the admin API is enabled");
+            };
+            let (route_docs, route) = admin_router(AdminState {
+                manager: model_manager.clone(),
+                watchers: model_watchers.clone(),
+                api_key,
+            });
             router = router.merge(route);
             all_docs.extend(route_docs);
         }
 
+        router = router.layer(DefaultBodyLimit::max(settings.limits.max_body_bytes));
+
+        if let Some(cors) = cors_layer(&settings.listener.cors_allowed_origins)? {
+            router = router.layer(cors);
+        }
+
         Ok(HttpService {
             models: model_manager,
             router,
-            port: config.port,
-            host: config.host,
+            port: settings.listener.port,
+            host: settings.listener.host.clone(),
+            settings,
+            api_keys,
+            model_watchers,
         })
     }
 }
+
+/// CORS layer for the allowed origins; `None` if no origins are allowed
+fn cors_layer(origins: &[String]) -> Result<Option<CorsLayer>> {
+    if origins.is_empty() {
+        return Ok(None);
+    }
+
+    let allow_origin = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins = origins
+            .iter()
+            .map(|o| HeaderValue::from_str(o))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!("Invalid CORS origin: {e}"))?;
+        AllowOrigin::list(origins)
+    };
+
+    Ok(Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods(Any)
+            .allow_headers(Any),
+    ))
+}
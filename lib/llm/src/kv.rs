@@ -13,12 +13,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod details;
+pub mod events;
+pub mod eviction;
+#[cfg(feature = "cuda_kv")]
 pub mod layer;
 pub mod manager;
 pub mod reserved;
 pub mod reuse;
 pub mod sequence;
 pub mod storage;
+pub mod tiers;
+pub mod transfer;
 
 // #[cfg(feature = "cuda_kv")]
 // pub mod storage;
@@ -50,6 +56,7 @@ pub struct KvBlock {
     token_block: TokenBlock,
     priority: u32,
     return_tick: u64,
+    block_id: usize,
 }
 
 // pub struct KvStorage {
@@ -70,16 +77,30 @@ impl KvBlock {
             token_block,
             priority: 0,
             return_tick: 0,
+            block_id: 0,
             // storage: None,
         }
     }
 
+    /// Creates an empty KvBlock for slot `block_id` of the storage backing its tier
+    pub fn with_block_id(block_id: usize) -> Self {
+        Self {
+            block_id,
+            ..Default::default()
+        }
+    }
+
+    /// Slot of the block in the storage backing its tier
+    pub fn block_id(&self) -> usize {
+        self.block_id
+    }
+
     /// Updates the token block
     pub fn update_token_block(&mut self, token_block: TokenBlock) {
         self.token_block = token_block;
     }
 
-    /// Resets the block to its initial state
+    /// Resets the block to its initial state; the block keeps its slot
     pub(crate) fn reset(&mut self) {
         self.token_block = TokenBlock::default();
         self.priority = 0;
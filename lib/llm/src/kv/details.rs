@@ -0,0 +1,148 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Shape of the kv blocks of a model
+//!
+//! These descriptions do not depend on where the blocks are stored; they are shared by the
+//! layer storage and the [transfer][super::transfer] of blocks between workers.
+
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+
+use super::storage::DType;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KvLayout {
+    /// Tensor is laid out as [kv, block, head, head_dim]
+    KvFirst,
+
+    /// Tensor is laid out as [block, kv, head, head_dim]
+    BlockFirst,
+}
+
+#[derive(Debug, Clone, Builder, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KvModelDetails {
+    /// The number of layers in the model
+    pub(super) number_of_layers: usize,
+
+    /// The number of heads in the tensor
+    pub(super) number_of_heads: usize,
+
+    /// The size of each head in the tensor
+    pub(super) head_size: usize,
+
+    /// Data type of the tensor
+    pub(super) dtype: DType,
+}
+
+impl KvModelDetails {
+    pub fn number_of_elements_per_token_per_layer(&self) -> usize {
+        2 * self.number_of_heads * self.head_size
+    }
+
+    pub fn bytes_per_token_per_layer(&self) -> usize {
+        self.number_of_elements_per_token_per_layer() * self.dtype.size_in_bytes()
+    }
+
+    // pub fn number_of_elements_per_token(&self) -> usize {
+    //     self.number_of_elements_per_token_per_layer() * self.number_of_layers
+    // }
+
+    // pub fn bytes_per_token(&self) -> usize {
+    //     self.number_of_elements_per_token() * self.dtype.size_in_bytes()
+    // }
+}
+
+#[derive(Debug, Clone, Builder, Validate, Serialize, Deserialize)]
+#[validate(schema(function = "validate_block_details", skip_on_field_errors = true))]
+pub struct KvBlockDetails {
+    /// The layout of the tensor
+    pub(super) layout: KvLayout,
+
+    /// The size of each block in the tensor
+    pub(super) block_size: usize,
+
+    /// The rank of the current process in the tensor parallel group
+    #[builder(default = "0")]
+    pub(super) tp_rank: usize,
+
+    /// The size of the tensor parallel group
+    #[builder(default = "1")]
+    pub(super) tp_size: usize,
+
+    /// The details of the model
+    pub(super) model_details: KvModelDetails,
+}
+
+impl KvBlockDetails {
+    pub fn bytes_per_token_block_per_layer(&self) -> usize {
+        (self.model_details.bytes_per_token_per_layer() * self.block_size) / self.tp_size
+    }
+
+    pub fn bytes_per_token_block(&self) -> usize {
+        self.bytes_per_token_block_per_layer() * self.model_details.number_of_layers
+    }
+
+    pub fn number_of_layers(&self) -> usize {
+        self.model_details.number_of_layers
+    }
+
+    pub fn is_compatible(&self, other: &KvBlockDetails) -> bool {
+        self.layout == other.layout
+            && self.block_size == other.block_size
+            && self.tp_size == other.tp_size
+            && self.model_details == other.model_details
+    }
+
+    pub fn prefix_dim(&self) -> usize {
+        match self.layout {
+            KvLayout::KvFirst => 2,
+            KvLayout::BlockFirst => 1,
+        }
+    }
+
+    pub fn suffix_dim(&self) -> usize {
+        let suffix_dim = self.block_size
+            * (self.model_details.number_of_heads / self.tp_size)
+            * self.model_details.head_size;
+
+        match self.layout {
+            KvLayout::KvFirst => suffix_dim,
+            KvLayout::BlockFirst => 2 * suffix_dim,
+        }
+    }
+
+    pub fn elem_size(&self) -> usize {
+        self.model_details.dtype.size_in_bytes()
+    }
+}
+
+fn validate_block_details(block_details: &KvBlockDetails) -> Result<(), ValidationError> {
+    // tp size must evenly divide the number of heads
+    if block_details.model_details.number_of_heads % block_details.tp_size != 0 {
+        return Err(ValidationError::new("tp_size must evenly divide num_heads"));
+    }
+
+    if block_details.tp_rank >= block_details.tp_size {
+        return Err(ValidationError::new("tp_rank must be less than tp_size"));
+    }
+
+    if block_details.tp_size > block_details.model_details.number_of_heads {
+        return Err(ValidationError::new("tp_size must be less than num_heads"));
+    }
+
+    Ok(())
+}
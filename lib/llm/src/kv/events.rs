@@ -0,0 +1,268 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Kv cache events of a [KvStorageManager][super::manager::KvStorageManager]
+//!
+//! With an event sink, the manager reports the blocks it holds in the [KvCacheEvent]s the
+//! [KvEventPublisher] sends to the router: a block is stored once it is registered as reusable,
+//! and removed once its contents are evicted for reuse or reset.
+//!
+//! A sequence hash is reported once no matter how many blocks hold its contents; it is stored
+//! when the first block holds it and removed when the last one stops holding it.
+
+use tokio::sync::mpsc;
+
+use super::*;
+use crate::kv_router::protocols::{
+    ExternalSequenceBlockHash, KvCacheEvent, KvCacheEventData, KvCacheRemoveData, KvCacheStoreData,
+    KvCacheStoredBlockData, LocalBlockHash,
+};
+use crate::kv_router::publisher::KvEventPublisher;
+
+/// Destination of the kv cache events of a block manager
+pub trait KvEventSink: Send + Sync {
+    fn publish(&self, event: KvCacheEvent) -> Result<()>;
+}
+
+impl KvEventSink for KvEventPublisher {
+    fn publish(&self, event: KvCacheEvent) -> Result<()> {
+        KvEventPublisher::publish(self, event)?;
+        Ok(())
+    }
+}
+
+impl KvEventSink for mpsc::UnboundedSender<KvCacheEvent> {
+    fn publish(&self, event: KvCacheEvent) -> Result<()> {
+        self.send(event)?;
+        Ok(())
+    }
+}
+
+/// Turns the block transitions of a manager into kv cache events
+pub(super) struct KvEventEmitter {
+    sink: Arc<dyn KvEventSink>,
+    next_event_id: u64,
+
+    /// Number of blocks holding the contents of each sequence hash
+    resident: HashMap<SequenceHash, usize>,
+}
+
+impl KvEventEmitter {
+    pub(super) fn new(sink: Arc<dyn KvEventSink>) -> Self {
+        Self {
+            sink,
+            next_event_id: 0,
+            resident: HashMap::new(),
+        }
+    }
+
+    /// Blocks now hold the contents of the token blocks; consecutive blocks of a sequence are
+    /// reported in a single event
+    pub(super) fn stored<'a>(&mut self, token_blocks: impl IntoIterator<Item = &'a TokenBlock>) {
+        let mut events: Vec<KvCacheStoreData> = Vec::new();
+
+        for token_block in token_blocks {
+            let count = self
+                .resident
+                .entry(token_block.sequence_hash())
+                .or_default();
+            *count += 1;
+            if *count > 1 {
+                continue;
+            }
+
+            let block = KvCacheStoredBlockData {
+                block_hash: ExternalSequenceBlockHash(token_block.sequence_hash()),
+                tokens_hash: LocalBlockHash(token_block.block_hash()),
+            };
+            let parent_hash = token_block
+                .parent_sequence_hash()
+                .map(ExternalSequenceBlockHash);
+
+            match events.last_mut() {
+                Some(event) if event.blocks.last().map(|last| last.block_hash) == parent_hash => {
+                    event.blocks.push(block)
+                }
+                _ => events.push(KvCacheStoreData {
+                    parent_hash,
+                    blocks: vec![block],
+                }),
+            }
+        }
+
+        for event in events {
+            self.publish(KvCacheEventData::Stored(event));
+        }
+    }
+
+    /// Blocks no longer hold the contents of the sequence hashes
+    pub(super) fn removed(&mut self, sequence_hashes: impl IntoIterator<Item = SequenceHash>) {
+        let mut block_hashes = Vec::new();
+
+        for sequence_hash in sequence_hashes {
+            let Some(count) = self.resident.get_mut(&sequence_hash) else {
+                continue;
+            };
+            *count -= 1;
+            if *count == 0 {
+                self.resident.remove(&sequence_hash);
+                block_hashes.push(ExternalSequenceBlockHash(sequence_hash));
+            }
+        }
+
+        if !block_hashes.is_empty() {
+            self.publish(KvCacheEventData::Removed(KvCacheRemoveData {
+                block_hashes,
+            }));
+        }
+    }
+
+    fn publish(&mut self, data: KvCacheEventData) {
+        let event = KvCacheEvent {
+            event_id: self.next_event_id,
+            data,
+        };
+        self.next_event_id += 1;
+
+        if let Err(e) = self.sink.publish(event) {
+            log::warn!("failed to publish kv cache event: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::manager::KvStorageManager;
+    use super::reuse::tests::create_token_sequence;
+    use super::*;
+
+    const BLOCK_SIZE: usize = 2;
+
+    async fn manager(blocks: usize) -> (KvStorageManager, mpsc::UnboundedReceiver<KvCacheEvent>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut manager = KvStorageManager::new(BLOCK_SIZE)
+            .await
+            .with_event_sink(Arc::new(tx));
+        for block_id in 0..blocks {
+            manager
+                .insert_block(KvBlock::with_block_id(block_id))
+                .await
+                .unwrap();
+        }
+        (manager, rx)
+    }
+
+    /// Prefills a sequence and registers its complete blocks, returning their sequence hashes
+    async fn prefill(manager: &mut KvStorageManager, tokens: &[u32]) -> Vec<SequenceHash> {
+        let matched = manager
+            .prepare_prefill_sequence(create_token_sequence(tokens))
+            .await
+            .unwrap();
+        let offload = manager.prepare_prefill_offload(matched).await.unwrap();
+        let (_, complete_prefill_blocks, _, _) = offload.dissolve();
+
+        let registered = manager.register_blocks(complete_prefill_blocks).unwrap();
+        registered
+            .iter()
+            .map(|block| block.token_block.sequence_hash())
+            .collect()
+    }
+
+    fn stored(event: KvCacheEvent) -> KvCacheStoreData {
+        match event.data {
+            KvCacheEventData::Stored(data) => data,
+            data => panic!("expected a stored event, got {data:?}"),
+        }
+    }
+
+    fn removed(event: KvCacheEvent) -> KvCacheRemoveData {
+        match event.data {
+            KvCacheEventData::Removed(data) => data,
+            data => panic!("expected a removed event, got {data:?}"),
+        }
+    }
+
+    fn block_hashes(hashes: &[SequenceHash]) -> Vec<ExternalSequenceBlockHash> {
+        hashes
+            .iter()
+            .copied()
+            .map(ExternalSequenceBlockHash)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_stored_and_evicted() {
+        let (mut manager, mut rx) = manager(3).await;
+
+        let first = prefill(&mut manager, &[1, 2, 3, 4]).await;
+        let event = stored(rx.try_recv().unwrap());
+        assert_eq!(event.parent_hash, None);
+        assert_eq!(
+            event
+                .blocks
+                .iter()
+                .map(|block| block.block_hash)
+                .collect::<Vec<_>>(),
+            block_hashes(&first)
+        );
+        assert!(rx.try_recv().is_err());
+
+        // the second sequence reuses the blocks of the first
+        let second = prefill(&mut manager, &[5, 6, 7, 8]).await;
+        let event = removed(rx.try_recv().unwrap());
+        assert_eq!(event.block_hashes, block_hashes(&first));
+        let event = rx.try_recv().unwrap();
+        assert_eq!(event.event_id, 2);
+        assert_eq!(stored(event).blocks.len(), second.len());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stored_with_parent() {
+        let (mut manager, mut rx) = manager(8).await;
+
+        let first = prefill(&mut manager, &[1, 2, 3, 4]).await;
+        stored(rx.try_recv().unwrap());
+
+        // the continuation matches the blocks of the first sequence; only its new block is
+        // stored, as a child of the last block of the first sequence
+        let second = prefill(&mut manager, &[1, 2, 3, 4, 5, 6]).await;
+        let event = stored(rx.try_recv().unwrap());
+        assert_eq!(event.parent_hash, Some(ExternalSequenceBlockHash(first[1])));
+        assert_eq!(event.blocks.len(), 1);
+        assert_eq!(
+            event.blocks[0].block_hash,
+            ExternalSequenceBlockHash(second[2])
+        );
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reset() {
+        let (mut manager, mut rx) = manager(3).await;
+
+        let first = prefill(&mut manager, &[1, 2, 3, 4]).await;
+        stored(rx.try_recv().unwrap());
+
+        manager.reset(vec![first[1]]).await.unwrap();
+        let event = removed(rx.try_recv().unwrap());
+        assert_eq!(event.block_hashes, block_hashes(&first[1..]));
+
+        manager.reset_all().await.unwrap();
+        let event = removed(rx.try_recv().unwrap());
+        assert_eq!(event.block_hashes, block_hashes(&first[..1]));
+        assert!(rx.try_recv().is_err());
+    }
+}
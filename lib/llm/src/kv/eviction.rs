@@ -0,0 +1,391 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Eviction Policies
+//!
+//! An [EvictionPolicy] decides which available block gives up its contents when the
+//! [AvailableBlocks][super::reuse::AvailableBlocks] pool runs out of empty blocks.
+//!
+//! - [PriorityFifo]: lowest priority first, then in the order blocks were returned; the default.
+//! - [Lru]: least recently returned first; priorities are ignored.
+//! - [Lfu]: least frequently matched first, with dynamic aging so blocks which were hot long ago
+//!   do not stay resident forever; priorities are ignored.
+//! - [PrefixAware]: leaf blocks before their parents, least recently returned leaf first; a
+//!   shared prefix, e.g. a system prompt, outlives every sequence that extends it.
+//!
+//! Policies only see blocks holding contents; empty blocks are always taken first.
+
+use std::collections::BTreeSet;
+
+use super::reuse::PriorityKey;
+use super::*;
+
+/// Ordering of the available blocks for eviction
+///
+/// The pool calls [EvictionPolicy::insert] when a block with contents becomes available and
+/// [EvictionPolicy::remove] when it leaves the pool without being evicted, e.g. it is matched,
+/// its priority is updated or it is reset.
+pub trait EvictionPolicy: Send + Sync {
+    /// A block with contents became available
+    fn insert(&mut self, block: &KvBlock);
+
+    /// An available block left the pool without being evicted
+    fn remove(&mut self, block: &KvBlock);
+
+    /// Selects the available block whose contents are evicted next and removes it from the
+    /// policy
+    fn evict(&mut self) -> Option<SequenceHash>;
+
+    /// An available block was matched, i.e. its contents were reused
+    fn matched(&mut self, _sequence_hash: SequenceHash) {}
+
+    /// The contents of the sequence hash were dropped; the policy may forget its history
+    fn forget(&mut self, _sequence_hash: SequenceHash) {}
+}
+
+/// Evicts by priority (lowest first), then in the order the blocks were returned to the pool
+#[derive(Default)]
+pub struct PriorityFifo {
+    blocks: BTreeMap<PriorityKey, SequenceHash>,
+}
+
+impl EvictionPolicy for PriorityFifo {
+    fn insert(&mut self, block: &KvBlock) {
+        let sequence_hash = block.token_block.sequence_hash();
+        let check_multiple_entries = self.blocks.insert(PriorityKey::from(block), sequence_hash);
+        assert!(
+            check_multiple_entries.is_none(),
+            "fatal error: multiple entries for the same sequence hash in priority set"
+        );
+    }
+
+    fn remove(&mut self, block: &KvBlock) {
+        self.blocks.remove(&PriorityKey::from(block));
+    }
+
+    fn evict(&mut self) -> Option<SequenceHash> {
+        self.blocks
+            .pop_first()
+            .map(|(_, sequence_hash)| sequence_hash)
+    }
+}
+
+/// Evicts the least recently returned block
+#[derive(Default)]
+pub struct Lru {
+    blocks: BTreeSet<(u64, SequenceHash)>,
+}
+
+impl EvictionPolicy for Lru {
+    fn insert(&mut self, block: &KvBlock) {
+        self.blocks
+            .insert((block.return_tick, block.token_block.sequence_hash()));
+    }
+
+    fn remove(&mut self, block: &KvBlock) {
+        self.blocks
+            .remove(&(block.return_tick, block.token_block.sequence_hash()));
+    }
+
+    fn evict(&mut self) -> Option<SequenceHash> {
+        self.blocks
+            .pop_first()
+            .map(|(_, sequence_hash)| sequence_hash)
+    }
+}
+
+/// Evicts the least frequently matched block, least recently returned first among equals
+///
+/// Frequencies are aged dynamically: a block enters the order at its match count plus the
+/// count it would have needed to outlive the last evicted block, so a block whose matches are
+/// long past is eventually evicted in favour of newer ones.
+#[derive(Default)]
+pub struct Lfu {
+    /// Match count of the contents of each sequence hash, kept while the contents are resident
+    counts: HashMap<SequenceHash, u64>,
+
+    /// Key of each available block in `order`
+    keys: HashMap<SequenceHash, (u64, u64)>,
+
+    order: BTreeSet<(u64, u64, SequenceHash)>,
+
+    /// Effective frequency of the last evicted block
+    age: u64,
+}
+
+impl EvictionPolicy for Lfu {
+    fn insert(&mut self, block: &KvBlock) {
+        let sequence_hash = block.token_block.sequence_hash();
+        let count = self.counts.entry(sequence_hash).or_default();
+        let key = (*count + self.age, block.return_tick);
+        self.order.insert((key.0, key.1, sequence_hash));
+        self.keys.insert(sequence_hash, key);
+    }
+
+    fn remove(&mut self, block: &KvBlock) {
+        let sequence_hash = block.token_block.sequence_hash();
+        if let Some((frequency, tick)) = self.keys.remove(&sequence_hash) {
+            self.order.remove(&(frequency, tick, sequence_hash));
+        }
+    }
+
+    fn evict(&mut self) -> Option<SequenceHash> {
+        let (frequency, _, sequence_hash) = self.order.pop_first()?;
+        self.keys.remove(&sequence_hash);
+        self.counts.remove(&sequence_hash);
+        self.age = frequency;
+        Some(sequence_hash)
+    }
+
+    fn matched(&mut self, sequence_hash: SequenceHash) {
+        *self.counts.entry(sequence_hash).or_default() += 1;
+    }
+
+    fn forget(&mut self, sequence_hash: SequenceHash) {
+        self.counts.remove(&sequence_hash);
+    }
+}
+
+/// Evicts leaf blocks before their parents, least recently returned leaf first
+///
+/// A block is a leaf while no other available block continues its sequence. Only the
+/// available blocks are considered; the parents of inflight blocks are inflight as well.
+#[derive(Default)]
+pub struct PrefixAware {
+    /// Parent and return tick of each available block
+    blocks: HashMap<SequenceHash, (Option<SequenceHash>, u64)>,
+
+    /// Number of available blocks continuing each sequence hash
+    children: HashMap<SequenceHash, usize>,
+
+    leaves: BTreeSet<(u64, SequenceHash)>,
+}
+
+impl PrefixAware {
+    fn remove_hash(&mut self, sequence_hash: SequenceHash) {
+        let Some((parent, tick)) = self.blocks.remove(&sequence_hash) else {
+            return;
+        };
+        self.leaves.remove(&(tick, sequence_hash));
+
+        // the parent becomes a leaf once its last available child is gone
+        if let Some(parent) = parent {
+            if let Some(count) = self.children.get_mut(&parent) {
+                *count -= 1;
+                if *count == 0 {
+                    self.children.remove(&parent);
+                    if let Some((_, parent_tick)) = self.blocks.get(&parent) {
+                        self.leaves.insert((*parent_tick, parent));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl EvictionPolicy for PrefixAware {
+    fn insert(&mut self, block: &KvBlock) {
+        let sequence_hash = block.token_block.sequence_hash();
+        let parent = block.token_block.parent_sequence_hash();
+        let tick = block.return_tick;
+
+        self.blocks.insert(sequence_hash, (parent, tick));
+        if !self.children.contains_key(&sequence_hash) {
+            self.leaves.insert((tick, sequence_hash));
+        }
+
+        if let Some(parent) = parent {
+            let count = self.children.entry(parent).or_default();
+            *count += 1;
+            if *count == 1 {
+                if let Some((_, parent_tick)) = self.blocks.get(&parent) {
+                    self.leaves.remove(&(*parent_tick, parent));
+                }
+            }
+        }
+    }
+
+    fn remove(&mut self, block: &KvBlock) {
+        self.remove_hash(block.token_block.sequence_hash());
+    }
+
+    fn evict(&mut self) -> Option<SequenceHash> {
+        let (_, sequence_hash) = self.leaves.first().copied()?;
+        self.remove_hash(sequence_hash);
+        Some(sequence_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::manager::KvStorageManager;
+    use super::reuse::tests::{create_blocks, create_token_sequence};
+    use super::*;
+
+    const BLOCK_SIZE: usize = 2;
+
+    fn sequence_blocks(values: &[u32], first_tick: u64) -> Vec<KvBlock> {
+        let mut blocks = create_blocks(create_token_sequence(values), BLOCK_SIZE);
+        for (tick, block) in blocks.iter_mut().enumerate() {
+            block.return_tick = first_tick + tick as u64;
+        }
+        blocks
+    }
+
+    fn evict_all(policy: &mut dyn EvictionPolicy) -> Vec<SequenceHash> {
+        std::iter::from_fn(|| policy.evict()).collect()
+    }
+
+    fn hashes(blocks: &[KvBlock]) -> Vec<SequenceHash> {
+        blocks
+            .iter()
+            .map(|block| block.token_block.sequence_hash())
+            .collect()
+    }
+
+    #[test]
+    fn test_lru_ignores_priority() {
+        let mut blocks = sequence_blocks(&[1, 2, 3, 4, 5, 6], 0);
+        blocks[0].priority = 1;
+
+        let mut policy = Lru::default();
+        for block in &blocks {
+            policy.insert(block);
+        }
+        assert_eq!(evict_all(&mut policy), hashes(&blocks));
+    }
+
+    #[test]
+    fn test_lfu_with_aging() {
+        let blocks = sequence_blocks(&[1, 2, 3, 4, 5, 6], 0);
+        let [hot, warm, cold] = [0, 1, 2].map(|i| blocks[i].token_block.sequence_hash());
+
+        let mut policy = Lfu::default();
+        for _ in 0..3 {
+            policy.matched(hot);
+        }
+        for _ in 0..2 {
+            policy.matched(warm);
+        }
+        for block in &blocks {
+            policy.insert(block);
+        }
+
+        // evicting warm ages the pool by its two matches
+        assert_eq!(policy.evict(), Some(cold));
+        assert_eq!(policy.evict(), Some(warm));
+
+        // a new block with fewer matches than hot now outlives it
+        let new = sequence_blocks(&[7, 8], 10).pop().unwrap();
+        for _ in 0..2 {
+            policy.matched(new.token_block.sequence_hash());
+        }
+        policy.insert(&new);
+        assert_eq!(
+            evict_all(&mut policy),
+            vec![hot, new.token_block.sequence_hash()]
+        );
+    }
+
+    #[test]
+    fn test_prefix_aware_evicts_leaves_first() {
+        // two sequences sharing the prefix [1, 2]; the prefix is returned last but is still
+        // evicted after the blocks which extend it
+        let first = sequence_blocks(&[1, 2, 3, 4], 0);
+        let second = sequence_blocks(&[1, 2, 5, 6], 2);
+
+        let mut policy = PrefixAware::default();
+        policy.insert(&first[1]);
+        policy.insert(&second[1]);
+        let prefix = sequence_blocks(&[1, 2], 10).pop().unwrap();
+        policy.insert(&prefix);
+
+        assert_eq!(
+            evict_all(&mut policy),
+            vec![
+                first[1].token_block.sequence_hash(),
+                second[1].token_block.sequence_hash(),
+                prefix.token_block.sequence_hash(),
+            ]
+        );
+    }
+
+    /// Replays a trace of requests against a manager with `capacity` blocks, returning the
+    /// fraction of complete blocks which were matched
+    async fn hit_rate(policy: Box<dyn EvictionPolicy>, capacity: usize, trace: &[Vec<u32>]) -> f64 {
+        let mut manager = KvStorageManager::with_eviction_policy(BLOCK_SIZE, policy).await;
+        for block_id in 0..capacity {
+            manager
+                .insert_block(KvBlock::with_block_id(block_id))
+                .await
+                .unwrap();
+        }
+
+        let (mut hits, mut total) = (0, 0);
+        for tokens in trace {
+            let matched = manager
+                .prepare_prefill_sequence(create_token_sequence(tokens))
+                .await
+                .unwrap();
+            hits += matched.inflight_blocks().len();
+            total += matched.inflight_blocks().len() + matched.remaining_blocks().len();
+
+            let offload = manager.prepare_prefill_offload(matched).await.unwrap();
+            let (inflight_blocks, complete_prefill_blocks, _, _) = offload.dissolve();
+            let registered = manager.register_blocks(complete_prefill_blocks).unwrap();
+
+            // the request completes, returning its blocks to the pool
+            drop(inflight_blocks);
+            drop(registered);
+            manager.fence().await.unwrap();
+        }
+
+        hits as f64 / total as f64
+    }
+
+    /// Requests sharing one of two system prompts, followed by a question which is asked once
+    fn trace() -> Vec<Vec<u32>> {
+        let system_prompts = [(1000..1008).collect::<Vec<u32>>(), (2000..2008).collect()];
+
+        (0..64u32)
+            .map(|i| {
+                // the first system prompt is used three times as often as the second
+                let mut tokens = system_prompts[usize::from(i % 4 == 3)].clone();
+                tokens.extend((0..8).map(|t| 10_000 + i * 8 + t));
+                tokens.push(0);
+                tokens
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_trace_hit_rates() {
+        let trace = trace();
+
+        // each request needs 9 blocks; 16 blocks leave room for one prompt plus a request
+        let capacity = 16;
+        let fifo = hit_rate(Box::new(PriorityFifo::default()), capacity, &trace).await;
+        let lru = hit_rate(Box::new(Lru::default()), capacity, &trace).await;
+        let lfu = hit_rate(Box::new(Lfu::default()), capacity, &trace).await;
+        let prefix = hit_rate(Box::new(PrefixAware::default()), capacity, &trace).await;
+
+        // without priorities, fifo and lru return blocks in the same order
+        assert_eq!(fifo, lru);
+
+        // both keep a system prompt resident across requests, which recency alone does not
+        assert!(lfu > lru, "lfu {lfu} <= lru {lru}");
+        assert!(prefix > lru, "prefix {prefix} <= lru {lru}");
+    }
+}
@@ -29,6 +29,7 @@ use dynamo_runtime::{error, raise, utils::pool::Returnable, ErrorContext, Result
 use std::{ptr::NonNull, sync::Arc};
 use validator::{Validate, ValidationError};
 
+pub use super::details::*;
 use super::storage::{DType, OwnedStorage, Storage, StorageType, TensorView};
 extern "C" {
     fn copy_blocks_3d(
@@ -96,121 +97,6 @@ extern "C" {
 
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum KvLayout {
-    /// Tensor is laid out as [kv, block, head, head_dim]
-    KvFirst,
-
-    /// Tensor is laid out as [block, kv, head, head_dim]
-    BlockFirst,
-}
-
-#[derive(Debug, Clone, Builder, PartialEq, Eq)]
-pub struct KvModelDetails {
-    /// The number of layers in the model
-    number_of_layers: usize,
-
-    /// The number of heads in the tensor
-    number_of_heads: usize,
-
-    /// The size of each head in the tensor
-    head_size: usize,
-
-    /// Data type of the tensor
-    dtype: DType,
-}
-
-impl KvModelDetails {
-    pub fn number_of_elements_per_token_per_layer(&self) -> usize {
-        2 * self.number_of_heads * self.head_size
-    }
-
-    pub fn bytes_per_token_per_layer(&self) -> usize {
-        self.number_of_elements_per_token_per_layer() * self.dtype.size_in_bytes()
-    }
-
-    // pub fn number_of_elements_per_token(&self) -> usize {
-    //     self.number_of_elements_per_token_per_layer() * self.number_of_layers
-    // }
-
-    // pub fn bytes_per_token(&self) -> usize {
-    //     self.number_of_elements_per_token() * self.dtype.size_in_bytes()
-    // }
-}
-
-#[derive(Debug, Clone, Builder, Validate)]
-#[validate(schema(function = "validate_block_details", skip_on_field_errors = true))]
-pub struct KvBlockDetails {
-    /// The layout of the tensor
-    layout: KvLayout,
-
-    /// The size of each block in the tensor
-    block_size: usize,
-
-    /// The rank of the current process in the tensor parallel group
-    #[builder(default = "0")]
-    tp_rank: usize,
-
-    /// The size of the tensor parallel group
-    #[builder(default = "1")]
-    tp_size: usize,
-
-    /// The details of the model
-    model_details: KvModelDetails,
-}
-
-impl KvBlockDetails {
-    pub fn bytes_per_token_block_per_layer(&self) -> usize {
-        (self.model_details.bytes_per_token_per_layer() * self.block_size) / self.tp_size
-    }
-
-    pub fn is_compatible(&self, other: &KvBlockDetails) -> bool {
-        self.layout == other.layout
-            && self.block_size == other.block_size
-            && self.tp_size == other.tp_size
-            && self.model_details == other.model_details
-    }
-
-    pub fn prefix_dim(&self) -> usize {
-        match self.layout {
-            KvLayout::KvFirst => 2,
-            KvLayout::BlockFirst => 1,
-        }
-    }
-
-    pub fn suffix_dim(&self) -> usize {
-        let suffix_dim = self.block_size
-            * (self.model_details.number_of_heads / self.tp_size)
-            * self.model_details.head_size;
-
-        match self.layout {
-            KvLayout::KvFirst => suffix_dim,
-            KvLayout::BlockFirst => 2 * suffix_dim,
-        }
-    }
-
-    pub fn elem_size(&self) -> usize {
-        self.model_details.dtype.size_in_bytes()
-    }
-}
-
-fn validate_block_details(block_details: &KvBlockDetails) -> Result<(), ValidationError> {
-    // tp size must evenly divide the number of heads
-    if block_details.model_details.number_of_heads % block_details.tp_size != 0 {
-        return Err(ValidationError::new("tp_size must evenly divide num_heads"));
-    }
-
-    if block_details.tp_rank >= block_details.tp_size {
-        return Err(ValidationError::new("tp_rank must be less than tp_size"));
-    }
-
-    if block_details.tp_size > block_details.model_details.number_of_heads {
-        return Err(ValidationError::new("tp_size must be less than num_heads"));
-    }
-
-    Ok(())
-}
-
 #[derive(Debug, Builder, Validate)]
 #[validate(schema(function = "validate_kv_layer", skip_on_field_errors = true))]
 pub struct KvLayer {
@@ -397,6 +283,9 @@ impl KvLayer {
             (_, StorageType::System) => {
                 raise!("Device to System copy not implemented");
             }
+            (StorageType::Disk, _) | (_, StorageType::Disk) => {
+                raise!("Disk copies are not supported by the block copy kernel");
+            }
         };
 
         let h_src_block_ids = src_block_ids
@@ -15,6 +15,9 @@
 
 use super::*;
 
+use derive_getters::Getters;
+use events::{KvEventEmitter, KvEventSink};
+use eviction::{EvictionPolicy, PriorityFifo};
 use reuse::AvailableBlocks;
 
 /// Manages the reservation and priority reuse of kv blocks for a single storage type,
@@ -23,15 +26,96 @@ pub struct KvStorageManager {
     available_blocks: AvailableBlocks,
     inflight_blocks: ReservedBlocks,
     block_size: usize,
+    events: Option<KvEventEmitter>,
 }
 
 impl KvStorageManager {
     pub async fn new(block_size: usize) -> Self {
+        Self::with_eviction_policy(block_size, Box::new(PriorityFifo::default())).await
+    }
+
+    /// Creates a manager whose available blocks are evicted in the order of `policy`
+    pub async fn with_eviction_policy(block_size: usize, policy: Box<dyn EvictionPolicy>) -> Self {
         Self {
-            available_blocks: AvailableBlocks::new().await,
+            available_blocks: AvailableBlocks::with_eviction_policy(policy).await,
             inflight_blocks: ReservedBlocks::new(block_size),
             block_size,
+            events: None,
+        }
+    }
+
+    /// Publishes a [KvCacheEvent][crate::kv_router::protocols::KvCacheEvent] to the sink when
+    /// registered blocks become reusable and when their contents are evicted or reset
+    pub fn with_event_sink(mut self, sink: Arc<dyn KvEventSink>) -> Self {
+        self.events = Some(KvEventEmitter::new(sink));
+        self
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Adds a block to the pool of available blocks, e.g. one per slot of the storage backing
+    /// this manager
+    pub async fn insert_block(&mut self, block: KvBlock) -> Result<()> {
+        if let Some(events) = self.events.as_mut() {
+            if block.token_block.sequence_hash() != 0 {
+                events.stored([&block.token_block]);
+            }
+        }
+        self.available_blocks.insert(block).await
+    }
+
+    /// Waits until the blocks returned before the call are available again
+    pub async fn fence(&self) -> Result<()> {
+        self.available_blocks.fence().await
+    }
+
+    /// Takes up to `count` blocks from the pool of available blocks, evicting their contents
+    pub(super) async fn take_blocks(&mut self, count: u32) -> Result<Vec<UniqueBlock>> {
+        let blocks = self.available_blocks.take_blocks(count).await?;
+        if let Some(events) = self.events.as_mut() {
+            events.removed(
+                blocks
+                    .iter()
+                    .filter_map(|block| EvictedBlock::from_block(block))
+                    .map(|evicted| evicted.sequence_hash),
+            );
+        }
+        Ok(blocks)
+    }
+
+    /// Registers the prefilled blocks as inflight, making their contents reusable by other
+    /// sequences
+    ///
+    /// If another sequence already registered a block with the same contents, the existing block
+    /// is returned in its place and the passed in block returns to the pool of available blocks.
+    pub fn register_blocks(&mut self, blocks: Vec<UniqueBlock>) -> Result<Vec<ReservedBlock>> {
+        if let Some(events) = self.events.as_mut() {
+            events.stored(blocks.iter().map(|block| &block.token_block));
+        }
+        blocks
+            .into_iter()
+            .map(|block| self.inflight_blocks.register(block))
+            .collect()
+    }
+
+    /// Resets the available blocks of the sequence hashes; returns the hashes which were reset
+    pub async fn reset(&mut self, sequence_hashes: Vec<SequenceHash>) -> Result<Vec<SequenceHash>> {
+        let reset = self.available_blocks.reset(sequence_hashes).await?;
+        if let Some(events) = self.events.as_mut() {
+            events.removed(reset.iter().copied());
         }
+        Ok(reset)
+    }
+
+    /// Resets all available blocks; returns the hashes which were reset
+    pub async fn reset_all(&mut self) -> Result<Vec<SequenceHash>> {
+        let reset = self.available_blocks.reset_all().await?;
+        if let Some(events) = self.events.as_mut() {
+            events.removed(reset.iter().copied());
+        }
+        Ok(reset)
     }
 
     pub async fn prepare_prefill_sequence(&mut self, tokens: Tokens) -> Result<PrefillMatched> {
@@ -82,10 +166,7 @@ impl KvStorageManager {
     ) -> Result<PrefillOffload> {
         let (inflight_blocks, remaining_blocks, tail_block) = matched.dissolve();
 
-        let mut blocks_to_reuse = self
-            .available_blocks
-            .take_blocks(remaining_blocks.len() as u32 + 1)
-            .await?;
+        let mut blocks_to_reuse = self.take_blocks(remaining_blocks.len() as u32 + 1).await?;
 
         if blocks_to_reuse.len() != remaining_blocks.len() + 1 {
             raise!(
@@ -95,6 +176,12 @@ impl KvStorageManager {
             );
         }
 
+        // the contents of reused blocks are evicted once they are overwritten
+        let evicted_blocks = blocks_to_reuse
+            .iter()
+            .filter_map(|block| EvictedBlock::from_block(block))
+            .collect();
+
         // update the blocks_to_reuse with the token block from remaining_blocks
         let complete_prefill_blocks: Vec<UniqueBlock> = remaining_blocks
             .into_iter()
@@ -117,28 +204,69 @@ impl KvStorageManager {
             inflight_blocks,
             complete_prefill_blocks,
             tail_prefill_block,
+            evicted_blocks,
         })
     }
 }
 
-#[derive(Dissolve)]
+#[derive(Dissolve, Getters)]
 pub struct PartialKvBlock {
     token_block: PartialTokenBlock,
     kv_block: UniqueBlock,
 }
 
-#[derive(Dissolve)]
+#[derive(Dissolve, Getters)]
 pub struct PrefillMatched {
     inflight_blocks: Vec<ReservedBlock>,
     remaining_blocks: Vec<TokenBlock>,
     tail_block: PartialTokenBlock,
 }
 
-#[derive(Dissolve)]
+impl PrefillMatched {
+    pub(super) fn new(
+        inflight_blocks: Vec<ReservedBlock>,
+        remaining_blocks: Vec<TokenBlock>,
+        tail_block: PartialTokenBlock,
+    ) -> Self {
+        Self {
+            inflight_blocks,
+            remaining_blocks,
+            tail_block,
+        }
+    }
+}
+
+#[derive(Dissolve, Getters)]
 pub struct PrefillOffload {
     inflight_blocks: Vec<ReservedBlock>,
     complete_prefill_blocks: Vec<UniqueBlock>,
     tail_prefill_block: PartialKvBlock,
+
+    /// Blocks whose previous contents are overwritten by this prefill
+    evicted_blocks: Vec<EvictedBlock>,
+}
+
+/// The previous contents of a block taken for reuse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvictedBlock {
+    pub sequence_hash: SequenceHash,
+    pub priority: u32,
+    pub block_id: usize,
+}
+
+impl EvictedBlock {
+    /// Returns None for blocks without contents
+    pub fn from_block(block: &KvBlock) -> Option<Self> {
+        let sequence_hash = block.token_block.sequence_hash();
+        if sequence_hash == 0 {
+            return None;
+        }
+        Some(Self {
+            sequence_hash,
+            priority: block.priority,
+            block_id: block.block_id,
+        })
+    }
 }
 
 // #[cfg(test)]
@@ -23,6 +23,7 @@
 //!
 //! - **Priority-Based FIFO**: Blocks are returned in first-in, first-out order within their priority levels.
 //!   Lower priority values are processed first, allowing important blocks to be retained longer.
+//!   Other orders can be selected with an [EvictionPolicy].
 //!
 //! - **State Matching**: Blocks can be matched against their previous state instead of being taken randomly,
 //!   enabling efficient reuse of blocks with specific sequence hashes.
@@ -45,6 +46,7 @@ use tokio::{
     task::JoinHandle,
 };
 
+use super::eviction::{EvictionPolicy, PriorityFifo};
 use super::*;
 
 pub struct AvailableBlocks {
@@ -159,7 +161,8 @@ impl AvailableBlocks {
         Ok(())
     }
 
-    pub async fn reset(&self, sequence_hashes: Vec<SequenceHash>) -> Result<()> {
+    /// Resets the available blocks of the sequence hashes; returns the hashes which were reset
+    pub async fn reset(&self, sequence_hashes: Vec<SequenceHash>) -> Result<Vec<SequenceHash>> {
         let (tx, rx) = oneshot::channel();
         if self
             .control_tx
@@ -171,11 +174,11 @@ impl AvailableBlocks {
         {
             raise!("failed to send reset request; channel closed");
         }
-        rx.await?;
-        Ok(())
+        Ok(rx.await?)
     }
 
-    pub async fn reset_all(&self) -> Result<()> {
+    /// Resets all available blocks; returns the hashes which were reset
+    pub async fn reset_all(&self) -> Result<Vec<SequenceHash>> {
         let (tx, rx) = oneshot::channel();
         if self
             .control_tx
@@ -184,8 +187,7 @@ impl AvailableBlocks {
         {
             raise!("failed to send reset all request; channel closed");
         }
-        rx.await?;
-        Ok(())
+        Ok(rx.await?)
     }
 
     pub async fn fence(&self) -> Result<()> {
@@ -212,6 +214,11 @@ impl ReturnHandle<KvBlock> for ReturnHandleImpl {
 
 impl AvailableBlocks {
     pub async fn new() -> Self {
+        Self::with_eviction_policy(Box::new(PriorityFifo::default())).await
+    }
+
+    /// Creates a pool which evicts the contents of its blocks in the order of `policy`
+    pub async fn with_eviction_policy(policy: Box<dyn EvictionPolicy>) -> Self {
         let (match_tx, match_rx) = mpsc::unbounded_channel();
         let (return_tx, return_rx) = mpsc::unbounded_channel();
         let (control_tx, control_rx) = mpsc::unbounded_channel();
@@ -232,6 +239,7 @@ impl AvailableBlocks {
             fence_rx,
             total_blocks.clone(),
             available_blocks.clone(),
+            policy,
         ));
 
         Self {
@@ -247,7 +255,7 @@ impl AvailableBlocks {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct PriorityKey {
+pub(super) struct PriorityKey {
     priority: u32,
     return_tick: u64,
     sequence_hash: SequenceHash,
@@ -278,13 +286,12 @@ impl From<&KvBlock> for PriorityKey {
     }
 }
 
-#[derive(Default)]
 struct AvailableBlocksState {
     // Direct lookup by sequence_hash
     lookup_map: HashMap<SequenceHash, PoolValue<KvBlock>>,
 
-    // // Ordered by timestamp (oldest first)
-    priority_set: BTreeMap<PriorityKey, SequenceHash>,
+    // Eviction order of the blocks in the lookup map
+    policy: Box<dyn EvictionPolicy>,
 
     // Fully Uninitialized
     uninitialized_set: VecDeque<PoolValue<KvBlock>>,
@@ -300,10 +307,14 @@ struct AvailableBlocksState {
 }
 
 impl AvailableBlocksState {
-    fn new(total_blocks: Arc<AtomicU64>, available_blocks: Arc<AtomicU64>) -> Self {
+    fn new(
+        total_blocks: Arc<AtomicU64>,
+        available_blocks: Arc<AtomicU64>,
+        policy: Box<dyn EvictionPolicy>,
+    ) -> Self {
         Self {
             lookup_map: HashMap::new(),
-            priority_set: BTreeMap::new(),
+            policy,
             uninitialized_set: VecDeque::new(),
             return_tick: 0,
             total_blocks,
@@ -323,13 +334,8 @@ impl AvailableBlocksState {
             return;
         }
 
-        // Insert into timestamp set
-        let key = PriorityKey::from(&*block);
-        let check_multiple_entries = self.priority_set.insert(key, sequence_hash);
-        assert!(
-            check_multiple_entries.is_none(),
-            "fatal error: multiple entries for the same sequence hash in timestamp set"
-        );
+        // Insert into the eviction order
+        self.policy.insert(&block);
 
         // Add to the lookup map
         let check_multiple_entries = self.lookup_map.insert(sequence_hash, block);
@@ -345,8 +351,8 @@ impl AvailableBlocksState {
     ) -> Option<PoolValue<KvBlock>> {
         match self.lookup_map.remove(&sequence_hash) {
             Some(block) => {
-                // Remove from timestamp set
-                self.priority_set.remove(&PriorityKey::from(&*block));
+                // Remove from the eviction order
+                self.policy.remove(&block);
                 Some(block)
             }
             None => None,
@@ -362,6 +368,7 @@ impl AvailableBlocksState {
 
         for hash in hashes {
             if let Some(block) = self.take_with_sequence_hash(hash) {
+                self.policy.matched(hash);
                 matched_blocks.push(self.create_pool_item(block, return_handle.clone()));
             } else {
                 break;
@@ -404,9 +411,9 @@ impl AvailableBlocksState {
             return Some(block);
         }
 
-        // if we have blocks in the priority set, pop the first (it's sorted by priority)
+        // otherwise evict the block selected by the eviction policy
         // a fatal error will occur if the block is not found in the lookup map
-        if let Some((_key, sequence_hash)) = self.priority_set.pop_first() {
+        if let Some(sequence_hash) = self.policy.evict() {
             let block = match self.lookup_map.remove(&sequence_hash) {
                 Some(block) => block,
                 None => {
@@ -479,15 +486,15 @@ impl AvailableBlocksState {
             }
             ControlRequest::Reset(reset) => {
                 let (sequence_hashes, tx) = reset.dissolve();
-                self.handle_reset(sequence_hashes);
-                if tx.send(()).is_err() {
+                let reset_hashes = self.handle_reset(sequence_hashes);
+                if tx.send(reset_hashes).is_err() {
                     log::trace!("Failed to send reset ack; receiver dropped");
                 }
             }
             ControlRequest::ResetAll(reset_all) => {
                 let tx = reset_all.dissolve();
-                self.handle_reset_all();
-                if tx.send(()).is_err() {
+                let reset_hashes = self.handle_reset_all();
+                if tx.send(reset_hashes).is_err() {
                     log::trace!("Failed to send reset all ack; receiver dropped");
                 }
             }
@@ -541,25 +548,33 @@ impl AvailableBlocksState {
         }
     }
 
-    fn handle_reset(&mut self, sequence_hashes: Vec<SequenceHash>) {
+    fn handle_reset(&mut self, sequence_hashes: Vec<SequenceHash>) -> Vec<SequenceHash> {
+        let mut reset_hashes = Vec::new();
         for hash in sequence_hashes {
             if let Some(mut block) = self.take_with_sequence_hash(hash) {
+                self.policy.forget(hash);
                 block.reset();
                 self.insert(block);
+                reset_hashes.push(hash);
             }
         }
+        reset_hashes
     }
 
-    fn handle_reset_all(&mut self) {
-        // for all blocks in the priority set, reset them
-        while let Some((_key, sequence_hash)) = self.priority_set.pop_first() {
+    fn handle_reset_all(&mut self) -> Vec<SequenceHash> {
+        let mut reset_hashes = Vec::new();
+        // for all blocks in the eviction order, reset them
+        while let Some(sequence_hash) = self.policy.evict() {
             if let Some(mut block) = self.lookup_map.remove(&sequence_hash) {
+                self.policy.forget(sequence_hash);
                 block.reset();
                 self.insert(block);
+                reset_hashes.push(sequence_hash);
             } else {
-                panic!("block from priority set not found in lookup map");
+                panic!("block from eviction order not found in lookup map");
             }
         }
+        reset_hashes
     }
 }
 
@@ -619,12 +634,12 @@ pub struct UpdateMultipleControl {
 #[derive(Dissolve)]
 pub struct ResetControl {
     sequence_hashes: Vec<SequenceHash>,
-    tx: oneshot::Sender<()>,
+    tx: oneshot::Sender<Vec<SequenceHash>>,
 }
 
 #[derive(Dissolve)]
 pub struct ResetAllControl {
-    tx: oneshot::Sender<()>,
+    tx: oneshot::Sender<Vec<SequenceHash>>,
 }
 
 pub enum ControlRequest {
@@ -642,13 +657,14 @@ pub async fn progress_engine(
     fence_rx: mpsc::UnboundedReceiver<oneshot::Sender<()>>,
     total_blocks: Arc<AtomicU64>,
     available_blocks: Arc<AtomicU64>,
+    policy: Box<dyn EvictionPolicy>,
 ) {
     let mut match_rx = match_rx;
     let mut return_rx = return_rx;
     let mut ctrl_rx = ctrl_rx;
     let mut fence_rx = fence_rx;
 
-    let mut state = AvailableBlocksState::new(total_blocks, available_blocks);
+    let mut state = AvailableBlocksState::new(total_blocks, available_blocks, policy);
 
     loop {
         tokio::select! {
@@ -1049,7 +1065,8 @@ pub(crate) mod tests {
         }
 
         // Reset Block 2
-        pool.reset(block2_hashes.clone()).await.unwrap();
+        let reset_hashes = pool.reset(block2_hashes.clone()).await.unwrap();
+        assert_eq!(reset_hashes, block2_hashes);
         pool.fence().await.unwrap();
 
         // Try to match from block 2 hashes, expect no matches
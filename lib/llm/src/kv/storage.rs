@@ -15,17 +15,21 @@
 
 //! Storage object representing large single slabs of bytes.
 //!
-//! There are three types denoted by [StorageType]
+//! There are four types denoted by [StorageType]
 //!
 //! - [StorageType::Device]: A pointer to a device memory allocation
 //! - [StorageType::Pinned]: A pointer to a pinned memory allocation from cudaMallocHost
 //! - [StorageType::System]: A pointer to a system memory allocation from malloc/calloc or
 //!   other forms of heap allocation.
+//! - [StorageType::Disk]: A pointer to a memory-mapped file on local disk.
 //!
 //! Use [StorageType::System] Grace and other embedded platforms.
 //!
 //! Use [StorageType::Pinned] and [StorageType::Device] on traditional x86 platforms.
 //!
+//! [StorageType::Device] and [StorageType::Pinned] require the `cuda_kv` feature; [SystemStorage]
+//! and [DiskStorage] work on CPU-only machines.
+//!
 //! WARNING: [Storage] and [OwnedStorage] are not Rust safe objects. For KV blocks, we use
 //! [Storage]-like stabs to form [KvLayers][super::layer::KvLayer], both of which do not
 //! conform to Rust's ownership or safety guarantees.
@@ -35,24 +39,33 @@
 //!
 //! The first unit of ownership that will be Rust safe is the [KvBlock][super::KvBlock].
 
+#[cfg(feature = "cuda_kv")]
 use bs62::num_traits;
+#[cfg(feature = "cuda_kv")]
 use cudarc::driver::{CudaContext, CudaSlice, CudaStream, DevicePtr};
 use dynamo_runtime::{error, raise, Result};
+#[cfg(feature = "cuda_kv")]
 use ndarray::{ArrayViewMut, IxDyn};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "cuda_kv")]
 use std::any::Any;
+#[cfg(feature = "cuda_kv")]
 use std::ffi::c_void;
+use std::path::{Path, PathBuf};
 use std::ptr::NonNull;
 use std::sync::Arc;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum StorageType {
+    #[cfg(feature = "cuda_kv")]
     Device(Arc<CudaContext>),
     Pinned,
-    System, // todo: for grace
+    System,
+    Disk,
 }
 
 /// Represents the data type of tensor elements
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DType {
     F32,
     F16,
@@ -88,6 +101,7 @@ impl DType {
     }
 }
 
+#[cfg(feature = "cuda_kv")]
 extern "C" {
     fn cuda_malloc_host(ptr: *mut *mut c_void, size: usize) -> i32;
     fn cuda_free_host(ptr: *mut c_void) -> i32;
@@ -135,24 +149,44 @@ impl OwnedStorage {
 
     pub fn create(bytes: usize, storage_type: StorageType) -> Result<Self> {
         match storage_type {
+            #[cfg(feature = "cuda_kv")]
             StorageType::Device(device) => Self::create_device_array(bytes, device),
+            #[cfg(feature = "cuda_kv")]
             StorageType::Pinned => Self::create_pinned_array(bytes),
-            StorageType::System => {
-                raise!("System memory not yet supported");
+            #[cfg(not(feature = "cuda_kv"))]
+            StorageType::Pinned => {
+                raise!("Pinned memory requires the cuda_kv feature");
+            }
+            StorageType::System => Self::create_system_array(bytes),
+            StorageType::Disk => {
+                raise!("Disk storage is backed by a file; use OwnedStorage::create_disk_array");
             }
         }
     }
 
+    pub fn create_system_array(bytes: usize) -> Result<Self> {
+        let system_memory = SystemStorage::new(bytes)?;
+        Ok(Self::new(Arc::new(system_memory)))
+    }
+
+    pub fn create_disk_array(bytes: usize, path: impl AsRef<Path>) -> Result<Self> {
+        let disk_storage = DiskStorage::new(bytes, path)?;
+        Ok(Self::new(Arc::new(disk_storage)))
+    }
+
+    #[cfg(feature = "cuda_kv")]
     pub fn create_device_array(bytes: usize, device: Arc<CudaContext>) -> Result<Self> {
         let device_storage = DeviceStorageOwned::new(bytes, device)?;
         Ok(Self::new(Arc::new(device_storage)))
     }
 
+    #[cfg(feature = "cuda_kv")]
     pub fn create_pinned_array(bytes: usize) -> Result<Self> {
         let pinned_memory = CudaPinnedMemory::new(bytes)?;
         Ok(Self::new(Arc::new(pinned_memory)))
     }
 
+    #[cfg(feature = "cuda_kv")]
     pub fn byo_device_array(
         device_ptr: u64,
         bytes: usize,
@@ -186,12 +220,14 @@ impl std::fmt::Debug for OwnedStorage {
     }
 }
 
+#[cfg(feature = "cuda_kv")]
 pub struct DeviceStorageOwned {
     bytes: usize,
     cuda_device: Arc<CudaContext>,
     cuda_slice: Arc<CudaSlice<u8>>,
 }
 
+#[cfg(feature = "cuda_kv")]
 impl DeviceStorageOwned {
     pub fn new(bytes: usize, device: Arc<CudaContext>) -> Result<Self> {
         let cuda_slice = device.default_stream().alloc_zeros::<u8>(bytes)?;
@@ -214,6 +250,7 @@ impl DeviceStorageOwned {
     }
 }
 
+#[cfg(feature = "cuda_kv")]
 impl Storage for DeviceStorageOwned {
     fn get_pointer(&self) -> u64 {
         self.device_ptr() as u64
@@ -228,6 +265,7 @@ impl Storage for DeviceStorageOwned {
     }
 }
 
+#[cfg(feature = "cuda_kv")]
 impl std::fmt::Debug for DeviceStorageOwned {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Storage")
@@ -238,6 +276,7 @@ impl std::fmt::Debug for DeviceStorageOwned {
 }
 
 /// Direct wrapper around CUDA pinned memory
+#[cfg(feature = "cuda_kv")]
 pub struct CudaPinnedMemory {
     /// Raw pointer to the pinned memory
     ptr: NonNull<c_void>,
@@ -245,9 +284,12 @@ pub struct CudaPinnedMemory {
     bytes: usize,
 }
 
+#[cfg(feature = "cuda_kv")]
 unsafe impl Send for CudaPinnedMemory {}
+#[cfg(feature = "cuda_kv")]
 unsafe impl Sync for CudaPinnedMemory {}
 
+#[cfg(feature = "cuda_kv")]
 impl CudaPinnedMemory {
     /// Allocate new pinned memory using CUDA
     pub fn new(bytes: usize) -> Result<Self> {
@@ -289,6 +331,7 @@ impl CudaPinnedMemory {
     }
 }
 
+#[cfg(feature = "cuda_kv")]
 impl Drop for CudaPinnedMemory {
     fn drop(&mut self) {
         let result = unsafe { cuda_free_host(self.ptr.as_ptr()) };
@@ -298,6 +341,7 @@ impl Drop for CudaPinnedMemory {
     }
 }
 
+#[cfg(feature = "cuda_kv")]
 // Implement Storage trait for the new CudaPinnedMemory
 impl Storage for CudaPinnedMemory {
     fn get_pointer(&self) -> u64 {
@@ -313,6 +357,7 @@ impl Storage for CudaPinnedMemory {
     }
 }
 
+#[cfg(feature = "cuda_kv")]
 impl std::fmt::Debug for CudaPinnedMemory {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("CudaPinnedMemory")
@@ -323,6 +368,167 @@ impl std::fmt::Debug for CudaPinnedMemory {
     }
 }
 
+/// Alignment of [SystemStorage] allocations; matches the alignment of cudaMallocHost
+const SYSTEM_STORAGE_ALIGNMENT: usize = 256;
+
+/// Zeroed heap allocation
+pub struct SystemStorage {
+    ptr: NonNull<u8>,
+    layout: std::alloc::Layout,
+}
+
+unsafe impl Send for SystemStorage {}
+unsafe impl Sync for SystemStorage {}
+
+impl SystemStorage {
+    pub fn new(bytes: usize) -> Result<Self> {
+        if bytes == 0 {
+            raise!("Bytes must be greater than 0");
+        }
+
+        let layout = std::alloc::Layout::from_size_align(bytes, SYSTEM_STORAGE_ALIGNMENT)?;
+
+        // Safety: the layout has a non-zero size
+        let ptr = unsafe { std::alloc::alloc_zeroed(layout) };
+        let ptr = NonNull::new(ptr).ok_or_else(|| error!("Failed to allocate system memory"))?;
+
+        Ok(Self { ptr, layout })
+    }
+}
+
+impl Drop for SystemStorage {
+    fn drop(&mut self) {
+        // Safety: allocated in new with the same layout
+        unsafe { std::alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+impl Storage for SystemStorage {
+    fn get_pointer(&self) -> u64 {
+        self.ptr.as_ptr() as u64
+    }
+
+    fn storage_size(&self) -> usize {
+        self.layout.size()
+    }
+
+    fn storage_type(&self) -> StorageType {
+        StorageType::System
+    }
+}
+
+impl std::fmt::Debug for SystemStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SystemStorage")
+            .field("ptr", &(self.ptr.as_ptr() as usize))
+            .field("bytes", &self.layout.size())
+            .field("storage_type", &self.storage_type())
+            .finish()
+    }
+}
+
+/// A file on local disk, memory-mapped so it is addressed like host memory.
+///
+/// The file is created, or truncated, on construction and removed on drop.
+pub struct DiskStorage {
+    mmap: memmap2::MmapMut,
+    path: PathBuf,
+}
+
+impl DiskStorage {
+    pub fn new(bytes: usize, path: impl AsRef<Path>) -> Result<Self> {
+        if bytes == 0 {
+            raise!("Bytes must be greater than 0");
+        }
+
+        let path = path.as_ref().to_path_buf();
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        file.set_len(bytes as u64)?;
+
+        // Safety: the file is private to this storage; nothing else maps or resizes it
+        let mmap = unsafe { memmap2::MmapMut::map_mut(&file)? };
+
+        Ok(Self { mmap, path })
+    }
+
+    /// Path of the backing file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for DiskStorage {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            tracing::warn!("Failed to remove {}: {e}", self.path.display());
+        }
+    }
+}
+
+impl Storage for DiskStorage {
+    fn get_pointer(&self) -> u64 {
+        self.mmap.as_ptr() as u64
+    }
+
+    fn storage_size(&self) -> usize {
+        self.mmap.len()
+    }
+
+    fn storage_type(&self) -> StorageType {
+        StorageType::Disk
+    }
+}
+
+impl std::fmt::Debug for DiskStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DiskStorage")
+            .field("path", &self.path)
+            .field("bytes", &self.mmap.len())
+            .field("storage_type", &self.storage_type())
+            .finish()
+    }
+}
+
+/// Blocking copy of `bytes` bytes from `src` at `src_offset` to `dst` at `dst_offset`.
+///
+/// Copies between host-accessible storages are plain memory copies; copies to or from device
+/// memory use cudaMemcpy.
+pub fn copy_bytes(
+    src: &dyn Storage,
+    src_offset: usize,
+    dst: &dyn Storage,
+    dst_offset: usize,
+    bytes: usize,
+) -> Result<()> {
+    if src_offset + bytes > src.storage_size() || dst_offset + bytes > dst.storage_size() {
+        raise!("Copy of {bytes} bytes is out of bounds");
+    }
+
+    let src_ptr = (src.get_pointer() as *const u8).wrapping_add(src_offset);
+    let dst_ptr = (dst.get_pointer() as *mut u8).wrapping_add(dst_offset);
+
+    #[cfg(feature = "cuda_kv")]
+    if matches!(src.storage_type(), StorageType::Device(_))
+        || matches!(dst.storage_type(), StorageType::Device(_))
+    {
+        let rc =
+            unsafe { cuda_memcpy_sync(dst_ptr as *mut c_void, src_ptr as *const c_void, bytes) };
+        if rc != 0 {
+            raise!("cudaMemcpy failed");
+        }
+        return Ok(());
+    }
+
+    // Safety: both ranges are in bounds of their storage
+    unsafe { std::ptr::copy(src_ptr, dst_ptr, bytes) };
+    Ok(())
+}
+
 /// A view into tensor data with statically-known dimension count
 #[derive(Clone)]
 pub struct TensorView<'a, T: Storage, const D: usize> {
@@ -518,10 +724,11 @@ impl<'a, T: Storage, const D: usize> TensorView<'a, T, D> {
     /// Get the element value at the specified indices (for host-accessible tensors)
     pub fn get_element<E: bytemuck::Pod + Copy>(&self, indices: &[usize; D]) -> Result<E> {
         match self.storage.storage_type() {
+            #[cfg(feature = "cuda_kv")]
             StorageType::Device(_) => {
                 return Err(error!("Cannot directly access elements from device tensor"))
             }
-            StorageType::System | StorageType::Pinned => {}
+            StorageType::System | StorageType::Pinned | StorageType::Disk => {}
         };
 
         if std::mem::size_of::<E>() != self.element_size {
@@ -547,8 +754,9 @@ impl<'a, T: Storage, const D: usize> TensorView<'a, T, D> {
         value: E,
     ) -> Result<()> {
         match self.storage.storage_type() {
+            #[cfg(feature = "cuda_kv")]
             StorageType::Device(_) => return Err(error!("Cannot directly modify device tensor")),
-            StorageType::System | StorageType::Pinned => {}
+            StorageType::System | StorageType::Pinned | StorageType::Disk => {}
         };
 
         if std::mem::size_of::<E>() != self.element_size {
@@ -570,8 +778,9 @@ impl<'a, T: Storage, const D: usize> TensorView<'a, T, D> {
     /// Fill the tensor with a single value (for host-accessible tensors)
     pub fn fill<E: bytemuck::Pod + Copy>(&mut self, value: E) -> Result<()> {
         match self.storage.storage_type() {
+            #[cfg(feature = "cuda_kv")]
             StorageType::Device(_) => return Err(error!("Cannot directly modify device tensor")),
-            StorageType::System | StorageType::Pinned => {}
+            StorageType::System | StorageType::Pinned | StorageType::Disk => {}
         };
 
         if std::mem::size_of::<E>() != self.element_size {
@@ -633,6 +842,7 @@ impl<'a, T: Storage, const D: usize> TensorView<'a, T, D> {
         self.total_elements * self.element_size
     }
 
+    #[cfg(feature = "cuda_kv")]
     pub fn copy_to_view_blocking<S: Storage>(
         &self,
         dst_view: &mut TensorView<'_, S, D>,
@@ -715,18 +925,21 @@ impl<'a, T: Storage, const D: usize> TensorView<'a, T, D> {
         })
     }
 
+    #[cfg(feature = "cuda_kv")]
     pub fn as_ndarray_view<DT>(&self) -> Result<ndarray::ArrayView<'_, DT, IxDyn>>
 // where
     //     DT: bytemuck::Pod,
     {
         match self.storage.storage_type() {
+            #[cfg(feature = "cuda_kv")]
             StorageType::Device(_) => raise!("Cannot convert device tensor to ndarray"),
-            StorageType::System | StorageType::Pinned => {}
+            StorageType::System | StorageType::Pinned | StorageType::Disk => {}
         };
 
         self.as_unsafe_ndarray_view::<DT>()
     }
 
+    #[cfg(feature = "cuda_kv")]
     pub(crate) fn as_unsafe_ndarray_view<DT>(&self) -> Result<ndarray::ArrayView<'_, DT, IxDyn>>
 // where
     //    DT: bytemuck::Pod,
@@ -758,15 +971,17 @@ impl<'a, T: Storage, const D: usize> TensorView<'a, T, D> {
     }
 
     /// Convert to a mutable ndarray view
+    #[cfg(feature = "cuda_kv")]
     pub fn as_ndarray_view_mut<DT>(&mut self) -> Result<ArrayViewMut<'_, DT, IxDyn>>
     where
         DT: bytemuck::Pod,
     {
         match self.storage.storage_type() {
+            #[cfg(feature = "cuda_kv")]
             StorageType::Device(_) => {
                 return Err(anyhow::anyhow!("Cannot convert device tensor to ndarray"))
             }
-            StorageType::System | StorageType::Pinned => {}
+            StorageType::System | StorageType::Pinned | StorageType::Disk => {}
         };
 
         // validate DT matches bytes per element
@@ -819,10 +1034,11 @@ impl<'a, T: Storage, const D: usize> TensorView<'a, T, D> {
         F: Fn(E) -> R,
     {
         match self.storage.storage_type() {
+            #[cfg(feature = "cuda_kv")]
             StorageType::Device(_) => {
                 return Err(error!("Cannot directly access elements from device tensor"))
             }
-            StorageType::System | StorageType::Pinned => {}
+            StorageType::System | StorageType::Pinned | StorageType::Disk => {}
         };
 
         if std::mem::size_of::<E>() != self.element_size {
@@ -852,8 +1068,9 @@ impl<'a, T: Storage, const D: usize> TensorView<'a, T, D> {
     /// Gets a slice of the underlying data if it's contiguous and on the host
     pub fn as_slice<E: bytemuck::Pod>(&self) -> Result<&[E]> {
         match self.storage.storage_type() {
+            #[cfg(feature = "cuda_kv")]
             StorageType::Device(_) => return Err(error!("Cannot get slice from device tensor")),
-            StorageType::System | StorageType::Pinned => {}
+            StorageType::System | StorageType::Pinned | StorageType::Disk => {}
         };
 
         if std::mem::size_of::<E>() != self.element_size {
@@ -879,10 +1096,11 @@ impl<'a, T: Storage, const D: usize> TensorView<'a, T, D> {
     /// Gets a mutable slice of the underlying data if it's contiguous and on the host
     pub fn as_slice_mut<E: bytemuck::Pod>(&mut self) -> Result<&mut [E]> {
         match self.storage.storage_type() {
+            #[cfg(feature = "cuda_kv")]
             StorageType::Device(_) => {
                 return Err(error!("Cannot get mutable slice from device tensor"))
             }
-            StorageType::System | StorageType::Pinned => {}
+            StorageType::System | StorageType::Pinned | StorageType::Disk => {}
         };
 
         if std::mem::size_of::<E>() != self.element_size {
@@ -911,6 +1129,7 @@ impl<'a, T: Storage, const D: usize> TensorView<'a, T, D> {
     ///
     /// This is a convenience method for copying data from a host tensor to a device tensor.
     /// Both tensors must have the same shape, element size, and total number of elements.
+    #[cfg(feature = "cuda_kv")]
     pub fn h2d<S: Storage>(
         &self,
         device_view: &mut TensorView<'_, S, D>,
@@ -918,14 +1137,16 @@ impl<'a, T: Storage, const D: usize> TensorView<'a, T, D> {
     ) -> Result<()> {
         // Ensure self is a host tensor
         match self.storage.storage_type() {
+            #[cfg(feature = "cuda_kv")]
             StorageType::Device(_) => {
                 return Err(error!("Source must be a host tensor (System or Pinned)"))
             }
-            StorageType::System | StorageType::Pinned => {}
+            StorageType::System | StorageType::Pinned | StorageType::Disk => {}
         };
 
         // Ensure device_view is a device tensor
         match device_view.storage_type() {
+            #[cfg(feature = "cuda_kv")]
             StorageType::Device(_) => {}
             _ => return Err(error!("Destination must be a device tensor")),
         };
@@ -980,6 +1201,7 @@ impl<'a, T: Storage, const D: usize> TensorView<'a, T, D> {
     ///
     /// This is a convenience method for copying data from a device tensor to a host tensor.
     /// Both tensors must have the same shape, element size, and total number of elements.
+    #[cfg(feature = "cuda_kv")]
     pub fn d2h<S: Storage>(
         &self,
         host_view: &mut TensorView<'_, S, D>,
@@ -987,18 +1209,20 @@ impl<'a, T: Storage, const D: usize> TensorView<'a, T, D> {
     ) -> Result<()> {
         // Ensure self is a device tensor
         match self.storage.storage_type() {
+            #[cfg(feature = "cuda_kv")]
             StorageType::Device(_) => {}
             _ => return Err(error!("Source must be a device tensor")),
         };
 
         // Ensure host_view is a host tensor
         match host_view.storage_type() {
+            #[cfg(feature = "cuda_kv")]
             StorageType::Device(_) => {
                 return Err(error!(
                     "Destination must be a host tensor (System or Pinned)"
                 ))
             }
-            StorageType::System | StorageType::Pinned => {}
+            StorageType::System | StorageType::Pinned | StorageType::Disk => {}
         };
 
         // Validate shape and element size
@@ -1049,11 +1273,12 @@ impl<'a, T: Storage, const D: usize> TensorView<'a, T, D> {
 
     /// Convert the tensor view to a new owned ndarray tensor in host memory
     /// This is not a performant operation, and should only be used for testing
+    #[cfg(feature = "cuda_kv")]
     pub fn to_owned<DT: std::fmt::Debug + Clone + num_traits::Zero>(
         &self,
     ) -> Result<ndarray::Array<DT, IxDyn>> {
         match self.storage.storage_type() {
-            StorageType::System | StorageType::Pinned => {
+            StorageType::System | StorageType::Pinned | StorageType::Disk => {
                 let nd = self.as_ndarray_view::<DT>()?;
                 Ok(nd.to_owned())
             }
@@ -1166,6 +1391,7 @@ pub mod tensor_indexing {
 
 /// Storage that wraps external device memory with metadata provided externally
 /// This is unsafe as it trusts that the provided device pointer and sizes are valid
+#[cfg(feature = "cuda_kv")]
 #[derive(Debug)]
 pub struct DeviceStorageFromAny {
     /// The original object that owns the memory (e.g., a PyObject)
@@ -1181,6 +1407,7 @@ pub struct DeviceStorageFromAny {
     device: Arc<CudaContext>,
 }
 
+#[cfg(feature = "cuda_kv")]
 impl DeviceStorageFromAny {
     /// Create a new DeviceStorageFromAny wrapper
     ///
@@ -1216,6 +1443,7 @@ impl DeviceStorageFromAny {
     }
 }
 
+#[cfg(feature = "cuda_kv")]
 impl Storage for DeviceStorageFromAny {
     fn get_pointer(&self) -> u64 {
         self.device_ptr
@@ -1749,6 +1977,7 @@ mod tests {
         assert_eq!(mut_view.get_element::<f32>(&[1, 2]).unwrap(), 60.0);
     }
 
+    #[cfg(feature = "cuda_kv")]
     #[test]
     fn test_ndarray_view_with_real_data() {
         use std::sync::{Arc, Mutex};
@@ -1871,6 +2100,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "cuda_kv")]
     #[test]
     fn test_host_device_transfers() {
         use cudarc::driver::CudaContext;
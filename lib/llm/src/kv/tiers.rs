@@ -0,0 +1,538 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Multi-tier management of kv blocks
+//!
+//! A [TieredKvStorageManager] extends the [KvStorageManager] of an upper tier, e.g. GPU memory,
+//! with lower tiers, e.g. system memory and then local disk. The contents of a block evicted from
+//! a tier are demoted to the tier below it; the last tier drops them. Blocks of a prefill that miss
+//! the upper tier but hit a lower tier are promoted back: their contents are copied into upper tier
+//! blocks, which are returned as inflight blocks like any other match.
+//!
+//! Lower tiers are inclusive: a promoted block stays in its lower tier, so evicting it again from
+//! the upper tier costs no copy.
+
+use std::collections::{BTreeSet, HashSet};
+use std::path::PathBuf;
+
+use super::events::KvEventSink;
+use super::manager::{EvictedBlock, KvStorageManager, PrefillMatched, PrefillOffload};
+use super::storage::{copy_bytes, OwnedStorage, Storage};
+use super::*;
+
+/// Storage of a lower tier
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TierStorage {
+    /// Heap allocation in system memory
+    System,
+
+    /// Memory-mapped file; created on construction and removed on drop
+    Disk(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TierConfig {
+    pub storage: TierStorage,
+
+    /// Number of blocks the tier holds
+    pub capacity: usize,
+}
+
+impl TierConfig {
+    pub fn system(capacity: usize) -> Self {
+        Self {
+            storage: TierStorage::System,
+            capacity,
+        }
+    }
+
+    pub fn disk(path: impl Into<PathBuf>, capacity: usize) -> Self {
+        Self {
+            storage: TierStorage::Disk(path.into()),
+            capacity,
+        }
+    }
+}
+
+/// Occupancy and effectiveness of a tier
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TierStats {
+    /// Number of blocks the tier holds
+    pub capacity: usize,
+
+    /// Number of blocks with contents; only tracked for lower tiers
+    pub used: usize,
+
+    /// Blocks of prefills found in the tier
+    pub hits: u64,
+
+    /// Blocks of prefills looked up in the tier but not found
+    pub misses: u64,
+
+    /// Blocks whose contents were evicted from the tier
+    pub evictions: u64,
+}
+
+impl TierStats {
+    /// Fraction of lookups found in the tier; 0 before the first lookup
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / lookups as f64
+    }
+}
+
+/// Manages kv blocks across an upper tier and any number of lower tiers
+pub struct TieredKvStorageManager {
+    upper: KvStorageManager,
+    upper_storage: OwnedStorage,
+    upper_stats: TierStats,
+    lower: Vec<LowerTier>,
+    block_bytes: usize,
+}
+
+impl TieredKvStorageManager {
+    /// Create a manager whose upper tier holds a block of `block_bytes` bytes for each slot of
+    /// `upper_storage`; `tiers` are ordered from the highest lower tier to the lowest.
+    pub async fn new(
+        block_size: usize,
+        block_bytes: usize,
+        upper_storage: OwnedStorage,
+        tiers: Vec<TierConfig>,
+    ) -> Result<Self> {
+        if block_bytes == 0 {
+            raise!("block_bytes must be greater than 0");
+        }
+
+        let capacity = upper_storage.storage_size() / block_bytes;
+        if capacity == 0 {
+            raise!("upper tier storage is smaller than a block");
+        }
+
+        let mut upper = KvStorageManager::new(block_size).await;
+        for block_id in 0..capacity {
+            upper.insert_block(KvBlock::with_block_id(block_id)).await?;
+        }
+
+        let lower = tiers
+            .into_iter()
+            .map(|config| LowerTier::new(config, block_bytes))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            upper,
+            upper_storage,
+            upper_stats: TierStats {
+                capacity,
+                ..Default::default()
+            },
+            lower,
+            block_bytes,
+        })
+    }
+
+    /// Publishes the kv cache events of the upper tier to the sink; see
+    /// [KvStorageManager::with_event_sink]
+    pub fn with_event_sink(mut self, sink: Arc<dyn KvEventSink>) -> Self {
+        self.upper = self.upper.with_event_sink(sink);
+        self
+    }
+
+    /// Storage of the upper tier; block `block_id` starts at `block_id * block_bytes`
+    pub fn upper_storage(&self) -> &OwnedStorage {
+        &self.upper_storage
+    }
+
+    /// Stats of the upper tier followed by those of the lower tiers
+    pub fn stats(&self) -> Vec<TierStats> {
+        std::iter::once(self.upper_stats)
+            .chain(self.lower.iter().map(|tier| tier.stats))
+            .collect()
+    }
+
+    /// Matches the blocks of a sequence to the upper tier, then promotes the blocks which
+    /// follow the match from the lower tiers.
+    pub async fn prepare_prefill_sequence(&mut self, tokens: Tokens) -> Result<PrefillMatched> {
+        let matched = self.upper.prepare_prefill_sequence(tokens).await?;
+        let (mut inflight_blocks, remaining_blocks, tail_block) = matched.dissolve();
+
+        self.upper_stats.hits += inflight_blocks.len() as u64;
+        self.upper_stats.misses += remaining_blocks.len() as u64;
+        let upper_matches = inflight_blocks.len();
+
+        // the lower tiers continue the match until the first block none of them holds
+        let mut sources = Vec::new();
+        for block in &remaining_blocks {
+            match self.lookup(block.sequence_hash()) {
+                Some(source) => sources.push(source),
+                None => break,
+            }
+        }
+
+        if !sources.is_empty() {
+            let targets = self.upper.take_blocks(sources.len() as u32).await?;
+            sources.truncate(targets.len());
+
+            // the blocks being promoted must survive the demotion of the blocks they replace
+            let pinned = sources
+                .iter()
+                .map(|source| source.sequence_hash)
+                .collect::<HashSet<_>>();
+            for target in targets.iter() {
+                if let Some(evicted) = EvictedBlock::from_block(target) {
+                    self.demote(evicted, &pinned)?;
+                }
+            }
+
+            for ((mut target, source), token_block) in
+                targets.into_iter().zip(sources).zip(&remaining_blocks)
+            {
+                let tier = &self.lower[source.tier];
+                copy_bytes(
+                    &tier.storage,
+                    source.slot * self.block_bytes,
+                    &self.upper_storage,
+                    target.block_id() * self.block_bytes,
+                    self.block_bytes,
+                )?;
+                target.update_token_block(token_block.clone());
+                inflight_blocks.extend(self.upper.register_blocks(vec![target])?);
+            }
+        }
+
+        let promoted = inflight_blocks.len() - upper_matches;
+        let remaining_blocks = remaining_blocks.into_iter().skip(promoted).collect();
+
+        Ok(PrefillMatched::new(
+            inflight_blocks,
+            remaining_blocks,
+            tail_block,
+        ))
+    }
+
+    /// Takes the upper tier blocks for a prefill, demoting the contents they held
+    pub async fn prepare_prefill_offload(
+        &mut self,
+        matched: PrefillMatched,
+    ) -> Result<PrefillOffload> {
+        let offload = self.upper.prepare_prefill_offload(matched).await?;
+
+        for evicted in offload.evicted_blocks() {
+            self.demote(*evicted, &HashSet::new())?;
+        }
+
+        Ok(offload)
+    }
+
+    /// Finds the highest lower tier holding a block
+    fn lookup(&mut self, sequence_hash: SequenceHash) -> Option<BlockSource> {
+        for (index, tier) in self.lower.iter_mut().enumerate() {
+            match tier.touch(sequence_hash) {
+                Some(slot) => {
+                    tier.stats.hits += 1;
+                    return Some(BlockSource {
+                        tier: index,
+                        slot,
+                        sequence_hash,
+                    });
+                }
+                None => tier.stats.misses += 1,
+            }
+        }
+        None
+    }
+
+    /// Demotes the contents of an upper tier block to the lower tiers
+    fn demote(&mut self, evicted: EvictedBlock, pinned: &HashSet<SequenceHash>) -> Result<()> {
+        self.upper_stats.evictions += 1;
+        demote(
+            &mut self.lower,
+            self.block_bytes,
+            &self.upper_storage,
+            evicted.block_id,
+            evicted.sequence_hash,
+            evicted.priority,
+            pinned,
+        )
+    }
+}
+
+/// Where a block being promoted is held
+struct BlockSource {
+    tier: usize,
+    slot: usize,
+    sequence_hash: SequenceHash,
+}
+
+/// Copies a block into the first of `tiers`, demoting the block it evicts to the next tier
+fn demote(
+    tiers: &mut [LowerTier],
+    block_bytes: usize,
+    src: &dyn Storage,
+    src_slot: usize,
+    sequence_hash: SequenceHash,
+    priority: u32,
+    pinned: &HashSet<SequenceHash>,
+) -> Result<()> {
+    let Some((tier, below)) = tiers.split_first_mut() else {
+        return Ok(());
+    };
+
+    if tier.touch(sequence_hash).is_some() {
+        return Ok(());
+    }
+
+    let slot = match tier.free_slots.pop() {
+        Some(slot) => slot,
+        None => match tier.evict(pinned) {
+            Some(victim) => {
+                demote(
+                    below,
+                    block_bytes,
+                    &tier.storage,
+                    victim.block_id,
+                    victim.sequence_hash,
+                    victim.priority,
+                    pinned,
+                )?;
+                victim.block_id
+            }
+            // every block of the tier is being promoted
+            None => {
+                return demote(
+                    below,
+                    block_bytes,
+                    src,
+                    src_slot,
+                    sequence_hash,
+                    priority,
+                    pinned,
+                )
+            }
+        },
+    };
+
+    copy_bytes(
+        src,
+        src_slot * block_bytes,
+        &tier.storage,
+        slot * block_bytes,
+        block_bytes,
+    )?;
+    tier.insert(sequence_hash, priority, slot);
+    Ok(())
+}
+
+struct ResidentBlock {
+    slot: usize,
+    priority: u32,
+    tick: u64,
+}
+
+/// Key of the eviction order: lowest priority first, then least recently used
+type EvictionKey = (u32, u64, SequenceHash);
+
+struct LowerTier {
+    storage: OwnedStorage,
+    blocks: HashMap<SequenceHash, ResidentBlock>,
+    order: BTreeSet<EvictionKey>,
+    free_slots: Vec<usize>,
+    tick: u64,
+    stats: TierStats,
+}
+
+impl LowerTier {
+    fn new(config: TierConfig, block_bytes: usize) -> Result<Self> {
+        if config.capacity == 0 {
+            raise!("tier capacity must be greater than 0");
+        }
+
+        let bytes = config.capacity * block_bytes;
+        let storage = match config.storage {
+            TierStorage::System => OwnedStorage::create_system_array(bytes)?,
+            TierStorage::Disk(path) => OwnedStorage::create_disk_array(bytes, path)?,
+        };
+
+        Ok(Self {
+            storage,
+            blocks: HashMap::new(),
+            order: BTreeSet::new(),
+            free_slots: (0..config.capacity).rev().collect(),
+            tick: 0,
+            stats: TierStats {
+                capacity: config.capacity,
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Marks a block as most recently used; returns its slot if the tier holds it
+    fn touch(&mut self, sequence_hash: SequenceHash) -> Option<usize> {
+        self.tick += 1;
+        let block = self.blocks.get_mut(&sequence_hash)?;
+        self.order
+            .remove(&(block.priority, block.tick, sequence_hash));
+        block.tick = self.tick;
+        self.order
+            .insert((block.priority, block.tick, sequence_hash));
+        Some(block.slot)
+    }
+
+    fn insert(&mut self, sequence_hash: SequenceHash, priority: u32, slot: usize) {
+        self.tick += 1;
+        self.order.insert((priority, self.tick, sequence_hash));
+        self.blocks.insert(
+            sequence_hash,
+            ResidentBlock {
+                slot,
+                priority,
+                tick: self.tick,
+            },
+        );
+        self.stats.used = self.blocks.len();
+    }
+
+    /// Removes the next block in eviction order which is not pinned; the block id of the
+    /// returned block is its slot in this tier
+    fn evict(&mut self, pinned: &HashSet<SequenceHash>) -> Option<EvictedBlock> {
+        let key = *self
+            .order
+            .iter()
+            .find(|(_, _, sequence_hash)| !pinned.contains(sequence_hash))?;
+        self.order.remove(&key);
+
+        let (priority, _, sequence_hash) = key;
+        let block = self.blocks.remove(&sequence_hash)?;
+        self.stats.used = self.blocks.len();
+        self.stats.evictions += 1;
+
+        Some(EvictedBlock {
+            sequence_hash,
+            priority,
+            block_id: block.slot,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use super::reuse::tests::create_token_sequence;
+
+    const BLOCK_SIZE: usize = 2;
+    const BLOCK_BYTES: usize = 16;
+
+    fn write_block(storage: &OwnedStorage, block_id: usize, value: u8) {
+        let ptr = (storage.get_pointer() as *mut u8).wrapping_add(block_id * BLOCK_BYTES);
+        unsafe { std::ptr::write_bytes(ptr, value, BLOCK_BYTES) };
+    }
+
+    fn read_block(storage: &OwnedStorage, block_id: usize) -> Vec<u8> {
+        let ptr = (storage.get_pointer() as *const u8).wrapping_add(block_id * BLOCK_BYTES);
+        unsafe { std::slice::from_raw_parts(ptr, BLOCK_BYTES) }.to_vec()
+    }
+
+    /// Prefills a sequence into the upper tier, writing the first token of each block into its
+    /// contents; the blocks return to the available pool when dropped
+    async fn prefill(manager: &mut TieredKvStorageManager, tokens: &[u32]) {
+        let matched = manager
+            .prepare_prefill_sequence(create_token_sequence(tokens))
+            .await
+            .unwrap();
+        assert!(matched.inflight_blocks().is_empty());
+
+        let offload = manager.prepare_prefill_offload(matched).await.unwrap();
+        for block in offload.complete_prefill_blocks() {
+            let value = block.token_block.tokens()[0] as u8;
+            write_block(manager.upper_storage(), block.block_id(), value);
+        }
+    }
+
+    #[test]
+    fn test_tier_stats_hit_rate() {
+        let mut stats = TierStats::default();
+        assert_eq!(stats.hit_rate(), 0.0);
+
+        stats.hits = 3;
+        stats.misses = 1;
+        assert_eq!(stats.hit_rate(), 0.75);
+    }
+
+    #[tokio::test]
+    async fn test_demote_and_promote() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // 4 upper tier blocks: a sequence of 3 complete blocks plus its tail
+        let upper_storage = OwnedStorage::create_system_array(4 * BLOCK_BYTES).unwrap();
+        let mut manager = TieredKvStorageManager::new(
+            BLOCK_SIZE,
+            BLOCK_BYTES,
+            upper_storage,
+            vec![
+                TierConfig::system(2),
+                TierConfig::disk(dir.path().join("kv.bin"), 4),
+            ],
+        )
+        .await
+        .unwrap();
+
+        prefill(&mut manager, &[1, 2, 3, 4, 5, 6]).await;
+
+        // the second sequence reuses, and so evicts, every block of the first: two are demoted
+        // to system memory, the oldest of which is then demoted on to disk by the third
+        prefill(&mut manager, &[11, 12, 13, 14, 15, 16]).await;
+
+        let stats = manager.stats();
+        assert_eq!(stats[0].evictions, 3);
+        assert_eq!(stats[1].used, 2);
+        assert_eq!(stats[1].evictions, 1);
+        assert_eq!(stats[2].used, 1);
+
+        // the first sequence misses the upper tier and is promoted from the lower tiers
+        let matched = manager
+            .prepare_prefill_sequence(create_token_sequence(&[1, 2, 3, 4, 5, 6]))
+            .await
+            .unwrap();
+        assert_eq!(matched.inflight_blocks().len(), 3);
+        assert!(matched.remaining_blocks().is_empty());
+
+        for (block, value) in matched.inflight_blocks().iter().zip([1u8, 3, 5]) {
+            assert_eq!(
+                read_block(manager.upper_storage(), block.block_id()),
+                vec![value; BLOCK_BYTES]
+            );
+        }
+
+        let stats = manager.stats();
+        assert_eq!(stats[0].hits, 0);
+        assert_eq!(stats[0].misses, 9);
+        // the first block is on disk, the others in system memory; the lookups of each earlier
+        // prefill missed both tiers at its first block
+        assert_eq!(stats[1].hits, 2);
+        assert_eq!(stats[1].misses, 3);
+        assert_eq!(stats[2].hits, 1);
+        assert_eq!(stats[2].misses, 2);
+        assert_eq!(stats[1].hit_rate(), 0.4);
+
+        // the promoted blocks replaced the empty tail block and two blocks of the second
+        // sequence, which went down to disk as every block in system memory was being promoted
+        assert_eq!(stats[0].evictions, 5);
+        assert_eq!(stats[1].used, 2);
+        assert_eq!(stats[2].used, 3);
+    }
+}
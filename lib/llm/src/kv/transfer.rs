@@ -0,0 +1,791 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # KV Block Transfer
+//!
+//! Moves the kv blocks of a sequence between workers, e.g. from the prefill worker which computed
+//! them to the decode worker which continues the sequence.
+//!
+//! A [KvSequenceDescriptor] names blocks held by one worker: the [KvBlockDetails] of their shape
+//! and layout, then the sequence hash and block id of each block. A transfer pairs the
+//! descriptor of the blocks being read with the descriptor of the blocks being written; both must
+//! describe compatible blocks with the same sequence hashes.
+//!
+//! A worker serves its blocks with a [KvTransferHandler] on the [KV_TRANSFER_ENDPOINT] of its
+//! component. A peer with a [KvTransferClient] can then:
+//! - [pull][KvTransferClient::pull] blocks from the worker's [KvBlockSource] into a local sink, or
+//! - [push][KvTransferClient::push] blocks from a local source into the worker's [KvBlockSink].
+//!
+//! Pulled blocks are streamed back over the response plane, one block per response. Pushed blocks
+//! travel in requests, which are limited by the max payload of the NATS request plane, so a push is
+//! split into requests of at most [MAX_PUSH_BYTES] of block data.
+//!
+//! The data of a block is that of each of its layers, in layer order. [HostKvBlocks] is a source
+//! and sink in system memory.
+
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+use dynamo_runtime::{
+    component::{Client, Component},
+    pipeline::{
+        network::Ingress, AsyncEngine, AsyncEngineContextProvider, Context, ManyOut,
+        ResponseStream, SingleIn,
+    },
+    protocols::annotated::Annotated,
+    raise, Error, Result,
+};
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use super::details::KvBlockDetails;
+use super::KvBlock;
+use crate::tokens::SequenceHash;
+
+/// Name of the endpoint serving kv block transfers
+pub const KV_TRANSFER_ENDPOINT: &str = "kv_transfer";
+
+/// Default upper bound of the block data of a push request. The data is base64 encoded, growing it
+/// by a third, and must fit the 1 MiB default max payload of NATS.
+pub const MAX_PUSH_BYTES: usize = 512 * 1024;
+
+/// A block held by a worker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KvBlockDescriptor {
+    pub sequence_hash: SequenceHash,
+    pub block_id: usize,
+}
+
+/// The blocks of a sequence held by a worker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KvSequenceDescriptor {
+    pub block_details: KvBlockDetails,
+    pub blocks: Vec<KvBlockDescriptor>,
+}
+
+impl KvSequenceDescriptor {
+    /// Describes the blocks in sequence order
+    pub fn new<'a>(
+        block_details: KvBlockDetails,
+        blocks: impl IntoIterator<Item = &'a KvBlock>,
+    ) -> Self {
+        let blocks = blocks
+            .into_iter()
+            .map(|block| KvBlockDescriptor {
+                sequence_hash: block.token_block.sequence_hash(),
+                block_id: block.block_id(),
+            })
+            .collect();
+        Self {
+            block_details,
+            blocks,
+        }
+    }
+
+    /// Checks that the blocks of `dst` can receive the blocks of `self`
+    pub fn validate_transfer(&self, dst: &KvSequenceDescriptor) -> Result<()> {
+        if !self.block_details.is_compatible(&dst.block_details) {
+            raise!(
+                "incompatible kv blocks: {:?} vs {:?}",
+                self.block_details,
+                dst.block_details
+            );
+        }
+        if self.blocks.len() != dst.blocks.len() {
+            raise!(
+                "expected {} destination blocks, got {}",
+                self.blocks.len(),
+                dst.blocks.len()
+            );
+        }
+        for (src, dst) in self.blocks.iter().zip(&dst.blocks) {
+            if src.sequence_hash != dst.sequence_hash {
+                raise!(
+                    "sequence hash mismatch: source block {} holds {}, destination block {} expects {}",
+                    src.block_id,
+                    src.sequence_hash,
+                    dst.block_id,
+                    dst.sequence_hash
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Worker storage blocks can be read from
+pub trait KvBlockSource: Send + Sync {
+    fn block_details(&self) -> &KvBlockDetails;
+
+    /// Reads the data of a block; fails if the block does not hold the sequence hash
+    fn read_block(&self, block: &KvBlockDescriptor) -> Result<Vec<u8>>;
+}
+
+/// Worker storage blocks can be written to
+pub trait KvBlockSink: Send + Sync {
+    fn block_details(&self) -> &KvBlockDetails;
+
+    /// Writes the data of a block, which then holds the sequence hash
+    fn write_block(&self, block: &KvBlockDescriptor, data: &[u8]) -> Result<()>;
+}
+
+/// The data of a block in a transfer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KvBlockData {
+    pub sequence_hash: SequenceHash,
+
+    #[serde(with = "crate::protocols::base64_bytes")]
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum KvTransferRequest {
+    /// Read the blocks from the worker's source
+    Pull { src: KvSequenceDescriptor },
+
+    /// Write the blocks into the worker's sink
+    Push {
+        dst: KvSequenceDescriptor,
+        blocks: Vec<KvBlockData>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum KvTransferResponse {
+    /// The data of the next pulled block
+    Block(KvBlockData),
+
+    /// The pushed blocks were written
+    Written { blocks: usize },
+}
+
+/// Serves the kv blocks of a worker; see the [module][self] documentation
+#[derive(Default)]
+pub struct KvTransferHandler {
+    source: Option<Arc<dyn KvBlockSource>>,
+    sink: Option<Arc<dyn KvBlockSink>>,
+}
+
+impl KvTransferHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serves pulls from `source`
+    pub fn with_source(mut self, source: Arc<dyn KvBlockSource>) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Serves pushes into `sink`
+    pub fn with_sink(mut self, sink: Arc<dyn KvBlockSink>) -> Self {
+        self.sink = Some(sink);
+        self
+    }
+
+    pub async fn create_endpoint(self, component: Component) -> Result<()> {
+        let handler = Ingress::for_engine(Arc::new(self))?;
+        component
+            .endpoint(KV_TRANSFER_ENDPOINT)
+            .endpoint_builder()
+            .handler(handler)
+            .start()
+            .await
+    }
+
+    fn pull(&self, src: &KvSequenceDescriptor) -> Result<Vec<KvTransferResponse>> {
+        let Some(source) = &self.source else {
+            raise!("worker does not serve kv block pulls");
+        };
+        if !source.block_details().is_compatible(&src.block_details) {
+            raise!("pulled blocks are incompatible with the worker's kv blocks");
+        }
+        src.blocks
+            .iter()
+            .map(|block| {
+                Ok(KvTransferResponse::Block(KvBlockData {
+                    sequence_hash: block.sequence_hash,
+                    data: source.read_block(block)?,
+                }))
+            })
+            .collect()
+    }
+
+    fn push(&self, dst: &KvSequenceDescriptor, blocks: &[KvBlockData]) -> Result<usize> {
+        let Some(sink) = &self.sink else {
+            raise!("worker does not serve kv block pushes");
+        };
+        if !sink.block_details().is_compatible(&dst.block_details) {
+            raise!("pushed blocks are incompatible with the worker's kv blocks");
+        }
+        write_blocks(sink.as_ref(), dst, blocks)?;
+        Ok(blocks.len())
+    }
+}
+
+#[async_trait]
+impl AsyncEngine<SingleIn<KvTransferRequest>, ManyOut<Annotated<KvTransferResponse>>, Error>
+    for KvTransferHandler
+{
+    async fn generate(
+        &self,
+        request: SingleIn<KvTransferRequest>,
+    ) -> Result<ManyOut<Annotated<KvTransferResponse>>> {
+        let context = request.context();
+        let (request, _) = request.into_parts();
+
+        let responses = match request {
+            KvTransferRequest::Pull { src } => self.pull(&src),
+            KvTransferRequest::Push { dst, blocks } => self
+                .push(&dst, &blocks)
+                .map(|blocks| vec![KvTransferResponse::Written { blocks }]),
+        };
+
+        let responses = match responses {
+            Ok(responses) => responses.into_iter().map(Annotated::from_data).collect(),
+            Err(e) => vec![Annotated::from_error(e.to_string())],
+        };
+        Ok(ResponseStream::new(
+            Box::pin(stream::iter(responses)),
+            context,
+        ))
+    }
+}
+
+/// Route to the [KvTransferHandler] of a peer
+pub type KvTransferEngine = Arc<
+    dyn AsyncEngine<SingleIn<KvTransferRequest>, ManyOut<Annotated<KvTransferResponse>>, Error>,
+>;
+
+/// Transfers kv blocks to and from the worker behind its route
+pub struct KvTransferClient {
+    route: KvTransferEngine,
+    max_push_bytes: usize,
+}
+
+impl KvTransferClient {
+    pub fn new(route: KvTransferEngine) -> Self {
+        Self {
+            route,
+            max_push_bytes: MAX_PUSH_BYTES,
+        }
+    }
+
+    /// Limits the block data of each push request, e.g. for a NATS server with a larger max
+    /// payload; see [MAX_PUSH_BYTES]
+    pub fn with_max_push_bytes(mut self, max_push_bytes: usize) -> Self {
+        self.max_push_bytes = max_push_bytes;
+        self
+    }
+
+    /// Transfers to and from the worker `worker_id` of the [KV_TRANSFER_ENDPOINT] client
+    pub fn direct(
+        client: Client<KvTransferRequest, Annotated<KvTransferResponse>>,
+        worker_id: i64,
+    ) -> Self {
+        Self::new(Arc::new(DirectRoute { client, worker_id }))
+    }
+
+    /// Pulls the blocks of `src` from the worker into the blocks of `dst` in `sink`
+    pub async fn pull(
+        &self,
+        src: KvSequenceDescriptor,
+        dst: &KvSequenceDescriptor,
+        sink: &dyn KvBlockSink,
+    ) -> Result<()> {
+        src.validate_transfer(dst)?;
+        if !sink.block_details().is_compatible(&dst.block_details) {
+            raise!("destination blocks are incompatible with the sink's kv blocks");
+        }
+
+        let mut blocks = Vec::with_capacity(src.blocks.len());
+        let mut stream = self
+            .route
+            .generate(Context::new(KvTransferRequest::Pull { src }))
+            .await?;
+        while let Some(response) = stream.next().await {
+            match response.into_result()? {
+                Some(KvTransferResponse::Block(block)) => blocks.push(block),
+                Some(response) => raise!("unexpected kv transfer response: {response:?}"),
+                None => {}
+            }
+        }
+
+        write_blocks(sink, dst, &blocks)
+    }
+
+    /// Pushes the blocks of `src` in `source` into the blocks of `dst` on the worker, in requests
+    /// of as many whole blocks as fit the [max push bytes][Self::with_max_push_bytes]
+    pub async fn push(
+        &self,
+        src: &KvSequenceDescriptor,
+        dst: KvSequenceDescriptor,
+        source: &dyn KvBlockSource,
+    ) -> Result<()> {
+        src.validate_transfer(&dst)?;
+        if !source.block_details().is_compatible(&src.block_details) {
+            raise!("source blocks are incompatible with the source's kv blocks");
+        }
+
+        let bytes_per_block = src.block_details.bytes_per_token_block();
+        let blocks_per_request = self.max_push_bytes / bytes_per_block.max(1);
+        if blocks_per_request == 0 {
+            raise!(
+                "kv blocks of {bytes_per_block} bytes exceed the push limit of {} bytes; pull them instead",
+                self.max_push_bytes
+            );
+        }
+
+        for (src_blocks, dst_blocks) in src
+            .blocks
+            .chunks(blocks_per_request)
+            .zip(dst.blocks.chunks(blocks_per_request))
+        {
+            let blocks = src_blocks
+                .iter()
+                .map(|block| {
+                    Ok(KvBlockData {
+                        sequence_hash: block.sequence_hash,
+                        data: source.read_block(block)?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let dst = KvSequenceDescriptor {
+                block_details: dst.block_details.clone(),
+                blocks: dst_blocks.to_vec(),
+            };
+            self.push_request(dst, blocks).await?;
+        }
+        Ok(())
+    }
+
+    async fn push_request(
+        &self,
+        dst: KvSequenceDescriptor,
+        blocks: Vec<KvBlockData>,
+    ) -> Result<()> {
+        let expected = blocks.len();
+        let mut stream = self
+            .route
+            .generate(Context::new(KvTransferRequest::Push { dst, blocks }))
+            .await?;
+        let mut written = None;
+        while let Some(response) = stream.next().await {
+            match response.into_result()? {
+                Some(KvTransferResponse::Written { blocks }) => written = Some(blocks),
+                Some(response) => raise!("unexpected kv transfer response: {response:?}"),
+                None => {}
+            }
+        }
+
+        match written {
+            Some(blocks) if blocks == expected => Ok(()),
+            Some(blocks) => raise!("expected {expected} blocks to be written, got {blocks}"),
+            None => raise!("kv transfer ended before the pushed blocks were written"),
+        }
+    }
+}
+
+struct DirectRoute {
+    client: Client<KvTransferRequest, Annotated<KvTransferResponse>>,
+    worker_id: i64,
+}
+
+#[async_trait]
+impl AsyncEngine<SingleIn<KvTransferRequest>, ManyOut<Annotated<KvTransferResponse>>, Error>
+    for DirectRoute
+{
+    async fn generate(
+        &self,
+        request: SingleIn<KvTransferRequest>,
+    ) -> Result<ManyOut<Annotated<KvTransferResponse>>> {
+        self.client.direct(request, self.worker_id).await
+    }
+}
+
+/// Writes the transferred blocks into the blocks of `dst`, in order
+fn write_blocks(
+    sink: &dyn KvBlockSink,
+    dst: &KvSequenceDescriptor,
+    blocks: &[KvBlockData],
+) -> Result<()> {
+    if blocks.len() != dst.blocks.len() {
+        raise!("expected {} blocks, got {}", dst.blocks.len(), blocks.len());
+    }
+
+    let bytes_per_block = dst.block_details.bytes_per_token_block();
+    for (block, data) in dst.blocks.iter().zip(blocks) {
+        if block.sequence_hash != data.sequence_hash {
+            raise!(
+                "block {} expects sequence hash {}, got {}",
+                block.block_id,
+                block.sequence_hash,
+                data.sequence_hash
+            );
+        }
+        if data.data.len() != bytes_per_block {
+            raise!(
+                "expected {bytes_per_block} bytes for block {}, got {}",
+                block.block_id,
+                data.data.len()
+            );
+        }
+        sink.write_block(block, &data.data)?;
+    }
+    Ok(())
+}
+
+/// Kv blocks in system memory
+///
+/// Like a `KvLayer`, each layer is a contiguous region holding the layer
+/// of every block.
+pub struct HostKvBlocks {
+    block_details: KvBlockDetails,
+    number_of_blocks: usize,
+    inner: RwLock<HostKvBlocksInner>,
+}
+
+struct HostKvBlocksInner {
+    layers: Vec<Vec<u8>>,
+
+    /// The sequence hash held by each block
+    sequence_hashes: Vec<Option<SequenceHash>>,
+}
+
+impl HostKvBlocks {
+    pub fn new(block_details: KvBlockDetails, number_of_blocks: usize) -> Self {
+        let layer_bytes = block_details.bytes_per_token_block_per_layer() * number_of_blocks;
+        let layers = (0..block_details.number_of_layers())
+            .map(|_| vec![0; layer_bytes])
+            .collect();
+        Self {
+            block_details,
+            number_of_blocks,
+            inner: RwLock::new(HostKvBlocksInner {
+                layers,
+                sequence_hashes: vec![None; number_of_blocks],
+            }),
+        }
+    }
+
+    pub fn number_of_blocks(&self) -> usize {
+        self.number_of_blocks
+    }
+
+    fn check_block_id(&self, block_id: usize) -> Result<()> {
+        if block_id >= self.number_of_blocks {
+            raise!(
+                "block {block_id} out of range; {} blocks",
+                self.number_of_blocks
+            );
+        }
+        Ok(())
+    }
+}
+
+impl KvBlockSource for HostKvBlocks {
+    fn block_details(&self) -> &KvBlockDetails {
+        &self.block_details
+    }
+
+    fn read_block(&self, block: &KvBlockDescriptor) -> Result<Vec<u8>> {
+        self.check_block_id(block.block_id)?;
+        let inner = self.inner.read().unwrap();
+        if inner.sequence_hashes[block.block_id] != Some(block.sequence_hash) {
+            raise!(
+                "block {} does not hold sequence hash {}",
+                block.block_id,
+                block.sequence_hash
+            );
+        }
+
+        let bytes = self.block_details.bytes_per_token_block_per_layer();
+        let offset = block.block_id * bytes;
+        let mut data = Vec::with_capacity(bytes * inner.layers.len());
+        for layer in &inner.layers {
+            data.extend_from_slice(&layer[offset..offset + bytes]);
+        }
+        Ok(data)
+    }
+}
+
+impl KvBlockSink for HostKvBlocks {
+    fn block_details(&self) -> &KvBlockDetails {
+        &self.block_details
+    }
+
+    fn write_block(&self, block: &KvBlockDescriptor, data: &[u8]) -> Result<()> {
+        self.check_block_id(block.block_id)?;
+        let bytes = self.block_details.bytes_per_token_block_per_layer();
+        if data.len() != bytes * self.block_details.number_of_layers() {
+            raise!(
+                "expected {} bytes for block {}, got {}",
+                bytes * self.block_details.number_of_layers(),
+                block.block_id,
+                data.len()
+            );
+        }
+
+        let mut inner = self.inner.write().unwrap();
+        let offset = block.block_id * bytes;
+        for (layer, data) in inner.layers.iter_mut().zip(data.chunks_exact(bytes)) {
+            layer[offset..offset + bytes].copy_from_slice(data);
+        }
+        inner.sequence_hashes[block.block_id] = Some(block.sequence_hash);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::details::{KvBlockDetailsBuilder, KvLayout, KvModelDetailsBuilder};
+    use super::super::manager::{KvStorageManager, PrefillOffload};
+    use super::super::reuse::tests::create_token_sequence;
+    use super::super::storage::DType;
+    use super::*;
+
+    const BLOCK_SIZE: usize = 2;
+
+    fn block_details() -> KvBlockDetails {
+        let model_details = KvModelDetailsBuilder::default()
+            .number_of_layers(2)
+            .number_of_heads(2)
+            .head_size(4)
+            .dtype(DType::F16)
+            .build()
+            .unwrap();
+        KvBlockDetailsBuilder::default()
+            .layout(KvLayout::KvFirst)
+            .block_size(BLOCK_SIZE)
+            .model_details(model_details)
+            .build()
+            .unwrap()
+    }
+
+    /// Allocates the blocks of a sequence from a fresh manager with `capacity` blocks
+    async fn allocate(capacity: usize, tokens: &[u32]) -> (KvStorageManager, PrefillOffload) {
+        let mut manager = KvStorageManager::new(BLOCK_SIZE).await;
+        for block_id in 0..capacity {
+            manager
+                .insert_block(KvBlock::with_block_id(block_id))
+                .await
+                .unwrap();
+        }
+
+        let matched = manager
+            .prepare_prefill_sequence(create_token_sequence(tokens))
+            .await
+            .unwrap();
+        let offload = manager.prepare_prefill_offload(matched).await.unwrap();
+        (manager, offload)
+    }
+
+    fn descriptor(offload: &PrefillOffload) -> KvSequenceDescriptor {
+        KvSequenceDescriptor::new(
+            block_details(),
+            offload
+                .complete_prefill_blocks()
+                .iter()
+                .map(|block| &**block),
+        )
+    }
+
+    /// Fills the blocks as a prefill would
+    fn prefill(blocks: &HostKvBlocks, descriptor: &KvSequenceDescriptor) {
+        let bytes = descriptor.block_details.bytes_per_token_block();
+        for (i, block) in descriptor.blocks.iter().enumerate() {
+            blocks
+                .write_block(block, &vec![i as u8 + 1; bytes])
+                .unwrap();
+        }
+    }
+
+    fn assert_same_data(
+        src: &HostKvBlocks,
+        src_descriptor: &KvSequenceDescriptor,
+        dst: &HostKvBlocks,
+        dst_descriptor: &KvSequenceDescriptor,
+    ) {
+        for (src_block, dst_block) in src_descriptor.blocks.iter().zip(&dst_descriptor.blocks) {
+            assert_eq!(
+                src.read_block(src_block).unwrap(),
+                dst.read_block(dst_block).unwrap()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pull_from_prefill_worker() {
+        let tokens = [1, 2, 3, 4, 5, 6, 7];
+
+        // the prefill worker computes the blocks of the sequence
+        let (_prefill_manager, prefill_offload) = allocate(4, &tokens).await;
+        let src = descriptor(&prefill_offload);
+        let prefill_blocks = Arc::new(HostKvBlocks::new(block_details(), 4));
+        prefill(&prefill_blocks, &src);
+        let handler = KvTransferHandler::new().with_source(prefill_blocks.clone());
+
+        // the decode worker allocates its own blocks and pulls into them
+        let (_decode_manager, decode_offload) = allocate(8, &tokens).await;
+        let dst = descriptor(&decode_offload);
+        assert_eq!(dst.blocks.len(), 3);
+        let decode_blocks = HostKvBlocks::new(block_details(), 8);
+        let client = KvTransferClient::new(Arc::new(handler));
+        client
+            .pull(src.clone(), &dst, &decode_blocks)
+            .await
+            .unwrap();
+
+        assert_same_data(&prefill_blocks, &src, &decode_blocks, &dst);
+    }
+
+    #[tokio::test]
+    async fn test_push_to_decode_worker() {
+        let tokens = [1, 2, 3, 4, 5, 6];
+
+        // the decode worker allocates the blocks and hands their descriptor to the prefill worker
+        let (mut decode_manager, decode_offload) = allocate(8, &tokens).await;
+        let dst = descriptor(&decode_offload);
+        let decode_blocks = Arc::new(HostKvBlocks::new(block_details(), 8));
+        let handler = KvTransferHandler::new().with_sink(decode_blocks.clone());
+
+        let (_prefill_manager, prefill_offload) = allocate(4, &tokens).await;
+        let src = descriptor(&prefill_offload);
+        let prefill_blocks = HostKvBlocks::new(block_details(), 4);
+        prefill(&prefill_blocks, &src);
+        let client = KvTransferClient::new(Arc::new(handler));
+        client
+            .push(&src, dst.clone(), &prefill_blocks)
+            .await
+            .unwrap();
+
+        assert_same_data(&prefill_blocks, &src, &decode_blocks, &dst);
+
+        // once the blocks are written, the decode worker registers them for reuse
+        let (_, complete_prefill_blocks, _, _) = decode_offload.dissolve();
+        let _registered = decode_manager
+            .register_blocks(complete_prefill_blocks)
+            .unwrap();
+        let matched = decode_manager
+            .prepare_prefill_sequence(create_token_sequence(&tokens))
+            .await
+            .unwrap();
+        assert_eq!(matched.inflight_blocks().len(), 3);
+    }
+
+    /// Counts the requests forwarded to a handler
+    struct CountingRoute {
+        handler: KvTransferHandler,
+        requests: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl AsyncEngine<SingleIn<KvTransferRequest>, ManyOut<Annotated<KvTransferResponse>>, Error>
+        for CountingRoute
+    {
+        async fn generate(
+            &self,
+            request: SingleIn<KvTransferRequest>,
+        ) -> Result<ManyOut<Annotated<KvTransferResponse>>> {
+            self.requests
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            self.handler.generate(request).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_push_in_chunks() {
+        let tokens = [1, 2, 3, 4, 5, 6];
+        let (_decode_manager, decode_offload) = allocate(8, &tokens).await;
+        let dst = descriptor(&decode_offload);
+        let decode_blocks = Arc::new(HostKvBlocks::new(block_details(), 8));
+        let route = Arc::new(CountingRoute {
+            handler: KvTransferHandler::new().with_sink(decode_blocks.clone()),
+            requests: Default::default(),
+        });
+
+        let (_prefill_manager, prefill_offload) = allocate(4, &tokens).await;
+        let src = descriptor(&prefill_offload);
+        let prefill_blocks = HostKvBlocks::new(block_details(), 4);
+        prefill(&prefill_blocks, &src);
+
+        // two blocks fit in a request, so the three blocks take two requests
+        let bytes_per_block = block_details().bytes_per_token_block();
+        let client = KvTransferClient::new(route.clone()).with_max_push_bytes(2 * bytes_per_block);
+        client
+            .push(&src, dst.clone(), &prefill_blocks)
+            .await
+            .unwrap();
+        assert_eq!(route.requests.load(std::sync::atomic::Ordering::Relaxed), 2);
+        assert_same_data(&prefill_blocks, &src, &decode_blocks, &dst);
+
+        // a block larger than the limit cannot be pushed
+        let client = KvTransferClient::new(route.clone()).with_max_push_bytes(bytes_per_block - 1);
+        assert!(client.push(&src, dst, &prefill_blocks).await.is_err());
+        assert_eq!(route.requests.load(std::sync::atomic::Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_transfer_errors() {
+        let tokens = [1, 2, 3, 4];
+        let (_manager, offload) = allocate(4, &tokens).await;
+        let src = descriptor(&offload);
+        let prefill_blocks = Arc::new(HostKvBlocks::new(block_details(), 4));
+
+        // the blocks were never written, so the source does not hold them
+        let client = KvTransferClient::new(Arc::new(
+            KvTransferHandler::new().with_source(prefill_blocks.clone()),
+        ));
+        let sink = HostKvBlocks::new(block_details(), 4);
+        assert!(client.pull(src.clone(), &src, &sink).await.is_err());
+
+        // the destination must describe the same sequence
+        prefill(&prefill_blocks, &src);
+        let (_manager, offload) = allocate(4, &[5, 6, 7, 8]).await;
+        let other = descriptor(&offload);
+        assert!(client.pull(src.clone(), &other, &sink).await.is_err());
+
+        // the worker serves no sink
+        assert!(client
+            .push(&src, src.clone(), &*prefill_blocks)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_request_serde() {
+        let request = KvTransferRequest::Push {
+            dst: KvSequenceDescriptor {
+                block_details: block_details(),
+                blocks: vec![KvBlockDescriptor {
+                    sequence_hash: 7,
+                    block_id: 1,
+                }],
+            },
+            blocks: vec![KvBlockData {
+                sequence_hash: 7,
+                data: vec![0, 1, 254, 255],
+            }],
+        };
+
+        let json = serde_json::to_string(&request).unwrap();
+        assert!(json.contains("\"AAH+/w==\""));
+        let KvTransferRequest::Push { dst, blocks } = serde_json::from_str(&json).unwrap() else {
+            panic!("expected a push request");
+        };
+        assert!(dst.block_details.is_compatible(&block_details()));
+        assert_eq!(blocks[0].data, vec![0, 1, 254, 255]);
+    }
+}
@@ -17,31 +17,40 @@ use anyhow::Result;
 use dynamo_runtime::{
     component::Component,
     pipeline::{
-        async_trait, AsyncEngine, AsyncEngineContextProvider, Error, ManyOut, ResponseStream,
-        SingleIn,
+        async_trait, network::Ingress, AsyncEngine, AsyncEngineContextProvider, Error, ManyOut,
+        ResponseStream, SingleIn,
     },
     prelude::*,
     protocols::annotated::Annotated,
+    CancellationToken,
 };
 use futures::stream::{self, StreamExt};
+use std::collections::BTreeSet;
 use std::sync::Arc;
+use std::time::Duration;
 
+pub mod approx;
 pub mod indexer;
 pub mod metrics_aggregator;
 pub mod protocols;
 pub mod publisher;
 pub mod scheduler;
 pub mod scoring;
+pub mod simulator;
 
-use crate::{
-    kv_router::{
-        indexer::{KvIndexer, KvIndexerInterface, RouterEvent},
-        metrics_aggregator::KvMetricsAggregator,
-        protocols::{LocalBlockHash, RouterRequest, RouterResponse, WorkerSelectionResult},
-        scheduler::{KvScheduler, KvSchedulerError, SchedulingRequest},
-        scoring::ProcessedEndpoints,
+use crate::kv_router::{
+    approx::{ApproxBlockTracker, ApproxIndexingConfig},
+    indexer::{
+        compute_block_hash_for_request, compute_block_hash_for_seq, KvIndexer, KvIndexerInterface,
+        KvIndexerSharded, RouterEvent,
     },
-    tokens::Tokens,
+    metrics_aggregator::KvMetricsAggregator,
+    protocols::{
+        ForwardPassMetrics, LocalBlockHash, RouterInspectRequest, RouterInspectResponse,
+        RouterRequest, RouterResponse, WorkerRouterState, WorkerSelectionResult,
+    },
+    scheduler::{KvScheduler, KvSchedulerError, SchedulingRequest},
+    scoring::ProcessedEndpoints,
 };
 
 use dynamo_runtime::traits::events::EventSubscriber;
@@ -51,6 +60,7 @@ use dynamo_runtime::traits::events::EventSubscriber;
 pub const KV_EVENT_SUBJECT: &str = "kv_events";
 pub const KV_HIT_RATE_SUBJECT: &str = "kv-hit-rate";
 pub const KV_METRICS_ENDPOINT: &str = "load_metrics";
+pub const KV_INSPECT_ENDPOINT: &str = "inspect";
 
 /// A trait that users can implement to define custom selection logic
 pub trait WorkerSelector {
@@ -62,9 +72,58 @@ pub trait WorkerSelector {
     ) -> Result<WorkerSelectionResult, KvSchedulerError>;
 }
 
+/// The indexer implementation of a [`KvRouter`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KvIndexerKind {
+    /// A single radix tree, see [`KvIndexer`]
+    #[default]
+    Single,
+    /// One radix tree per shard, each holding a subset of the workers, see [`KvIndexerSharded`]
+    Sharded { num_shards: usize },
+}
+
+/// Configuration of a [`KvRouter`]
+#[derive(Debug, Clone, Default)]
+pub struct KvRouterConfig {
+    /// Assume each worker caches the prompts routed to it, for engines which do not publish
+    /// KV cache events
+    pub approximate: Option<ApproxIndexingConfig>,
+    /// The indexer implementation
+    pub indexer: KvIndexerKind,
+    /// Track how often the cached blocks were matched within this duration
+    pub frequency_expiration: Option<Duration>,
+}
+
+impl KvRouterConfig {
+    /// Create the indexer described by this configuration
+    pub fn build_indexer(
+        &self,
+        cancellation_token: CancellationToken,
+        block_size: usize,
+    ) -> Box<dyn KvIndexerInterface + Send + Sync> {
+        match self.indexer {
+            KvIndexerKind::Single => Box::new(KvIndexer::new_with_frequency(
+                cancellation_token,
+                self.frequency_expiration,
+                block_size,
+            )),
+            KvIndexerKind::Sharded { num_shards } => {
+                Box::new(KvIndexerSharded::new_with_frequency(
+                    cancellation_token,
+                    num_shards,
+                    self.frequency_expiration,
+                    block_size,
+                ))
+            }
+        }
+    }
+}
+
 pub struct KvRouter {
-    indexer: KvIndexer,
+    indexer: Box<dyn KvIndexerInterface + Send + Sync>,
     scheduler: KvScheduler,
+    metrics_aggregator: KvMetricsAggregator,
+    approx: Option<ApproxBlockTracker>,
     block_size: usize,
 }
 
@@ -73,12 +132,21 @@ impl KvRouter {
         component: Component,
         block_size: usize,
         selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
+    ) -> Result<Arc<Self>> {
+        Self::new_with_config(component, block_size, selector, KvRouterConfig::default()).await
+    }
+
+    pub async fn new_with_config(
+        component: Component,
+        block_size: usize,
+        selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
+        config: KvRouterConfig,
     ) -> Result<Arc<Self>> {
         let cancellation_token = component.drt().primary_lease().primary_token();
 
         let metrics_aggregator =
             KvMetricsAggregator::new(component.clone(), cancellation_token.clone()).await;
-        let indexer = KvIndexer::new(cancellation_token.clone(), block_size);
+        let indexer = config.build_indexer(cancellation_token.clone(), block_size);
         let scheduler = KvScheduler::start(
             component.namespace().clone(),
             block_size,
@@ -86,12 +154,37 @@ impl KvRouter {
             selector,
         )
         .await?;
+        let approx = config.approximate.map(|approx_config| {
+            ApproxBlockTracker::new(
+                cancellation_token.clone(),
+                approx_config,
+                indexer.event_sender(),
+                metrics_aggregator.endpoints_watcher(),
+            )
+        });
 
         // [gluo TODO] try subscribe_with_type::<RouterEvent>,
         // error checking below will be different.
         let mut kv_events_rx = component.subscribe(KV_EVENT_SUBJECT).await?;
         let kv_events_tx = indexer.event_sender();
 
+        // the workers register their metrics endpoint in etcd under their lease
+        let instances = component
+            .endpoint(KV_METRICS_ENDPOINT)
+            .client::<(), Annotated<ForwardPassMetrics>>()
+            .await?;
+        let mut remove_worker_txs = vec![indexer.remove_worker_sender()];
+        remove_worker_txs.extend(
+            approx
+                .as_ref()
+                .map(ApproxBlockTracker::remove_worker_sender),
+        );
+        tokio::spawn(remove_departed_workers(
+            instances.endpoint_ids().clone(),
+            remove_worker_txs,
+            cancellation_token.clone(),
+        ));
+
         tokio::spawn(async move {
             while let Some(event) = kv_events_rx.next().await {
                 let event: RouterEvent = match serde_json::from_slice(&event.payload) {
@@ -115,23 +208,124 @@ impl KvRouter {
         Ok(Arc::new(Self {
             scheduler,
             indexer,
+            metrics_aggregator,
+            approx,
             block_size,
         }))
     }
 
+    /// Snapshot what the router knows about the workers, and the overlap scores the
+    /// [`RouterInspectRequest::tokens`] would get, without affecting routing
+    pub async fn inspect(&self, request: RouterInspectRequest) -> Result<RouterInspectResponse> {
+        let tree = self.indexer.stats().await?;
+        let endpoints = self.metrics_aggregator.get_endpoints();
+
+        let mut worker_ids: BTreeSet<i64> = tree.worker_blocks.keys().copied().collect();
+        worker_ids.extend(endpoints.endpoints.keys().copied());
+        let workers = worker_ids
+            .into_iter()
+            .map(|worker_id| WorkerRouterState {
+                worker_id,
+                cached_blocks: tree.worker_blocks.get(&worker_id).copied().unwrap_or(0),
+                metrics: endpoints
+                    .endpoints
+                    .get(&worker_id)
+                    .map(|endpoint| endpoint.data.clone()),
+            })
+            .collect();
+
+        let overlap = match request.tokens {
+            Some(tokens) => {
+                let sequence = compute_block_hash_for_seq(&tokens, self.block_size);
+                Some(self.indexer.peek_matches(sequence).await?)
+            }
+            None => None,
+        };
+
+        Ok(RouterInspectResponse {
+            block_size: self.block_size,
+            tree,
+            workers,
+            recent_decisions: self.scheduler.recent_decisions(),
+            overlap,
+        })
+    }
+
+    /// Serve [`KvRouter::inspect`] on the [`KV_INSPECT_ENDPOINT`] of `component`
+    pub async fn create_inspect_endpoint(self: Arc<Self>, component: Component) -> Result<()> {
+        let handler = Ingress::for_engine(Arc::new(KvRouterInspector { router: self }))?;
+        component
+            .endpoint(KV_INSPECT_ENDPOINT)
+            .endpoint_builder()
+            .handler(handler)
+            .start()
+            .await
+    }
+
     // [TODO] indexer needs to take 'lora_id' as parameter
-    pub async fn schedule(&self, token_ids: &Vec<u32>, _lora_id: u64) -> Result<i64> {
+    pub async fn schedule(&self, request: &RouterRequest, _lora_id: u64) -> Result<i64> {
         // Extracting part of the code in KvRouter::generate() for only
         // the decision making part, routing is done by the caller
-        let isl_tokens = token_ids.len();
+        let isl_tokens = request.tokens.len();
+        let local_block_hashes = compute_block_hash_for_request(request, self.block_size);
         let overlap_scores = self
             .indexer
-            .find_matches_for_request(token_ids.as_slice())
+            .find_matches(local_block_hashes.clone())
             .await?;
         tracing::debug!("KV router overlap_scores: {:?}", overlap_scores);
         let worker_id = self.scheduler.schedule(overlap_scores, isl_tokens).await?;
+        self.record_routing_decision(worker_id, local_block_hashes)
+            .await?;
         Ok(worker_id)
     }
+
+    /// In approximate mode, assume the worker will cache the blocks of the request routed to it
+    async fn record_routing_decision(
+        &self,
+        worker_id: i64,
+        local_block_hashes: Vec<LocalBlockHash>,
+    ) -> Result<()> {
+        if let Some(approx) = &self.approx {
+            approx
+                .process_routing_decision(worker_id, local_block_hashes)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Remove the blocks of the workers whose metrics endpoint was removed from etcd, when their lease
+/// expired or was revoked, from the indexer and, in approximate mode, the block tracker
+///
+/// A worker missing a metrics scrape keeps its lease, so its blocks stay in the indexer.
+async fn remove_departed_workers(
+    mut instances_rx: tokio::sync::watch::Receiver<Vec<i64>>,
+    remove_worker_txs: Vec<tokio::sync::mpsc::Sender<i64>>,
+    cancellation_token: CancellationToken,
+) {
+    let mut known: BTreeSet<i64> = BTreeSet::new();
+
+    loop {
+        let current: BTreeSet<i64> = instances_rx.borrow_and_update().iter().copied().collect();
+        for worker_id in known.difference(&current) {
+            tracing::info!(worker_id, "removing departed worker from the kv indexer");
+            for remove_worker_tx in &remove_worker_txs {
+                if remove_worker_tx.send(*worker_id).await.is_err() {
+                    return;
+                }
+            }
+        }
+        known = current;
+
+        tokio::select! {
+            _ = cancellation_token.cancelled() => return,
+            changed = instances_rx.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -146,15 +340,17 @@ impl AsyncEngine<SingleIn<RouterRequest>, ManyOut<Annotated<RouterResponse>>, Er
 
         // Compute the block hashes in a blocking task
         let local_block_hashes: Vec<LocalBlockHash> = tokio::task::spawn_blocking(move || {
-            Tokens::compute_block_hash(&request.tokens, block_size)
-                .into_iter()
-                .map(LocalBlockHash)
-                .collect()
+            compute_block_hash_for_request(&request, block_size)
         })
         .await?;
 
-        let overlap_scores = self.indexer.find_matches(local_block_hashes).await?;
+        let overlap_scores = self
+            .indexer
+            .find_matches(local_block_hashes.clone())
+            .await?;
         let worker_id = self.scheduler.schedule(overlap_scores, isl_tokens).await?;
+        self.record_routing_decision(worker_id, local_block_hashes)
+            .await?;
 
         let response = RouterResponse { worker_id };
         let response = Annotated::from_data(response);
@@ -162,3 +358,22 @@ impl AsyncEngine<SingleIn<RouterRequest>, ManyOut<Annotated<RouterResponse>>, Er
         Ok(ResponseStream::new(Box::pin(stream), ctx.context()))
     }
 }
+
+struct KvRouterInspector {
+    router: Arc<KvRouter>,
+}
+
+#[async_trait]
+impl AsyncEngine<SingleIn<RouterInspectRequest>, ManyOut<Annotated<RouterInspectResponse>>, Error>
+    for KvRouterInspector
+{
+    async fn generate(
+        &self,
+        request: SingleIn<RouterInspectRequest>,
+    ) -> Result<ManyOut<Annotated<RouterInspectResponse>>> {
+        let (request, ctx) = request.into_parts();
+        let response = Annotated::from_data(self.router.inspect(request).await?);
+        let stream = stream::iter(vec![response]);
+        Ok(ResponseStream::new(Box::pin(stream), ctx.context()))
+    }
+}
@@ -0,0 +1,447 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Approximate KV indexing
+//!
+//! Engines which do not publish [`KvCacheEvent`]s give the router no view of their kv caches. In
+//! approximate mode the router assumes instead that a worker caches the prompt of every request
+//! routed to it: the [`ApproxBlockTracker`] synthesizes the [`RouterEvent`]s storing the blocks of
+//! the request for the selected worker, and the events removing them once no request used them
+//! for a while, or once the worker would hold more blocks than its kv cache.
+//!
+//! A block is only ever assumed evicted after its descendants, as a real prefix cache would.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+use std::time::{Duration, Instant};
+
+use tokio::sync::{mpsc, watch};
+use tokio_util::sync::CancellationToken;
+
+use super::indexer::{compute_sequence_hashes, KvRouterError, RouterEvent, WorkerId};
+use super::protocols::{
+    ExternalSequenceBlockHash, KvCacheEvent, KvCacheEventData, KvCacheRemoveData, KvCacheStoreData,
+    KvCacheStoredBlockData, LocalBlockHash,
+};
+use super::scoring::ProcessedEndpoints;
+
+/// Bounds of the period at which the blocks are checked for expiry; a zero TTL expires the blocks
+/// on the next check
+const MIN_EXPIRY_INTERVAL: Duration = Duration::from_millis(10);
+const MAX_EXPIRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Configuration of approximate KV indexing
+#[derive(Debug, Clone, Copy)]
+pub struct ApproxIndexingConfig {
+    /// Time after which a block no request was routed to is assumed evicted
+    pub ttl: Duration,
+
+    /// Number of blocks a worker is assumed to cache; defaults to the `kv_total_blocks` the worker
+    /// reports in its load metrics. The least recently used blocks beyond it are assumed evicted.
+    pub max_blocks_per_worker: Option<u64>,
+}
+
+impl Default for ApproxIndexingConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(120),
+            max_blocks_per_worker: None,
+        }
+    }
+}
+
+/// A request routed to a worker
+struct RoutingDecision {
+    worker_id: WorkerId,
+    local_hashes: Vec<LocalBlockHash>,
+}
+
+/// Feeds an indexer with the blocks the workers are assumed to cache
+pub struct ApproxBlockTracker {
+    decision_tx: mpsc::Sender<RoutingDecision>,
+    remove_worker_tx: mpsc::Sender<WorkerId>,
+}
+
+impl ApproxBlockTracker {
+    /// Start tracking the blocks of the requests routed to the workers, sending the synthesized
+    /// events to an indexer through `event_tx`
+    pub fn new(
+        token: CancellationToken,
+        config: ApproxIndexingConfig,
+        event_tx: mpsc::Sender<RouterEvent>,
+        endpoints_rx: watch::Receiver<ProcessedEndpoints>,
+    ) -> Self {
+        let (decision_tx, mut decision_rx) = mpsc::channel::<RoutingDecision>(1024);
+        let (remove_worker_tx, mut remove_worker_rx) = mpsc::channel::<WorkerId>(16);
+
+        tokio::spawn(async move {
+            let mut blocks = ApproxBlockIndex::new(config);
+            let mut expiry =
+                tokio::time::interval(config.ttl.clamp(MIN_EXPIRY_INTERVAL, MAX_EXPIRY_INTERVAL));
+
+            loop {
+                let events = tokio::select! {
+                    _ = token.cancelled() => {
+                        tracing::debug!("approximate kv block tracker shutting down");
+                        break;
+                    }
+
+                    decision = decision_rx.recv() => {
+                        let Some(decision) = decision else {
+                            break;
+                        };
+                        // the kv capacity the worker reported, if any
+                        let capacity = endpoints_rx
+                            .borrow()
+                            .endpoints
+                            .get(&decision.worker_id)
+                            .map(|endpoint| endpoint.data.kv_total_blocks);
+                        blocks.record(
+                            decision.worker_id,
+                            &decision.local_hashes,
+                            capacity,
+                            Instant::now(),
+                        )
+                    }
+
+                    worker_id = remove_worker_rx.recv() => {
+                        let Some(worker_id) = worker_id else {
+                            break;
+                        };
+                        // the indexer removes the worker's blocks itself
+                        blocks.remove_worker(worker_id);
+                        Vec::new()
+                    }
+
+                    _ = expiry.tick() => blocks.expire(Instant::now()),
+                };
+
+                for event in events {
+                    if event_tx.send(event).await.is_err() {
+                        tracing::trace!("failed to send kv event to indexer; shutting down");
+                        return;
+                    }
+                }
+            }
+        });
+
+        Self {
+            decision_tx,
+            remove_worker_tx,
+        }
+    }
+
+    /// Channel to stop tracking the blocks of a worker, e.g. once it was removed from the indexer
+    pub fn remove_worker_sender(&self) -> mpsc::Sender<WorkerId> {
+        self.remove_worker_tx.clone()
+    }
+
+    /// Record that the request with blocks `local_hashes` was routed to `worker_id`
+    pub async fn process_routing_decision(
+        &self,
+        worker_id: WorkerId,
+        local_hashes: Vec<LocalBlockHash>,
+    ) -> Result<(), KvRouterError> {
+        self.decision_tx
+            .send(RoutingDecision {
+                worker_id,
+                local_hashes,
+            })
+            .await
+            .map_err(|_| KvRouterError::IndexerOffline)
+    }
+}
+
+/// The blocks a worker is assumed to cache
+#[derive(Default)]
+struct WorkerBlocks {
+    /// Last use and depth of each block
+    blocks: HashMap<ExternalSequenceBlockHash, (Instant, usize)>,
+
+    /// Blocks in eviction order: least recently used first, deepest first
+    order: BTreeSet<(Instant, Reverse<usize>, ExternalSequenceBlockHash)>,
+
+    next_event_id: u64,
+}
+
+impl WorkerBlocks {
+    fn event(&mut self, worker_id: WorkerId, data: KvCacheEventData) -> RouterEvent {
+        let event = KvCacheEvent {
+            event_id: self.next_event_id,
+            data,
+        };
+        self.next_event_id += 1;
+        RouterEvent::new(worker_id, event)
+    }
+
+    fn evict_first(&mut self) -> Option<ExternalSequenceBlockHash> {
+        let (_, _, hash) = self.order.pop_first()?;
+        self.blocks.remove(&hash);
+        Some(hash)
+    }
+}
+
+/// The blocks the workers are assumed to cache
+struct ApproxBlockIndex {
+    config: ApproxIndexingConfig,
+    workers: HashMap<WorkerId, WorkerBlocks>,
+}
+
+impl ApproxBlockIndex {
+    fn new(config: ApproxIndexingConfig) -> Self {
+        Self {
+            config,
+            workers: HashMap::new(),
+        }
+    }
+
+    /// Record the blocks of a request routed to `worker_id` at `now`, returning the events
+    /// storing its new blocks and removing the blocks it pushed out of a cache of `capacity`
+    /// blocks. A capacity of zero is unknown, as reported by workers before their first metrics.
+    fn record(
+        &mut self,
+        worker_id: WorkerId,
+        local_hashes: &[LocalBlockHash],
+        capacity: Option<u64>,
+        now: Instant,
+    ) -> Vec<RouterEvent> {
+        let sequence_hashes = compute_sequence_hashes(local_hashes);
+        let worker = self.workers.entry(worker_id).or_default();
+
+        // the cached blocks of a sequence are always a prefix of it, so the new ones are a suffix
+        let mut new_blocks = Vec::new();
+        for (depth, hash) in sequence_hashes.iter().enumerate() {
+            match worker.blocks.insert(*hash, (now, depth)) {
+                Some((last_used, depth)) => {
+                    worker.order.remove(&(last_used, Reverse(depth), *hash));
+                }
+                None => new_blocks.push(KvCacheStoredBlockData {
+                    block_hash: *hash,
+                    tokens_hash: local_hashes[depth],
+                }),
+            }
+            worker.order.insert((now, Reverse(depth), *hash));
+        }
+
+        let mut events = Vec::new();
+        if !new_blocks.is_empty() {
+            let first_new = sequence_hashes.len() - new_blocks.len();
+            let parent_hash = first_new
+                .checked_sub(1)
+                .map(|parent| sequence_hashes[parent]);
+            events.push(worker.event(
+                worker_id,
+                KvCacheEventData::Stored(KvCacheStoreData {
+                    parent_hash,
+                    blocks: new_blocks,
+                }),
+            ));
+        }
+
+        let capacity = capacity.filter(|&capacity| capacity > 0);
+        if let Some(capacity) = self.config.max_blocks_per_worker.or(capacity) {
+            let mut evicted = Vec::new();
+            while worker.blocks.len() as u64 > capacity {
+                evicted.extend(worker.evict_first());
+            }
+            if !evicted.is_empty() {
+                events.push(worker.event(
+                    worker_id,
+                    KvCacheEventData::Removed(KvCacheRemoveData {
+                        block_hashes: evicted,
+                    }),
+                ));
+            }
+        }
+
+        events
+    }
+
+    /// Forget the blocks of a worker which left
+    fn remove_worker(&mut self, worker_id: WorkerId) {
+        self.workers.remove(&worker_id);
+    }
+
+    /// Return the events removing the blocks no request used within the ttl before `now`
+    fn expire(&mut self, now: Instant) -> Vec<RouterEvent> {
+        let ttl = self.config.ttl;
+        let mut events = Vec::new();
+        for (worker_id, worker) in self.workers.iter_mut() {
+            let mut evicted = Vec::new();
+            while let Some((last_used, _, _)) = worker.order.first() {
+                if now.duration_since(*last_used) < ttl {
+                    break;
+                }
+                evicted.extend(worker.evict_first());
+            }
+            if !evicted.is_empty() {
+                events.push(worker.event(
+                    *worker_id,
+                    KvCacheEventData::Removed(KvCacheRemoveData {
+                        block_hashes: evicted,
+                    }),
+                ));
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kv_router::indexer::RadixTree;
+
+    fn hashes(values: &[u64]) -> Vec<LocalBlockHash> {
+        values.iter().copied().map(LocalBlockHash).collect()
+    }
+
+    fn apply(trie: &mut RadixTree, events: Vec<RouterEvent>) {
+        for event in events {
+            trie.apply_event(event);
+        }
+    }
+
+    #[test]
+    fn test_record_shared_prefix() {
+        let mut index = ApproxBlockIndex::new(ApproxIndexingConfig::default());
+        let mut trie = RadixTree::new();
+        let now = Instant::now();
+
+        apply(&mut trie, index.record(0, &hashes(&[1, 2, 3]), None, now));
+        let events = index.record(1, &hashes(&[1, 2]), None, now);
+        assert_eq!(events.len(), 1);
+        apply(&mut trie, events);
+
+        // a known prefix is stored as the parent of the new blocks
+        let events = index.record(0, &hashes(&[1, 2, 4]), None, now);
+        assert_eq!(events.len(), 1);
+        apply(&mut trie, events);
+
+        let scores = trie.find_matches(hashes(&[1, 2, 4]), false).scores;
+        assert_eq!(scores, HashMap::from([(0, 3), (1, 2)]));
+        assert_eq!(trie.stats().worker_blocks[&0], 4);
+        assert!(index.record(0, &hashes(&[1, 2, 3]), None, now).is_empty());
+    }
+
+    #[test]
+    fn test_expire() {
+        let config = ApproxIndexingConfig {
+            ttl: Duration::from_secs(10),
+            max_blocks_per_worker: None,
+        };
+        let mut index = ApproxBlockIndex::new(config);
+        let mut trie = RadixTree::new();
+        let start = Instant::now();
+
+        apply(&mut trie, index.record(0, &hashes(&[1, 2]), None, start));
+        apply(
+            &mut trie,
+            index.record(0, &hashes(&[1, 3]), None, start + Duration::from_secs(5)),
+        );
+
+        assert!(index.expire(start + Duration::from_secs(9)).is_empty());
+
+        // the shared block was used again, so only the other branch expires
+        apply(&mut trie, index.expire(start + Duration::from_secs(10)));
+        let scores = trie.find_matches(hashes(&[1, 2]), false).scores;
+        assert_eq!(scores, HashMap::from([(0, 1)]));
+
+        apply(&mut trie, index.expire(start + Duration::from_secs(15)));
+        assert!(trie.find_matches(hashes(&[1, 3]), false).scores.is_empty());
+        assert_eq!(trie.stats().num_blocks, 0);
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut index = ApproxBlockIndex::new(ApproxIndexingConfig::default());
+        let mut trie = RadixTree::new();
+        let start = Instant::now();
+
+        apply(&mut trie, index.record(0, &hashes(&[1, 2]), Some(3), start));
+        apply(
+            &mut trie,
+            index.record(0, &hashes(&[3, 4]), Some(3), start + Duration::from_secs(1)),
+        );
+
+        // the leaf of the least recently used sequence is evicted first
+        let scores = trie.find_matches(hashes(&[1, 2]), false).scores;
+        assert_eq!(scores, HashMap::from([(0, 1)]));
+        let scores = trie.find_matches(hashes(&[3, 4]), false).scores;
+        assert_eq!(scores, HashMap::from([(0, 2)]));
+
+        // a configured capacity overrides the reported one
+        let config = ApproxIndexingConfig {
+            max_blocks_per_worker: Some(1),
+            ..Default::default()
+        };
+        let mut index = ApproxBlockIndex::new(config);
+        let events = index.record(0, &hashes(&[1, 2]), Some(100), start);
+        assert_eq!(events.len(), 2);
+        assert_eq!(index.workers[&0].blocks.len(), 1);
+
+        // a reported capacity of zero is unknown rather than evicting every block
+        let mut index = ApproxBlockIndex::new(ApproxIndexingConfig::default());
+        let events = index.record(0, &hashes(&[1, 2]), Some(0), start);
+        assert_eq!(events.len(), 1);
+        assert_eq!(index.workers[&0].blocks.len(), 2);
+    }
+
+    #[test]
+    fn test_remove_worker() {
+        let mut index = ApproxBlockIndex::new(ApproxIndexingConfig::default());
+        let start = Instant::now();
+        index.record(0, &hashes(&[1, 2]), None, start);
+        index.record(1, &hashes(&[1]), None, start);
+
+        index.remove_worker(0);
+        assert!(!index.workers.contains_key(&0));
+        assert!(index.workers.contains_key(&1));
+
+        // blocks routed to a worker with the same id again are all new
+        let mut trie = RadixTree::new();
+        apply(&mut trie, index.record(0, &hashes(&[1, 2]), None, start));
+        let scores = trie.find_matches(hashes(&[1, 2]), false).scores;
+        assert_eq!(scores, HashMap::from([(0, 2)]));
+    }
+
+    #[tokio::test]
+    async fn test_zero_ttl() {
+        let config = ApproxIndexingConfig {
+            ttl: Duration::ZERO,
+            max_blocks_per_worker: None,
+        };
+        let (event_tx, mut event_rx) = mpsc::channel(16);
+        let (_endpoints_tx, endpoints_rx) = watch::channel(ProcessedEndpoints::default());
+        let tracker =
+            ApproxBlockTracker::new(CancellationToken::new(), config, event_tx, endpoints_rx);
+
+        tracker
+            .process_routing_decision(0, hashes(&[1]))
+            .await
+            .unwrap();
+
+        // the block is stored, then expired on the next check
+        let mut trie = RadixTree::new();
+        for _ in 0..2 {
+            let event = tokio::time::timeout(Duration::from_secs(1), event_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            trie.apply_event(event);
+        }
+        assert_eq!(trie.stats().num_blocks, 0);
+    }
+}
@@ -49,8 +49,7 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::{
     cell::RefCell,
-    collections::{HashMap, HashSet, VecDeque},
-    iter,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     rc::Rc,
     sync::OnceLock,
     thread::JoinHandle,
@@ -64,6 +63,7 @@ use xxhash_rust::xxh3;
 pub const XXH3_SEED: u64 = 1337;
 
 use crate::kv_router::protocols::*;
+use crate::tokens::Tokens;
 
 /// Errors that can occur in the KV Router.
 #[derive(Debug, thiserror::Error)]
@@ -134,6 +134,92 @@ pub fn compute_block_hash_for_seq(tokens: &[u32], kv_block_size: usize) -> Vec<L
         .collect()
 }
 
+/// Compute the hash for a sequence of tokens, mixing each [`BlockExtraKey`] into the hashes of the
+/// blocks it overlaps.
+///
+/// The hashes of blocks without extra keys equal those of [`compute_block_hash_for_seq`].
+///
+/// ### Arguments
+///
+/// * `tokens` - A vector of `u32` tokens.
+/// * `extra_keys` - The keys of the media of the sequence.
+///
+/// ### Returns
+///
+/// A vector of `LocalBlockHash` representing the computed hashes for each chunk of tokens.
+pub fn compute_block_hash_for_seq_with_extra_keys(
+    tokens: &[u32],
+    kv_block_size: usize,
+    extra_keys: &[BlockExtraKey],
+) -> Vec<LocalBlockHash> {
+    tokens
+        .chunks_exact(kv_block_size)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let start = index * kv_block_size;
+            let end = start + kv_block_size;
+
+            let mut bytes: Vec<u8> = chunk.iter().flat_map(|&num| num.to_le_bytes()).collect();
+            for key in extra_keys
+                .iter()
+                .filter(|key| key.offset < end && key.offset.saturating_add(key.length) > start)
+            {
+                bytes.extend_from_slice(&key.hash.to_le_bytes());
+            }
+
+            compute_block_hash(&bytes)
+        })
+        .collect()
+}
+
+/// Compute the hash for the prompt of a [`RouterRequest`], mixing in the keys of its media.
+pub fn compute_block_hash_for_request(
+    request: &RouterRequest,
+    kv_block_size: usize,
+) -> Vec<LocalBlockHash> {
+    if request.extra_keys.is_empty() {
+        // long prompts are hashed in parallel
+        Tokens::compute_block_hash(&request.tokens, kv_block_size)
+            .into_iter()
+            .map(LocalBlockHash)
+            .collect()
+    } else {
+        compute_block_hash_for_seq_with_extra_keys(
+            &request.tokens,
+            kv_block_size,
+            &request.extra_keys,
+        )
+    }
+}
+
+/// Compute sequence hashes for a sequence of [`LocalBlockHash`]es, chaining the hash of each
+/// block with the hash of its prefix, for callers which synthesize [`KvCacheEvent`]s rather than
+/// receive them from a worker.
+///
+/// ### Arguments
+///
+/// * `local_hashes` - The hashes of the blocks of the sequence.
+///
+/// ### Returns
+///
+/// A vector of `ExternalSequenceBlockHash` identifying each block and its prefix.
+pub fn compute_sequence_hashes(local_hashes: &[LocalBlockHash]) -> Vec<ExternalSequenceBlockHash> {
+    let mut parent: Option<u64> = None;
+    local_hashes
+        .iter()
+        .map(|local| {
+            let hash = match parent {
+                None => local.0,
+                Some(parent) => {
+                    compute_hash(&[parent.to_le_bytes(), local.0.to_le_bytes()].concat())
+                }
+            };
+            parent = Some(hash);
+            ExternalSequenceBlockHash(hash)
+        })
+        .collect()
+}
+
 /// A [`KvCacheEvent`] on a specific LLM worker denoted by [`WorkerId`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RouterEvent {
@@ -167,23 +253,63 @@ struct RadixBlock {
     workers: HashSet<WorkerId>,
     /// A buffer of times that this block was last traversed
     recent_uses: VecDeque<Instant>,
+    /// The depth of this block in the tree; the root is at depth 0.
+    depth: usize,
 }
 
 impl RadixBlock {
     /// Create a new `RadixBlock`.
     ///
+    /// ### Arguments
+    ///
+    /// * `depth` - The depth of the block in the tree.
+    ///
     /// ### Returns
     ///
     /// A new `RadixBlock`.
-    pub fn new() -> Self {
+    pub fn new(depth: usize) -> Self {
         Self {
             children: HashMap::new(),
             workers: HashSet::new(),
             recent_uses: VecDeque::new(),
+            depth,
         }
     }
 }
 
+/// Counts of the blocks cached by at least one worker, kept up to date as events are applied so
+/// that [`RadixTree::stats`] does not have to walk the tree.
+#[derive(Debug, Default)]
+struct BlockCounts {
+    /// The number of cached blocks at each depth.
+    depths: BTreeMap<usize, usize>,
+}
+
+impl BlockCounts {
+    /// Record that a block at `depth` gained its first worker.
+    fn cached(&mut self, depth: usize) {
+        *self.depths.entry(depth).or_default() += 1;
+    }
+
+    /// Record that a block at `depth` lost its last worker.
+    fn evicted(&mut self, depth: usize) {
+        if let Some(count) = self.depths.get_mut(&depth) {
+            *count -= 1;
+            if *count == 0 {
+                self.depths.remove(&depth);
+            }
+        }
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.depths.values().sum()
+    }
+
+    fn max_depth(&self) -> usize {
+        self.depths.keys().next_back().copied().unwrap_or_default()
+    }
+}
+
 pub struct RadixTree {
     /// This is the root of the radix/prefix tree
     /// This will only contain root blocks
@@ -200,6 +326,8 @@ pub struct RadixTree {
     lookup: HashMap<WorkerId, HashMap<ExternalSequenceBlockHash, SharedRadixBlock>>,
     /// The time buffer the radix tree should check when considering frequence of block accesses
     expiration_duration: Option<Duration>,
+    /// The blocks cached by at least one worker, by depth
+    counts: BlockCounts,
 }
 
 impl Default for RadixTree {
@@ -216,9 +344,10 @@ impl RadixTree {
     /// A new `RadixTree`.
     pub fn new_with_frequency(expiration_duration: Option<Duration>) -> Self {
         Self {
-            root: Rc::new(RefCell::new(RadixBlock::new())),
+            root: Rc::new(RefCell::new(RadixBlock::new(0))),
             lookup: HashMap::new(),
             expiration_duration,
+            counts: BlockCounts::default(),
         }
     }
 
@@ -276,6 +405,62 @@ impl RadixTree {
         scores
     }
 
+    /// Traverse the radix tree like [`RadixTree::find_matches`], without recording the traversal
+    /// in the block access frequencies, so the lookup does not affect routing.
+    ///
+    /// ### Arguments
+    ///
+    /// * `sequence` - A vector of `LocalBlockHash` representing the sequence to match.
+    ///
+    /// ### Returns
+    ///
+    /// An `OverlapScores` representing the match scores.
+    pub fn peek_matches(&self, sequence: Vec<LocalBlockHash>) -> OverlapScores {
+        let mut scores = OverlapScores::new();
+        let mut current = self.root.clone();
+        let now = Instant::now();
+        for block_hash in sequence {
+            let next_block = current.borrow().children.get(&block_hash).cloned();
+            let Some(block) = next_block else {
+                break;
+            };
+
+            scores.update_scores(&block.borrow().workers);
+            if let Some(expiration_duration) = self.expiration_duration {
+                let recent_uses = block
+                    .borrow()
+                    .recent_uses
+                    .iter()
+                    .filter(|access_time| now.duration_since(**access_time) <= expiration_duration)
+                    .count();
+                scores.add_frequency(recent_uses);
+            }
+
+            current = block;
+        }
+
+        scores
+    }
+
+    /// Report the size of the radix tree and the number of blocks cached by each worker.
+    ///
+    /// The block counts are maintained as events are applied, so this does not walk the tree.
+    ///
+    /// ### Returns
+    ///
+    /// A `RadixTreeStats` describing the tree.
+    pub fn stats(&self) -> RadixTreeStats {
+        RadixTreeStats {
+            num_blocks: self.counts.num_blocks(),
+            max_depth: self.counts.max_depth(),
+            worker_blocks: self
+                .lookup
+                .iter()
+                .map(|(worker_id, blocks)| (*worker_id, blocks.len()))
+                .collect(),
+        }
+    }
+
     /// Apply a [`RouterEvent`] to the radix tree.
     ///
     /// ### Arguments
@@ -313,6 +498,7 @@ impl RadixTree {
 
                 for block_id in op.blocks {
                     let mut inner = current.borrow_mut();
+                    let depth = inner.depth + 1;
                     let block = match inner.children.get(&block_id.tokens_hash) {
                         Some(block) => block.clone(),
                         None => {
@@ -320,7 +506,7 @@ impl RadixTree {
                             let new_block = worker_lookup
                                 .get(&block_id.block_hash)
                                 .cloned()
-                                .unwrap_or_else(|| Rc::new(RefCell::new(RadixBlock::new())));
+                                .unwrap_or_else(|| Rc::new(RefCell::new(RadixBlock::new(depth))));
 
                             // insert into radix tree
                             inner
@@ -332,7 +518,12 @@ impl RadixTree {
                     };
 
                     // add our worker_id to the block
-                    block.borrow_mut().workers.insert(worker_id);
+                    {
+                        let mut guard = block.borrow_mut();
+                        if guard.workers.insert(worker_id) && guard.workers.len() == 1 {
+                            self.counts.cached(guard.depth);
+                        }
+                    }
 
                     // add the block to the worker_id lookup table
                     worker_lookup.insert(block_id.block_hash, block.clone());
@@ -365,7 +556,9 @@ impl RadixTree {
                     };
 
                     let mut guard = entry.borrow_mut();
-                    guard.workers.remove(&worker_id);
+                    if guard.workers.remove(&worker_id) && guard.workers.is_empty() {
+                        self.counts.evicted(guard.depth);
+                    }
                     if guard.workers.is_empty() {
                         // if no worker are using this block, that is true for all children
                         guard.children.clear();
@@ -380,10 +573,80 @@ impl RadixTree {
     pub fn remove_worker(&mut self, worker: WorkerId) {
         if let Some((_, blocks)) = self.lookup.remove_entry(&worker) {
             blocks.iter().for_each(|(_, block)| {
-                block.borrow_mut().workers.remove(&worker);
+                let mut guard = block.borrow_mut();
+                if guard.workers.remove(&worker) && guard.workers.is_empty() {
+                    self.counts.evicted(guard.depth);
+                }
             });
         }
     }
+
+    /// Build the events which store the blocks of a worker in another radix tree, parents first.
+    ///
+    /// Blocks whose parent the worker no longer holds cannot be stored, and are left out.
+    ///
+    /// ### Arguments
+    ///
+    /// * `worker` - The worker whose blocks to store.
+    ///
+    /// ### Returns
+    ///
+    /// A vector of `RouterEvent`s storing the blocks of the worker.
+    pub fn dump_worker(&self, worker: WorkerId) -> Vec<RouterEvent> {
+        let Some(blocks) = self.lookup.get(&worker) else {
+            return Vec::new();
+        };
+        let block_hashes: HashMap<*const RefCell<RadixBlock>, ExternalSequenceBlockHash> = blocks
+            .iter()
+            .map(|(block_hash, block)| (Rc::as_ptr(block), *block_hash))
+            .collect();
+
+        let mut events = Vec::new();
+        let mut stack: Vec<(SharedRadixBlock, Option<ExternalSequenceBlockHash>)> =
+            vec![(self.root.clone(), None)];
+        while let Some((block, parent_hash)) = stack.pop() {
+            for (tokens_hash, child) in block.borrow().children.iter() {
+                let Some(block_hash) = block_hashes.get(&Rc::as_ptr(child)) else {
+                    continue;
+                };
+                let event = KvCacheEvent {
+                    event_id: events.len() as u64,
+                    data: KvCacheEventData::Stored(KvCacheStoreData {
+                        parent_hash,
+                        blocks: vec![KvCacheStoredBlockData {
+                            block_hash: *block_hash,
+                            tokens_hash: *tokens_hash,
+                        }],
+                    }),
+                };
+                events.push(RouterEvent::new(worker, event));
+                stack.push((child.clone(), Some(*block_hash)));
+            }
+        }
+
+        events
+    }
+}
+
+/// Size of a [`RadixTree`] and the number of blocks cached by each worker.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RadixTreeStats {
+    /// Number of blocks cached by at least one worker
+    pub num_blocks: usize,
+    /// Length in blocks of the longest cached sequence
+    pub max_depth: usize,
+    /// Number of blocks cached by each worker
+    pub worker_blocks: HashMap<WorkerId, usize>,
+}
+
+impl RadixTreeStats {
+    /// Combine the stats of the trees of two shards; a block cached by workers of several shards
+    /// is counted once per shard.
+    fn merge(&mut self, other: RadixTreeStats) {
+        self.num_blocks += other.num_blocks;
+        self.max_depth = self.max_depth.max(other.max_depth);
+        self.worker_blocks.extend(other.worker_blocks);
+    }
 }
 
 /// Scores representing the overlap of workers.
@@ -435,6 +698,44 @@ impl OverlapScores {
             self.frequencies.push(frequency);
         }
     }
+
+    /// Combine the scores of the trees of two shards, which track disjoint sets of workers.
+    fn merge(&mut self, other: OverlapScores) {
+        self.scores.extend(other.scores);
+
+        if other.frequencies.len() > self.frequencies.len() {
+            self.frequencies.resize(other.frequencies.len(), 0);
+        }
+        for (frequency, other) in self.frequencies.iter_mut().zip(other.frequencies) {
+            *frequency += other;
+        }
+    }
+}
+
+/// A request to inspect the Radix Tree without affecting routing.
+enum InspectRequest {
+    /// Find matches without recording them in the block access frequencies.
+    PeekMatches {
+        sequence: Vec<LocalBlockHash>,
+        resp: oneshot::Sender<OverlapScores>,
+    },
+    /// Compute the size of the tree.
+    Stats {
+        resp: oneshot::Sender<RadixTreeStats>,
+    },
+}
+
+impl InspectRequest {
+    fn handle(self, trie: &RadixTree) {
+        match self {
+            InspectRequest::PeekMatches { sequence, resp } => {
+                let _ = resp.send(trie.peek_matches(sequence));
+            }
+            InspectRequest::Stats { resp } => {
+                let _ = resp.send(trie.stats());
+            }
+        }
+    }
 }
 
 /// A request to find matches in the Radix Tree.
@@ -463,20 +764,38 @@ pub trait KvIndexerInterface {
         sequence: Vec<LocalBlockHash>,
     ) -> Result<OverlapScores, KvRouterError>;
 
-    /// Find matches for a given sequence of tokens.
+    /// Find matches for the prompt of a request.
     ///
     /// ### Arguments
     ///
-    /// * `tokens` - A vector of `u32` tokens.
+    /// * `request` - The request, whose media keys are mixed into the block hashes.
     ///
     /// ### Returns
     ///
     /// An `OverlapScores` representing the match scores.
     async fn find_matches_for_request(
         &self,
-        tokens: &[u32],
+        request: &RouterRequest,
+    ) -> Result<OverlapScores, KvRouterError>;
+
+    /// Find matches like [`KvIndexerInterface::find_matches`], without recording the lookup in
+    /// the block access frequencies.
+    ///
+    /// ### Arguments
+    ///
+    /// * `sequence` - A vector of `LocalBlockHash` representing the sequence to match.
+    ///
+    /// ### Returns
+    ///
+    /// An `OverlapScores` representing the match scores.
+    async fn peek_matches(
+        &self,
+        sequence: Vec<LocalBlockHash>,
     ) -> Result<OverlapScores, KvRouterError>;
 
+    /// Get the size of the index and the number of blocks cached by each worker.
+    async fn stats(&self) -> Result<RadixTreeStats, KvRouterError>;
+
     /// Apply a `RouterEvent` to the KV store.
     ///
     /// ### Arguments
@@ -491,6 +810,21 @@ pub trait KvIndexerInterface {
     /// * `worker` - The worker to remove from the trie.
     async fn remove_worker(&mut self, worker: WorkerId);
 
+    /// Get a sender for `RouterEvent`s, to apply events without exclusive access to the indexer.
+    ///
+    /// ### Returns
+    ///
+    /// A `mpsc::Sender` for `RouterEvent`s.
+    fn event_sender(&self) -> mpsc::Sender<RouterEvent>;
+
+    /// Get a sender for the workers to remove, to remove them without exclusive access to the
+    /// indexer.
+    ///
+    /// ### Returns
+    ///
+    /// A `mpsc::Sender` for `WorkerId`s.
+    fn remove_worker_sender(&self) -> mpsc::Sender<WorkerId>;
+
     /// Shutdown the KV Indexer.
     fn shutdown(&mut self);
 }
@@ -505,6 +839,8 @@ pub struct KvIndexer {
     match_tx: mpsc::Sender<MatchRequest>,
     /// A sender for remove worker requests.
     remove_worker_tx: mpsc::Sender<WorkerId>,
+    /// A sender for `InspectRequest`s.
+    inspect_tx: mpsc::Sender<InspectRequest>,
     /// A handle to the background task managing the KV store.
     task: OnceLock<std::thread::JoinHandle<()>>,
     /// The size of the KV block this indexer can handle.
@@ -530,6 +866,7 @@ impl KvIndexer {
         let (event_tx, event_rx) = mpsc::channel::<RouterEvent>(2048);
         let (match_tx, match_rx) = mpsc::channel::<MatchRequest>(128);
         let (remove_worker_tx, remove_worker_rx) = mpsc::channel::<WorkerId>(16);
+        let (inspect_tx, inspect_rx) = mpsc::channel::<InspectRequest>(16);
         let cancel_clone = token.clone();
         let task = std::thread::spawn(move || {
             // create a new tokio runtime which will only perform work on a single thread
@@ -547,6 +884,7 @@ impl KvIndexer {
                     let mut match_rx = match_rx;
                     let mut event_rx = event_rx;
                     let mut remove_worker_rx = remove_worker_rx;
+                    let mut inspect_rx = inspect_rx;
                     let mut trie = RadixTree::new_with_frequency(expiration_duration);
                     loop {
                         tokio::select! {
@@ -561,6 +899,10 @@ impl KvIndexer {
                                 let _ = req.resp.send(matches);
                             }
 
+                            Some(req) = inspect_rx.recv() => {
+                                req.handle(&trie);
+                            }
+
                             _ = cancel.cancelled() => {
                                 log::debug!("KvCacheIndexer progress loop shutting down");
                                 return;
@@ -587,6 +929,7 @@ impl KvIndexer {
             event_tx,
             match_tx,
             remove_worker_tx,
+            inspect_tx,
             task: once,
             kv_block_size,
         }
@@ -599,15 +942,6 @@ impl KvIndexer {
     pub fn new(token: CancellationToken, kv_block_size: usize) -> Self {
         Self::new_with_frequency(token, None, kv_block_size)
     }
-
-    /// Get a sender for `RouterEvent`s.
-    ///
-    /// ### Returns
-    ///
-    /// A `mpsc::Sender` for `RouterEvent`s.
-    pub fn event_sender(&self) -> mpsc::Sender<RouterEvent> {
-        self.event_tx.clone()
-    }
 }
 
 #[async_trait]
@@ -638,18 +972,48 @@ impl KvIndexerInterface for KvIndexer {
 
     async fn find_matches_for_request(
         &self,
-        tokens: &[u32],
+        request: &RouterRequest,
     ) -> Result<OverlapScores, KvRouterError> {
         log::debug!(
             "Finding matches for request tokens: {:?} / len: {}",
-            tokens,
-            tokens.len()
+            request.tokens,
+            request.tokens.len()
         );
-        let sequence = compute_block_hash_for_seq(tokens, self.kv_block_size);
+        let sequence = compute_block_hash_for_request(request, self.kv_block_size);
         log::debug!("Computed sequence: {:?}", sequence);
         self.find_matches(sequence).await
     }
 
+    async fn peek_matches(
+        &self,
+        sequence: Vec<LocalBlockHash>,
+    ) -> Result<OverlapScores, KvRouterError> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.inspect_tx
+            .send(InspectRequest::PeekMatches {
+                sequence,
+                resp: resp_tx,
+            })
+            .await
+            .map_err(|_| KvRouterError::IndexerOffline)?;
+
+        resp_rx
+            .await
+            .map_err(|_| KvRouterError::IndexerDroppedRequest)
+    }
+
+    async fn stats(&self) -> Result<RadixTreeStats, KvRouterError> {
+        let (resp_tx, resp_rx) = oneshot::channel();
+        self.inspect_tx
+            .send(InspectRequest::Stats { resp: resp_tx })
+            .await
+            .map_err(|_| KvRouterError::IndexerOffline)?;
+
+        resp_rx
+            .await
+            .map_err(|_| KvRouterError::IndexerDroppedRequest)
+    }
+
     async fn apply_event(&mut self, event: RouterEvent) {
         self.event_tx.send(event).await.unwrap();
     }
@@ -658,6 +1022,14 @@ impl KvIndexerInterface for KvIndexer {
         self.remove_worker_tx.send(worker).await.unwrap();
     }
 
+    fn event_sender(&self) -> mpsc::Sender<RouterEvent> {
+        self.event_tx.clone()
+    }
+
+    fn remove_worker_sender(&self) -> mpsc::Sender<WorkerId> {
+        self.remove_worker_tx.clone()
+    }
+
     fn shutdown(&mut self) {
         self.cancel.cancel();
         if let Some(task) = self.task.take() {
@@ -673,18 +1045,152 @@ pub struct ShardedMatchRequest {
     resp: mpsc::Sender<OverlapScores>,
 }
 
+/// A message to a shard of a [`KvIndexerSharded`], handled in order with the events of its
+/// workers.
+enum ShardEvent {
+    /// Apply a `RouterEvent` to the shard's trie.
+    Apply(RouterEvent),
+    /// Remove a worker from the shard's trie.
+    Remove(WorkerId),
+    /// Remove a worker from the shard's trie, responding with the events which store its blocks
+    /// in another shard.
+    Migrate {
+        worker: WorkerId,
+        resp: oneshot::Sender<Vec<RouterEvent>>,
+    },
+}
+
+/// Assigns the workers of a [`KvIndexerSharded`] to its shards and forwards their events.
+///
+/// A new worker is assigned to the shard with the fewest workers. When removing workers leaves
+/// the shards unbalanced, workers are migrated from the shards with the most workers.
+struct ShardDispatcher {
+    worker_assignments: HashMap<WorkerId, usize>,
+    worker_counts: Vec<usize>,
+    event_tx: Vec<mpsc::Sender<ShardEvent>>,
+}
+
+impl ShardDispatcher {
+    async fn apply_event(&mut self, event: RouterEvent) {
+        #[allow(clippy::map_entry)]
+        if !self.worker_assignments.contains_key(&event.worker_id) {
+            // Get the shard with the smallest amount of workers.
+            let selected_shard = self
+                .worker_counts
+                .iter()
+                .enumerate()
+                .min_by_key(|&(_, value)| value)
+                .unwrap()
+                .0;
+
+            self.worker_assignments
+                .insert(event.worker_id, selected_shard);
+            self.worker_counts[selected_shard] += 1;
+        }
+
+        let shard = self.worker_assignments[&event.worker_id];
+        if self.event_tx[shard]
+            .send(ShardEvent::Apply(event))
+            .await
+            .is_err()
+        {
+            log::trace!(
+                "Failed to send event to shard {}; the indexer is shutting down",
+                shard
+            );
+        }
+    }
+
+    async fn remove_worker(&mut self, worker: WorkerId) {
+        if let Some((_, shard)) = self.worker_assignments.remove_entry(&worker) {
+            self.worker_counts[shard] -= 1;
+            if self.event_tx[shard]
+                .send(ShardEvent::Remove(worker))
+                .await
+                .is_err()
+            {
+                return;
+            }
+            self.rebalance().await;
+        }
+    }
+
+    /// Migrate workers until the worker counts of the shards differ by at most one. The blocks of
+    /// a migrating worker are missing from the matches until its target shard applied them.
+    async fn rebalance(&mut self) {
+        loop {
+            let (from, to) = {
+                let counts = self.worker_counts.iter().enumerate();
+                let (from, max) = counts.clone().max_by_key(|&(_, count)| count).unwrap();
+                let (to, min) = counts.min_by_key(|&(_, count)| count).unwrap();
+                if max - min <= 1 {
+                    return;
+                }
+                (from, to)
+            };
+
+            let Some(worker) = self
+                .worker_assignments
+                .iter()
+                .filter(|&(_, shard)| *shard == from)
+                .map(|(worker, _)| *worker)
+                .min()
+            else {
+                return;
+            };
+
+            let (resp_tx, resp_rx) = oneshot::channel();
+            if self.event_tx[from]
+                .send(ShardEvent::Migrate {
+                    worker,
+                    resp: resp_tx,
+                })
+                .await
+                .is_err()
+            {
+                return;
+            }
+            let Ok(events) = resp_rx.await else {
+                return;
+            };
+            log::debug!(
+                worker_id = worker.to_string(),
+                from,
+                to,
+                blocks = events.len(),
+                "Migrating worker between indexer shards"
+            );
+
+            self.worker_assignments.insert(worker, to);
+            self.worker_counts[from] -= 1;
+            self.worker_counts[to] += 1;
+            for event in events {
+                if self.event_tx[to]
+                    .send(ShardEvent::Apply(event))
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        }
+    }
+}
+
 /// The KV Indexer, managing the KV store and handling events and match requests.
 pub struct KvIndexerSharded {
     /// A `CancellationToken` for managing shutdown.
     cancel: CancellationToken,
     /// The size of the KV block this indexer can handle.
     kv_block_size: usize,
-    worker_assignments: HashMap<WorkerId, usize>,
-    worker_counts: Vec<usize>,
+    num_shards: usize,
 
-    event_tx: Vec<mpsc::Sender<RouterEvent>>,
+    /// A sender for `RouterEvent`s to the `ShardDispatcher`.
+    event_tx: mpsc::Sender<RouterEvent>,
     request_broadcast_tx: broadcast::Sender<ShardedMatchRequest>,
-    remove_worker_tx: Vec<mpsc::Sender<WorkerId>>,
+    /// A sender for remove worker requests to the `ShardDispatcher`.
+    remove_worker_tx: mpsc::Sender<WorkerId>,
+    inspect_tx: Vec<mpsc::Sender<InspectRequest>>,
     tasks: Vec<JoinHandle<()>>,
 }
 
@@ -706,24 +1212,22 @@ impl KvIndexerSharded {
         expiration_duration: Option<Duration>,
         kv_block_size: usize,
     ) -> Self {
-        let worker_assignments: HashMap<WorkerId, usize> = HashMap::new();
-        let worker_counts: Vec<usize> = vec![0; num_shards];
+        assert!(num_shards > 0, "a sharded indexer needs at least one shard");
 
-        let mut event_tx = Vec::new();
-        let mut remove_worker_tx = Vec::new();
+        let mut shard_event_tx = Vec::new();
+        let mut inspect_tx = Vec::new();
         let mut tasks = Vec::new();
 
         let (request_broadcast_tx, _) = broadcast::channel::<ShardedMatchRequest>(1048576);
 
         for _ in 0..num_shards {
-            let (shard_event_tx, mut shard_event_rx) = mpsc::channel::<RouterEvent>(2048);
-            let (shard_remove_worker_tx, mut shard_remove_worker_rx) =
-                mpsc::channel::<WorkerId>(16);
+            let (event_tx, mut shard_event_rx) = mpsc::channel::<ShardEvent>(2048);
+            let (shard_inspect_tx, mut shard_inspect_rx) = mpsc::channel::<InspectRequest>(16);
             let mut shard_broadcast_rx = request_broadcast_tx.subscribe();
             let cancel = token.clone();
 
-            event_tx.push(shard_event_tx);
-            remove_worker_tx.push(shard_remove_worker_tx);
+            shard_event_tx.push(event_tx);
+            inspect_tx.push(shard_inspect_tx);
 
             let runtime = tokio::runtime::Builder::new_multi_thread()
                 .worker_threads(1)
@@ -741,10 +1245,6 @@ impl KvIndexerSharded {
                             tokio::select! {
                                 biased;
 
-                                Some(worker) = shard_remove_worker_rx.recv() => {
-                                    trie.remove_worker(worker);
-                                }
-
                                 Ok(req) = shard_broadcast_rx.recv() => {
                                     let matches = trie.find_matches(req.sequence, req.early_exit);
                                     if let Err(e) = req.resp.send(matches).await {
@@ -752,14 +1252,24 @@ impl KvIndexerSharded {
                                     }
                                 }
 
+                                Some(req) = shard_inspect_rx.recv() => {
+                                    req.handle(&trie);
+                                }
+
                                 _ = cancel.cancelled() => {
                                     log::debug!("KvCacheIndexer progress loop shutting down");
                                     return;
                                 }
 
-                                Some(event) = shard_event_rx.recv() => {
-                                    trie.apply_event(event);
-                                }
+                                Some(event) = shard_event_rx.recv() => match event {
+                                    ShardEvent::Apply(event) => trie.apply_event(event),
+                                    ShardEvent::Remove(worker) => trie.remove_worker(worker),
+                                    ShardEvent::Migrate { worker, resp } => {
+                                        let events = trie.dump_worker(worker);
+                                        trie.remove_worker(worker);
+                                        let _ = resp.send(events);
+                                    }
+                                },
                             }
                         }
                     })
@@ -771,14 +1281,54 @@ impl KvIndexerSharded {
             }));
         }
 
+        let (event_tx, mut event_rx) = mpsc::channel::<RouterEvent>(2048);
+        let (remove_worker_tx, mut remove_worker_rx) = mpsc::channel::<WorkerId>(16);
+        let mut dispatcher = ShardDispatcher {
+            worker_assignments: HashMap::new(),
+            worker_counts: vec![0; num_shards],
+            event_tx: shard_event_tx,
+        };
+        let cancel = token.clone();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        tasks.push(std::thread::spawn(move || {
+            runtime.block_on(async move {
+                loop {
+                    tokio::select! {
+                        biased;
+
+                        Some(worker) = remove_worker_rx.recv() => {
+                            // apply the events received before the removal first
+                            while let Ok(event) = event_rx.try_recv() {
+                                dispatcher.apply_event(event).await;
+                            }
+                            dispatcher.remove_worker(worker).await;
+                        }
+
+                        _ = cancel.cancelled() => {
+                            log::debug!("KvIndexerSharded dispatcher shutting down");
+                            return;
+                        }
+
+                        Some(event) = event_rx.recv() => {
+                            dispatcher.apply_event(event).await;
+                        }
+                    }
+                }
+            });
+        }));
+
         Self {
             cancel: token,
             kv_block_size,
-            worker_assignments,
-            worker_counts,
+            num_shards,
             event_tx,
             request_broadcast_tx,
             remove_worker_tx,
+            inspect_tx,
             tasks,
         }
     }
@@ -799,7 +1349,7 @@ impl KvIndexerInterface for KvIndexerSharded {
         sequence: Vec<LocalBlockHash>,
     ) -> Result<OverlapScores, KvRouterError> {
         'match_loop: loop {
-            let (match_tx, mut match_rx) = mpsc::channel(self.event_tx.len());
+            let (match_tx, mut match_rx) = mpsc::channel(self.num_shards);
             self.request_broadcast_tx
                 .send(ShardedMatchRequest {
                     sequence: sequence.clone(),
@@ -810,28 +1360,9 @@ impl KvIndexerInterface for KvIndexerSharded {
 
             let mut scores = OverlapScores::new();
 
-            for response_num in 0..self.event_tx.len() {
+            for _ in 0..self.num_shards {
                 match match_rx.recv().await {
-                    Some(response) => {
-                        scores.scores.extend(response.scores);
-
-                        if response_num == 0 {
-                            scores.frequencies = response.frequencies;
-                        } else {
-                            let diff = (response.frequencies.len() as i64)
-                                - (scores.frequencies.len() as i64);
-
-                            if diff > 0 {
-                                scores
-                                    .frequencies
-                                    .extend(iter::repeat(0).take(diff as usize));
-                            }
-
-                            for i in 0..response.frequencies.len() {
-                                scores.frequencies[i] += response.frequencies[i];
-                            }
-                        }
-                    }
+                    Some(response) => scores.merge(response),
                     None => {
                         // This can only happen if the broadcast channel overflows.
                         // In this case, we don't want to recursively call find_matches again. Otherwise, we could overflow the stack.
@@ -845,40 +1376,74 @@ impl KvIndexerInterface for KvIndexerSharded {
 
     async fn find_matches_for_request(
         &self,
-        tokens: &[u32],
+        request: &RouterRequest,
     ) -> Result<OverlapScores, KvRouterError> {
-        let sequence = compute_block_hash_for_seq(tokens, self.kv_block_size);
+        let sequence = compute_block_hash_for_request(request, self.kv_block_size);
         self.find_matches(sequence).await
     }
 
-    async fn apply_event(&mut self, event: RouterEvent) {
-        #[allow(clippy::map_entry)]
-        if !self.worker_assignments.contains_key(&event.worker_id) {
-            // Get the shard with the smallest amount of workers.
-            let selected_shard = self
-                .worker_counts
-                .iter()
-                .enumerate()
-                .min_by_key(|&(_, value)| value)
-                .unwrap()
-                .0;
+    async fn peek_matches(
+        &self,
+        sequence: Vec<LocalBlockHash>,
+    ) -> Result<OverlapScores, KvRouterError> {
+        let mut responses = Vec::with_capacity(self.inspect_tx.len());
+        for shard_tx in &self.inspect_tx {
+            let (resp_tx, resp_rx) = oneshot::channel();
+            shard_tx
+                .send(InspectRequest::PeekMatches {
+                    sequence: sequence.clone(),
+                    resp: resp_tx,
+                })
+                .await
+                .map_err(|_| KvRouterError::IndexerOffline)?;
+            responses.push(resp_rx);
+        }
 
-            self.worker_assignments
-                .insert(event.worker_id, selected_shard);
-            self.worker_counts[selected_shard] += 1;
+        let mut scores = OverlapScores::new();
+        for resp_rx in responses {
+            let response = resp_rx
+                .await
+                .map_err(|_| KvRouterError::IndexerDroppedRequest)?;
+            scores.merge(response);
         }
+        Ok(scores)
+    }
 
-        self.event_tx[self.worker_assignments[&event.worker_id]]
-            .send(event)
-            .await
-            .unwrap();
+    async fn stats(&self) -> Result<RadixTreeStats, KvRouterError> {
+        let mut responses = Vec::with_capacity(self.inspect_tx.len());
+        for shard_tx in &self.inspect_tx {
+            let (resp_tx, resp_rx) = oneshot::channel();
+            shard_tx
+                .send(InspectRequest::Stats { resp: resp_tx })
+                .await
+                .map_err(|_| KvRouterError::IndexerOffline)?;
+            responses.push(resp_rx);
+        }
+
+        let mut stats = RadixTreeStats::default();
+        for resp_rx in responses {
+            let response = resp_rx
+                .await
+                .map_err(|_| KvRouterError::IndexerDroppedRequest)?;
+            stats.merge(response);
+        }
+        Ok(stats)
+    }
+
+    async fn apply_event(&mut self, event: RouterEvent) {
+        self.event_tx.send(event).await.unwrap();
     }
 
     async fn remove_worker(&mut self, worker: WorkerId) {
-        if let Some((_, shard)) = self.worker_assignments.remove_entry(&worker) {
-            self.worker_counts[shard] -= 1;
-            self.remove_worker_tx[shard].send(worker).await.unwrap();
-        }
+        self.remove_worker_tx.send(worker).await.unwrap();
+    }
+
+    fn event_sender(&self) -> mpsc::Sender<RouterEvent> {
+        self.event_tx.clone()
+    }
+
+    fn remove_worker_sender(&self) -> mpsc::Sender<WorkerId> {
+        self.remove_worker_tx.clone()
     }
 
     /// Shutdown the KV Indexer.
@@ -1201,6 +1766,114 @@ mod tests {
         assert!(result.len() == 2 && result[&worker_0] == 2 && result[&worker_1] == 1);
     }
 
+    #[test]
+    fn test_stats() {
+        let mut trie = RadixTree::new();
+
+        let worker_0 = 0;
+        let worker_1 = 1;
+
+        assert_eq!(trie.stats(), RadixTreeStats::default());
+
+        trie.apply_event(create_store_event(worker_0, 0, vec![0, 1, 2], None));
+        trie.apply_event(create_store_event(worker_1, 0, vec![0, 3], None));
+
+        let stats = trie.stats();
+        assert_eq!(stats.num_blocks, 4);
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(
+            stats.worker_blocks,
+            HashMap::from([(worker_0, 3), (worker_1, 2)])
+        );
+
+        trie.apply_event(create_remove_event(worker_0, 1, vec![1, 2]));
+
+        let stats = trie.stats();
+        assert_eq!(stats.num_blocks, 2);
+        assert_eq!(stats.max_depth, 2);
+        assert_eq!(
+            stats.worker_blocks,
+            HashMap::from([(worker_0, 1), (worker_1, 2)])
+        );
+
+        trie.remove_worker(worker_1);
+
+        let stats = trie.stats();
+        assert_eq!(stats.num_blocks, 1);
+        assert_eq!(stats.max_depth, 1);
+        assert_eq!(stats.worker_blocks, HashMap::from([(worker_0, 1)]));
+
+        trie.apply_event(create_remove_event(worker_0, 2, vec![0]));
+        assert_eq!(trie.stats().num_blocks, 0);
+        assert_eq!(trie.stats().max_depth, 0);
+    }
+
+    #[test]
+    fn test_peek_matches() {
+        let mut trie = RadixTree::new_with_frequency(Some(Duration::from_secs(60)));
+
+        let worker_0 = 0;
+        trie.apply_event(create_store_event(worker_0, 0, vec![0, 1], None));
+
+        let sequence = vec![LocalBlockHash(0), LocalBlockHash(1)];
+        trie.find_matches(sequence.clone(), false);
+
+        // peeking reports the recorded uses without adding to them
+        for _ in 0..2 {
+            let scores = trie.peek_matches(sequence.clone());
+            assert_eq!(scores.scores, HashMap::from([(worker_0, 2)]));
+            assert_eq!(scores.frequencies, vec![1, 1]);
+        }
+
+        let scores = trie.find_matches(sequence, false);
+        assert_eq!(scores.frequencies, vec![1, 1]);
+    }
+
+    #[test]
+    fn test_dump_worker() {
+        let mut trie = RadixTree::new();
+
+        let worker_0 = 0;
+        let worker_1 = 1;
+
+        assert!(trie.dump_worker(worker_0).is_empty());
+
+        trie.apply_event(create_store_event(worker_0, 0, vec![0, 1, 2], None));
+        trie.apply_event(create_store_event(
+            worker_0,
+            1,
+            vec![3],
+            Some(ExternalSequenceBlockHash(100)),
+        ));
+        trie.apply_event(create_store_event(worker_1, 0, vec![0, 4], None));
+
+        let mut copy = RadixTree::new();
+        for event in trie.dump_worker(worker_0) {
+            assert_eq!(event.worker_id, worker_0);
+            copy.apply_event(event);
+        }
+        let stats = copy.stats();
+        assert_eq!(stats.num_blocks, 4);
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(stats.worker_blocks, HashMap::from([(worker_0, 4)]));
+
+        // the copied blocks keep their external hashes, so they can be removed
+        copy.apply_event(create_remove_event(worker_0, 2, vec![2]));
+        let scores = copy.find_matches(
+            vec![LocalBlockHash(0), LocalBlockHash(1), LocalBlockHash(2)],
+            false,
+        );
+        assert_eq!(scores.scores, HashMap::from([(worker_0, 2)]));
+
+        // the blocks below a removed block are left out
+        trie.apply_event(create_remove_event(worker_0, 2, vec![1]));
+        let mut copy = RadixTree::new();
+        for event in trie.dump_worker(worker_0) {
+            copy.apply_event(event);
+        }
+        assert_eq!(copy.stats().worker_blocks, HashMap::from([(worker_0, 2)]));
+    }
+
     #[rstest]
     #[case(11)]
     #[case(32)]
@@ -1226,6 +1899,74 @@ mod tests {
         assert_eq!(hashes.len(), 2);
     }
 
+    #[test]
+    fn test_compute_block_hash_for_seq_with_extra_keys() {
+        let kv_block_size = 4;
+        let sequence = (0..12).collect::<Vec<u32>>();
+        let plain = compute_block_hash_for_seq(&sequence, kv_block_size);
+
+        let image = |hash| BlockExtraKey {
+            offset: 5,
+            length: 4,
+            hash,
+        };
+
+        // only the blocks overlapping the image change
+        let hashes =
+            compute_block_hash_for_seq_with_extra_keys(&sequence, kv_block_size, &[image(1)]);
+        assert_eq!(hashes[0], plain[0]);
+        assert_ne!(hashes[1], plain[1]);
+        assert_ne!(hashes[2], plain[2]);
+
+        // different images do not match
+        let other =
+            compute_block_hash_for_seq_with_extra_keys(&sequence, kv_block_size, &[image(2)]);
+        assert_eq!(other[0], hashes[0]);
+        assert_ne!(other[1], hashes[1]);
+
+        assert_eq!(
+            compute_block_hash_for_seq_with_extra_keys(&sequence, kv_block_size, &[]),
+            plain
+        );
+    }
+
+    #[test]
+    fn test_compute_block_hash_for_request() {
+        use crate::protocols::common::preprocessor::{
+            MediaKind, MultiModalItem, PlaceholderRange, PreprocessedRequest,
+        };
+        use crate::protocols::common::{SamplingOptions, StopConditions};
+
+        let kv_block_size = 4;
+        let prompt = |hash| {
+            PreprocessedRequest::builder()
+                .token_ids((0..12).collect())
+                .stop_conditions(StopConditions::default())
+                .sampling_options(SamplingOptions::default())
+                .multi_modal_data(vec![MultiModalItem {
+                    kind: MediaKind::Image,
+                    mime_type: None,
+                    data: vec![],
+                    hash,
+                    placeholder: Some(PlaceholderRange {
+                        offset: 4,
+                        length: 4,
+                    }),
+                }])
+                .build()
+                .unwrap()
+        };
+
+        // the same placeholder tokens with different images get different block hashes
+        let first = compute_block_hash_for_request(&(&prompt(1)).into(), kv_block_size);
+        let second = compute_block_hash_for_request(&(&prompt(2)).into(), kv_block_size);
+        let same = compute_block_hash_for_request(&(&prompt(1)).into(), kv_block_size);
+        assert_eq!(first, same);
+        assert_eq!(first[0], second[0]);
+        assert_ne!(first[1], second[1]);
+        assert_eq!(first[2], second[2]);
+    }
+
     fn make_indexer(
         token: &CancellationToken,
         num_shards: usize,
@@ -1275,8 +2016,11 @@ mod tests {
         let token = CancellationToken::new();
         let kv_indexer = make_indexer(&token, num_shards, kv_block_size);
 
-        let tokens = vec![1, 2, 3, 4];
-        let scores = kv_indexer.find_matches_for_request(&tokens).await;
+        let request = RouterRequest {
+            tokens: vec![1, 2, 3, 4],
+            ..Default::default()
+        };
+        let scores = kv_indexer.find_matches_for_request(&request).await;
 
         assert!(scores.unwrap().scores.is_empty());
     }
@@ -1295,6 +2039,96 @@ mod tests {
         // No assertion here, just ensuring it runs without panic
     }
 
+    #[tokio::test]
+    #[apply(indexer_template)]
+    async fn test_inspect(num_shards: usize, kv_block_size: usize) {
+        let token = CancellationToken::new();
+        let mut kv_indexer = make_indexer(&token, num_shards, kv_block_size);
+
+        kv_indexer
+            .apply_event(create_store_event(0, 0, vec![1, 2, 3], None))
+            .await;
+        kv_indexer
+            .apply_event(create_store_event(1, 0, vec![1, 4], None))
+            .await;
+
+        // events and inspect requests are received on different channels
+        time::sleep(Duration::from_millis(10)).await;
+
+        let stats = kv_indexer.stats().await.unwrap();
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(stats.worker_blocks, HashMap::from([(0, 3), (1, 2)]));
+
+        let scores = kv_indexer
+            .peek_matches(vec![LocalBlockHash(1), LocalBlockHash(2)])
+            .await
+            .unwrap();
+        assert_eq!(scores.scores, HashMap::from([(0, 2), (1, 1)]));
+    }
+
+    #[tokio::test]
+    async fn test_sharded_rebalance() {
+        let token = CancellationToken::new();
+        let mut kv_indexer = KvIndexerSharded::new(token.clone(), 3, 32);
+
+        // two workers on each shard
+        for worker_id in 0..6 {
+            kv_indexer
+                .apply_event(create_store_event(
+                    worker_id,
+                    0,
+                    vec![1, 2, 10 + worker_id as u64],
+                    None,
+                ))
+                .await;
+        }
+
+        // removing both workers of a shard migrates a worker to it
+        kv_indexer.remove_worker(1).await;
+        kv_indexer.remove_worker(4).await;
+
+        // events and inspect requests are received on different channels
+        time::sleep(Duration::from_millis(50)).await;
+
+        let stats = kv_indexer.stats().await.unwrap();
+        assert_eq!(
+            stats.worker_blocks,
+            HashMap::from([(0, 3), (2, 3), (3, 3), (5, 3)])
+        );
+
+        let scores = kv_indexer
+            .peek_matches(vec![
+                LocalBlockHash(1),
+                LocalBlockHash(2),
+                LocalBlockHash(12),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(
+            scores.scores,
+            HashMap::from([(0, 2), (2, 3), (3, 2), (5, 2)])
+        );
+
+        // the migrated workers keep receiving their events
+        for worker_id in [0, 2, 3, 5] {
+            kv_indexer
+                .apply_event(create_remove_event(
+                    worker_id,
+                    1,
+                    vec![10 + worker_id as u64],
+                ))
+                .await;
+        }
+        time::sleep(Duration::from_millis(50)).await;
+
+        let stats = kv_indexer.stats().await.unwrap();
+        assert_eq!(
+            stats.worker_blocks,
+            HashMap::from([(0, 2), (2, 2), (3, 2), (5, 2)])
+        );
+        token.cancel();
+    }
+
     #[tokio::test]
     #[apply(indexer_template)]
     async fn test_shutdown(num_shards: usize, kv_block_size: usize) {
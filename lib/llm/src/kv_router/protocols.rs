@@ -13,12 +13,55 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::kv_router::indexer::{OverlapScores, RadixTreeStats};
+use crate::kv_router::scheduler::SelectionDecision;
+use crate::protocols::common::preprocessor::PreprocessedRequest;
 use crate::tokens::Token;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct RouterRequest {
     pub tokens: Vec<Token>,
+
+    /// Keys of the media of a multimodal request, see [`BlockExtraKey`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_keys: Vec<BlockExtraKey>,
+}
+
+impl From<&PreprocessedRequest> for RouterRequest {
+    fn from(request: &PreprocessedRequest) -> Self {
+        let extra_keys = request
+            .multi_modal_data
+            .iter()
+            .map(|item| {
+                // media whose tokens are unknown conservatively covers the whole sequence
+                let (offset, length) = item
+                    .placeholder
+                    .map_or((0, request.token_ids.len()), |p| (p.offset, p.length));
+                BlockExtraKey {
+                    offset,
+                    length,
+                    hash: item.hash,
+                }
+            })
+            .collect();
+
+        RouterRequest {
+            tokens: request.token_ids.clone(),
+            extra_keys,
+        }
+    }
+}
+
+/// A key, e.g. the hash of an image, which is mixed into the [`LocalBlockHash`] of every block
+/// overlapping the tokens `offset..offset + length`, so that blocks with the same placeholder
+/// tokens but different media do not match.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BlockExtraKey {
+    pub offset: usize,
+    pub length: usize,
+    pub hash: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -26,7 +69,45 @@ pub struct RouterResponse {
     pub worker_id: i64,
 }
 
-#[derive(Debug)]
+/// A request to the router's inspect endpoint
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RouterInspectRequest {
+    /// Prompt to compute the overlap scores of, without routing it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<Vec<Token>>,
+}
+
+/// The router's view of a worker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkerRouterState {
+    pub worker_id: i64,
+
+    /// Number of blocks the worker reported as cached
+    pub cached_blocks: usize,
+
+    /// Last load metrics scraped from the worker, if any
+    pub metrics: Option<ForwardPassMetrics>,
+}
+
+/// The state of a router, as returned by its inspect endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouterInspectResponse {
+    pub block_size: usize,
+
+    /// Size of the router's index of the cached blocks
+    pub tree: RadixTreeStats,
+
+    pub workers: Vec<WorkerRouterState>,
+
+    /// The most recent selection decisions, oldest first
+    pub recent_decisions: Vec<SelectionDecision>,
+
+    /// Overlap scores of the [`RouterInspectRequest::tokens`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overlap: Option<OverlapScores>,
+}
+
+#[derive(Debug, Default)]
 pub struct WorkerSelectionResult {
     /// The worker id of the selected worker
     pub worker_id: i64,
@@ -37,6 +118,26 @@ pub struct WorkerSelectionResult {
     /// The number of blocks that the selected worker may already have cached.
     /// This is not a guarantee, but an estimate.
     pub overlap_blocks: usize,
+
+    /// The score of each candidate worker, for selectors which compute one
+    pub logits: Option<HashMap<i64, f64>>,
+}
+
+impl WorkerSelectionResult {
+    pub fn new(worker_id: i64, required_blocks: u64, overlap_blocks: usize) -> Self {
+        Self {
+            worker_id,
+            required_blocks,
+            overlap_blocks,
+            logits: None,
+        }
+    }
+
+    /// Attach the score the selector computed for each candidate worker
+    pub fn with_logits(mut self, logits: HashMap<i64, f64>) -> Self {
+        self.logits = Some(logits);
+        self
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
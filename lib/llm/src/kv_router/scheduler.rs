@@ -18,7 +18,8 @@ use dynamo_runtime::traits::events::EventPublisher;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::borrow::BorrowMut;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
 
 use crate::kv_router::indexer::OverlapScores;
 pub use crate::kv_router::protocols::ForwardPassMetrics;
@@ -35,6 +36,24 @@ pub struct KVHitRateEvent {
     pub overlap_blocks: usize,
 }
 
+/// Number of selection decisions a [`KvScheduler`] keeps for inspection
+pub const RECENT_DECISIONS_CAPACITY: usize = 64;
+
+/// A decision of the [`WorkerSelector`], kept for inspection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelectionDecision {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub isl_tokens: usize,
+    /// Number of blocks of the request each worker was expected to have cached
+    pub overlap_scores: HashMap<i64, u32>,
+    pub worker_id: i64,
+    pub required_blocks: u64,
+    pub overlap_blocks: usize,
+    /// Score of each candidate worker, if the selector computes one
+    #[serde(default)]
+    pub logits: HashMap<i64, f64>,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum KvSchedulerError {
     #[error("no endpoints aviailable to route work")]
@@ -87,6 +106,7 @@ impl SchedulingRequest {
 
 pub struct KvScheduler {
     request_tx: tokio::sync::mpsc::Sender<SchedulingRequest>,
+    recent_decisions: Arc<Mutex<VecDeque<SelectionDecision>>>,
 }
 
 impl KvScheduler {
@@ -96,10 +116,6 @@ impl KvScheduler {
         endpoints_rx: tokio::sync::watch::Receiver<ProcessedEndpoints>,
         selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
     ) -> Result<Self, KvSchedulerError> {
-        let selector = selector.unwrap_or(Box::new(DefaultWorkerSelector));
-        let mut endpoints_rx = endpoints_rx;
-        let mut endpoints: ProcessedEndpoints = endpoints_rx.borrow_and_update().clone();
-
         let (event_tx, event_rx) = tokio::sync::mpsc::unbounded_channel::<KVHitRateEvent>();
         tokio::spawn(async move {
             let mut event_rx = event_rx;
@@ -110,8 +126,27 @@ impl KvScheduler {
             }
         });
 
+        Self::start_with_event_sender(block_size, endpoints_rx, selector, event_tx)
+    }
+
+    /// Start a scheduler which sends its [`KVHitRateEvent`]s to `event_tx` instead of publishing
+    /// them on a namespace, e.g. to run it outside of a distributed runtime
+    pub fn start_with_event_sender(
+        block_size: usize,
+        endpoints_rx: tokio::sync::watch::Receiver<ProcessedEndpoints>,
+        selector: Option<Box<dyn WorkerSelector + Send + Sync>>,
+        event_tx: tokio::sync::mpsc::UnboundedSender<KVHitRateEvent>,
+    ) -> Result<Self, KvSchedulerError> {
+        let selector = selector.unwrap_or(Box::new(DefaultWorkerSelector));
+        let mut endpoints_rx = endpoints_rx;
+        let mut endpoints: ProcessedEndpoints = endpoints_rx.borrow_and_update().clone();
+
         // Channel to accept new scheduling requests
         let (request_tx, request_rx) = tokio::sync::mpsc::channel::<SchedulingRequest>(1024);
+        let recent_decisions = Arc::new(Mutex::new(VecDeque::with_capacity(
+            RECENT_DECISIONS_CAPACITY,
+        )));
+        let decisions = recent_decisions.clone();
         tracing::debug!("scheduler starting");
         // Background task to handle scheduling requests
         tokio::spawn(async move {
@@ -141,10 +176,31 @@ impl KvScheduler {
                         continue 'outer;
                     }
                 };
+                // the request may have been picked over a pending update of the endpoints
+                if endpoints_rx.has_changed().unwrap_or(false) {
+                    endpoints = endpoints_rx.borrow_and_update().clone();
+                }
                 tracing::debug!("selected");
                 loop {
                     match selector.select_worker(&endpoints, &request, block_size) {
-                        Ok(selection) => {
+                        Ok(mut selection) => {
+                            let decision = SelectionDecision {
+                                timestamp: chrono::Utc::now(),
+                                isl_tokens: request.isl_tokens,
+                                overlap_scores: request.overlap.scores.clone(),
+                                worker_id: selection.worker_id,
+                                required_blocks: selection.required_blocks,
+                                overlap_blocks: selection.overlap_blocks,
+                                logits: selection.logits.take().unwrap_or_default(),
+                            };
+                            {
+                                let mut decisions = decisions.lock().unwrap();
+                                if decisions.len() == RECENT_DECISIONS_CAPACITY {
+                                    decisions.pop_front();
+                                }
+                                decisions.push_back(decision);
+                            }
+
                             let worker_id = process_worker_selection(
                                 endpoints.borrow_mut(),
                                 selection,
@@ -175,7 +231,20 @@ impl KvScheduler {
             tracing::trace!("background endpoint subscriber shutting down");
         });
 
-        Ok(KvScheduler { request_tx })
+        Ok(KvScheduler {
+            request_tx,
+            recent_decisions,
+        })
+    }
+
+    /// The most recent selection decisions, oldest first
+    pub fn recent_decisions(&self) -> Vec<SelectionDecision> {
+        self.recent_decisions
+            .lock()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect()
     }
 
     pub async fn schedule(
@@ -244,6 +313,10 @@ impl WorkerSelector for DefaultWorkerSelector {
     ) -> Result<WorkerSelectionResult, KvSchedulerError> {
         assert!(request.isl_tokens > 0);
 
+        if workers.endpoints.is_empty() {
+            return Err(KvSchedulerError::NoEndpoints);
+        }
+
         let mut worker_scores = HashMap::new();
         let mut max_active = 0.0;
 
@@ -259,15 +332,12 @@ impl WorkerSelector for DefaultWorkerSelector {
             max_active = f64::max(max_active, ep.data.request_active_slots as f64);
         }
 
-        if max_active == 0.0 {
-            return Err(KvSchedulerError::NoEndpoints);
-        }
-
         // make immutable
         let worker_scores = worker_scores;
         let max_active = max_active;
 
         // Calculate logits for each worker
+        let mut logits = HashMap::new();
         let mut best_logit = f64::NEG_INFINITY;
         let mut best_workers = Vec::new();
 
@@ -297,6 +367,7 @@ impl WorkerSelector for DefaultWorkerSelector {
                 gpu_cache_usage,
                 normalized_active
             );
+            logits.insert(worker_id, logit);
 
             // Track best workers
             match logit.partial_cmp(&best_logit) {
@@ -313,7 +384,7 @@ impl WorkerSelector for DefaultWorkerSelector {
         }
 
         // Return early if no valid workers found
-        if best_workers.is_empty() || best_logit == 0.0 {
+        if best_workers.is_empty() {
             return Err(KvSchedulerError::NoEndpoints);
         }
 
@@ -328,13 +399,80 @@ impl WorkerSelector for DefaultWorkerSelector {
         // Log selection metrics
         tracing::info!("Selected worker: {}, logit: {:.3}", worker_id, best_logit);
 
-        let total_blocks = std::cmp::min(request.isl_tokens / block_size, 1) as u64;
+        let total_blocks = request.isl_tokens.div_ceil(block_size) as u64;
         let overlap_blocks = request.overlap.scores.get(&worker_id).copied().unwrap_or(0) as usize;
 
-        Ok(WorkerSelectionResult {
-            worker_id,
-            required_blocks: total_blocks,
-            overlap_blocks,
-        })
+        Ok(WorkerSelectionResult::new(worker_id, total_blocks, overlap_blocks).with_logits(logits))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint(worker_id: i64, active_slots: u64, kv_active_blocks: u64) -> Endpoint {
+        Endpoint {
+            name: format!("worker-{worker_id}"),
+            subject: format!("namespace.component.generate-{worker_id:x}"),
+            data: ForwardPassMetrics {
+                request_active_slots: active_slots,
+                request_total_slots: 8,
+                kv_active_blocks,
+                kv_total_blocks: 100,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn request(isl_tokens: usize, overlap: &[(i64, u32)]) -> SchedulingRequest {
+        let (resp_tx, _) = tokio::sync::oneshot::channel();
+        let mut scores = OverlapScores::default();
+        scores.scores.extend(overlap.iter().copied());
+        SchedulingRequest {
+            isl_tokens,
+            overlap: scores,
+            resp_tx,
+        }
+    }
+
+    #[test]
+    fn test_select_without_endpoints() {
+        let workers = ProcessedEndpoints::default();
+        let result = DefaultWorkerSelector.select_worker(&workers, &request(32, &[]), 16);
+        assert!(matches!(result, Err(KvSchedulerError::NoEndpoints)));
+    }
+
+    #[test]
+    fn test_select_idle_workers() {
+        // no worker has an active request, so every logit is 0
+        let workers = ProcessedEndpoints::new(vec![endpoint(1, 0, 0), endpoint(2, 0, 0)]);
+        let result = DefaultWorkerSelector
+            .select_worker(&workers, &request(32, &[]), 16)
+            .unwrap();
+        assert!([1, 2].contains(&result.worker_id));
+        assert_eq!(result.overlap_blocks, 0);
+    }
+
+    #[test]
+    fn test_select_zero_logit() {
+        // worker 1: 2.0 * (3 * 16 / 64) - 0.5 - 1.0 = 0.0
+        // worker 2: 2.0 * 0.0 - 0.5 - 1.0 = -1.5
+        let workers = ProcessedEndpoints::new(vec![endpoint(1, 1, 50), endpoint(2, 1, 50)]);
+        let result = DefaultWorkerSelector
+            .select_worker(&workers, &request(64, &[(1, 3)]), 16)
+            .unwrap();
+        assert_eq!(result.worker_id, 1);
+        assert_eq!(result.overlap_blocks, 3);
+    }
+
+    #[test]
+    fn test_required_blocks() {
+        let workers = ProcessedEndpoints::new(vec![endpoint(1, 0, 0)]);
+        for (isl_tokens, required_blocks) in [(1, 1), (16, 1), (17, 2), (32, 2), (33, 3)] {
+            let result = DefaultWorkerSelector
+                .select_worker(&workers, &request(isl_tokens, &[]), 16)
+                .unwrap();
+            assert_eq!(result.required_blocks, required_blocks, "isl {isl_tokens}");
+        }
     }
 }
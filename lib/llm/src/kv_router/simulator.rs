@@ -0,0 +1,852 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! KV router trace replay
+//!
+//! Replays a recorded request trace against simulated workers to evaluate a [`WorkerSelector`]
+//! before deploying it. Each simulated worker models:
+//!
+//! - a kv cache of a fixed number of blocks; the blocks no running request holds are evicted
+//!   least recently used first, the deepest block of a sequence first;
+//! - a fixed number of request slots, with the requests beyond them waiting in a queue;
+//! - one prefill at a time, taking a fixed time per token missing from the cache;
+//! - a fixed decode time per output token, independent of the other running requests.
+//!
+//! The workers report the contents of their caches as [`RouterEvent`]s to the [`RadixTree`] a
+//! [`KvIndexer`][super::indexer::KvIndexer] maintains, and their load as [`ForwardPassMetrics`]
+//! to a [`KvScheduler`], so each request is routed by the same code as in a deployment, in
+//! simulated time. The radix tree is driven directly rather than through an indexer so every
+//! match sees all the events emitted before it, which keeps a replay reproducible.
+//!
+//! The kv cache of the generated tokens is not modelled.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap, VecDeque};
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, watch};
+
+use super::indexer::{
+    compute_block_hash_for_seq, compute_sequence_hashes, RadixTree, RouterEvent, WorkerId,
+};
+use super::protocols::{
+    ExternalSequenceBlockHash, ForwardPassMetrics, KvCacheEvent, KvCacheEventData,
+    KvCacheRemoveData, KvCacheStoreData, KvCacheStoredBlockData, LocalBlockHash,
+    WorkerSelectionResult,
+};
+use super::scheduler::{Endpoint, KvScheduler, KvSchedulerError, SchedulingRequest};
+use super::scoring::ProcessedEndpoints;
+use super::WorkerSelector;
+
+/// A request of a recorded trace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceRequest {
+    /// Arrival time of the request, in milliseconds since the start of the trace
+    pub timestamp: u64,
+
+    /// Prompt of the request
+    pub token_ids: Vec<u32>,
+
+    /// Number of tokens generated for the request
+    #[serde(default = "default_output_length")]
+    pub output_length: usize,
+}
+
+fn default_output_length() -> usize {
+    1
+}
+
+/// Load a trace with one json [`TraceRequest`] per line
+pub fn load_trace(path: impl AsRef<Path>) -> Result<Vec<TraceRequest>> {
+    let file = std::fs::File::open(path)?;
+    let mut trace = Vec::new();
+    for line in std::io::BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        trace.push(serde_json::from_str(&line)?);
+    }
+    Ok(trace)
+}
+
+/// Capacity and speed of a simulated worker
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SimulatedWorkerConfig {
+    /// Number of blocks of the kv cache
+    pub kv_total_blocks: u64,
+
+    /// Number of requests run concurrently
+    pub request_total_slots: u64,
+
+    /// Prefill time of a token missing from the kv cache
+    pub prefill_ms_per_token: f64,
+
+    /// Decode time of an output token
+    pub decode_ms_per_token: f64,
+}
+
+impl Default for SimulatedWorkerConfig {
+    fn default() -> Self {
+        Self {
+            kv_total_blocks: 1024,
+            request_total_slots: 64,
+            prefill_ms_per_token: 0.1,
+            decode_ms_per_token: 20.0,
+        }
+    }
+}
+
+/// Configuration of a [`Simulation`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationConfig {
+    /// Number of tokens per kv block
+    pub block_size: usize,
+
+    /// Number of simulated workers
+    pub num_workers: usize,
+
+    /// Configuration of each worker
+    pub worker: SimulatedWorkerConfig,
+}
+
+/// Summary statistics of a set of samples
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Distribution {
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl Distribution {
+    pub fn from_samples(mut samples: Vec<f64>) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_by(f64::total_cmp);
+        let percentile = |p: f64| {
+            let rank = (p * samples.len() as f64).ceil() as usize;
+            samples[rank.clamp(1, samples.len()) - 1]
+        };
+        Self {
+            mean: samples.iter().sum::<f64>() / samples.len() as f64,
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            p99: percentile(0.99),
+            max: samples[samples.len() - 1],
+        }
+    }
+}
+
+/// Outcome of the replay of a trace with one selector
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationReport {
+    /// Number of requests routed
+    pub requests: usize,
+
+    /// Number of requests dropped because they are empty or do not fit in a kv cache
+    pub rejected: usize,
+
+    /// Fraction of the prompt blocks found in the kv cache of the worker they were routed to
+    pub prefix_hit_rate: f64,
+
+    /// Fraction of the prompt blocks the router expected to be cached on the selected worker
+    pub router_overlap_rate: f64,
+
+    /// Time to first token, in milliseconds
+    pub ttft_ms: Distribution,
+
+    /// Ratio of the largest to the mean number of requests on a worker, averaged over the
+    /// arrivals; 1 when the requests are spread evenly
+    pub load_imbalance: f64,
+
+    /// Number of requests routed to each worker
+    pub requests_per_worker: Vec<usize>,
+
+    /// Time until the last request completed, in milliseconds
+    pub makespan_ms: f64,
+}
+
+impl std::fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "requests:            {}", self.requests)?;
+        writeln!(f, "rejected:            {}", self.rejected)?;
+        writeln!(f, "prefix hit rate:     {:.3}", self.prefix_hit_rate)?;
+        writeln!(f, "router overlap rate: {:.3}", self.router_overlap_rate)?;
+        writeln!(
+            f,
+            "ttft (ms):           mean {:.1} p50 {:.1} p90 {:.1} p99 {:.1} max {:.1}",
+            self.ttft_ms.mean,
+            self.ttft_ms.p50,
+            self.ttft_ms.p90,
+            self.ttft_ms.p99,
+            self.ttft_ms.max
+        )?;
+        writeln!(f, "load imbalance:      {:.3}", self.load_imbalance)?;
+        writeln!(f, "requests per worker: {:?}", self.requests_per_worker)?;
+        write!(f, "makespan (ms):       {:.1}", self.makespan_ms)
+    }
+}
+
+/// Replays traces against simulated workers
+pub struct Simulation {
+    config: SimulationConfig,
+}
+
+impl Simulation {
+    pub fn new(config: SimulationConfig) -> Self {
+        Self { config }
+    }
+
+    /// Replay `trace`, routing its requests with `selector`
+    pub async fn run(
+        &self,
+        trace: &[TraceRequest],
+        selector: Box<dyn WorkerSelector + Send + Sync>,
+    ) -> Result<SimulationReport> {
+        let workers: Vec<SimulatedWorker> = (0..self.config.num_workers)
+            .map(|worker_id| SimulatedWorker::new(worker_id as WorkerId, self.config.worker))
+            .collect();
+        if workers.is_empty() {
+            anyhow::bail!("a simulation needs at least one worker");
+        }
+
+        let (endpoints_tx, endpoints_rx) = watch::channel(endpoints(&workers));
+        let (hit_rate_tx, mut hit_rate_rx) = mpsc::unbounded_channel();
+        let scheduler = KvScheduler::start_with_event_sender(
+            self.config.block_size,
+            endpoints_rx,
+            Some(selector),
+            hit_rate_tx,
+        )?;
+
+        let mut state = SimulationState {
+            block_size: self.config.block_size,
+            requests: trace
+                .iter()
+                .map(|request| SimulatedRequest::new(request, self.config.block_size))
+                .collect(),
+            workers,
+            tree: RadixTree::new(),
+            events: BinaryHeap::new(),
+            next_event: 0,
+            now: 0,
+            rejected: 0,
+            ttft_ms: Vec::new(),
+            load_imbalance: Vec::new(),
+        };
+        for (index, request) in trace.iter().enumerate() {
+            state.push(request.timestamp * 1000, EventKind::Arrival, index);
+        }
+
+        while let Some(Reverse(event)) = state.events.pop() {
+            state.now = event.time;
+            match event.kind {
+                EventKind::Arrival => {
+                    let request = &state.requests[event.request];
+                    if request.isl_tokens == 0 {
+                        state.rejected += 1;
+                        continue;
+                    }
+
+                    endpoints_tx.send_replace(endpoints(&state.workers));
+                    let overlap = state.tree.find_matches(request.local_hashes.clone(), false);
+                    let worker_id = scheduler.schedule(overlap, request.isl_tokens).await?;
+                    state.assign(event.request, worker_id)?;
+                }
+                EventKind::PrefillDone => state.prefill_done(event.request),
+                EventKind::DecodeDone => state.decode_done(event.request),
+            }
+        }
+
+        let mut isl_blocks = 0;
+        let mut overlap_blocks = 0;
+        while let Ok(event) = hit_rate_rx.try_recv() {
+            isl_blocks += event.isl_blocks;
+            overlap_blocks += event.overlap_blocks;
+        }
+
+        let (hit_blocks, total_blocks) =
+            state.workers.iter().fold((0, 0), |(hit, total), worker| {
+                (hit + worker.hit_blocks, total + worker.total_blocks)
+            });
+
+        Ok(SimulationReport {
+            requests: state.ttft_ms.len(),
+            rejected: state.rejected,
+            prefix_hit_rate: ratio(hit_blocks as f64, total_blocks as f64),
+            router_overlap_rate: ratio(overlap_blocks as f64, isl_blocks as f64),
+            ttft_ms: Distribution::from_samples(state.ttft_ms),
+            load_imbalance: Distribution::from_samples(state.load_imbalance).mean,
+            requests_per_worker: state.workers.iter().map(|worker| worker.routed).collect(),
+            makespan_ms: state.now as f64 / 1000.0,
+        })
+    }
+}
+
+fn ratio(numerator: f64, denominator: f64) -> f64 {
+    if denominator > 0.0 {
+        numerator / denominator
+    } else {
+        0.0
+    }
+}
+
+fn endpoints(workers: &[SimulatedWorker]) -> ProcessedEndpoints {
+    ProcessedEndpoints::new(workers.iter().map(SimulatedWorker::endpoint).collect())
+}
+
+/// Kinds of simulation events; events at the same time are handled in this order, so the
+/// capacity released by a request is available to the requests arriving at that time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum EventKind {
+    DecodeDone,
+    PrefillDone,
+    Arrival,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Event {
+    /// Simulated time, in microseconds
+    time: u64,
+    kind: EventKind,
+    sequence: u64,
+    request: usize,
+}
+
+/// A trace request as it goes through a simulated worker
+struct SimulatedRequest {
+    arrival: u64,
+    isl_tokens: usize,
+    output_length: usize,
+    local_hashes: Vec<LocalBlockHash>,
+    sequence_hashes: Vec<ExternalSequenceBlockHash>,
+
+    /// Worker the request was routed to
+    worker: usize,
+
+    /// Number of leading blocks found in the kv cache of the worker
+    cached_blocks: usize,
+}
+
+impl SimulatedRequest {
+    fn new(request: &TraceRequest, block_size: usize) -> Self {
+        let local_hashes = compute_block_hash_for_seq(&request.token_ids, block_size);
+        let sequence_hashes = compute_sequence_hashes(&local_hashes);
+
+        Self {
+            arrival: request.timestamp * 1000,
+            isl_tokens: request.token_ids.len(),
+            output_length: request.output_length,
+            local_hashes,
+            sequence_hashes,
+            worker: 0,
+            cached_blocks: 0,
+        }
+    }
+}
+
+struct CachedBlock {
+    /// Number of running requests holding the block
+    refs: usize,
+    last_used: u64,
+    depth: usize,
+}
+
+struct SimulatedWorker {
+    worker_id: WorkerId,
+    config: SimulatedWorkerConfig,
+
+    /// Blocks of the kv cache holding computed tokens
+    blocks: HashMap<ExternalSequenceBlockHash, CachedBlock>,
+
+    /// Blocks no running request holds, in eviction order
+    evictable: BTreeSet<(u64, Reverse<usize>, ExternalSequenceBlockHash)>,
+
+    /// Blocks allocated to the prefill in flight
+    allocated_blocks: u64,
+
+    /// Requests routed to the worker and not yet admitted
+    waiting: VecDeque<usize>,
+    prefilling: bool,
+    running: u64,
+
+    next_event_id: u64,
+    routed: usize,
+    hit_blocks: u64,
+    total_blocks: u64,
+}
+
+impl SimulatedWorker {
+    fn new(worker_id: WorkerId, config: SimulatedWorkerConfig) -> Self {
+        Self {
+            worker_id,
+            config,
+            blocks: HashMap::new(),
+            evictable: BTreeSet::new(),
+            allocated_blocks: 0,
+            waiting: VecDeque::new(),
+            prefilling: false,
+            running: 0,
+            next_event_id: 0,
+            routed: 0,
+            hit_blocks: 0,
+            total_blocks: 0,
+        }
+    }
+
+    fn endpoint(&self) -> Endpoint {
+        let active_blocks =
+            (self.blocks.len() - self.evictable.len()) as u64 + self.allocated_blocks;
+        Endpoint {
+            name: "generate".to_string(),
+            subject: format!("simulated-{:x}", self.worker_id),
+            data: ForwardPassMetrics {
+                request_active_slots: self.running,
+                request_total_slots: self.config.request_total_slots,
+                kv_active_blocks: active_blocks,
+                kv_total_blocks: self.config.kv_total_blocks,
+                num_requests_waiting: self.waiting.len() as u64,
+                gpu_cache_usage_perc: active_blocks as f32 / self.config.kv_total_blocks as f32,
+                gpu_prefix_cache_hit_rate: ratio(self.hit_blocks as f64, self.total_blocks as f64)
+                    as f32,
+            },
+        }
+    }
+
+    fn event(&mut self, data: KvCacheEventData) -> RouterEvent {
+        let event = KvCacheEvent {
+            event_id: self.next_event_id,
+            data,
+        };
+        self.next_event_id += 1;
+        RouterEvent::new(self.worker_id, event)
+    }
+
+    /// Hold the block for a running request
+    fn pin(&mut self, hash: ExternalSequenceBlockHash) {
+        let block = self.blocks.get_mut(&hash).expect("pinned block is cached");
+        if block.refs == 0 {
+            self.evictable
+                .remove(&(block.last_used, Reverse(block.depth), hash));
+        }
+        block.refs += 1;
+    }
+
+    /// Release the block held by a request completed at `now`
+    fn unpin(&mut self, hash: ExternalSequenceBlockHash, now: u64) {
+        let block = self
+            .blocks
+            .get_mut(&hash)
+            .expect("unpinned block is cached");
+        block.refs -= 1;
+        if block.refs == 0 {
+            block.last_used = now;
+            self.evictable.insert((now, Reverse(block.depth), hash));
+        }
+    }
+}
+
+struct SimulationState {
+    block_size: usize,
+    requests: Vec<SimulatedRequest>,
+    workers: Vec<SimulatedWorker>,
+    tree: RadixTree,
+    events: BinaryHeap<Reverse<Event>>,
+    next_event: u64,
+
+    /// Simulated time, in microseconds
+    now: u64,
+    rejected: usize,
+    ttft_ms: Vec<f64>,
+    load_imbalance: Vec<f64>,
+}
+
+impl SimulationState {
+    fn push(&mut self, time: u64, kind: EventKind, request: usize) {
+        self.events.push(Reverse(Event {
+            time,
+            kind,
+            sequence: self.next_event,
+            request,
+        }));
+        self.next_event += 1;
+    }
+
+    fn assign(&mut self, request: usize, worker_id: WorkerId) -> Result<()> {
+        let worker = usize::try_from(worker_id)
+            .ok()
+            .filter(|worker| *worker < self.workers.len())
+            .ok_or_else(|| anyhow::anyhow!("selector returned unknown worker {worker_id}"))?;
+
+        let loads: Vec<f64> = self
+            .workers
+            .iter()
+            .map(|worker| (worker.running + worker.waiting.len() as u64) as f64)
+            .collect();
+        let mean = loads.iter().sum::<f64>() / loads.len() as f64;
+        if mean > 0.0 {
+            let max = loads.iter().copied().fold(0.0, f64::max);
+            self.load_imbalance.push(max / mean);
+        }
+
+        self.requests[request].worker = worker;
+        self.workers[worker].routed += 1;
+        self.workers[worker].waiting.push_back(request);
+        self.admit(worker);
+        Ok(())
+    }
+
+    /// Start the prefill of the next waiting request of `worker`, if it has a free slot and
+    /// room in its kv cache
+    fn admit(&mut self, worker: usize) {
+        loop {
+            let state = &self.workers[worker];
+            if state.prefilling || state.running >= state.config.request_total_slots {
+                return;
+            }
+            let Some(&index) = state.waiting.front() else {
+                return;
+            };
+
+            let request = &self.requests[index];
+            let cached = request
+                .sequence_hashes
+                .iter()
+                .take_while(|hash| state.blocks.contains_key(hash))
+                .count();
+            let missing = (request.sequence_hashes.len() - cached) as u64;
+            let used = state.blocks.len() as u64 + state.allocated_blocks;
+            let available =
+                state.config.kv_total_blocks.saturating_sub(used) + state.evictable.len() as u64;
+            // the cached blocks of the request are evictable until it holds them
+            let evictable_cached = request.sequence_hashes[..cached]
+                .iter()
+                .filter(|hash| state.blocks[*hash].refs == 0)
+                .count() as u64;
+
+            if missing + evictable_cached > available {
+                if state.running == 0 {
+                    // the request does not fit even in an idle worker
+                    self.workers[worker].waiting.pop_front();
+                    self.rejected += 1;
+                    continue;
+                }
+                return;
+            }
+
+            let state = &mut self.workers[worker];
+            state.waiting.pop_front();
+            for hash in &request.sequence_hashes[..cached] {
+                state.pin(*hash);
+            }
+
+            let mut evicted = Vec::new();
+            while state.blocks.len() as u64 + state.allocated_blocks + missing
+                > state.config.kv_total_blocks
+            {
+                let (_, _, hash) = state
+                    .evictable
+                    .pop_first()
+                    .expect("admitted request fits in the kv cache");
+                state.blocks.remove(&hash);
+                evicted.push(hash);
+            }
+            if !evicted.is_empty() {
+                let event = state.event(KvCacheEventData::Removed(KvCacheRemoveData {
+                    block_hashes: evicted,
+                }));
+                self.tree.apply_event(event);
+            }
+
+            state.allocated_blocks += missing;
+            state.prefilling = true;
+            state.running += 1;
+            state.hit_blocks += cached as u64;
+            state.total_blocks += request.sequence_hashes.len() as u64;
+
+            let uncached_tokens = request.isl_tokens - cached * self.block_size;
+            let prefill_us =
+                (uncached_tokens as f64 * state.config.prefill_ms_per_token * 1000.0).round();
+            self.requests[index].cached_blocks = cached;
+            self.push(self.now + prefill_us as u64, EventKind::PrefillDone, index);
+            return;
+        }
+    }
+
+    fn prefill_done(&mut self, index: usize) {
+        let request = &self.requests[index];
+        let worker = request.worker;
+        let state = &mut self.workers[worker];
+
+        let missing = request.sequence_hashes.len() - request.cached_blocks;
+        state.allocated_blocks -= missing as u64;
+
+        // the blocks another request computed meanwhile are shared, the others are stored
+        let mut stored: Vec<KvCacheStoreData> = Vec::new();
+        for depth in request.cached_blocks..request.sequence_hashes.len() {
+            let hash = request.sequence_hashes[depth];
+            if state.blocks.contains_key(&hash) {
+                state.pin(hash);
+                continue;
+            }
+            state.blocks.insert(
+                hash,
+                CachedBlock {
+                    refs: 1,
+                    last_used: self.now,
+                    depth,
+                },
+            );
+
+            let block = KvCacheStoredBlockData {
+                block_hash: hash,
+                tokens_hash: request.local_hashes[depth],
+            };
+            let parent_hash = depth
+                .checked_sub(1)
+                .map(|parent| request.sequence_hashes[parent]);
+            match stored.last_mut() {
+                Some(event) if event.blocks.last().map(|last| last.block_hash) == parent_hash => {
+                    event.blocks.push(block)
+                }
+                _ => stored.push(KvCacheStoreData {
+                    parent_hash,
+                    blocks: vec![block],
+                }),
+            }
+        }
+        for data in stored {
+            let event = state.event(KvCacheEventData::Stored(data));
+            self.tree.apply_event(event);
+        }
+
+        state.prefilling = false;
+        self.ttft_ms
+            .push((self.now - request.arrival) as f64 / 1000.0);
+
+        let decode_us =
+            (request.output_length as f64 * state.config.decode_ms_per_token * 1000.0).round();
+        self.push(self.now + decode_us as u64, EventKind::DecodeDone, index);
+        self.admit(worker);
+    }
+
+    fn decode_done(&mut self, index: usize) {
+        let request = &self.requests[index];
+        let worker = request.worker;
+        let state = &mut self.workers[worker];
+
+        for hash in &request.sequence_hashes {
+            state.unpin(*hash, self.now);
+        }
+        state.running -= 1;
+        self.admit(worker);
+    }
+}
+
+/// Baseline selector sending the requests to the workers in turn, ignoring their caches
+#[derive(Default)]
+pub struct RoundRobinWorkerSelector {
+    next: AtomicUsize,
+}
+
+impl WorkerSelector for RoundRobinWorkerSelector {
+    fn select_worker(
+        &self,
+        workers: &ProcessedEndpoints,
+        request: &SchedulingRequest,
+        block_size: usize,
+    ) -> Result<WorkerSelectionResult, KvSchedulerError> {
+        let mut worker_ids: Vec<WorkerId> = workers.endpoints.keys().copied().collect();
+        if worker_ids.is_empty() {
+            return Err(KvSchedulerError::NoEndpoints);
+        }
+        worker_ids.sort_unstable();
+
+        let next = self.next.fetch_add(1, Ordering::Relaxed);
+        Ok(selection(
+            worker_ids[next % worker_ids.len()],
+            request,
+            block_size,
+        ))
+    }
+}
+
+/// Baseline selector sending each request to the worker with the fewest running and waiting
+/// requests, ignoring their caches
+#[derive(Default)]
+pub struct LeastLoadedWorkerSelector;
+
+impl WorkerSelector for LeastLoadedWorkerSelector {
+    fn select_worker(
+        &self,
+        workers: &ProcessedEndpoints,
+        request: &SchedulingRequest,
+        block_size: usize,
+    ) -> Result<WorkerSelectionResult, KvSchedulerError> {
+        let worker_id = workers
+            .endpoints
+            .iter()
+            .min_by_key(|(worker_id, endpoint)| {
+                (
+                    endpoint.data.request_active_slots + endpoint.data.num_requests_waiting,
+                    **worker_id,
+                )
+            })
+            .map(|(worker_id, _)| *worker_id)
+            .ok_or(KvSchedulerError::NoEndpoints)?;
+
+        Ok(selection(worker_id, request, block_size))
+    }
+}
+
+fn selection(
+    worker_id: WorkerId,
+    request: &SchedulingRequest,
+    block_size: usize,
+) -> WorkerSelectionResult {
+    WorkerSelectionResult::new(
+        worker_id,
+        request.isl_tokens.div_ceil(block_size) as u64,
+        request.overlap.scores.get(&worker_id).copied().unwrap_or(0) as usize,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kv_router::scheduler::DefaultWorkerSelector;
+
+    const BLOCK_SIZE: usize = 4;
+
+    fn config(num_workers: usize, kv_total_blocks: u64) -> SimulationConfig {
+        SimulationConfig {
+            block_size: BLOCK_SIZE,
+            num_workers,
+            worker: SimulatedWorkerConfig {
+                kv_total_blocks,
+                request_total_slots: 4,
+                prefill_ms_per_token: 1.0,
+                decode_ms_per_token: 10.0,
+            },
+        }
+    }
+
+    fn request(timestamp: u64, prefix: u32, len: u32) -> TraceRequest {
+        TraceRequest {
+            timestamp,
+            token_ids: (0..len).map(|token| prefix * 1000 + token).collect(),
+            output_length: 2,
+        }
+    }
+
+    #[test]
+    fn test_distribution() {
+        let distribution = Distribution::from_samples((1..=100).map(f64::from).collect());
+        assert_eq!(distribution.mean, 50.5);
+        assert_eq!(distribution.p50, 50.0);
+        assert_eq!(distribution.p90, 90.0);
+        assert_eq!(distribution.p99, 99.0);
+        assert_eq!(distribution.max, 100.0);
+        assert_eq!(Distribution::from_samples(vec![]), Distribution::default());
+    }
+
+    #[tokio::test]
+    async fn test_prefix_reuse() {
+        // the second request arrives after the first completed and reuses its 2 blocks
+        let trace = vec![request(0, 1, 8), request(100, 1, 8)];
+        let report = Simulation::new(config(1, 16))
+            .run(&trace, Box::new(DefaultWorkerSelector))
+            .await
+            .unwrap();
+
+        assert_eq!(report.requests, 2);
+        assert_eq!(report.rejected, 0);
+        assert_eq!(report.prefix_hit_rate, 0.5);
+        assert_eq!(report.router_overlap_rate, 0.5);
+        // 8 uncached tokens, then a fully cached prompt
+        assert_eq!(report.ttft_ms.max, 8.0);
+        assert_eq!(report.ttft_ms.p50, 0.0);
+        assert_eq!(report.makespan_ms, 120.0);
+    }
+
+    #[tokio::test]
+    async fn test_eviction() {
+        // a cache of 2 blocks holds a single prompt, so alternating prompts never hit
+        let trace = vec![
+            request(0, 1, 8),
+            request(100, 2, 8),
+            request(200, 1, 8),
+            request(300, 3, 9),
+        ];
+        let report = Simulation::new(config(1, 2))
+            .run(&trace, Box::new(DefaultWorkerSelector))
+            .await
+            .unwrap();
+
+        assert_eq!(report.requests, 3);
+        assert_eq!(report.rejected, 1);
+        assert_eq!(report.prefix_hit_rate, 0.0);
+        assert_eq!(report.router_overlap_rate, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_prefix_affinity() {
+        // two prefixes arriving in turn; the kv aware selector keeps each on its own worker
+        let trace: Vec<TraceRequest> = (0..8)
+            .map(|i| request(i * 100, 1 + (i as u32 % 2), 16))
+            .collect();
+
+        let simulation = Simulation::new(config(2, 64));
+        let round_robin = simulation
+            .run(&trace, Box::new(RoundRobinWorkerSelector::default()))
+            .await
+            .unwrap();
+        let kv_aware = simulation
+            .run(&trace, Box::new(DefaultWorkerSelector))
+            .await
+            .unwrap();
+
+        assert_eq!(round_robin.requests_per_worker, vec![4, 4]);
+        assert_eq!(round_robin.prefix_hit_rate, 0.75);
+        assert_eq!(kv_aware.prefix_hit_rate, 0.75);
+        assert_eq!(kv_aware.router_overlap_rate, 0.75);
+        assert!(kv_aware.ttft_ms.mean <= round_robin.ttft_ms.mean);
+    }
+
+    #[tokio::test]
+    async fn test_least_loaded() {
+        // long decodes keep each request running while the next arrives
+        let trace: Vec<TraceRequest> = (0..4)
+            .map(|i| TraceRequest {
+                output_length: 100,
+                ..request(i, i as u32, 4)
+            })
+            .collect();
+        let report = Simulation::new(config(2, 64))
+            .run(&trace, Box::new(LeastLoadedWorkerSelector))
+            .await
+            .unwrap();
+
+        assert_eq!(report.requests_per_worker, vec![2, 2]);
+        assert_eq!(report.rejected, 0);
+    }
+}
@@ -24,6 +24,7 @@ pub mod disagg_router;
 pub mod engines;
 pub mod gguf;
 pub mod http;
+pub mod kv;
 pub mod kv_router;
 pub mod model_card;
 pub mod model_type;
@@ -32,6 +33,3 @@ pub mod protocols;
 pub mod tokenizers;
 pub mod tokens;
 pub mod types;
-
-#[cfg(feature = "cuda_kv")]
-pub mod kv;
@@ -82,6 +82,8 @@ impl ModelDeploymentCard {
             revision: 0,
             last_published: None,
             requires_preprocessing: true,
+            reasoning_parser: None,
+            multimodal: None,
         })
     }
 
@@ -104,6 +106,8 @@ impl ModelDeploymentCard {
             revision: 0,
             last_published: None,
             requires_preprocessing: true,
+            reasoning_parser: None,
+            multimodal: None,
         })
     }
 }
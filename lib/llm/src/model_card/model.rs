@@ -39,6 +39,8 @@ use serde::{Deserialize, Serialize};
 use tokenizers::Tokenizer as HfTokenizer;
 
 use crate::gguf::{Content, ContentConfig};
+use crate::preprocessor::media::MultiModalConfig;
+use crate::protocols::openai::chat_completions::ReasoningParserType;
 use crate::protocols::TokenIdType;
 
 pub const BUCKET_NAME: &str = "mdc";
@@ -128,6 +130,16 @@ pub struct ModelDeploymentCard {
     /// a ChatCompletionRequest JSON.
     #[serde(default)]
     pub requires_preprocessing: bool,
+
+    /// Separates the reasoning span of thinking models into `reasoning_content`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub reasoning_parser: Option<ReasoningParserType>,
+
+    /// How the media of chat messages is passed to the model, if it is multimodal
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub multimodal: Option<MultiModalConfig>,
 }
 
 impl ModelDeploymentCard {
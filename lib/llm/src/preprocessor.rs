@@ -23,6 +23,7 @@
 //!
 //! The Preprocessor will accept any IngressRequest and transform it to a BackendRequest.
 
+pub mod media;
 pub mod prompt;
 pub mod tools;
 
@@ -33,7 +34,9 @@ use std::{collections::HashMap, sync::Arc};
 use tracing;
 
 use crate::model_card::model::{ModelDeploymentCard, ModelInfo, TokenizerKind};
+use crate::preprocessor::media::{MediaLoader, PlaceholderExpander, RepeatPlaceholder};
 use crate::preprocessor::prompt::OAIChatLikeRequest;
+use crate::protocols::TokenIdType;
 use crate::tokenizers::Encoding;
 
 use dynamo_runtime::engine::{AsyncEngine, AsyncEngineContextProvider, ResponseStream};
@@ -43,9 +46,12 @@ use dynamo_runtime::pipeline::{
 use dynamo_runtime::protocols::annotated::{Annotated, AnnotationsProvider};
 
 use crate::protocols::{
-    common::{SamplingOptionsProvider, StopConditionsProvider},
+    common::{preprocessor::MultiModalItem, SamplingOptionsProvider, StopConditionsProvider},
     openai::{
-        chat_completions::{NvCreateChatCompletionRequest, NvCreateChatCompletionStreamResponse},
+        chat_completions::{
+            NvCreateChatCompletionRequest, NvCreateChatCompletionStreamResponse,
+            ReasoningParserType,
+        },
         completions::{CompletionRequest, CompletionResponse},
         nvext::NvExtProvider,
         DeltaGeneratorExt,
@@ -60,15 +66,45 @@ pub use crate::protocols::common::llm_backend::{BackendInput, BackendOutput};
 pub const ANNOTATION_FORMATTED_PROMPT: &str = "formatted_prompt";
 pub const ANNOTATION_TOKEN_IDS: &str = "token_ids";
 
+/// Key of the request [`dynamo_runtime::pipeline::Context`] entry holding a Jinja chat template
+/// which replaces the model's template. It is only set server side, e.g. from the per-model
+/// defaults of the HTTP service, never from the request body.
+pub const CONTEXT_CHAT_TEMPLATE: &str = "chat_template";
+
 pub struct OpenAIPreprocessor {
     mdcsum: String,
     formatter: Arc<dyn OAIPromptFormatter>,
     tokenizer: Arc<dyn Tokenizer>,
     model_info: Arc<dyn ModelInfo>,
+    reasoning_parser: Option<ReasoningParserType>,
+    /// Only present if the model's deployment card declares it multimodal
+    media_loader: Option<MediaLoader>,
+    placeholder_expander: Option<Arc<dyn PlaceholderExpander>>,
 }
 
 impl OpenAIPreprocessor {
     pub async fn new(mdc: ModelDeploymentCard) -> Result<Arc<Self>> {
+        let placeholder_expander = mdc
+            .multimodal
+            .as_ref()
+            .and_then(RepeatPlaceholder::new)
+            .map(|expander| Arc::new(expander) as Arc<dyn PlaceholderExpander>);
+        Ok(Arc::new(Self::from_mdc(mdc, placeholder_expander).await?))
+    }
+
+    /// Create a preprocessor for a multimodal model whose placeholder tokens need more than
+    /// [`RepeatPlaceholder`], e.g. a token count that depends on the size of each image
+    pub async fn with_placeholder_expander(
+        mdc: ModelDeploymentCard,
+        expander: Arc<dyn PlaceholderExpander>,
+    ) -> Result<Arc<Self>> {
+        Ok(Arc::new(Self::from_mdc(mdc, Some(expander)).await?))
+    }
+
+    async fn from_mdc(
+        mdc: ModelDeploymentCard,
+        placeholder_expander: Option<Arc<dyn PlaceholderExpander>>,
+    ) -> Result<Self> {
         let formatter = PromptFormatter::from_mdc(mdc.clone()).await?;
         let PromptFormatter::OAI(formatter) = formatter;
 
@@ -84,12 +120,17 @@ impl OpenAIPreprocessor {
 
         let mdcsum = mdc.mdcsum();
 
-        Ok(Arc::new(Self {
+        let media_loader = mdc.multimodal.as_ref().map(MediaLoader::new).transpose()?;
+
+        Ok(Self {
             formatter,
             tokenizer,
             model_info,
             mdcsum,
-        }))
+            reasoning_parser: mdc.reasoning_parser,
+            media_loader,
+            placeholder_expander,
+        })
     }
 
     /// Encode a string to it's tokens
@@ -97,9 +138,30 @@ impl OpenAIPreprocessor {
         self.tokenizer.encode(s)
     }
 
+    /// Decode token ids to a string
+    pub fn detokenize(
+        &self,
+        token_ids: &[TokenIdType],
+        skip_special_tokens: bool,
+    ) -> anyhow::Result<String> {
+        self.tokenizer.decode(token_ids, skip_special_tokens)
+    }
+
+    /// Render the messages of a request with the model's chat template
+    pub fn render(&self, request: &dyn OAIChatLikeRequest) -> anyhow::Result<String> {
+        self.formatter.render(request)
+    }
+
+    /// Maximum number of tokens in a sequence of the model
+    pub fn context_length(&self) -> usize {
+        self.model_info.max_position_embeddings()
+    }
+
     /// Translate a [`NvCreateChatCompletionRequest`] request to a common completion request.
     /// Returns both the common completion request and a hashmap of annotations.
     ///
+    /// The prompt is rendered with `chat_template` instead of the model's template when set.
+    ///
     /// Annotations evaluated by this method include:
     /// - `formatted_prompt`
     /// - `token_ids`
@@ -112,6 +174,7 @@ impl OpenAIPreprocessor {
     >(
         &self,
         request: &R,
+        chat_template: Option<&str>,
     ) -> Result<(BackendInput, HashMap<String, String>)> {
         let mut annotations = HashMap::new();
         let mut builder = BackendInput::builder();
@@ -120,16 +183,21 @@ impl OpenAIPreprocessor {
             .nvext()
             .is_some_and(|ext| ext.use_raw_prompt.unwrap_or(false));
 
+        let render = || match chat_template {
+            Some(template) => self.formatter.render_with_template(request, template),
+            None => self.formatter.render(request),
+        };
+
         let formatted_prompt = if use_raw_prompt {
             match request.raw_prompt() {
                 Some(prompt) => prompt,
                 None => {
                     tracing::warn!("Raw prompt requested but not available");
-                    self.formatter.render(request)?
+                    render()?
                 }
             }
         } else {
-            self.formatter.render(request)?
+            render()?
         };
 
         let encoding = tokio::task::block_in_place(|| self.tokenizer.encode(&formatted_prompt))?;
@@ -172,6 +240,23 @@ impl OpenAIPreprocessor {
         Ok((builder.build()?, annotations))
     }
 
+    /// Attach the media of a request to its preprocessed form, expanding the placeholder tokens
+    /// of the media in the prompt
+    pub fn attach_media(
+        &self,
+        request: &mut BackendInput,
+        mut media: Vec<MultiModalItem>,
+    ) -> Result<()> {
+        if media.is_empty() {
+            return Ok(());
+        }
+        if let Some(expander) = &self.placeholder_expander {
+            expander.expand(&mut request.token_ids, &mut media)?;
+        }
+        request.multi_modal_data = media;
+        Ok(())
+    }
+
     pub fn transform_postprocessor_stream<Resp: Send + Sync + 'static + std::fmt::Debug>(
         stream: ManyOut<Annotated<BackendOutput>>,
         generator: Box<dyn DeltaGeneratorExt<Resp>>,
@@ -183,6 +268,7 @@ impl OpenAIPreprocessor {
             response_generator: Box<dyn DeltaGeneratorExt<Resp>>,
             context: Arc<dyn AsyncEngineContext>,
             cancelled: bool,
+            finished: bool,
         }
 
         let state = State {
@@ -190,11 +276,17 @@ impl OpenAIPreprocessor {
             response_generator: generator,
             context: context.clone(),
             cancelled: false,
+            finished: false,
         };
 
         // transform the common response stream into a chat response stream
         let stream = stream::unfold(state, |mut inner| {
             async move {
+                // the response stream is not polled again once it has ended
+                if inner.finished {
+                    return None;
+                }
+
                 if let Some(response) = inner.response_stream.next().await {
                     if inner.cancelled {
                         tracing::debug!(
@@ -233,6 +325,11 @@ impl OpenAIPreprocessor {
                     );
 
                     Some((response, inner))
+                } else if !inner.cancelled {
+                    // end the stream with the token usage, if requested
+                    inner.finished = true;
+                    let usage = inner.response_generator.usage_chunk()?;
+                    Some((Annotated::from_data(usage), inner))
                 } else {
                     // stream closed with out graceful closure
                     // we did not detect an is_finished/completed message
@@ -274,8 +371,22 @@ impl
         let response_generator = request.response_generator();
         let mut response_generator = Box::new(response_generator);
 
+        // the model's reasoning parser applies unless the request selected one
+        if let Some(parser_type) = &self.reasoning_parser {
+            response_generator.default_reasoning_parser(parser_type);
+        }
+
+        // load the media of the messages before any work on the prompt
+        let media = match &self.media_loader {
+            Some(loader) => loader.load(&request.inner.messages).await?,
+            None => Vec::new(),
+        };
+
         // convert the chat completion request to a common completion request
-        let (common_request, annotations) = self.preprocess_request(&request)?;
+        let chat_template = context.get::<String>(CONTEXT_CHAT_TEMPLATE).ok();
+        let (mut common_request, annotations) =
+            self.preprocess_request(&request, chat_template.as_deref().map(String::as_str))?;
+        self.attach_media(&mut common_request, media)?;
 
         // update isl
         response_generator.update_isl(common_request.token_ids.len() as u32);
@@ -328,7 +439,9 @@ impl
         let response_generator = request.response_generator();
         let mut response_generator = Box::new(response_generator);
         // convert the chat completion request to a common completion request
-        let (common_request, annotations) = self.preprocess_request(&request)?;
+        let chat_template = context.get::<String>(CONTEXT_CHAT_TEMPLATE).ok();
+        let (common_request, annotations) =
+            self.preprocess_request(&request, chat_template.as_deref().map(String::as_str))?;
 
         // update isl
         response_generator.update_isl(common_request.token_ids.len() as i32);
@@ -0,0 +1,627 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Media of multimodal chat requests
+//!
+//! The [`MediaLoader`] reads the `image_url` and `input_audio` content parts of chat messages into
+//! [`MultiModalItem`]s: data URIs are decoded and, if the model's [`MultiModalConfig`] allows it,
+//! `http(s)` URLs are fetched. A [`PlaceholderExpander`] then locates the tokens of each item in
+//! the prompt, expanding the placeholder rendered by the chat template to the number of tokens the
+//! model's encoder produces for the item.
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::StreamExt;
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect, Url,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::protocols::common::preprocessor::{MediaKind, MultiModalItem, PlaceholderRange};
+use crate::protocols::TokenIdType;
+
+/// Default upper bound of the size of a media item: 20 MiB
+pub const DEFAULT_MAX_MEDIA_BYTES: usize = 20 * 1024 * 1024;
+
+/// Time allowed to fetch a media item from a URL
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Redirects followed when fetching a media item
+const MAX_REDIRECTS: usize = 10;
+
+/// Invalid media in a request, which is the client's error. The HTTP service responds to it with
+/// a 400.
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct MediaError(pub String);
+
+/// How the media of chat messages is passed to a multimodal model
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MultiModalConfig {
+    /// Placeholder token of images in the rendered prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_placeholder: Option<PlaceholderExpansion>,
+
+    /// Placeholder token of audio in the rendered prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_placeholder: Option<PlaceholderExpansion>,
+
+    /// Fetch media referenced by `http(s)` URLs; otherwise only data URIs are accepted. URLs
+    /// which refer to loopback, private or link-local addresses are refused.
+    #[serde(default)]
+    pub fetch_http: bool,
+
+    /// Upper bound of the size of a media item in bytes
+    #[serde(default = "default_max_media_bytes")]
+    pub max_media_bytes: usize,
+}
+
+fn default_max_media_bytes() -> usize {
+    DEFAULT_MAX_MEDIA_BYTES
+}
+
+impl Default for MultiModalConfig {
+    fn default() -> Self {
+        Self {
+            image_placeholder: None,
+            audio_placeholder: None,
+            fetch_http: false,
+            max_media_bytes: DEFAULT_MAX_MEDIA_BYTES,
+        }
+    }
+}
+
+/// A placeholder token which the chat template renders once per media item
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaceholderExpansion {
+    pub token_id: TokenIdType,
+
+    /// Number of tokens the placeholder is expanded to, e.g. the number of patch embeddings of
+    /// the vision encoder; 1 leaves the prompt unchanged
+    pub tokens_per_item: usize,
+}
+
+/// Locates, and expands as needed, the placeholder tokens of each media item in the prompt, setting
+/// [`MultiModalItem::placeholder`].
+///
+/// The default is [`RepeatPlaceholder`]; models whose token count depends on the media itself,
+/// e.g. on the size of an image, implement their own.
+pub trait PlaceholderExpander: Send + Sync {
+    fn expand(&self, token_ids: &mut Vec<TokenIdType>, items: &mut [MultiModalItem]) -> Result<()>;
+}
+
+/// Replaces each placeholder token with a fixed number of copies of itself. The n-th placeholder
+/// of a kind belongs to the n-th item of that kind.
+pub struct RepeatPlaceholder {
+    image: Option<PlaceholderExpansion>,
+    audio: Option<PlaceholderExpansion>,
+}
+
+impl RepeatPlaceholder {
+    /// Returns None if the config has no placeholders
+    pub fn new(config: &MultiModalConfig) -> Option<Self> {
+        if config.image_placeholder.is_none() && config.audio_placeholder.is_none() {
+            return None;
+        }
+        Some(Self {
+            image: config.image_placeholder,
+            audio: config.audio_placeholder,
+        })
+    }
+
+    fn expansion(&self, kind: MediaKind) -> Option<PlaceholderExpansion> {
+        match kind {
+            MediaKind::Image => self.image,
+            MediaKind::Audio => self.audio,
+        }
+    }
+}
+
+impl PlaceholderExpander for RepeatPlaceholder {
+    fn expand(&self, token_ids: &mut Vec<TokenIdType>, items: &mut [MultiModalItem]) -> Result<()> {
+        let kinds = [MediaKind::Image, MediaKind::Audio];
+        // the items of each kind, in order
+        let mut queues = kinds.map(|kind| {
+            items
+                .iter()
+                .enumerate()
+                .filter(|(_, item)| item.kind == kind)
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>()
+                .into_iter()
+        });
+
+        let mut expanded = Vec::with_capacity(token_ids.len());
+        for &token_id in token_ids.iter() {
+            let placeholder = kinds.iter().position(|&kind| {
+                self.expansion(kind)
+                    .is_some_and(|expansion| expansion.token_id == token_id)
+            });
+            let Some(position) = placeholder else {
+                expanded.push(token_id);
+                continue;
+            };
+
+            let kind = kinds[position];
+            let expansion = self.expansion(kind).unwrap();
+            let Some(index) = queues[position].next() else {
+                return Err(bad_request(format!(
+                    "The prompt has more {} placeholders than the request has media",
+                    kind_name(kind)
+                )));
+            };
+            items[index].placeholder = Some(PlaceholderRange {
+                offset: expanded.len(),
+                length: expansion.tokens_per_item,
+            });
+            expanded.extend(std::iter::repeat_n(token_id, expansion.tokens_per_item));
+        }
+
+        for (position, mut queue) in queues.into_iter().enumerate() {
+            let kind = kinds[position];
+            if self.expansion(kind).is_some() && queue.next().is_some() {
+                return Err(bad_request(format!(
+                    "The request has more {} media than the prompt has placeholders",
+                    kind_name(kind)
+                )));
+            }
+        }
+
+        *token_ids = expanded;
+        Ok(())
+    }
+}
+
+/// Reads the media of chat messages
+pub struct MediaLoader {
+    max_media_bytes: usize,
+
+    /// Only present if fetching `http(s)` URLs is allowed
+    client: Option<reqwest::Client>,
+
+    /// Allow fetching from loopback, private and link-local addresses; only set by tests
+    allow_private_hosts: bool,
+}
+
+impl MediaLoader {
+    pub fn new(config: &MultiModalConfig) -> Result<Self> {
+        let client = if config.fetch_http {
+            // clients must not reach the service's own network, neither directly, through a
+            // name which resolves into it, nor through a redirect
+            let redirects = redirect::Policy::custom(|attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    attempt.error("too many redirects")
+                } else if is_private_url(attempt.url()) {
+                    attempt.error("redirect to a private address")
+                } else {
+                    attempt.follow()
+                }
+            });
+            Some(
+                reqwest::Client::builder()
+                    .timeout(FETCH_TIMEOUT)
+                    .redirect(redirects)
+                    .dns_resolver(Arc::new(PublicResolver))
+                    .build()?,
+            )
+        } else {
+            None
+        };
+        Ok(Self {
+            max_media_bytes: config.max_media_bytes,
+            client,
+            allow_private_hosts: false,
+        })
+    }
+
+    /// Load the media of the content parts of chat messages, in order
+    pub async fn load<T: Serialize>(&self, messages: &T) -> Result<Vec<MultiModalItem>> {
+        let messages = serde_json::to_value(messages)?;
+
+        let parts = messages
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|message| message.get("content")?.as_array())
+            .flatten()
+            .filter_map(|part| Some((media_kind(part)?, part)));
+
+        let mut items = Vec::new();
+        for (kind, part) in parts {
+            let item = match kind {
+                MediaKind::Image => {
+                    let url = part
+                        .pointer("/image_url/url")
+                        .and_then(|url| url.as_str())
+                        .ok_or_else(|| bad_request("image_url part without a url".to_string()))?;
+                    self.load_url(kind, url).await?
+                }
+                MediaKind::Audio => {
+                    let data = part
+                        .pointer("/input_audio/data")
+                        .and_then(|data| data.as_str())
+                        .ok_or_else(|| bad_request("input_audio part without data".to_string()))?;
+                    let format = part
+                        .pointer("/input_audio/format")
+                        .and_then(|format| format.as_str());
+                    let data = STANDARD.decode(data).map_err(|e| {
+                        bad_request(format!("input_audio data is not valid base64: {e}"))
+                    })?;
+                    let mime_type = format.map(|format| match format {
+                        "mp3" => "audio/mpeg".to_string(),
+                        format => format!("audio/{format}"),
+                    });
+                    MultiModalItem::new(kind, mime_type, data)
+                }
+            };
+
+            if item.data.len() > self.max_media_bytes {
+                return Err(self.too_large(kind));
+            }
+            items.push(item);
+        }
+
+        Ok(items)
+    }
+
+    async fn load_url(&self, kind: MediaKind, url: &str) -> Result<MultiModalItem> {
+        if url.starts_with("data:") {
+            let (mime_type, data) = decode_data_uri(url)?;
+            return Ok(MultiModalItem::new(kind, mime_type, data));
+        }
+
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(bad_request(
+                "Media URLs must be data URIs or http(s) URLs".to_string(),
+            ));
+        }
+        let Some(client) = &self.client else {
+            return Err(bad_request(
+                "Fetching media from http(s) URLs is disabled for this model; use a data URI"
+                    .to_string(),
+            ));
+        };
+        let parsed = Url::parse(url).map_err(|e| bad_request(format!("Invalid media URL: {e}")))?;
+        if !self.allow_private_hosts && is_private_url(&parsed) {
+            return Err(bad_request(
+                "Media URLs must not refer to private addresses".to_string(),
+            ));
+        }
+
+        let response = client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| bad_request(format!("Failed to fetch media: {e}")))?;
+
+        if response
+            .content_length()
+            .is_some_and(|length| length > self.max_media_bytes as u64)
+        {
+            return Err(self.too_large(kind));
+        }
+
+        let mime_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        // the content length is only a hint, so the download is cut off at the limit
+        let mut data = Vec::new();
+        let mut body = response.bytes_stream();
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.map_err(|e| bad_request(format!("Failed to fetch media: {e}")))?;
+            if data.len() + chunk.len() > self.max_media_bytes {
+                return Err(self.too_large(kind));
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        Ok(MultiModalItem::new(kind, mime_type, data))
+    }
+
+    fn too_large(&self, kind: MediaKind) -> anyhow::Error {
+        bad_request(format!(
+            "{} media exceeds the limit of {} bytes",
+            kind_name(kind),
+            self.max_media_bytes
+        ))
+    }
+}
+
+/// Resolves host names to their public addresses only, so that a name cannot be used to reach
+/// the service's own network
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| !is_private_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// True if the host of `url` is a loopback, private or link-local IP address. Host names are
+/// checked when they are resolved, by [`PublicResolver`].
+fn is_private_url(url: &Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return true;
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host.parse::<IpAddr>().is_ok_and(is_private_ip)
+}
+
+/// True if `ip` is a loopback, unspecified, private or link-local address
+fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                // shared address space, RFC 6598
+                || (ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_ip(IpAddr::V4(ip)),
+            None => {
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+            }
+        },
+    }
+}
+
+/// Kind of the media of a chat message content part, if it holds media
+pub(crate) fn media_kind(part: &serde_json::Value) -> Option<MediaKind> {
+    match part.get("type")?.as_str()? {
+        "image_url" => Some(MediaKind::Image),
+        "input_audio" => Some(MediaKind::Audio),
+        _ => None,
+    }
+}
+
+/// Decode a base64 data URI, `data:[<media type>][;<parameter>...];base64,<data>`, into its MIME
+/// type and bytes
+pub fn decode_data_uri(uri: &str) -> Result<(Option<String>, Vec<u8>)> {
+    let (header, data) = uri
+        .strip_prefix("data:")
+        .and_then(|uri| uri.split_once(','))
+        .ok_or_else(|| bad_request("Malformed data URI".to_string()))?;
+
+    let mut params = header.split(';');
+    let mime_type = params
+        .next()
+        .filter(|mime_type| !mime_type.is_empty())
+        .map(str::to_string);
+    if !params.any(|param| param == "base64") {
+        return Err(bad_request(
+            "Only base64 data URIs are supported".to_string(),
+        ));
+    }
+
+    let data = STANDARD
+        .decode(data)
+        .map_err(|e| bad_request(format!("Data URI is not valid base64: {e}")))?;
+    Ok((mime_type, data))
+}
+
+fn kind_name(kind: MediaKind) -> &'static str {
+    match kind {
+        MediaKind::Image => "image",
+        MediaKind::Audio => "audio",
+    }
+}
+
+fn bad_request(message: String) -> anyhow::Error {
+    MediaError(message).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const IMAGE_TOKEN: TokenIdType = 99;
+
+    fn image(data: &[u8]) -> MultiModalItem {
+        MultiModalItem::new(MediaKind::Image, None, data.to_vec())
+    }
+
+    #[test]
+    fn test_decode_data_uri() {
+        let (mime_type, data) = decode_data_uri("data:image/png;base64,aGVsbG8=").unwrap();
+        assert_eq!(mime_type.as_deref(), Some("image/png"));
+        assert_eq!(data, b"hello");
+
+        assert!(decode_data_uri("data:image/png,hello").is_err());
+        assert!(decode_data_uri("data:image/png;base64,!!!").is_err());
+        assert!(decode_data_uri("image/png;base64,aGVsbG8=").is_err());
+    }
+
+    #[test]
+    fn test_repeat_placeholder() {
+        let config = MultiModalConfig {
+            image_placeholder: Some(PlaceholderExpansion {
+                token_id: IMAGE_TOKEN,
+                tokens_per_item: 3,
+            }),
+            ..Default::default()
+        };
+        let expander = RepeatPlaceholder::new(&config).unwrap();
+
+        let mut token_ids = vec![1, IMAGE_TOKEN, 2, IMAGE_TOKEN];
+        let mut items = vec![image(b"a"), image(b"b")];
+        expander.expand(&mut token_ids, &mut items).unwrap();
+
+        assert_eq!(token_ids, [1, 99, 99, 99, 2, 99, 99, 99]);
+        assert_eq!(
+            items[0].placeholder,
+            Some(PlaceholderRange {
+                offset: 1,
+                length: 3
+            })
+        );
+        assert_eq!(
+            items[1].placeholder,
+            Some(PlaceholderRange {
+                offset: 5,
+                length: 3
+            })
+        );
+
+        // the number of placeholders must match the number of images
+        let mut token_ids = vec![1, IMAGE_TOKEN];
+        let mut items = vec![image(b"a"), image(b"b")];
+        assert!(expander.expand(&mut token_ids, &mut items).is_err());
+
+        let mut token_ids = vec![IMAGE_TOKEN, IMAGE_TOKEN];
+        let mut items = vec![image(b"a")];
+        assert!(expander.expand(&mut token_ids, &mut items).is_err());
+
+        assert!(RepeatPlaceholder::new(&MultiModalConfig::default()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_load() {
+        let loader = MediaLoader::new(&MultiModalConfig::default()).unwrap();
+        let messages = json!([
+            { "role": "system", "content": "You are helpful." },
+            { "role": "user", "content": [
+                { "type": "text", "text": "Compare" },
+                { "type": "image_url", "image_url": { "url": "data:image/png;base64,aGVsbG8=" } },
+                { "type": "input_audio", "input_audio": { "data": "d29ybGQ=", "format": "mp3" } },
+                { "type": "image_url", "image_url": { "url": "data:image/png;base64,aGVsbG8=" } }
+            ]}
+        ]);
+
+        let items = loader.load(&messages).await.unwrap();
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].kind, MediaKind::Image);
+        assert_eq!(items[0].data, b"hello");
+        assert_eq!(items[1].kind, MediaKind::Audio);
+        assert_eq!(items[1].mime_type.as_deref(), Some("audio/mpeg"));
+        assert_eq!(items[1].data, b"world");
+        // the same media has the same hash
+        assert_eq!(items[0].hash, items[2].hash);
+        assert_ne!(items[0].hash, items[1].hash);
+
+        // fetching URLs is opt-in
+        let messages = json!([{ "role": "user", "content": [
+            { "type": "image_url", "image_url": { "url": "https://example.com/cat.png" } }
+        ]}]);
+        let err = loader.load(&messages).await.unwrap_err();
+        assert!(err.downcast_ref::<MediaError>().is_some());
+
+        let loader = MediaLoader::new(&MultiModalConfig {
+            max_media_bytes: 4,
+            ..Default::default()
+        })
+        .unwrap();
+        let messages = json!([{ "role": "user", "content": [
+            { "type": "image_url", "image_url": { "url": "data:image/png;base64,aGVsbG8=" } }
+        ]}]);
+        assert!(loader.load(&messages).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_load_url_limit() {
+        // a chunked body has no content length, so the limit is enforced while downloading
+        let app = axum::Router::new().route(
+            "/media",
+            axum::routing::get(|| async {
+                let chunks = ["hel", "lo"].map(Ok::<_, std::io::Error>);
+                axum::body::Body::from_stream(futures::stream::iter(chunks))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/media", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let loader = |max_media_bytes| {
+            let mut loader = MediaLoader::new(&MultiModalConfig {
+                fetch_http: true,
+                max_media_bytes,
+                ..Default::default()
+            })
+            .unwrap();
+            loader.allow_private_hosts = true;
+            loader
+        };
+
+        let item = loader(5).load_url(MediaKind::Image, &url).await.unwrap();
+        assert_eq!(item.data, b"hello");
+
+        let err = loader(4)
+            .load_url(MediaKind::Image, &url)
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<MediaError>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_load_url_private() {
+        let loader = MediaLoader::new(&MultiModalConfig {
+            fetch_http: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+        for url in [
+            "http://127.0.0.1/cat.png",
+            "http://10.1.2.3/cat.png",
+            "http://192.168.0.1/cat.png",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://[::1]/cat.png",
+            "http://[fe80::1]/cat.png",
+            "http://[::ffff:127.0.0.1]/cat.png",
+            // names are checked once resolved
+            "http://localhost/cat.png",
+        ] {
+            let err = loader.load_url(MediaKind::Image, url).await.unwrap_err();
+            assert!(err.downcast_ref::<MediaError>().is_some(), "{url}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_is_private_ip() {
+        for ip in ["8.8.8.8", "100.128.0.1", "2001:4860:4860::8888"] {
+            assert!(!is_private_ip(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["0.0.0.0", "172.16.0.1", "100.64.0.1", "fd00::1", "::"] {
+            assert!(is_private_ip(ip.parse().unwrap()), "{ip}");
+        }
+    }
+}
@@ -54,6 +54,15 @@ pub trait OAIChatLikeRequest {
 pub trait OAIPromptFormatter: Send + Sync + 'static {
     fn supports_add_generation_prompt(&self) -> bool;
     fn render(&self, req: &dyn OAIChatLikeRequest) -> Result<String>;
+
+    /// Render the request with the Jinja `template` instead of the model's chat template
+    fn render_with_template(
+        &self,
+        _req: &dyn OAIChatLikeRequest,
+        _template: &str,
+    ) -> Result<String> {
+        anyhow::bail!("This prompt formatter does not support chat template overrides")
+    }
 }
 
 pub enum PromptFormatter {
@@ -17,14 +17,58 @@ use super::*;
 
 use minijinja::{context, value::Value};
 
+use crate::preprocessor::media::media_kind;
+use crate::protocols::common::preprocessor::MediaKind;
 use crate::protocols::openai::{
     chat_completions::NvCreateChatCompletionRequest, completions::CompletionRequest,
+    tokenize::TokenizeRequest,
 };
 use tracing;
 
+/// Messages as chat templates expect them.
+///
+/// Content-part arrays without media are joined into a string, which is all most templates
+/// handle. Media parts become the `{"type": "image"}` and `{"type": "audio"}` parts of
+/// multimodal templates, which render the model's placeholder token for them.
+fn template_messages<T: serde::Serialize>(messages: &T) -> Value {
+    let mut messages = match serde_json::to_value(messages) {
+        Ok(messages) => messages,
+        Err(_) => return Value::from_serialize(messages),
+    };
+
+    for message in messages.as_array_mut().into_iter().flatten() {
+        let Some(content) = message.get_mut("content") else {
+            continue;
+        };
+        let Some(parts) = content.as_array_mut() else {
+            continue;
+        };
+
+        if parts.iter().all(|part| media_kind(part).is_none()) {
+            let text = parts
+                .iter()
+                .filter_map(|part| part.get("text").and_then(|text| text.as_str()))
+                .collect::<Vec<_>>()
+                .join("\n");
+            *content = serde_json::Value::String(text);
+            continue;
+        }
+
+        for part in parts.iter_mut() {
+            match media_kind(part) {
+                Some(MediaKind::Image) => *part = serde_json::json!({ "type": "image" }),
+                Some(MediaKind::Audio) => *part = serde_json::json!({ "type": "audio" }),
+                None => {}
+            }
+        }
+    }
+
+    Value::from_serialize(&messages)
+}
+
 impl OAIChatLikeRequest for NvCreateChatCompletionRequest {
     fn messages(&self) -> Value {
-        Value::from_serialize(&self.inner.messages)
+        template_messages(&self.inner.messages)
     }
 
     fn tools(&self) -> Option<Value> {
@@ -79,12 +123,56 @@ impl OAIChatLikeRequest for CompletionRequest {
     }
 }
 
+impl OAIChatLikeRequest for TokenizeRequest {
+    fn messages(&self) -> Value {
+        match &self.messages {
+            Some(messages) => template_messages(messages),
+            // a prompt is rendered as a single user message
+            None => {
+                let message = async_openai::types::ChatCompletionRequestMessage::User(
+                    async_openai::types::ChatCompletionRequestUserMessage {
+                        content: async_openai::types::ChatCompletionRequestUserMessageContent::Text(
+                            self.prompt.clone().unwrap_or_default(),
+                        ),
+                        name: None,
+                    },
+                );
+                Value::from_serialize(vec![message])
+            }
+        }
+    }
+
+    fn should_add_generation_prompt(&self) -> bool {
+        self.add_generation_prompt.unwrap_or(true)
+    }
+}
+
 impl OAIPromptFormatter for HfTokenizerConfigJsonFormatter {
     fn supports_add_generation_prompt(&self) -> bool {
         self.supports_add_generation_prompt
     }
 
     fn render(&self, req: &dyn OAIChatLikeRequest) -> Result<String> {
+        let ctx = self.context(req);
+
+        let tmpl = if req.tools().is_some() {
+            self.env.get_template("tool_use")?
+        } else {
+            self.env.get_template("default")?
+        };
+
+        Ok(tmpl.render(&ctx)?)
+    }
+
+    fn render_with_template(&self, req: &dyn OAIChatLikeRequest, template: &str) -> Result<String> {
+        let ctx = self.context(req);
+        Ok(self.env.render_str(template, &ctx)?)
+    }
+}
+
+impl HfTokenizerConfigJsonFormatter {
+    /// Template context of a request
+    fn context(&self, req: &dyn OAIChatLikeRequest) -> Value {
         let mixins = Value::from_dyn_object(self.mixins.clone());
 
         let tools = req.tools();
@@ -107,16 +195,8 @@ impl OAIPromptFormatter for HfTokenizerConfigJsonFormatter {
             ..mixins
         };
 
-        let ctx = context! { ..ctx, ..context! {
-
-        }};
+        context! { ..ctx, ..context! {
 
-        let tmpl = if has_tools {
-            self.env.get_template("tool_use")?
-        } else {
-            self.env.get_template("default")?
-        };
-
-        Ok(tmpl.render(&ctx)?)
+        }}
     }
 }
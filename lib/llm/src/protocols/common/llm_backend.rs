@@ -44,6 +44,10 @@ pub struct BackendOutput {
     // TODO: Enrich this with more information as can apply our first-level postprocessing
     // logic and return more detailed information
     pub finish_reason: Option<FinishReason>,
+
+    /// Number of prompt tokens served from the engine's prefix cache, if the engine reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_tokens: Option<u32>,
     // Model Deployment Card checksum
     //pub mdcsum: String,
 }
@@ -77,6 +81,10 @@ pub struct LLMEngineOutput {
     // TODO: Enrich this with more information as can apply our first-level postprocessing
     // logic and return more detailed information
     pub finish_reason: Option<FinishReason>,
+
+    /// Number of prompt tokens served from the engine's prefix cache, if the engine reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_tokens: Option<u32>,
 }
 
 impl LLMEngineOutput {
@@ -88,6 +96,7 @@ impl LLMEngineOutput {
             cum_log_probs: None,
             log_probs: None,
             finish_reason: Some(FinishReason::Cancelled),
+            cached_tokens: None,
         }
     }
 
@@ -99,6 +108,7 @@ impl LLMEngineOutput {
             cum_log_probs: None,
             log_probs: None,
             finish_reason: Some(FinishReason::Stop),
+            cached_tokens: None,
         }
     }
 
@@ -110,6 +120,7 @@ impl LLMEngineOutput {
             cum_log_probs: None,
             log_probs: None,
             finish_reason: Some(FinishReason::Length),
+            cached_tokens: None,
         }
     }
 
@@ -121,6 +132,7 @@ impl LLMEngineOutput {
             cum_log_probs: None,
             log_probs: None,
             finish_reason: Some(FinishReason::Error(err_msg)),
+            cached_tokens: None,
         }
     }
 }
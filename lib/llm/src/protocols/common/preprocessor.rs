@@ -17,6 +17,7 @@ use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 
 use super::{SamplingOptions, StopConditions};
+use crate::kv_router::indexer::compute_hash;
 use crate::protocols::TokenIdType;
 
 /// [`PreprocessedRequest`] is the internal representation of an LLM request. The [`dynamo.llm-preprocessor`]
@@ -47,6 +48,11 @@ pub struct PreprocessedRequest {
     /// User requested annotations for the request
     #[builder(default)]
     pub annotations: Vec<String>,
+
+    /// Media of a multimodal request, in the order of their placeholders in the prompt
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub multi_modal_data: Vec<MultiModalItem>,
 }
 
 impl PreprocessedRequest {
@@ -60,3 +66,52 @@ impl PreprocessedRequest {
         PreprocessedRequestBuilder::default()
     }
 }
+
+/// Kind of a media item
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    Image,
+    Audio,
+}
+
+/// A media item of a multimodal request, e.g. an image of a chat message
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MultiModalItem {
+    pub kind: MediaKind,
+
+    /// MIME type given by the data URI, the HTTP response or the audio format, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+
+    /// The encoded media, e.g. the bytes of a PNG file; base64 encoded when serialized
+    #[serde(with = "crate::protocols::base64_bytes")]
+    pub data: Vec<u8>,
+
+    /// Hash of `data`, which is mixed into the KV block hashes of the item's tokens
+    pub hash: u64,
+
+    /// Tokens of the item in `token_ids`, if the placeholder of the model is known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placeholder: Option<PlaceholderRange>,
+}
+
+impl MultiModalItem {
+    pub fn new(kind: MediaKind, mime_type: Option<String>, data: Vec<u8>) -> Self {
+        let hash = compute_hash(&data);
+        Self {
+            kind,
+            mime_type,
+            data,
+            hash,
+            placeholder: None,
+        }
+    }
+}
+
+/// Span of the placeholder tokens of a media item
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaceholderRange {
+    pub offset: usize,
+    pub length: usize,
+}
@@ -17,6 +17,8 @@ pub mod chat_completions;
 pub mod completions;
 pub mod models;
 pub mod nvext;
+pub mod responses;
+pub mod tokenize;
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
@@ -306,6 +308,10 @@ pub trait DeltaGeneratorExt<ResponseType: Send + Sync + 'static + std::fmt::Debu
         &mut self,
         response: common::llm_backend::BackendOutput,
     ) -> Result<ResponseType>;
+
+    /// The final response of a stream, reporting the token usage of the request; `None` unless
+    /// the request set `stream_options.include_usage`
+    fn usage_chunk(&self) -> Option<ResponseType>;
 }
 
 #[cfg(test)]
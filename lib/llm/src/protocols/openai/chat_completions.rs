@@ -18,14 +18,19 @@ use super::nvext::NvExtProvider;
 use super::OpenAISamplingOptionsProvider;
 use super::OpenAIStopConditionsProvider;
 use dynamo_runtime::protocols::annotated::AnnotationsProvider;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
 use validator::Validate;
 
 mod aggregator;
 mod delta;
+mod reasoning;
 
 pub use aggregator::DeltaAggregator;
 pub use delta::DeltaGenerator;
+pub use reasoning::{
+    ParsedDelta, ReasoningParser, ReasoningParserType, ReasoningTags, REASONING_CONTENT,
+};
 
 /// A request structure for creating a chat completion, extending OpenAI's
 /// `CreateChatCompletionRequest` with [`NvExt`] extensions.
@@ -47,24 +52,70 @@ pub struct NvCreateChatCompletionRequest {
 /// `CreateChatCompletionResponse`.
 ///
 /// # Fields
-/// - `inner`: The base OpenAI unary chat completion response, embedded
-///   using `serde(flatten)`.
-#[derive(Serialize, Deserialize, Validate, Debug, Clone)]
+/// - `inner`: The base OpenAI unary chat completion response.
+/// - `reasoning_content`: The reasoning text of each choice, keyed by choice index. It is
+///   serialized as the `reasoning_content` field of the choice's `message`.
+#[derive(Validate, Debug, Clone)]
 pub struct NvCreateChatCompletionResponse {
-    #[serde(flatten)]
     pub inner: async_openai::types::CreateChatCompletionResponse,
+    pub reasoning_content: HashMap<u32, String>,
 }
 
 /// A response structure for streamed chat completions, embedding OpenAI's
 /// `CreateChatCompletionStreamResponse`.
 ///
 /// # Fields
-/// - `inner`: The base OpenAI streaming chat completion response, embedded
-///   using `serde(flatten)`.
-#[derive(Serialize, Deserialize, Validate, Debug, Clone)]
+/// - `inner`: The base OpenAI streaming chat completion response.
+/// - `reasoning_content`: The reasoning text of each choice, keyed by choice index. It is
+///   serialized as the `reasoning_content` field of the choice's `delta`.
+#[derive(Validate, Debug, Clone)]
 pub struct NvCreateChatCompletionStreamResponse {
-    #[serde(flatten)]
     pub inner: async_openai::types::CreateChatCompletionStreamResponse,
+    pub reasoning_content: HashMap<u32, String>,
+}
+
+impl Serialize for NvCreateChatCompletionResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        reasoning::serialize_with_reasoning(
+            &self.inner,
+            &self.reasoning_content,
+            "message",
+            serializer,
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for NvCreateChatCompletionResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (inner, reasoning_content) =
+            reasoning::deserialize_with_reasoning("message", deserializer)?;
+        Ok(Self {
+            inner,
+            reasoning_content,
+        })
+    }
+}
+
+impl Serialize for NvCreateChatCompletionStreamResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        reasoning::serialize_with_reasoning(
+            &self.inner,
+            &self.reasoning_content,
+            "delta",
+            serializer,
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for NvCreateChatCompletionStreamResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (inner, reasoning_content) =
+            reasoning::deserialize_with_reasoning("delta", deserializer)?;
+        Ok(Self {
+            inner,
+            reasoning_content,
+        })
+    }
 }
 
 /// Implements `NvExtProvider` for `NvCreateChatCompletionRequest`,
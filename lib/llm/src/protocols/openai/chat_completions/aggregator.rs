@@ -53,6 +53,8 @@ struct DeltaChoice {
     index: u32,
     /// The accumulated text content for the choice.
     text: String,
+    /// The accumulated reasoning text for the choice.
+    reasoning: String,
     /// The role associated with this message (e.g., `system`, `user`, `assistant`).
     role: Option<async_openai::types::Role>,
     /// The reason the completion was finished (if applicable).
@@ -123,6 +125,7 @@ impl DeltaAggregator {
                     }
 
                     // Aggregate choices incrementally.
+                    let mut reasoning_content = delta.reasoning_content;
                     for choice in delta.inner.choices {
                         let state_choice =
                             aggregator
@@ -131,6 +134,7 @@ impl DeltaAggregator {
                                 .or_insert(DeltaChoice {
                                     index: choice.index,
                                     text: "".to_string(),
+                                    reasoning: "".to_string(),
                                     role: choice.delta.role,
                                     finish_reason: None,
                                     logprobs: choice.logprobs,
@@ -141,6 +145,11 @@ impl DeltaAggregator {
                             state_choice.text.push_str(content);
                         }
 
+                        // Append reasoning content if available.
+                        if let Some(reasoning) = reasoning_content.remove(&choice.index) {
+                            state_choice.reasoning.push_str(&reasoning);
+                        }
+
                         // Update finish reason if provided.
                         if let Some(finish_reason) = choice.finish_reason {
                             state_choice.finish_reason = Some(finish_reason);
@@ -158,6 +167,14 @@ impl DeltaAggregator {
             aggregator
         };
 
+        // Collect the reasoning text of the choices which have any.
+        let reasoning_content = aggregator
+            .choices
+            .values_mut()
+            .filter(|choice| !choice.reasoning.is_empty())
+            .map(|choice| (choice.index, std::mem::take(&mut choice.reasoning)))
+            .collect();
+
         // Extract aggregated choices and sort them by index.
         let mut choices: Vec<_> = aggregator
             .choices
@@ -179,7 +196,10 @@ impl DeltaAggregator {
             service_tier: aggregator.service_tier,
         };
 
-        let response = NvCreateChatCompletionResponse { inner };
+        let response = NvCreateChatCompletionResponse {
+            inner,
+            reasoning_content,
+        };
 
         Ok(response)
     }
@@ -278,7 +298,10 @@ mod tests {
             object: "chat.completion".to_string(),
         };
 
-        let data = NvCreateChatCompletionStreamResponse { inner };
+        let data = NvCreateChatCompletionStreamResponse {
+            inner,
+            reasoning_content: HashMap::new(),
+        };
 
         Annotated {
             data: Some(data),
@@ -420,7 +443,10 @@ mod tests {
             object: "chat.completion".to_string(),
         };
 
-        let data = NvCreateChatCompletionStreamResponse { inner: delta };
+        let data = NvCreateChatCompletionStreamResponse {
+            inner: delta,
+            reasoning_content: HashMap::new(),
+        };
 
         // Wrap it in Annotated and create a stream
         let annotated_delta = Annotated {
@@ -459,4 +485,42 @@ mod tests {
         );
         assert_eq!(choice1.message.role, async_openai::types::Role::Assistant);
     }
+
+    #[tokio::test]
+    async fn test_reasoning_content() {
+        let mut delta1 = create_test_delta(0, "", Some(async_openai::types::Role::Assistant), None);
+        delta1.data.as_mut().unwrap().inner.choices[0].delta.content = None;
+        delta1
+            .data
+            .as_mut()
+            .unwrap()
+            .reasoning_content
+            .insert(0, "Let me ".to_string());
+        let mut delta2 =
+            create_test_delta(0, "42", None, Some(async_openai::types::FinishReason::Stop));
+        delta2
+            .data
+            .as_mut()
+            .unwrap()
+            .reasoning_content
+            .insert(0, "think.".to_string());
+
+        let stream = Box::pin(stream::iter(vec![delta1, delta2]));
+        let response = DeltaAggregator::apply(stream).await.unwrap();
+
+        assert_eq!(
+            response.inner.choices[0].message.content.as_deref(),
+            Some("42")
+        );
+        assert_eq!(response.reasoning_content[&0], "Let me think.");
+
+        // the reasoning text is serialized into the choice's message
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(
+            json["choices"][0]["message"]["reasoning_content"],
+            "Let me think."
+        );
+        let response: NvCreateChatCompletionResponse = serde_json::from_value(json).unwrap();
+        assert_eq!(response.reasoning_content[&0], "Let me think.");
+    }
 }
@@ -13,8 +13,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::{NvCreateChatCompletionRequest, NvCreateChatCompletionStreamResponse};
+use super::{
+    NvCreateChatCompletionRequest, NvCreateChatCompletionStreamResponse, ReasoningParser,
+    ReasoningParserType,
+};
 use crate::protocols::common;
+use std::collections::HashMap;
 
 /// Provides a method for generating a [`DeltaGenerator`] from a chat completion request.
 impl NvCreateChatCompletionRequest {
@@ -24,8 +28,16 @@ impl NvCreateChatCompletionRequest {
     /// * [`DeltaGenerator`] configured with model name and response options.
     pub fn response_generator(&self) -> DeltaGenerator {
         let options = DeltaGeneratorOptions {
-            enable_usage: true,
+            enable_usage: self
+                .inner
+                .stream_options
+                .as_ref()
+                .is_some_and(|options| options.include_usage),
             enable_logprobs: self.inner.logprobs.unwrap_or(false),
+            reasoning_parser: self
+                .nvext
+                .as_ref()
+                .and_then(|nvext| nvext.reasoning_parser.clone()),
         };
 
         DeltaGenerator::new(self.inner.model.clone(), options)
@@ -35,10 +47,12 @@ impl NvCreateChatCompletionRequest {
 /// Configuration options for the [`DeltaGenerator`], controlling response behavior.
 #[derive(Debug, Clone, Default)]
 pub struct DeltaGeneratorOptions {
-    /// Determines whether token usage statistics should be included in the response.
+    /// Determines whether a final chunk with token usage statistics ends the response stream.
     pub enable_usage: bool,
     /// Determines whether log probabilities should be included in the response.
     pub enable_logprobs: bool,
+    /// Separates the reasoning span of the output into `reasoning_content` if set.
+    pub reasoning_parser: Option<ReasoningParserType>,
 }
 
 /// Generates incremental chat completion responses in a streaming fashion.
@@ -60,6 +74,10 @@ pub struct DeltaGenerator {
     usage: async_openai::types::CompletionUsage,
     /// Counter tracking the number of messages issued.
     msg_counter: u64,
+    /// Splits the output into reasoning and answer text, if enabled.
+    reasoning: Option<ReasoningParser>,
+    /// Number of completion tokens generated inside the reasoning span.
+    reasoning_tokens: u32,
     /// Configuration options for response generation.
     options: DeltaGeneratorOptions,
 }
@@ -89,6 +107,11 @@ impl DeltaGenerator {
             completion_tokens_details: None,
         };
 
+        let reasoning = options
+            .reasoning_parser
+            .as_ref()
+            .map(|parser_type| ReasoningParser::new(parser_type.tags()));
+
         Self {
             id: format!("chatcmpl-{}", uuid::Uuid::new_v4()),
             object: "chat.completion.chunk".to_string(),
@@ -98,6 +121,8 @@ impl DeltaGenerator {
             service_tier: None,
             usage,
             msg_counter: 0,
+            reasoning,
+            reasoning_tokens: 0,
             options,
         }
     }
@@ -110,6 +135,16 @@ impl DeltaGenerator {
         self.usage.prompt_tokens = isl;
     }
 
+    /// Separates the reasoning span with the given parser unless the request selected one.
+    ///
+    /// # Arguments
+    /// * `parser_type` - The reasoning parser configured for the model.
+    pub fn default_reasoning_parser(&mut self, parser_type: &ReasoningParserType) {
+        if self.reasoning.is_none() {
+            self.reasoning = Some(ReasoningParser::new(parser_type.tags()));
+        }
+    }
+
     /// Creates a choice within a chat completion response.
     ///
     /// # Arguments
@@ -157,14 +192,45 @@ impl DeltaGenerator {
             model: self.model.clone(),
             system_fingerprint: self.system_fingerprint.clone(),
             choices,
-            usage: if self.options.enable_usage {
-                Some(self.usage.clone())
-            } else {
-                None
-            },
+            usage: None,
             service_tier: self.service_tier.clone(),
         }
     }
+
+    /// Creates the final chunk of a response stream, which has no choices and reports the token
+    /// usage of the request.
+    ///
+    /// # Returns
+    /// * `Some` if usage statistics were requested, `None` otherwise.
+    pub fn create_usage_chunk(
+        &self,
+    ) -> Option<async_openai::types::CreateChatCompletionStreamResponse> {
+        if !self.options.enable_usage {
+            return None;
+        }
+
+        let mut usage = self.usage.clone();
+        usage.total_tokens = usage.prompt_tokens + usage.completion_tokens;
+        if self.reasoning.is_some() {
+            usage.completion_tokens_details = Some(async_openai::types::CompletionTokensDetails {
+                accepted_prediction_tokens: None,
+                audio_tokens: None,
+                reasoning_tokens: Some(self.reasoning_tokens),
+                rejected_prediction_tokens: None,
+            });
+        }
+
+        Some(async_openai::types::CreateChatCompletionStreamResponse {
+            id: self.id.clone(),
+            object: self.object.clone(),
+            created: self.created,
+            model: self.model.clone(),
+            system_fingerprint: self.system_fingerprint.clone(),
+            choices: vec![],
+            usage: Some(usage),
+            service_tier: self.service_tier.clone(),
+        })
+    }
 }
 
 /// Implements the [`crate::protocols::openai::DeltaGeneratorExt`] trait for [`DeltaGenerator`], allowing
@@ -184,9 +250,13 @@ impl crate::protocols::openai::DeltaGeneratorExt<NvCreateChatCompletionStreamRes
         &mut self,
         delta: crate::protocols::common::llm_backend::BackendOutput,
     ) -> anyhow::Result<NvCreateChatCompletionStreamResponse> {
-        // Aggregate token usage if enabled.
-        if self.options.enable_usage {
-            self.usage.completion_tokens += delta.token_ids.len() as u32;
+        // Aggregate token usage; it is only reported if enabled.
+        self.usage.completion_tokens += delta.token_ids.len() as u32;
+        if let Some(cached_tokens) = delta.cached_tokens {
+            self.usage.prompt_tokens_details = Some(async_openai::types::PromptTokensDetails {
+                audio_tokens: None,
+                cached_tokens: Some(cached_tokens),
+            });
         }
 
         // TODO: Implement log probabilities aggregation.
@@ -204,12 +274,39 @@ impl crate::protocols::openai::DeltaGeneratorExt<NvCreateChatCompletionStreamRes
             None => None,
         };
 
-        // Create the streaming response.
+        // Split the reasoning span from the answer; tokens which start or end inside the span
+        // are counted as reasoning tokens.
         let index = 0;
-        let stream_response = self.create_choice(index, delta.text, finish_reason, logprobs);
+        let mut text = delta.text;
+        let mut reasoning_content = HashMap::new();
+        if let Some(parser) = self.reasoning.as_mut() {
+            let was_reasoning = parser.in_reasoning();
+            let mut parsed = parser.push(text.as_deref().unwrap_or_default());
+            if finish_reason.is_some() {
+                parsed = parsed.chain(parser.finish());
+            }
+            if was_reasoning || parser.in_reasoning() {
+                self.reasoning_tokens += delta.token_ids.len() as u32;
+            }
+            text = parsed.content;
+            reasoning_content.extend(parsed.reasoning.map(|reasoning| (index, reasoning)));
+        }
+
+        // Create the streaming response.
+        let stream_response = self.create_choice(index, text, finish_reason, logprobs);
 
         Ok(NvCreateChatCompletionStreamResponse {
             inner: stream_response,
+            reasoning_content,
         })
     }
+
+    /// Creates the final chunk reporting token usage, if enabled.
+    fn usage_chunk(&self) -> Option<NvCreateChatCompletionStreamResponse> {
+        self.create_usage_chunk()
+            .map(|inner| NvCreateChatCompletionStreamResponse {
+                inner,
+                reasoning_content: HashMap::new(),
+            })
+    }
 }
@@ -0,0 +1,378 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Separation of the reasoning span emitted by thinking models from the answer.
+//!
+//! Models like DeepSeek-R1 and Qwen3 wrap their chain of thought in a pair of tags, e.g.
+//! `<think>...</think>`. The [`ReasoningParser`] runs incrementally over the decoded text of a
+//! response stream and routes the text inside the span to the `reasoning_content` field of the
+//! choice, holding back just enough text to recognize tags split across tokens.
+//!
+//! The async-openai types have no `reasoning_content` field, so the reasoning text is carried
+//! beside them by [`super::NvCreateChatCompletionStreamResponse`] and
+//! [`super::NvCreateChatCompletionResponse`] and inserted into the serialized choices.
+
+use std::collections::HashMap;
+
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Name of the field holding the reasoning text of a choice's `delta` or `message`.
+pub const REASONING_CONTENT: &str = "reasoning_content";
+
+/// Selects the tags delimiting the reasoning span of a model family.
+///
+/// Set for a model with `reasoning_parser` in its model deployment card; a request can select a
+/// different parser with `nvext.reasoning_parser`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReasoningParserType {
+    /// DeepSeek-R1: the chat template opens the `<think>` span in the prompt, so the output starts
+    /// inside the reasoning span and usually only the closing tag is generated.
+    DeepseekR1,
+    /// Qwen3: the output opens and closes the span with `<think>` and `</think>`.
+    Qwen3,
+    /// Any other tag pair.
+    Custom(ReasoningTags),
+}
+
+/// Tag pair delimiting a reasoning span.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReasoningTags {
+    pub start: String,
+    pub end: String,
+
+    /// Whether the output begins inside the span even if the start tag is not generated.
+    #[serde(default)]
+    pub starts_in_reasoning: bool,
+}
+
+impl ReasoningParserType {
+    pub fn tags(&self) -> ReasoningTags {
+        let think = |starts_in_reasoning| ReasoningTags {
+            start: "<think>".to_string(),
+            end: "</think>".to_string(),
+            starts_in_reasoning,
+        };
+        match self {
+            ReasoningParserType::DeepseekR1 => think(true),
+            ReasoningParserType::Qwen3 => think(false),
+            ReasoningParserType::Custom(tags) => tags.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// Nothing has been emitted yet; a leading start tag opens the span.
+    Start,
+    /// Inside the reasoning span.
+    Reasoning,
+    /// The span just closed; whitespace separating it from the answer is dropped.
+    Closed,
+    /// Answer text; tags are no longer recognized.
+    Content,
+}
+
+/// Text of a delta split into its reasoning and answer parts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParsedDelta {
+    pub reasoning: Option<String>,
+    pub content: Option<String>,
+}
+
+impl ParsedDelta {
+    /// Appends the text of a later delta.
+    pub fn chain(self, next: ParsedDelta) -> ParsedDelta {
+        let join = |a: Option<String>, b: Option<String>| match (a, b) {
+            (Some(a), Some(b)) => Some(a + &b),
+            (a, b) => a.or(b),
+        };
+        ParsedDelta {
+            reasoning: join(self.reasoning, next.reasoning),
+            content: join(self.content, next.content),
+        }
+    }
+}
+
+/// Incrementally splits the decoded text of one choice into reasoning and answer text.
+#[derive(Debug, Clone)]
+pub struct ReasoningParser {
+    tags: ReasoningTags,
+    phase: Phase,
+    /// Text held back because it may be the beginning of a tag.
+    pending: String,
+}
+
+impl ReasoningParser {
+    pub fn new(tags: ReasoningTags) -> Self {
+        Self {
+            tags,
+            phase: Phase::Start,
+            pending: String::new(),
+        }
+    }
+
+    /// Whether the text parsed so far ends inside the reasoning span.
+    pub fn in_reasoning(&self) -> bool {
+        self.phase == Phase::Reasoning
+    }
+
+    /// Parses the next piece of decoded text.
+    pub fn push(&mut self, text: &str) -> ParsedDelta {
+        let mut text = std::mem::take(&mut self.pending) + text;
+        let mut reasoning = String::new();
+        let mut content = String::new();
+
+        loop {
+            match self.phase {
+                Phase::Start => {
+                    let trimmed = text.trim_start();
+                    if let Some(rest) = trimmed.strip_prefix(&self.tags.start) {
+                        text = rest.to_string();
+                        self.phase = Phase::Reasoning;
+                    } else if self.tags.start.starts_with(trimmed) {
+                        self.pending = text;
+                        break;
+                    } else if self.tags.starts_in_reasoning {
+                        self.phase = Phase::Reasoning;
+                    } else {
+                        self.phase = Phase::Content;
+                    }
+                }
+                Phase::Reasoning => match text.find(&self.tags.end) {
+                    Some(end) => {
+                        reasoning.push_str(&text[..end]);
+                        text = text[end + self.tags.end.len()..].to_string();
+                        self.phase = Phase::Closed;
+                    }
+                    None => {
+                        let held = partial_tag_len(&text, &self.tags.end);
+                        self.pending = text.split_off(text.len() - held);
+                        reasoning.push_str(&text);
+                        break;
+                    }
+                },
+                Phase::Closed => {
+                    let trimmed = text.trim_start();
+                    if !trimmed.is_empty() {
+                        text = trimmed.to_string();
+                        self.phase = Phase::Content;
+                    } else {
+                        break;
+                    }
+                }
+                Phase::Content => {
+                    content.push_str(&text);
+                    break;
+                }
+            }
+        }
+
+        ParsedDelta {
+            reasoning: (!reasoning.is_empty()).then_some(reasoning),
+            content: (!content.is_empty()).then_some(content),
+        }
+    }
+
+    /// Releases the text held back at the end of the stream.
+    pub fn finish(&mut self) -> ParsedDelta {
+        let pending = std::mem::take(&mut self.pending);
+        if pending.is_empty() {
+            return ParsedDelta::default();
+        }
+        match self.phase {
+            Phase::Reasoning => ParsedDelta {
+                reasoning: Some(pending),
+                content: None,
+            },
+            Phase::Start if self.tags.starts_in_reasoning => ParsedDelta {
+                reasoning: Some(pending),
+                content: None,
+            },
+            _ => ParsedDelta {
+                reasoning: None,
+                content: Some(pending),
+            },
+        }
+    }
+}
+
+/// Length of the longest suffix of `text` that is a proper prefix of `tag`.
+fn partial_tag_len(text: &str, tag: &str) -> usize {
+    (1..tag.len().min(text.len() + 1))
+        .rev()
+        .find(|&len| {
+            text.is_char_boundary(text.len() - len) && tag.starts_with(&text[text.len() - len..])
+        })
+        .unwrap_or(0)
+}
+
+/// Serializes an OpenAI response, setting `reasoning_content` on the `field` object (`delta` or
+/// `message`) of the choices with reasoning text.
+pub(super) fn serialize_with_reasoning<S: Serializer, T: Serialize>(
+    inner: &T,
+    reasoning: &HashMap<u32, String>,
+    field: &str,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    if reasoning.is_empty() {
+        return inner.serialize(serializer);
+    }
+
+    let mut value = serde_json::to_value(inner).map_err(serde::ser::Error::custom)?;
+    if let Some(choices) = value.get_mut("choices").and_then(|c| c.as_array_mut()) {
+        for choice in choices {
+            let text = choice
+                .get("index")
+                .and_then(|index| index.as_u64())
+                .and_then(|index| reasoning.get(&(index as u32)));
+            if let (Some(text), Some(object)) =
+                (text, choice.get_mut(field).and_then(|f| f.as_object_mut()))
+            {
+                object.insert(REASONING_CONTENT.to_string(), text.clone().into());
+            }
+        }
+    }
+    value.serialize(serializer)
+}
+
+/// Inverse of [`serialize_with_reasoning`].
+pub(super) fn deserialize_with_reasoning<'de, D: Deserializer<'de>, T: DeserializeOwned>(
+    field: &str,
+    deserializer: D,
+) -> Result<(T, HashMap<u32, String>), D::Error> {
+    let mut value = serde_json::Value::deserialize(deserializer)?;
+    let mut reasoning = HashMap::new();
+    if let Some(choices) = value.get_mut("choices").and_then(|c| c.as_array_mut()) {
+        for choice in choices {
+            let index = choice.get("index").and_then(|index| index.as_u64());
+            let text = choice
+                .get_mut(field)
+                .and_then(|f| f.as_object_mut())
+                .and_then(|object| object.remove(REASONING_CONTENT));
+            if let (Some(index), Some(serde_json::Value::String(text))) = (index, text) {
+                reasoning.insert(index as u32, text);
+            }
+        }
+    }
+    let inner = serde_json::from_value(value).map_err(serde::de::Error::custom)?;
+    Ok((inner, reasoning))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds the text to the parser in the given pieces and collects the reasoning and content.
+    fn parse(parser_type: ReasoningParserType, pieces: &[&str]) -> (String, String) {
+        let mut parser = ReasoningParser::new(parser_type.tags());
+        let mut parsed = ParsedDelta::default();
+        for piece in pieces {
+            parsed = parsed.chain(parser.push(piece));
+        }
+        let parsed = parsed.chain(parser.finish());
+        (
+            parsed.reasoning.unwrap_or_default(),
+            parsed.content.unwrap_or_default(),
+        )
+    }
+
+    #[test]
+    fn test_qwen3() {
+        let pieces = [
+            "<think>",
+            "\nsome ",
+            "thought\n",
+            "</think>",
+            "\n\n",
+            "the answer",
+        ];
+        assert_eq!(
+            parse(ReasoningParserType::Qwen3, &pieces),
+            ("\nsome thought\n".to_string(), "the answer".to_string())
+        );
+    }
+
+    #[test]
+    fn test_tags_split_across_tokens() {
+        let pieces = ["<th", "ink>a", " b</", "thi", "nk>", "c </thi", "nk>"];
+        assert_eq!(
+            parse(ReasoningParserType::Qwen3, &pieces),
+            ("a b".to_string(), "c </think>".to_string())
+        );
+    }
+
+    #[test]
+    fn test_deepseek_r1_without_start_tag() {
+        let pieces = ["Okay", ", let me", " think.</think>", "Hello"];
+        assert_eq!(
+            parse(ReasoningParserType::DeepseekR1, &pieces),
+            ("Okay, let me think.".to_string(), "Hello".to_string())
+        );
+
+        // some versions of the template let the model open the span itself
+        let pieces = ["<think>\n", "hmm", "</think>", "Hi"];
+        assert_eq!(
+            parse(ReasoningParserType::DeepseekR1, &pieces),
+            ("\nhmm".to_string(), "Hi".to_string())
+        );
+    }
+
+    #[test]
+    fn test_no_reasoning() {
+        let pieces = ["<", "b>bold</b> text"];
+        assert_eq!(
+            parse(ReasoningParserType::Qwen3, &pieces),
+            ("".to_string(), "<b>bold</b> text".to_string())
+        );
+
+        // text that could still be a start tag is released when the stream ends
+        assert_eq!(
+            parse(ReasoningParserType::Qwen3, &["<thi"]),
+            ("".to_string(), "<thi".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unterminated_reasoning() {
+        let pieces = ["<think>", "still thinking</th"];
+        assert_eq!(
+            parse(ReasoningParserType::Qwen3, &pieces),
+            ("still thinking</th".to_string(), "".to_string())
+        );
+    }
+
+    #[test]
+    fn test_custom_tags() {
+        let parser_type: ReasoningParserType =
+            serde_json::from_str(r#"{"custom": {"start": "[R]", "end": "[/R]"}}"#).unwrap();
+        assert_eq!(
+            parse(parser_type, &["[R]", "why", "[/", "R] because"]),
+            ("why".to_string(), "because".to_string())
+        );
+
+        let parser_type: ReasoningParserType = serde_json::from_str(r#""deepseek_r1""#).unwrap();
+        assert_eq!(parser_type, ReasoningParserType::DeepseekR1);
+    }
+
+    #[test]
+    fn test_multibyte_text() {
+        let pieces = ["<think>日本", "語</think>答え"];
+        assert_eq!(
+            parse(ReasoningParserType::Qwen3, &pieces),
+            ("日本語".to_string(), "答え".to_string())
+        );
+    }
+}
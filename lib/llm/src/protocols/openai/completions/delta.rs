@@ -15,14 +15,18 @@
 
 use super::{CompletionChoice, CompletionRequest, CompletionResponse};
 use crate::protocols::common;
-use crate::protocols::openai::CompletionUsage;
+use crate::protocols::openai::{CompletionUsage, PromptTokensDetails};
 
 impl CompletionRequest {
     // put this method on the request
     // inspect the request to extract options
     pub fn response_generator(&self) -> DeltaGenerator {
         let options = DeltaGeneratorOptions {
-            enable_usage: true,
+            enable_usage: self
+                .inner
+                .stream_options
+                .as_ref()
+                .is_some_and(|options| options.include_usage),
             enable_logprobs: false,
         };
 
@@ -90,13 +94,30 @@ impl DeltaGenerator {
                 finish_reason,
                 logprobs: None,
             }],
-            usage: if self.options.enable_usage {
-                Some(self.usage.clone())
-            } else {
-                None
-            },
+            usage: None,
         }
     }
+
+    /// The final chunk of a response stream, without choices and with the token usage of the
+    /// request; `None` unless usage was requested
+    pub fn create_usage_chunk(&self) -> Option<CompletionResponse> {
+        if !self.options.enable_usage {
+            return None;
+        }
+
+        let mut usage = self.usage.clone();
+        usage.total_tokens = usage.prompt_tokens + usage.completion_tokens;
+
+        Some(CompletionResponse {
+            id: self.id.clone(),
+            object: self.object.clone(),
+            created: self.created,
+            model: self.model.clone(),
+            system_fingerprint: self.system_fingerprint.clone(),
+            choices: vec![],
+            usage: Some(usage),
+        })
+    }
 }
 
 impl crate::protocols::openai::DeltaGeneratorExt<CompletionResponse> for DeltaGenerator {
@@ -104,9 +125,13 @@ impl crate::protocols::openai::DeltaGeneratorExt<CompletionResponse> for DeltaGe
         &mut self,
         delta: common::llm_backend::BackendOutput,
     ) -> anyhow::Result<CompletionResponse> {
-        // aggregate usage
-        if self.options.enable_usage {
-            self.usage.completion_tokens += delta.token_ids.len() as i32;
+        // aggregate usage; it is only reported if enabled
+        self.usage.completion_tokens += delta.token_ids.len() as i32;
+        if let Some(cached_tokens) = delta.cached_tokens {
+            self.usage.prompt_tokens_details = Some(PromptTokensDetails {
+                audio_tokens: None,
+                cached_tokens: Some(cached_tokens as i32),
+            });
         }
 
         // todo logprobs
@@ -126,4 +151,8 @@ impl crate::protocols::openai::DeltaGeneratorExt<CompletionResponse> for DeltaGe
         let index = 0;
         Ok(self.create_choice(index, delta.text, finish_reason))
     }
+
+    fn usage_chunk(&self) -> Option<CompletionResponse> {
+        self.create_usage_chunk()
+    }
 }
@@ -17,6 +17,8 @@ use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 use validator::{Validate, ValidationError};
 
+use super::chat_completions::ReasoningParserType;
+
 pub trait NvExtProvider {
     fn nvext(&self) -> Option<&NvExt>;
     fn raw_prompt(&self) -> Option<String>;
@@ -61,6 +63,54 @@ pub struct NvExt {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     #[builder(default, setter(strip_option))]
     pub annotations: Option<Vec<String>>,
+
+    /// Priority class of the request when it has to wait for admission in the HTTP frontend.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub priority: Option<Priority>,
+
+    /// Separates the reasoning span of the output into `reasoning_content`, overriding the
+    /// parser configured in the model deployment card.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    pub reasoning_parser: Option<ReasoningParserType>,
+}
+
+/// Priority classes for admission into the HTTP frontend; when a model is at its in-flight limit,
+/// queued requests of a higher class are admitted first and may displace queued requests of a
+/// lower class when the queue is full.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Batch,
+    #[default]
+    Normal,
+    Interactive,
+}
+
+impl Priority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Batch => "batch",
+            Priority::Normal => "normal",
+            Priority::Interactive => "interactive",
+        }
+    }
+}
+
+impl std::str::FromStr for Priority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "batch" => Ok(Priority::Batch),
+            "normal" => Ok(Priority::Normal),
+            "interactive" => Ok(Priority::Interactive),
+            other => Err(format!(
+                "unknown priority '{other}'; expected interactive, normal or batch"
+            )),
+        }
+    }
 }
 
 impl Default for NvExt {
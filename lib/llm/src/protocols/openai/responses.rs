@@ -0,0 +1,1326 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Types of the OpenAI Responses API (`/v1/responses`).
+//!
+//! Responses are served by the chat completions engines: a [`NvCreateResponseRequest`] is
+//! translated into a [`NvCreateChatCompletionRequest`], and the [`ResponseEventGenerator`] maps
+//! the chat completion stream back into Responses streaming events and the final [`NvResponse`].
+//! Responses are not stored, so a request can not continue from a `previous_response_id`; the
+//! conversation is passed in full as `input` instead.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use async_openai::types::{
+    ChatCompletionMessageToolCall, ChatCompletionMessageToolCallChunk,
+    ChatCompletionNamedToolChoice, ChatCompletionRequestAssistantMessage,
+    ChatCompletionRequestAssistantMessageContent, ChatCompletionRequestMessage,
+    ChatCompletionRequestSystemMessage, ChatCompletionRequestSystemMessageContent,
+    ChatCompletionRequestToolMessage, ChatCompletionRequestToolMessageContent,
+    ChatCompletionRequestUserMessage, ChatCompletionRequestUserMessageContent,
+    ChatCompletionStreamOptions, ChatCompletionTool, ChatCompletionToolChoiceOption,
+    ChatCompletionToolType, CompletionUsage, CreateChatCompletionRequest, FinishReason,
+    FunctionCall, FunctionName, FunctionObject,
+};
+use serde::{Deserialize, Serialize};
+
+use super::chat_completions::{
+    NvCreateChatCompletionRequest, NvCreateChatCompletionStreamResponse,
+};
+use super::nvext::NvExt;
+
+/// A request to create a model response.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NvCreateResponseRequest {
+    pub model: String,
+
+    /// Text, or a list of conversation items, to generate a response for
+    pub input: ResponseInput,
+
+    /// System message inserted before the input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+
+    /// Functions the model may call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<ResponseTool>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ResponseToolChoice>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+
+    /// Whether the response is streamed as server-sent events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+
+    /// Upper bound of the number of generated tokens, including reasoning tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+
+    /// Key-value pairs which are echoed in the response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<HashMap<String, String>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+
+    /// Accepted for compatibility; responses are never stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store: Option<bool>,
+
+    /// Not supported, as responses are not stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_response_id: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nvext: Option<NvExt>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ResponseInput {
+    /// Equivalent to a single user message
+    Text(String),
+    Items(Vec<InputItem>),
+}
+
+/// An item of the conversation. Messages may leave out their `type`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum InputItem {
+    Item(TypedInputItem),
+    Message(InputMessage),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TypedInputItem {
+    Message(InputMessage),
+
+    /// A function call made by the model in an earlier turn
+    FunctionCall {
+        call_id: String,
+        name: String,
+        arguments: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+    },
+
+    /// The result of a function call
+    FunctionCallOutput {
+        call_id: String,
+        output: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InputMessage {
+    pub role: InputRole,
+    pub content: InputContent,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum InputRole {
+    User,
+    Assistant,
+    System,
+    Developer,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum InputContent {
+    Text(String),
+    Parts(Vec<InputContentPart>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputContentPart {
+    InputText {
+        text: String,
+    },
+    /// Text of an earlier assistant message
+    OutputText {
+        text: String,
+    },
+}
+
+impl InputContent {
+    /// Text of the content, with the text of each part on its own line
+    fn into_text(self) -> String {
+        match self {
+            InputContent::Text(text) => text,
+            InputContent::Parts(parts) => parts
+                .into_iter()
+                .map(|part| match part {
+                    InputContentPart::InputText { text }
+                    | InputContentPart::OutputText { text } => text,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseTool {
+    Function {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        /// JSON schema of the arguments
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parameters: Option<serde_json::Value>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        strict: Option<bool>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ResponseToolChoice {
+    Mode(ToolChoiceMode),
+    Named(NamedToolChoice),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolChoiceMode {
+    None,
+    Auto,
+    Required,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NamedToolChoice {
+    Function { name: String },
+}
+
+impl From<ResponseTool> for ChatCompletionTool {
+    fn from(tool: ResponseTool) -> Self {
+        match tool {
+            ResponseTool::Function {
+                name,
+                description,
+                parameters,
+                strict,
+            } => ChatCompletionTool {
+                r#type: ChatCompletionToolType::Function,
+                function: FunctionObject {
+                    name,
+                    description,
+                    parameters,
+                    strict,
+                },
+            },
+        }
+    }
+}
+
+impl From<ResponseToolChoice> for ChatCompletionToolChoiceOption {
+    fn from(choice: ResponseToolChoice) -> Self {
+        match choice {
+            ResponseToolChoice::Mode(ToolChoiceMode::None) => ChatCompletionToolChoiceOption::None,
+            ResponseToolChoice::Mode(ToolChoiceMode::Auto) => ChatCompletionToolChoiceOption::Auto,
+            ResponseToolChoice::Mode(ToolChoiceMode::Required) => {
+                ChatCompletionToolChoiceOption::Required
+            }
+            ResponseToolChoice::Named(NamedToolChoice::Function { name }) => {
+                ChatCompletionToolChoiceOption::Named(ChatCompletionNamedToolChoice {
+                    r#type: ChatCompletionToolType::Function,
+                    function: FunctionName { name },
+                })
+            }
+        }
+    }
+}
+
+/// Translate a Responses request into a streaming chat completions request which reports usage
+impl TryFrom<NvCreateResponseRequest> for NvCreateChatCompletionRequest {
+    type Error = anyhow::Error;
+
+    fn try_from(request: NvCreateResponseRequest) -> Result<Self> {
+        if request.previous_response_id.is_some() {
+            anyhow::bail!(
+                "previous_response_id is not supported as responses are not stored; pass the conversation as input"
+            );
+        }
+
+        let mut messages = Vec::new();
+        if let Some(instructions) = request.instructions {
+            messages.push(system_message(instructions));
+        }
+
+        match request.input {
+            ResponseInput::Text(text) => messages.push(user_message(text)),
+            ResponseInput::Items(items) => {
+                for item in items {
+                    push_input_item(&mut messages, item);
+                }
+            }
+        }
+
+        if messages.is_empty() {
+            anyhow::bail!("input must not be empty");
+        }
+
+        let inner = CreateChatCompletionRequest {
+            model: request.model,
+            messages,
+            temperature: request.temperature,
+            top_p: request.top_p,
+            max_completion_tokens: request.max_output_tokens,
+            tools: request
+                .tools
+                .map(|tools| tools.into_iter().map(Into::into).collect()),
+            tool_choice: request.tool_choice.map(Into::into),
+            parallel_tool_calls: request.parallel_tool_calls,
+            user: request.user,
+            stream: Some(true),
+            stream_options: Some(ChatCompletionStreamOptions {
+                include_usage: true,
+            }),
+            ..Default::default()
+        };
+
+        Ok(NvCreateChatCompletionRequest {
+            inner,
+            nvext: request.nvext,
+        })
+    }
+}
+
+fn system_message(text: String) -> ChatCompletionRequestMessage {
+    ChatCompletionRequestMessage::System(ChatCompletionRequestSystemMessage {
+        content: ChatCompletionRequestSystemMessageContent::Text(text),
+        name: None,
+    })
+}
+
+fn user_message(text: String) -> ChatCompletionRequestMessage {
+    ChatCompletionRequestMessage::User(ChatCompletionRequestUserMessage {
+        content: ChatCompletionRequestUserMessageContent::Text(text),
+        name: None,
+    })
+}
+
+fn push_input_item(messages: &mut Vec<ChatCompletionRequestMessage>, item: InputItem) {
+    let item = match item {
+        InputItem::Item(item) => item,
+        InputItem::Message(message) => TypedInputItem::Message(message),
+    };
+
+    match item {
+        TypedInputItem::Message(InputMessage { role, content }) => {
+            let text = content.into_text();
+            messages.push(match role {
+                InputRole::User => user_message(text),
+                InputRole::System | InputRole::Developer => system_message(text),
+                InputRole::Assistant => {
+                    ChatCompletionRequestMessage::Assistant(ChatCompletionRequestAssistantMessage {
+                        content: Some(ChatCompletionRequestAssistantMessageContent::Text(text)),
+                        ..Default::default()
+                    })
+                }
+            });
+        }
+        TypedInputItem::FunctionCall {
+            call_id,
+            name,
+            arguments,
+            ..
+        } => {
+            let tool_call = ChatCompletionMessageToolCall {
+                id: call_id,
+                r#type: ChatCompletionToolType::Function,
+                function: FunctionCall { name, arguments },
+            };
+            // calls of the same turn are made by a single assistant message
+            match messages.last_mut() {
+                Some(ChatCompletionRequestMessage::Assistant(message)) => message
+                    .tool_calls
+                    .get_or_insert_with(Vec::new)
+                    .push(tool_call),
+                _ => messages.push(ChatCompletionRequestMessage::Assistant(
+                    ChatCompletionRequestAssistantMessage {
+                        tool_calls: Some(vec![tool_call]),
+                        ..Default::default()
+                    },
+                )),
+            }
+        }
+        TypedInputItem::FunctionCallOutput { call_id, output } => {
+            messages.push(ChatCompletionRequestMessage::Tool(
+                ChatCompletionRequestToolMessage {
+                    content: ChatCompletionRequestToolMessageContent::Text(output),
+                    tool_call_id: call_id,
+                },
+            ));
+        }
+    }
+}
+
+/// The response object, returned in full by non-streaming requests and in the lifecycle events of
+/// streaming requests.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NvResponse {
+    pub id: String,
+
+    /// Always `response`
+    pub object: String,
+
+    /// Unix timestamp in seconds
+    pub created_at: u64,
+
+    pub status: ResponseStatus,
+
+    pub model: String,
+
+    pub output: Vec<OutputItem>,
+
+    pub instructions: Option<String>,
+
+    pub tools: Vec<ResponseTool>,
+
+    pub tool_choice: ResponseToolChoice,
+
+    pub parallel_tool_calls: bool,
+
+    pub temperature: Option<f32>,
+
+    pub top_p: Option<f32>,
+
+    pub max_output_tokens: Option<u32>,
+
+    pub previous_response_id: Option<String>,
+
+    pub metadata: HashMap<String, String>,
+
+    pub user: Option<String>,
+
+    /// Always false
+    pub store: bool,
+
+    pub usage: Option<ResponseUsage>,
+
+    pub incomplete_details: Option<IncompleteDetails>,
+
+    pub error: Option<ResponseError>,
+}
+
+impl NvResponse {
+    /// Concatenated text of the output messages
+    pub fn output_text(&self) -> String {
+        self.output
+            .iter()
+            .filter_map(|item| match item {
+                OutputItem::Message { content, .. } => Some(content),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|part| match part {
+                OutputContent::OutputText { text, .. } => Some(text.as_str()),
+                OutputContent::ReasoningText { .. } => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseStatus {
+    InProgress,
+    Completed,
+    Incomplete,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemStatus {
+    InProgress,
+    Completed,
+    Incomplete,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputItem {
+    Message {
+        id: String,
+        /// Always `assistant`
+        role: String,
+        status: ItemStatus,
+        content: Vec<OutputContent>,
+    },
+    FunctionCall {
+        id: String,
+        call_id: String,
+        name: String,
+        arguments: String,
+        status: ItemStatus,
+    },
+    /// The reasoning span of a thinking model, see [`super::chat_completions::ReasoningParser`]
+    Reasoning {
+        id: String,
+        summary: Vec<serde_json::Value>,
+        content: Vec<OutputContent>,
+        status: ItemStatus,
+    },
+}
+
+impl OutputItem {
+    pub fn id(&self) -> &str {
+        match self {
+            OutputItem::Message { id, .. }
+            | OutputItem::FunctionCall { id, .. }
+            | OutputItem::Reasoning { id, .. } => id,
+        }
+    }
+
+    fn set_status(&mut self, new_status: ItemStatus) {
+        match self {
+            OutputItem::Message { status, .. }
+            | OutputItem::FunctionCall { status, .. }
+            | OutputItem::Reasoning { status, .. } => *status = new_status,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputContent {
+    OutputText {
+        text: String,
+        annotations: Vec<serde_json::Value>,
+    },
+    ReasoningText {
+        text: String,
+    },
+}
+
+impl OutputContent {
+    fn text_mut(&mut self) -> &mut String {
+        match self {
+            OutputContent::OutputText { text, .. } | OutputContent::ReasoningText { text } => text,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ResponseUsage {
+    pub input_tokens: u32,
+    pub input_tokens_details: InputTokensDetails,
+    pub output_tokens: u32,
+    pub output_tokens_details: OutputTokensDetails,
+    pub total_tokens: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct InputTokensDetails {
+    /// Prompt tokens served from the KV cache
+    pub cached_tokens: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct OutputTokensDetails {
+    pub reasoning_tokens: u32,
+}
+
+impl From<CompletionUsage> for ResponseUsage {
+    fn from(usage: CompletionUsage) -> Self {
+        ResponseUsage {
+            input_tokens: usage.prompt_tokens,
+            input_tokens_details: InputTokensDetails {
+                cached_tokens: usage
+                    .prompt_tokens_details
+                    .and_then(|details| details.cached_tokens)
+                    .unwrap_or(0),
+            },
+            output_tokens: usage.completion_tokens,
+            output_tokens_details: OutputTokensDetails {
+                reasoning_tokens: usage
+                    .completion_tokens_details
+                    .and_then(|details| details.reasoning_tokens)
+                    .unwrap_or(0),
+            },
+            total_tokens: usage.total_tokens,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IncompleteDetails {
+    /// `max_output_tokens` or `content_filter`
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResponseError {
+    pub code: String,
+    pub message: String,
+}
+
+/// A streaming event; its `type` is also sent as the name of the server-sent event.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResponseStreamEvent {
+    #[serde(flatten)]
+    pub event: ResponseEvent,
+
+    /// Position of the event in the stream, starting at 0
+    pub sequence_number: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum ResponseEvent {
+    #[serde(rename = "response.created")]
+    Created { response: NvResponse },
+
+    #[serde(rename = "response.in_progress")]
+    InProgress { response: NvResponse },
+
+    #[serde(rename = "response.completed")]
+    Completed { response: NvResponse },
+
+    #[serde(rename = "response.incomplete")]
+    Incomplete { response: NvResponse },
+
+    #[serde(rename = "response.failed")]
+    Failed { response: NvResponse },
+
+    #[serde(rename = "response.output_item.added")]
+    OutputItemAdded {
+        output_index: usize,
+        item: OutputItem,
+    },
+
+    #[serde(rename = "response.output_item.done")]
+    OutputItemDone {
+        output_index: usize,
+        item: OutputItem,
+    },
+
+    #[serde(rename = "response.content_part.added")]
+    ContentPartAdded {
+        item_id: String,
+        output_index: usize,
+        content_index: usize,
+        part: OutputContent,
+    },
+
+    #[serde(rename = "response.content_part.done")]
+    ContentPartDone {
+        item_id: String,
+        output_index: usize,
+        content_index: usize,
+        part: OutputContent,
+    },
+
+    #[serde(rename = "response.output_text.delta")]
+    OutputTextDelta {
+        item_id: String,
+        output_index: usize,
+        content_index: usize,
+        delta: String,
+    },
+
+    #[serde(rename = "response.output_text.done")]
+    OutputTextDone {
+        item_id: String,
+        output_index: usize,
+        content_index: usize,
+        text: String,
+    },
+
+    #[serde(rename = "response.reasoning_text.delta")]
+    ReasoningTextDelta {
+        item_id: String,
+        output_index: usize,
+        content_index: usize,
+        delta: String,
+    },
+
+    #[serde(rename = "response.reasoning_text.done")]
+    ReasoningTextDone {
+        item_id: String,
+        output_index: usize,
+        content_index: usize,
+        text: String,
+    },
+
+    #[serde(rename = "response.function_call_arguments.delta")]
+    FunctionCallArgumentsDelta {
+        item_id: String,
+        output_index: usize,
+        delta: String,
+    },
+
+    #[serde(rename = "response.function_call_arguments.done")]
+    FunctionCallArgumentsDone {
+        item_id: String,
+        output_index: usize,
+        arguments: String,
+    },
+}
+
+impl ResponseEvent {
+    /// The `type` of the event
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            ResponseEvent::Created { .. } => "response.created",
+            ResponseEvent::InProgress { .. } => "response.in_progress",
+            ResponseEvent::Completed { .. } => "response.completed",
+            ResponseEvent::Incomplete { .. } => "response.incomplete",
+            ResponseEvent::Failed { .. } => "response.failed",
+            ResponseEvent::OutputItemAdded { .. } => "response.output_item.added",
+            ResponseEvent::OutputItemDone { .. } => "response.output_item.done",
+            ResponseEvent::ContentPartAdded { .. } => "response.content_part.added",
+            ResponseEvent::ContentPartDone { .. } => "response.content_part.done",
+            ResponseEvent::OutputTextDelta { .. } => "response.output_text.delta",
+            ResponseEvent::OutputTextDone { .. } => "response.output_text.done",
+            ResponseEvent::ReasoningTextDelta { .. } => "response.reasoning_text.delta",
+            ResponseEvent::ReasoningTextDone { .. } => "response.reasoning_text.done",
+            ResponseEvent::FunctionCallArgumentsDelta { .. } => {
+                "response.function_call_arguments.delta"
+            }
+            ResponseEvent::FunctionCallArgumentsDone { .. } => {
+                "response.function_call_arguments.done"
+            }
+        }
+    }
+}
+
+/// Kind of an output item which holds text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextKind {
+    Reasoning,
+    Message,
+}
+
+/// Builds the Responses events and the final [`NvResponse`] from the chunks of a chat completion
+/// stream. Only the first choice is used.
+///
+/// Reasoning, message text and function calls each become an output item. An item is done when
+/// an item of another kind starts or the stream finishes.
+pub struct ResponseEventGenerator {
+    response: NvResponse,
+    sequence_number: u64,
+
+    /// Output index of the open reasoning item
+    reasoning: Option<usize>,
+
+    /// Output index of the open message item
+    message: Option<usize>,
+
+    /// Output index of each open function call item, keyed by the index of the chat tool call
+    tool_calls: BTreeMap<u32, usize>,
+
+    finish_reason: Option<FinishReason>,
+    usage: Option<CompletionUsage>,
+}
+
+impl ResponseEventGenerator {
+    /// Start an in progress response which echoes the settings of the request
+    pub fn new(request: &NvCreateResponseRequest) -> Self {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let response = NvResponse {
+            id: format!("resp_{}", uuid::Uuid::new_v4().simple()),
+            object: "response".to_string(),
+            created_at,
+            status: ResponseStatus::InProgress,
+            model: request.model.clone(),
+            output: Vec::new(),
+            instructions: request.instructions.clone(),
+            tools: request.tools.clone().unwrap_or_default(),
+            tool_choice: request
+                .tool_choice
+                .clone()
+                .unwrap_or(ResponseToolChoice::Mode(ToolChoiceMode::Auto)),
+            parallel_tool_calls: request.parallel_tool_calls.unwrap_or(true),
+            temperature: request.temperature,
+            top_p: request.top_p,
+            max_output_tokens: request.max_output_tokens,
+            previous_response_id: None,
+            metadata: request.metadata.clone().unwrap_or_default(),
+            user: request.user.clone(),
+            store: false,
+            usage: None,
+            incomplete_details: None,
+            error: None,
+        };
+
+        Self {
+            response,
+            sequence_number: 0,
+            reasoning: None,
+            message: None,
+            tool_calls: BTreeMap::new(),
+            finish_reason: None,
+            usage: None,
+        }
+    }
+
+    /// The response as built so far
+    pub fn response(&self) -> &NvResponse {
+        &self.response
+    }
+
+    pub fn into_response(self) -> NvResponse {
+        self.response
+    }
+
+    /// `response.created` and `response.in_progress`
+    pub fn start(&mut self) -> Vec<ResponseStreamEvent> {
+        let mut events = Vec::new();
+        self.emit(
+            &mut events,
+            ResponseEvent::Created {
+                response: self.response.clone(),
+            },
+        );
+        self.emit(
+            &mut events,
+            ResponseEvent::InProgress {
+                response: self.response.clone(),
+            },
+        );
+        events
+    }
+
+    /// Apply a chunk of the chat completion stream
+    pub fn push(
+        &mut self,
+        chunk: &NvCreateChatCompletionStreamResponse,
+    ) -> Vec<ResponseStreamEvent> {
+        let mut events = Vec::new();
+
+        // usage is reported by a final chunk without choices
+        if let Some(usage) = &chunk.inner.usage {
+            self.usage = Some(usage.clone());
+        }
+
+        let Some(choice) = chunk.inner.choices.iter().find(|choice| choice.index == 0) else {
+            return events;
+        };
+
+        if let Some(text) = chunk.reasoning_content.get(&0).filter(|t| !t.is_empty()) {
+            self.push_text(TextKind::Reasoning, text, &mut events);
+        }
+
+        if let Some(text) = choice.delta.content.as_ref().filter(|t| !t.is_empty()) {
+            self.push_text(TextKind::Message, text, &mut events);
+        }
+
+        for tool_call in choice.delta.tool_calls.iter().flatten() {
+            self.push_tool_call(tool_call, &mut events);
+        }
+
+        if let Some(finish_reason) = choice.finish_reason {
+            self.finish_reason = Some(finish_reason);
+        }
+
+        events
+    }
+
+    /// Close the open items and complete the response; a response cut short by the token limit
+    /// or the content filter is `incomplete`
+    pub fn finish(&mut self) -> Vec<ResponseStreamEvent> {
+        let mut events = Vec::new();
+
+        let reason = match self.finish_reason {
+            Some(FinishReason::Length) => Some("max_output_tokens"),
+            Some(FinishReason::ContentFilter) => Some("content_filter"),
+            _ => None,
+        };
+        let item_status = match reason {
+            Some(_) => ItemStatus::Incomplete,
+            None => ItemStatus::Completed,
+        };
+
+        self.close_text(TextKind::Reasoning, item_status, &mut events);
+        self.close_text(TextKind::Message, item_status, &mut events);
+        self.close_tool_calls(item_status, &mut events);
+
+        self.response.usage = self.usage.take().map(Into::into);
+        match reason {
+            Some(reason) => {
+                self.response.status = ResponseStatus::Incomplete;
+                self.response.incomplete_details = Some(IncompleteDetails {
+                    reason: reason.to_string(),
+                });
+                let response = self.response.clone();
+                self.emit(&mut events, ResponseEvent::Incomplete { response });
+            }
+            None => {
+                self.response.status = ResponseStatus::Completed;
+                let response = self.response.clone();
+                self.emit(&mut events, ResponseEvent::Completed { response });
+            }
+        }
+
+        events
+    }
+
+    /// Fail the response, e.g. when the engine reports an error mid-stream
+    pub fn fail(&mut self, message: &str) -> Vec<ResponseStreamEvent> {
+        let mut events = Vec::new();
+        self.response.status = ResponseStatus::Failed;
+        self.response.error = Some(ResponseError {
+            code: "server_error".to_string(),
+            message: message.to_string(),
+        });
+        let response = self.response.clone();
+        self.emit(&mut events, ResponseEvent::Failed { response });
+        events
+    }
+
+    fn emit(&mut self, events: &mut Vec<ResponseStreamEvent>, event: ResponseEvent) {
+        events.push(ResponseStreamEvent {
+            event,
+            sequence_number: self.sequence_number,
+        });
+        self.sequence_number += 1;
+    }
+
+    fn open_text(&mut self, kind: TextKind) -> &mut Option<usize> {
+        match kind {
+            TextKind::Reasoning => &mut self.reasoning,
+            TextKind::Message => &mut self.message,
+        }
+    }
+
+    fn push_text(&mut self, kind: TextKind, delta: &str, events: &mut Vec<ResponseStreamEvent>) {
+        let other = match kind {
+            TextKind::Reasoning => TextKind::Message,
+            TextKind::Message => TextKind::Reasoning,
+        };
+        self.close_text(other, ItemStatus::Completed, events);
+        self.close_tool_calls(ItemStatus::Completed, events);
+
+        let output_index = match *self.open_text(kind) {
+            Some(output_index) => output_index,
+            None => self.add_text_item(kind, events),
+        };
+
+        let item = &mut self.response.output[output_index];
+        let item_id = item.id().to_string();
+        if let OutputItem::Message { content, .. } | OutputItem::Reasoning { content, .. } = item {
+            content[0].text_mut().push_str(delta);
+        }
+
+        let delta = delta.to_string();
+        let event = match kind {
+            TextKind::Reasoning => ResponseEvent::ReasoningTextDelta {
+                item_id,
+                output_index,
+                content_index: 0,
+                delta,
+            },
+            TextKind::Message => ResponseEvent::OutputTextDelta {
+                item_id,
+                output_index,
+                content_index: 0,
+                delta,
+            },
+        };
+        self.emit(events, event);
+    }
+
+    /// Add an item of `kind` with a single empty content part
+    fn add_text_item(&mut self, kind: TextKind, events: &mut Vec<ResponseStreamEvent>) -> usize {
+        let (item, part) = match kind {
+            TextKind::Reasoning => (
+                OutputItem::Reasoning {
+                    id: format!("rs_{}", uuid::Uuid::new_v4().simple()),
+                    summary: Vec::new(),
+                    content: Vec::new(),
+                    status: ItemStatus::InProgress,
+                },
+                OutputContent::ReasoningText {
+                    text: String::new(),
+                },
+            ),
+            TextKind::Message => (
+                OutputItem::Message {
+                    id: format!("msg_{}", uuid::Uuid::new_v4().simple()),
+                    role: "assistant".to_string(),
+                    status: ItemStatus::InProgress,
+                    content: Vec::new(),
+                },
+                OutputContent::OutputText {
+                    text: String::new(),
+                    annotations: Vec::new(),
+                },
+            ),
+        };
+
+        let output_index = self.response.output.len();
+        let item_id = item.id().to_string();
+        self.emit(
+            events,
+            ResponseEvent::OutputItemAdded {
+                output_index,
+                item: item.clone(),
+            },
+        );
+        self.emit(
+            events,
+            ResponseEvent::ContentPartAdded {
+                item_id,
+                output_index,
+                content_index: 0,
+                part: part.clone(),
+            },
+        );
+
+        let mut item = item;
+        if let OutputItem::Message { content, .. } | OutputItem::Reasoning { content, .. } =
+            &mut item
+        {
+            content.push(part);
+        }
+        self.response.output.push(item);
+        *self.open_text(kind) = Some(output_index);
+        output_index
+    }
+
+    fn close_text(
+        &mut self,
+        kind: TextKind,
+        status: ItemStatus,
+        events: &mut Vec<ResponseStreamEvent>,
+    ) {
+        let Some(output_index) = self.open_text(kind).take() else {
+            return;
+        };
+
+        let item = &mut self.response.output[output_index];
+        item.set_status(status);
+        let item = item.clone();
+        let item_id = item.id().to_string();
+        let part = match &item {
+            OutputItem::Message { content, .. } | OutputItem::Reasoning { content, .. } => {
+                content[0].clone()
+            }
+            OutputItem::FunctionCall { .. } => unreachable!("text items hold text"),
+        };
+
+        let text = match &part {
+            OutputContent::OutputText { text, .. } | OutputContent::ReasoningText { text } => {
+                text.clone()
+            }
+        };
+        let event = match kind {
+            TextKind::Reasoning => ResponseEvent::ReasoningTextDone {
+                item_id: item_id.clone(),
+                output_index,
+                content_index: 0,
+                text,
+            },
+            TextKind::Message => ResponseEvent::OutputTextDone {
+                item_id: item_id.clone(),
+                output_index,
+                content_index: 0,
+                text,
+            },
+        };
+        self.emit(events, event);
+        self.emit(
+            events,
+            ResponseEvent::ContentPartDone {
+                item_id,
+                output_index,
+                content_index: 0,
+                part,
+            },
+        );
+        self.emit(events, ResponseEvent::OutputItemDone { output_index, item });
+    }
+
+    fn push_tool_call(
+        &mut self,
+        tool_call: &ChatCompletionMessageToolCallChunk,
+        events: &mut Vec<ResponseStreamEvent>,
+    ) {
+        let function = tool_call.function.as_ref();
+
+        let output_index = match self.tool_calls.get(&tool_call.index) {
+            Some(output_index) => *output_index,
+            None => {
+                self.close_text(TextKind::Reasoning, ItemStatus::Completed, events);
+                self.close_text(TextKind::Message, ItemStatus::Completed, events);
+
+                let item = OutputItem::FunctionCall {
+                    id: format!("fc_{}", uuid::Uuid::new_v4().simple()),
+                    call_id: tool_call
+                        .id
+                        .clone()
+                        .unwrap_or_else(|| format!("call_{}", uuid::Uuid::new_v4().simple())),
+                    name: function
+                        .and_then(|function| function.name.clone())
+                        .unwrap_or_default(),
+                    arguments: String::new(),
+                    status: ItemStatus::InProgress,
+                };
+                let output_index = self.response.output.len();
+                self.emit(
+                    events,
+                    ResponseEvent::OutputItemAdded {
+                        output_index,
+                        item: item.clone(),
+                    },
+                );
+                self.response.output.push(item);
+                self.tool_calls.insert(tool_call.index, output_index);
+                output_index
+            }
+        };
+
+        let Some(delta) = function
+            .and_then(|function| function.arguments.as_ref())
+            .filter(|arguments| !arguments.is_empty())
+        else {
+            return;
+        };
+
+        let item = &mut self.response.output[output_index];
+        let item_id = item.id().to_string();
+        if let OutputItem::FunctionCall { arguments, .. } = item {
+            arguments.push_str(delta);
+        }
+        self.emit(
+            events,
+            ResponseEvent::FunctionCallArgumentsDelta {
+                item_id,
+                output_index,
+                delta: delta.clone(),
+            },
+        );
+    }
+
+    fn close_tool_calls(&mut self, status: ItemStatus, events: &mut Vec<ResponseStreamEvent>) {
+        for output_index in std::mem::take(&mut self.tool_calls).into_values() {
+            let item = &mut self.response.output[output_index];
+            item.set_status(status);
+            let item = item.clone();
+            if let OutputItem::FunctionCall { id, arguments, .. } = &item {
+                self.emit(
+                    events,
+                    ResponseEvent::FunctionCallArgumentsDone {
+                        item_id: id.clone(),
+                        output_index,
+                        arguments: arguments.clone(),
+                    },
+                );
+            }
+            self.emit(events, ResponseEvent::OutputItemDone { output_index, item });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(value: serde_json::Value) -> NvCreateResponseRequest {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn chunk(value: serde_json::Value) -> NvCreateChatCompletionStreamResponse {
+        let mut value = value;
+        let object = value.as_object_mut().unwrap();
+        object.insert("id".into(), json!("chatcmpl-1"));
+        object.insert("object".into(), json!("chat.completion.chunk"));
+        object.insert("created".into(), json!(0));
+        object.insert("model".into(), json!("test"));
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn delta(
+        delta: serde_json::Value,
+        finish_reason: Option<&str>,
+    ) -> NvCreateChatCompletionStreamResponse {
+        chunk(json!({
+            "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }]
+        }))
+    }
+
+    fn event_types(events: &[ResponseStreamEvent]) -> Vec<&'static str> {
+        events.iter().map(|e| e.event.event_type()).collect()
+    }
+
+    #[test]
+    fn test_request_to_chat() {
+        let request = request(json!({
+            "model": "test",
+            "instructions": "Be brief.",
+            "input": [
+                { "role": "user", "content": "What is the weather in Paris?" },
+                { "type": "function_call", "call_id": "call_1", "name": "weather", "arguments": "{\"city\":\"Paris\"}" },
+                { "type": "function_call", "call_id": "call_2", "name": "time", "arguments": "{}" },
+                { "type": "function_call_output", "call_id": "call_1", "output": "sunny" },
+                { "type": "message", "role": "assistant", "content": [{ "type": "output_text", "text": "It is sunny." }] },
+                { "role": "user", "content": [{ "type": "input_text", "text": "Thanks" }] }
+            ],
+            "tools": [{ "type": "function", "name": "weather", "parameters": { "type": "object" } }],
+            "tool_choice": { "type": "function", "name": "weather" },
+            "max_output_tokens": 64,
+            "nvext": { "ignore_eos": true }
+        }));
+
+        let chat = NvCreateChatCompletionRequest::try_from(request).unwrap();
+        let value = serde_json::to_value(&chat).unwrap();
+
+        let roles: Vec<_> = value["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            roles,
+            ["system", "user", "assistant", "tool", "assistant", "user"]
+        );
+        assert_eq!(value["messages"][0]["content"], "Be brief.");
+        assert_eq!(value["messages"][2]["tool_calls"][1]["id"], "call_2");
+        assert_eq!(value["messages"][3]["tool_call_id"], "call_1");
+        assert_eq!(value["messages"][4]["content"], "It is sunny.");
+        assert_eq!(value["tools"][0]["function"]["name"], "weather");
+        assert_eq!(value["tool_choice"]["function"]["name"], "weather");
+        assert_eq!(value["max_completion_tokens"], 64);
+        assert_eq!(value["stream"], true);
+        assert_eq!(value["stream_options"]["include_usage"], true);
+        assert_eq!(value["nvext"]["ignore_eos"], true);
+    }
+
+    #[test]
+    fn test_previous_response_id_is_rejected() {
+        let request = request(json!({
+            "model": "test",
+            "input": "Hello",
+            "previous_response_id": "resp_123"
+        }));
+        assert!(NvCreateChatCompletionRequest::try_from(request).is_err());
+    }
+
+    #[test]
+    fn test_events() {
+        let request = request(json!({ "model": "test", "input": "Hello", "max_output_tokens": 8 }));
+        let mut generator = ResponseEventGenerator::new(&request);
+
+        let mut events = generator.start();
+
+        let mut reasoning = delta(json!({ "role": "assistant" }), None);
+        reasoning.reasoning_content.insert(0, "think".to_string());
+        events.extend(generator.push(&reasoning));
+        events.extend(generator.push(&delta(json!({ "content": "Hel" }), None)));
+        events.extend(generator.push(&delta(json!({ "content": "lo" }), None)));
+        events.extend(generator.push(&delta(
+            json!({ "tool_calls": [{ "index": 0, "id": "call_1", "type": "function", "function": { "name": "weather", "arguments": "{}" } }] }),
+            Some("length"),
+        )));
+        events.extend(generator.push(&chunk(json!({
+            "choices": [],
+            "usage": {
+                "prompt_tokens": 3,
+                "completion_tokens": 8,
+                "total_tokens": 11,
+                "completion_tokens_details": { "reasoning_tokens": 1 }
+            }
+        }))));
+        events.extend(generator.finish());
+
+        assert_eq!(
+            event_types(&events),
+            [
+                "response.created",
+                "response.in_progress",
+                "response.output_item.added",
+                "response.content_part.added",
+                "response.reasoning_text.delta",
+                "response.reasoning_text.done",
+                "response.content_part.done",
+                "response.output_item.done",
+                "response.output_item.added",
+                "response.content_part.added",
+                "response.output_text.delta",
+                "response.output_text.delta",
+                "response.output_text.done",
+                "response.content_part.done",
+                "response.output_item.done",
+                "response.output_item.added",
+                "response.function_call_arguments.delta",
+                "response.function_call_arguments.done",
+                "response.output_item.done",
+                "response.incomplete",
+            ]
+        );
+        for (i, event) in events.iter().enumerate() {
+            assert_eq!(event.sequence_number, i as u64);
+        }
+
+        let response = generator.into_response();
+        assert_eq!(response.status, ResponseStatus::Incomplete);
+        assert_eq!(
+            response.incomplete_details.as_ref().unwrap().reason,
+            "max_output_tokens"
+        );
+        assert_eq!(response.output_text(), "Hello");
+        assert_eq!(response.output.len(), 3);
+        match &response.output[2] {
+            OutputItem::FunctionCall {
+                call_id,
+                name,
+                arguments,
+                status,
+                ..
+            } => {
+                assert_eq!(call_id, "call_1");
+                assert_eq!(name, "weather");
+                assert_eq!(arguments, "{}");
+                assert_eq!(*status, ItemStatus::Incomplete);
+            }
+            item => panic!("unexpected item: {item:?}"),
+        }
+        let usage = response.usage.unwrap();
+        assert_eq!(usage.input_tokens, 3);
+        assert_eq!(usage.output_tokens_details.reasoning_tokens, 1);
+
+        let value = serde_json::to_value(&events[4]).unwrap();
+        assert_eq!(value["type"], "response.reasoning_text.delta");
+        assert_eq!(value["delta"], "think");
+        assert_eq!(value["sequence_number"], 4);
+    }
+}
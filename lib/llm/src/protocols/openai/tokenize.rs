@@ -0,0 +1,91 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Request and response types of the `/tokenize` and `/detokenize` endpoints, which expose a
+//! model's tokenizer and chat template without running inference.
+
+use serde::{Deserialize, Serialize};
+
+use crate::protocols::TokenIdType;
+
+/// Tokenizes either a prompt or a list of chat messages.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TokenizeRequest {
+    pub model: String,
+
+    /// Text to tokenize
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+
+    /// Chat messages to render with the model's chat template and tokenize
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub messages: Option<Vec<async_openai::types::ChatCompletionRequestMessage>>,
+
+    /// Whether to render the input with the chat template; a prompt is rendered as a single user
+    /// message. Defaults to true for messages and false for a prompt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub apply_chat_template: Option<bool>,
+
+    /// Whether the rendered template ends with the prompt of the assistant's turn. Defaults to
+    /// true.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub add_generation_prompt: Option<bool>,
+}
+
+impl TokenizeRequest {
+    pub fn apply_chat_template(&self) -> bool {
+        self.apply_chat_template.unwrap_or(self.messages.is_some())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TokenizeResponse {
+    pub model: String,
+
+    /// Number of tokens
+    pub count: usize,
+
+    pub token_ids: Vec<TokenIdType>,
+
+    /// String form of each token
+    pub tokens: Vec<String>,
+
+    /// The text which was tokenized, after applying the chat template if requested
+    pub formatted_prompt: String,
+
+    /// Maximum number of tokens in a sequence of the model
+    pub context_length: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DetokenizeRequest {
+    pub model: String,
+
+    pub token_ids: Vec<TokenIdType>,
+
+    /// Whether special tokens such as the end of sequence token are left out of the text
+    #[serde(default)]
+    pub skip_special_tokens: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DetokenizeResponse {
+    pub model: String,
+
+    pub text: String,
+
+    /// Maximum number of tokens in a sequence of the model
+    pub context_length: usize,
+}
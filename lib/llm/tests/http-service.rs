@@ -16,12 +16,21 @@
 use anyhow::Error;
 use async_stream::stream;
 use dynamo_llm::http::service::{
+    admin::AdminModels,
+    admission::{PRIORITY_HEADER, REJECTED_DISPLACED, REJECTED_QUEUE_FULL, REJECTED_QUEUE_TIMEOUT},
+    audit::{AuditRecord, AuditStatus},
+    auth::ApiKeyConfig,
+    config::{HttpServiceSettings, Redaction},
     error::HttpError,
     metrics::{Endpoint, RequestType, Status},
     service_v2::HttpService,
     Metrics,
 };
+use dynamo_llm::model_card::model::ModelDeploymentCard;
+use dynamo_llm::model_type::ModelType;
+use dynamo_llm::preprocessor::OpenAIPreprocessor;
 use dynamo_llm::protocols::{
+    common::{llm_backend::BackendOutput, FinishReason},
     openai::{
         chat_completions::{NvCreateChatCompletionRequest, NvCreateChatCompletionStreamResponse},
         completions::{CompletionRequest, CompletionResponse},
@@ -29,14 +38,16 @@ use dynamo_llm::protocols::{
     Annotated,
 };
 use dynamo_runtime::{
+    logging::TraceParent,
     pipeline::{
-        async_trait, AsyncEngine, AsyncEngineContextProvider, ManyOut, ResponseStream, SingleIn,
+        async_trait, AsyncEngine, AsyncEngineContext, AsyncEngineContextProvider, ManyOut,
+        ResponseStream, SingleIn,
     },
     CancellationToken,
 };
 use prometheus::{proto::MetricType, Registry};
 use reqwest::StatusCode;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 struct CounterEngine {}
 
@@ -69,6 +80,7 @@ impl
 
                 let output = NvCreateChatCompletionStreamResponse {
                     inner,
+                    reasoning_content: Default::default(),
                 };
 
                 yield Annotated::from_data(output);
@@ -115,6 +127,136 @@ impl AsyncEngine<SingleIn<CompletionRequest>, ManyOut<Annotated<CompletionRespon
     }
 }
 
+/// Generates three tokens for a five token prompt, two of which were cached, and converts them
+/// with the preprocessor's response generators
+struct UsageEngine {}
+
+/// Backend stream generating one token per text, the last of which finishes the stream
+fn backend_stream(
+    context: Arc<dyn AsyncEngineContext>,
+    texts: Vec<String>,
+) -> ManyOut<Annotated<BackendOutput>> {
+    let count = texts.len();
+    let outputs: Vec<_> = texts
+        .into_iter()
+        .enumerate()
+        .map(|(i, text)| {
+            Annotated::from_data(BackendOutput {
+                token_ids: vec![i as u32],
+                tokens: vec![None],
+                text: Some(text),
+                cum_log_probs: None,
+                log_probs: None,
+                finish_reason: (i + 1 == count).then_some(FinishReason::Stop),
+                cached_tokens: Some(2),
+            })
+        })
+        .collect();
+    ResponseStream::new(Box::pin(futures::stream::iter(outputs)), context)
+}
+
+fn usage_backend_stream(context: Arc<dyn AsyncEngineContext>) -> ManyOut<Annotated<BackendOutput>> {
+    backend_stream(context, (0..3).map(|i| format!("token {i} ")).collect())
+}
+
+#[async_trait]
+impl
+    AsyncEngine<
+        SingleIn<NvCreateChatCompletionRequest>,
+        ManyOut<Annotated<NvCreateChatCompletionStreamResponse>>,
+        Error,
+    > for UsageEngine
+{
+    async fn generate(
+        &self,
+        request: SingleIn<NvCreateChatCompletionRequest>,
+    ) -> Result<ManyOut<Annotated<NvCreateChatCompletionStreamResponse>>, Error> {
+        let (request, context) = request.transfer(());
+        let mut generator = request.response_generator();
+        generator.update_isl(5);
+        let stream = usage_backend_stream(context.context());
+        Ok(OpenAIPreprocessor::transform_postprocessor_stream(
+            stream,
+            Box::new(generator),
+        ))
+    }
+}
+
+#[async_trait]
+impl AsyncEngine<SingleIn<CompletionRequest>, ManyOut<Annotated<CompletionResponse>>, Error>
+    for UsageEngine
+{
+    async fn generate(
+        &self,
+        request: SingleIn<CompletionRequest>,
+    ) -> Result<ManyOut<Annotated<CompletionResponse>>, Error> {
+        let (request, context) = request.transfer(());
+        let mut generator = request.response_generator();
+        generator.update_isl(5);
+        let stream = usage_backend_stream(context.context());
+        Ok(OpenAIPreprocessor::transform_postprocessor_stream(
+            stream,
+            Box::new(generator),
+        ))
+    }
+}
+
+/// Generates a Qwen3 style reasoning span split across tokens followed by the answer
+struct ReasoningEngine {}
+
+#[async_trait]
+impl
+    AsyncEngine<
+        SingleIn<NvCreateChatCompletionRequest>,
+        ManyOut<Annotated<NvCreateChatCompletionStreamResponse>>,
+        Error,
+    > for ReasoningEngine
+{
+    async fn generate(
+        &self,
+        request: SingleIn<NvCreateChatCompletionRequest>,
+    ) -> Result<ManyOut<Annotated<NvCreateChatCompletionStreamResponse>>, Error> {
+        let (request, context) = request.transfer(());
+        let generator = request.response_generator();
+        let texts = ["<think>", "let me ", "see</th", "ink>\n\n", "yes"];
+        let stream = backend_stream(
+            context.context(),
+            texts.iter().map(|text| text.to_string()).collect(),
+        );
+        Ok(OpenAIPreprocessor::transform_postprocessor_stream(
+            stream,
+            Box::new(generator),
+        ))
+    }
+}
+
+/// Records the trace context each request arrives with and returns an empty stream
+#[derive(Default)]
+struct TraceEngine {
+    seen: Mutex<Vec<Option<TraceParent>>>,
+}
+
+#[async_trait]
+impl
+    AsyncEngine<
+        SingleIn<NvCreateChatCompletionRequest>,
+        ManyOut<Annotated<NvCreateChatCompletionStreamResponse>>,
+        Error,
+    > for TraceEngine
+{
+    async fn generate(
+        &self,
+        request: SingleIn<NvCreateChatCompletionRequest>,
+    ) -> Result<ManyOut<Annotated<NvCreateChatCompletionStreamResponse>>, Error> {
+        self.seen
+            .lock()
+            .unwrap()
+            .push(request.trace_parent().cloned());
+        let stream = futures::stream::empty();
+        Ok(ResponseStream::new(Box::pin(stream), request.context()))
+    }
+}
+
 fn compare_counter(
     metrics: Arc<Metrics>,
     model: &str,
@@ -463,3 +605,1050 @@ async fn test_http_service() {
     cancel_token.cancel();
     task.await.unwrap().unwrap();
 }
+
+#[tokio::test]
+async fn test_http_service_trace_context() {
+    let service = HttpService::builder().port(8990).build().unwrap();
+    let manager = service.model_manager().clone();
+
+    let token = CancellationToken::new();
+    let cancel_token = token.clone();
+    let task = tokio::spawn(async move { service.run(token.clone()).await });
+
+    let engine = Arc::new(TraceEngine::default());
+    manager
+        .add_chat_completions_model("foo", engine.clone())
+        .unwrap();
+
+    let request = serde_json::json!({
+        "model": "foo",
+        "messages": [{"role": "user", "content": "hi"}],
+        "stream": true,
+    });
+
+    let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    let client = reqwest::Client::new();
+
+    // without span export, the caller's trace context is forwarded unchanged
+    let response = client
+        .post("http://localhost:8990/v1/chat/completions")
+        .header("traceparent", traceparent)
+        .header("tracestate", "congo=t61rcWkgMzE")
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+    let _ = response.bytes().await.unwrap();
+
+    // malformed or missing headers yield no trace context
+    for headers in [vec![("traceparent", "not-a-traceparent")], vec![]] {
+        let mut builder = client.post("http://localhost:8990/v1/chat/completions");
+        for (name, value) in headers {
+            builder = builder.header(name, value);
+        }
+        let response = builder.json(&request).send().await.unwrap();
+        assert!(response.status().is_success(), "{:?}", response);
+        let _ = response.bytes().await.unwrap();
+    }
+
+    let seen = engine.seen.lock().unwrap().clone();
+    assert_eq!(
+        seen,
+        vec![
+            TraceParent::new(traceparent, Some("congo=t61rcWkgMzE")),
+            None,
+            None
+        ]
+    );
+
+    cancel_token.cancel();
+    task.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_http_service_api_keys() {
+    let mut settings = HttpServiceSettings::default();
+    settings.auth.enabled = true;
+    settings.auth.keys = vec![
+        ApiKeyConfig {
+            id: "team-a".to_string(),
+            key: "sk-a".to_string(),
+            requests_per_second: None,
+            tokens_per_minute: None,
+            models: Some(vec!["foo".to_string()]),
+        },
+        ApiKeyConfig {
+            id: "team-b".to_string(),
+            key: "sk-b".to_string(),
+            requests_per_second: Some(1.0),
+            tokens_per_minute: None,
+            models: None,
+        },
+    ];
+
+    let service = HttpService::builder()
+        .port(8991)
+        .settings(settings)
+        .build()
+        .unwrap();
+    let manager = service.model_manager().clone();
+
+    let token = CancellationToken::new();
+    let cancel_token = token.clone();
+    let task = tokio::spawn(async move { service.run(token.clone()).await });
+
+    manager
+        .add_chat_completions_model("foo", Arc::new(CounterEngine {}))
+        .unwrap();
+    manager
+        .add_chat_completions_model("bar", Arc::new(CounterEngine {}))
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let url = "http://localhost:8991/v1/chat/completions";
+    let request = |model: &str| {
+        serde_json::json!({
+            "model": model,
+            "messages": [{"role": "user", "content": "hi"}],
+        })
+    };
+
+    // missing or unknown keys are rejected
+    let response = client.post(url).json(&request("foo")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "invalid_api_key");
+
+    let response = client
+        .post(url)
+        .bearer_auth("sk-unknown")
+        .json(&request("foo"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // the allow-list is enforced per key
+    let response = client
+        .post(url)
+        .bearer_auth("sk-a")
+        .json(&request("foo"))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+    let _ = response.bytes().await.unwrap();
+
+    let response = client
+        .post(url)
+        .bearer_auth("sk-a")
+        .json(&request("bar"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "model_not_found");
+
+    // requests per second are limited per key
+    let response = client
+        .post(url)
+        .bearer_auth("sk-b")
+        .json(&request("bar"))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+    let _ = response.bytes().await.unwrap();
+
+    let response = client
+        .post(url)
+        .bearer_auth("sk-b")
+        .json(&request("bar"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key("retry-after"));
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "rate_limit_exceeded");
+    assert_eq!(body["error"]["type"], "requests");
+
+    // requests are counted under the key id
+    let metrics = manager.metrics();
+    assert_eq!(
+        metrics.get_request_counter_for_key(
+            "team-a",
+            "foo",
+            &Endpoint::ChatCompletions,
+            &RequestType::Unary,
+            &Status::Success
+        ),
+        1
+    );
+    assert_eq!(
+        metrics.get_request_counter(
+            "foo",
+            &Endpoint::ChatCompletions,
+            &RequestType::Unary,
+            &Status::Success
+        ),
+        0
+    );
+
+    // metrics do not require a key
+    let response = client
+        .get("http://localhost:8991/metrics")
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+
+    cancel_token.cancel();
+    task.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_http_service_model_aliases_and_backends() {
+    let service = HttpService::builder().port(8992).build().unwrap();
+    let manager = service.model_manager().clone();
+
+    let token = CancellationToken::new();
+    let cancel_token = token.clone();
+    let task = tokio::spawn(async move { service.run(token.clone()).await });
+
+    let stable = Arc::new(TraceEngine::default());
+    let canary = Arc::new(TraceEngine::default());
+    manager
+        .add_chat_completions_backend("foo", "stable", 3, stable.clone())
+        .unwrap();
+    manager
+        .add_chat_completions_backend("foo", "canary", 1, canary.clone())
+        .unwrap();
+    assert!(manager
+        .add_chat_completions_backend("foo", "canary", 1, canary.clone())
+        .is_err());
+
+    manager
+        .add_alias(ModelType::Chat, "foo-latest", "foo")
+        .unwrap();
+    assert!(manager.add_alias(ModelType::Chat, "foo", "bar").is_err());
+    assert!(manager
+        .add_chat_completions_model("foo-latest", Arc::new(CounterEngine {}))
+        .is_err());
+    assert!(manager.has_model_any("foo-latest"));
+
+    // aliases are admitted and counted as the model they name
+    let state = manager.state();
+    assert_eq!(state.chat_completions_model("foo-latest"), "foo");
+    assert_eq!(state.chat_completions_model("foo"), "foo");
+    assert_eq!(state.completions_model("foo-latest"), "foo-latest");
+
+    let client = reqwest::Client::new();
+    let url = "http://localhost:8992/v1/chat/completions";
+    let request = |model: &str| {
+        serde_json::json!({
+            "model": model,
+            "messages": [{"role": "user", "content": "hi"}],
+            "stream": true,
+        })
+    };
+
+    // requests through the alias are split across both backends by weight
+    for _ in 0..40 {
+        let response = client
+            .post(url)
+            .json(&request("foo-latest"))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success(), "{:?}", response);
+        let _ = response.bytes().await.unwrap();
+    }
+    let stable_calls = stable.seen.lock().unwrap().len();
+    let canary_calls = canary.seen.lock().unwrap().len();
+    assert_eq!(stable_calls + canary_calls, 40);
+    assert!(
+        stable_calls > canary_calls,
+        "{stable_calls} vs {canary_calls}"
+    );
+    assert!(canary_calls > 0);
+
+    // removing the canary sends all traffic to the remaining backend
+    manager
+        .remove_chat_completions_backend("foo", "canary")
+        .unwrap();
+    for _ in 0..5 {
+        let response = client.post(url).json(&request("foo")).send().await.unwrap();
+        assert!(response.status().is_success(), "{:?}", response);
+        let _ = response.bytes().await.unwrap();
+    }
+    assert_eq!(stable.seen.lock().unwrap().len(), stable_calls + 5);
+    assert_eq!(canary.seen.lock().unwrap().len(), canary_calls);
+
+    // aliases are listed with their target as parent
+    let models: serde_json::Value = client
+        .get("http://localhost:8992/v1/models")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let data = models["data"].as_array().unwrap();
+    assert_eq!(data.len(), 2);
+    let alias = data.iter().find(|m| m["id"] == "foo-latest").unwrap();
+    assert_eq!(alias["parent"], "foo");
+    let model = data.iter().find(|m| m["id"] == "foo").unwrap();
+    assert!(model.get("parent").is_none());
+
+    manager.remove_alias(ModelType::Chat, "foo-latest").unwrap();
+    let response = client
+        .post(url)
+        .json(&request("foo-latest"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    cancel_token.cancel();
+    task.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_http_service_admin_api() {
+    let mut settings = HttpServiceSettings::default();
+    settings.admin.enabled = true;
+    settings.admin.api_key = Some("admin-secret".to_string());
+
+    let service = HttpService::builder()
+        .port(8993)
+        .settings(settings)
+        .build()
+        .unwrap();
+    let manager = service.model_manager().clone();
+
+    let token = CancellationToken::new();
+    let cancel_token = token.clone();
+    let task = tokio::spawn(async move { service.run(token.clone()).await });
+
+    manager
+        .add_chat_completions_model("foo", Arc::new(CounterEngine {}))
+        .unwrap();
+    manager
+        .add_alias(ModelType::Chat, "foo-latest", "foo")
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let admin = |path: &str| format!("http://localhost:8993/admin/{path}");
+
+    // the admin token is required
+    let response = client.get(admin("models")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = client
+        .get(admin("models"))
+        .bearer_auth("wrong")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let models: AdminModels = client
+        .get(admin("models"))
+        .bearer_auth("admin-secret")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(models.models.len(), 1);
+    assert_eq!(models.models[0].name, "foo");
+    assert_eq!(models.models[0].backends.len(), 1);
+    assert_eq!(models.models[0].backends[0].instances, None);
+    assert_eq!(models.aliases.len(), 1);
+    assert_eq!(models.default_model, None);
+
+    // the default model must exist
+    let response = client
+        .put(admin("default_model"))
+        .bearer_auth("admin-secret")
+        .json(&serde_json::json!({ "model": "missing" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = client
+        .put(admin("default_model"))
+        .bearer_auth("admin-secret")
+        .json(&serde_json::json!({ "model": "foo" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let chat = |model: &str| {
+        client
+            .post("http://localhost:8993/v1/chat/completions")
+            .json(&serde_json::json!({
+                "model": model,
+                "messages": [{"role": "user", "content": "hi"}],
+            }))
+            .send()
+    };
+
+    let response = chat("").await.unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+    let _ = response.bytes().await.unwrap();
+
+    // maintenance applies to the model and its aliases
+    let response = client
+        .put(admin("maintenance/foo"))
+        .bearer_auth("admin-secret")
+        .json(&serde_json::json!({ "enabled": true }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    for model in ["foo", "foo-latest", ""] {
+        let response = chat(model).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    let models: AdminModels = client
+        .get(admin("models"))
+        .bearer_auth("admin-secret")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(models.models[0].maintenance);
+    assert_eq!(models.default_model.as_deref(), Some("foo"));
+
+    let response = client
+        .put(admin("maintenance/foo"))
+        .bearer_auth("admin-secret")
+        .json(&serde_json::json!({ "enabled": false }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = chat("foo-latest").await.unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+    let _ = response.bytes().await.unwrap();
+
+    // registering models requires model discovery
+    let response = client
+        .post(admin("models"))
+        .bearer_auth("admin-secret")
+        .json(&serde_json::json!({ "name": "bar", "target": "foo", "model_type": "Chat" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client
+        .delete(admin("models/embedding/foo"))
+        .bearer_auth("admin-secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    cancel_token.cancel();
+    task.await.unwrap().unwrap();
+}
+
+#[test]
+fn test_http_service_admin_requires_key() {
+    let mut settings = HttpServiceSettings::default();
+    settings.admin.enabled = true;
+    assert!(HttpService::builder().settings(settings).build().is_err());
+}
+
+#[tokio::test]
+async fn test_http_service_admission_control() {
+    let mut settings = HttpServiceSettings::default();
+    settings.admission.max_inflight = Some(1);
+    settings.admission.max_queued = 1;
+    settings.admission.queue_timeout_ms = 500;
+    settings.admission.retry_after_secs = 2;
+
+    let service = HttpService::builder()
+        .port(8994)
+        .settings(settings)
+        .build()
+        .unwrap();
+    let manager = service.model_manager().clone();
+    let metrics = manager.metrics();
+
+    let token = CancellationToken::new();
+    let cancel_token = token.clone();
+    let task = tokio::spawn(async move { service.run(token.clone()).await });
+
+    manager
+        .add_chat_completions_model("foo", Arc::new(CounterEngine {}))
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let chat = |priority: Option<&str>, max_tokens: u32| {
+        let mut request = client
+            .post("http://localhost:8994/v1/chat/completions")
+            .json(&serde_json::json!({
+                "model": "foo",
+                "messages": [{"role": "user", "content": "hi"}],
+                "max_tokens": max_tokens,
+            }));
+        if let Some(priority) = priority {
+            request = request.header(PRIORITY_HEADER, priority);
+        }
+        request.send()
+    };
+
+    // occupies the only in-flight slot for 1.5s
+    let running = tokio::spawn(chat(None, 1500));
+    tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+    assert_eq!(metrics.get_inflight_count("foo"), 1);
+
+    // queued, then displaced by a request of a higher priority
+    let batch = tokio::spawn(chat(Some("batch"), 0));
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    assert_eq!(metrics.get_queued_count("foo", "batch"), 1);
+
+    let interactive = tokio::spawn(
+        client
+            .post("http://localhost:8994/v1/chat/completions")
+            .json(&serde_json::json!({
+                "model": "foo",
+                "messages": [{"role": "user", "content": "hi"}],
+                "nvext": { "priority": "interactive" },
+            }))
+            .send(),
+    );
+
+    let response = batch.await.unwrap().unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["retry-after"], "2");
+    assert_eq!(metrics.get_rejected_count("foo", REJECTED_DISPLACED), 1);
+
+    // the queue is full of higher priority requests
+    let response = chat(Some("normal"), 0).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(metrics.get_rejected_count("foo", REJECTED_QUEUE_FULL), 1);
+
+    // the queued request times out before the slot is released
+    let response = interactive.await.unwrap().unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()["retry-after"], "2");
+    assert_eq!(metrics.get_rejected_count("foo", REJECTED_QUEUE_TIMEOUT), 1);
+    assert_eq!(metrics.get_queued_count("foo", "interactive"), 0);
+
+    let response = running.await.unwrap().unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+    let _ = response.bytes().await.unwrap();
+
+    // the slot is free again
+    let response = chat(Some("batch"), 0).await.unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+    let _ = response.bytes().await.unwrap();
+
+    let response = chat(Some("urgent"), 0).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    cancel_token.cancel();
+    task.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_http_service_audit_log() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("audit.jsonl");
+
+    let mut settings = HttpServiceSettings::default();
+    settings.audit.enabled = true;
+    settings.audit.path = path.clone();
+    settings.audit.redaction = Redaction::None;
+
+    let service = HttpService::builder()
+        .port(8995)
+        .settings(settings)
+        .build()
+        .unwrap();
+    let manager = service.model_manager().clone();
+
+    let token = CancellationToken::new();
+    let cancel_token = token.clone();
+    let task = tokio::spawn(async move { service.run(token.clone()).await });
+
+    manager
+        .add_chat_completions_model("foo", Arc::new(CounterEngine {}))
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    for stream in [false, true] {
+        let response = client
+            .post("http://localhost:8995/v1/chat/completions")
+            .json(&serde_json::json!({
+                "model": "foo",
+                "messages": [{"role": "user", "content": "hi"}],
+                "temperature": 0.5,
+                "stream": stream,
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success(), "{:?}", response);
+        let _ = response.bytes().await.unwrap();
+    }
+
+    // records are written in the background once each response completes
+    let mut records = Vec::new();
+    for _ in 0..50 {
+        records = std::fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .map(|line| serde_json::from_str::<AuditRecord>(line).unwrap())
+            .collect();
+        if records.len() == 2 {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(records.len(), 2);
+
+    for (record, streaming) in records.iter().zip([false, true]) {
+        assert_eq!(record.streaming, streaming);
+        assert_eq!(record.model, "foo");
+        assert_eq!(record.endpoint, "chat_completions");
+        assert_eq!(record.status, AuditStatus::Success);
+        assert_eq!(record.sampling.temperature, Some(0.5));
+        assert_eq!(record.prompt.as_ref().unwrap()[0]["content"], "hi");
+        assert_eq!(record.choices.len(), 10);
+        assert_eq!(record.choices[3].text.as_deref(), Some("choice 3"));
+    }
+    assert!(records[1].first_chunk_ms.is_some());
+
+    cancel_token.cancel();
+    task.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_http_service_stream_usage() {
+    let service = HttpService::builder().port(8996).build().unwrap();
+    let manager = service.model_manager().clone();
+
+    let token = CancellationToken::new();
+    let cancel_token = token.clone();
+    let task = tokio::spawn(async move { service.run(token.clone()).await });
+
+    manager
+        .add_chat_completions_model("foo", Arc::new(UsageEngine {}))
+        .unwrap();
+    manager
+        .add_completions_model("foo", Arc::new(UsageEngine {}))
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let chat = |body: serde_json::Value| {
+        client
+            .post("http://localhost:8996/v1/chat/completions")
+            .json(&body)
+            .send()
+    };
+    let sse_chunks = |body: String| -> Vec<serde_json::Value> {
+        body.lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter(|data| *data != "[DONE]")
+            .map(|data| serde_json::from_str(data).unwrap())
+            .collect()
+    };
+    let expected_usage = |usage: &serde_json::Value| {
+        assert_eq!(usage["prompt_tokens"], 5);
+        assert_eq!(usage["completion_tokens"], 3);
+        assert_eq!(usage["total_tokens"], 8);
+        assert_eq!(usage["prompt_tokens_details"]["cached_tokens"], 2);
+    };
+
+    // the usage is reported by a final chunk without choices
+    let response = chat(serde_json::json!({
+        "model": "foo",
+        "messages": [{"role": "user", "content": "hi"}],
+        "stream": true,
+        "stream_options": { "include_usage": true },
+    }))
+    .await
+    .unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+    let chunks = sse_chunks(response.text().await.unwrap());
+    assert_eq!(chunks.len(), 4);
+    let (last, deltas) = chunks.split_last().unwrap();
+    assert!(deltas.iter().all(|chunk| chunk["usage"].is_null()));
+    assert_eq!(last["choices"].as_array().unwrap().len(), 0);
+    expected_usage(&last["usage"]);
+
+    // no usage unless requested
+    let response = chat(serde_json::json!({
+        "model": "foo",
+        "messages": [{"role": "user", "content": "hi"}],
+        "stream": true,
+    }))
+    .await
+    .unwrap();
+    let chunks = sse_chunks(response.text().await.unwrap());
+    assert_eq!(chunks.len(), 3);
+    assert!(chunks.iter().all(|chunk| chunk["usage"].is_null()));
+
+    // aggregated responses always report usage
+    let response: serde_json::Value = chat(serde_json::json!({
+        "model": "foo",
+        "messages": [{"role": "user", "content": "hi"}],
+    }))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(
+        response["choices"][0]["message"]["content"],
+        "token 0 token 1 token 2 "
+    );
+    expected_usage(&response["usage"]);
+
+    let response: serde_json::Value = client
+        .post("http://localhost:8996/v1/completions")
+        .json(&serde_json::json!({ "model": "foo", "prompt": "hi" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(response["choices"][0]["text"], "token 0 token 1 token 2 ");
+    expected_usage(&response["usage"]);
+
+    cancel_token.cancel();
+    task.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_http_service_reasoning_content() {
+    let service = HttpService::builder().port(8997).build().unwrap();
+    let manager = service.model_manager().clone();
+
+    let token = CancellationToken::new();
+    let cancel_token = token.clone();
+    let task = tokio::spawn(async move { service.run(token.clone()).await });
+
+    manager
+        .add_chat_completions_model("foo", Arc::new(ReasoningEngine {}))
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let chat = |body: serde_json::Value| {
+        client
+            .post("http://localhost:8997/v1/chat/completions")
+            .json(&body)
+            .send()
+    };
+
+    let response = chat(serde_json::json!({
+        "model": "foo",
+        "messages": [{"role": "user", "content": "hi"}],
+        "stream": true,
+        "stream_options": { "include_usage": true },
+        "nvext": { "reasoning_parser": "qwen3" },
+    }))
+    .await
+    .unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+    let body = response.text().await.unwrap();
+    let chunks: Vec<serde_json::Value> = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .filter(|data| *data != "[DONE]")
+        .map(|data| serde_json::from_str(data).unwrap())
+        .collect();
+    let collect = |field: &str| -> String {
+        chunks
+            .iter()
+            .filter_map(|chunk| chunk["choices"][0]["delta"][field].as_str())
+            .collect()
+    };
+    assert_eq!(collect("reasoning_content"), "let me see");
+    assert_eq!(collect("content"), "yes");
+    let usage = &chunks.last().unwrap()["usage"];
+    assert_eq!(usage["completion_tokens"], 5);
+    assert_eq!(usage["completion_tokens_details"]["reasoning_tokens"], 4);
+
+    // aggregated responses carry the reasoning text in the message
+    let response: serde_json::Value = chat(serde_json::json!({
+        "model": "foo",
+        "messages": [{"role": "user", "content": "hi"}],
+        "nvext": { "reasoning_parser": "qwen3" },
+    }))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    let message = &response["choices"][0]["message"];
+    assert_eq!(message["reasoning_content"], "let me see");
+    assert_eq!(message["content"], "yes");
+
+    // without a parser the tags are passed through
+    let response: serde_json::Value = chat(serde_json::json!({
+        "model": "foo",
+        "messages": [{"role": "user", "content": "hi"}],
+    }))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    let message = &response["choices"][0]["message"];
+    assert!(message.get("reasoning_content").is_none());
+    assert_eq!(message["content"], "<think>let me see</think>\n\nyes");
+
+    cancel_token.cancel();
+    task.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_http_service_tokenize() {
+    let service = HttpService::builder().port(8998).build().unwrap();
+    let manager = service.model_manager().clone();
+
+    let token = CancellationToken::new();
+    let cancel_token = token.clone();
+    let task = tokio::spawn(async move { service.run(token.clone()).await });
+
+    let card = ModelDeploymentCard::from_local_path(
+        "tests/data/sample-models/mock-llama-3.1-8b-instruct",
+        None,
+    )
+    .await
+    .unwrap();
+    let preprocessor = OpenAIPreprocessor::new(card).await.unwrap();
+    manager
+        .add_chat_completions_model("foo", Arc::new(CounterEngine {}))
+        .unwrap();
+    manager.add_preprocessor("foo", preprocessor);
+    manager
+        .add_alias(ModelType::Chat, "foo-latest", "foo")
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let post = |path: &str, body: serde_json::Value| {
+        client
+            .post(format!("http://localhost:8998{path}"))
+            .json(&body)
+            .send()
+    };
+
+    // messages are rendered with the chat template
+    let response = post(
+        "/tokenize",
+        serde_json::json!({
+            "model": "foo-latest",
+            "messages": [{"role": "user", "content": "Hello"}],
+        }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let tokenized: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        tokenized["formatted_prompt"],
+        "<|begin_of_text|><|start_header_id|>user<|end_header_id|>\n\nHello<|eot_id|><|start_header_id|>assistant<|end_header_id|>\n\n"
+    );
+    assert_eq!(tokenized["context_length"], 8192);
+    let token_ids: Vec<u32> = serde_json::from_value(tokenized["token_ids"].clone()).unwrap();
+    assert_eq!(tokenized["count"], token_ids.len());
+    assert_eq!(
+        tokenized["tokens"].as_array().unwrap().len(),
+        token_ids.len()
+    );
+    assert!(token_ids.contains(&128006));
+
+    // a prompt is tokenized as is unless the template is requested
+    let tokenized: serde_json::Value = post(
+        "/tokenize",
+        serde_json::json!({ "model": "foo", "prompt": "<|eot_id|>" }),
+    )
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(tokenized["formatted_prompt"], "<|eot_id|>");
+    let tokenized: serde_json::Value = post(
+        "/tokenize",
+        serde_json::json!({
+            "model": "foo",
+            "prompt": "Hello",
+            "apply_chat_template": true,
+            "add_generation_prompt": false,
+        }),
+    )
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(
+        tokenized["formatted_prompt"],
+        "<|begin_of_text|><|start_header_id|>user<|end_header_id|>\n\nHello"
+    );
+
+    let detokenized: serde_json::Value = post(
+        "/detokenize",
+        serde_json::json!({ "model": "foo", "token_ids": [128006, 128007] }),
+    )
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(detokenized["text"], "<|start_header_id|><|end_header_id|>");
+    assert_eq!(detokenized["context_length"], 8192);
+
+    // invalid requests
+    let response = post(
+        "/tokenize",
+        serde_json::json!({ "model": "foo", "prompt": "a", "messages": [] }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = post(
+        "/tokenize",
+        serde_json::json!({ "model": "bar", "prompt": "a" }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // the preprocessor is dropped with the model
+    manager.remove_chat_completions_model("foo").unwrap();
+    let response = post(
+        "/detokenize",
+        serde_json::json!({ "model": "foo", "token_ids": [128006] }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    cancel_token.cancel();
+    task.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_http_service_responses() {
+    let service = HttpService::builder().port(8999).build().unwrap();
+    let manager = service.model_manager().clone();
+
+    let token = CancellationToken::new();
+    let cancel_token = token.clone();
+    let task = tokio::spawn(async move { service.run(token.clone()).await });
+
+    manager
+        .add_chat_completions_model("foo", Arc::new(ReasoningEngine {}))
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let responses = |body: serde_json::Value| {
+        client
+            .post("http://localhost:8999/v1/responses")
+            .json(&body)
+            .send()
+    };
+
+    let response = responses(serde_json::json!({
+        "model": "foo",
+        "instructions": "Answer briefly.",
+        "input": [{"role": "user", "content": "hi"}],
+        "stream": true,
+        "nvext": { "reasoning_parser": "qwen3" },
+    }))
+    .await
+    .unwrap();
+    assert!(response.status().is_success(), "{:?}", response);
+    let body = response.text().await.unwrap();
+
+    // each event is named after its type
+    let names: Vec<&str> = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("event: "))
+        .collect();
+    let events: Vec<serde_json::Value> = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .filter(|data| *data != "[DONE]")
+        .map(|data| serde_json::from_str(data).unwrap())
+        .collect();
+    assert_eq!(names.len(), events.len());
+    for (i, (name, event)) in names.iter().zip(&events).enumerate() {
+        assert_eq!(event["type"], *name);
+        assert_eq!(event["sequence_number"], i);
+    }
+    assert_eq!(names.first(), Some(&"response.created"));
+    assert_eq!(names.last(), Some(&"response.completed"));
+
+    let collect = |name: &str| -> String {
+        events
+            .iter()
+            .filter(|event| event["type"] == name)
+            .filter_map(|event| event["delta"].as_str())
+            .collect()
+    };
+    assert_eq!(collect("response.reasoning_text.delta"), "let me see");
+    assert_eq!(collect("response.output_text.delta"), "yes");
+
+    let completed = &events.last().unwrap()["response"];
+    assert_eq!(completed["status"], "completed");
+    assert_eq!(completed["instructions"], "Answer briefly.");
+    assert_eq!(completed["usage"]["output_tokens"], 5);
+    assert_eq!(
+        completed["usage"]["output_tokens_details"]["reasoning_tokens"],
+        4
+    );
+
+    // non-streaming requests return the response object
+    let response: serde_json::Value = responses(serde_json::json!({
+        "model": "foo",
+        "input": "hi",
+        "nvext": { "reasoning_parser": "qwen3" },
+    }))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert_eq!(response["object"], "response");
+    assert_eq!(response["status"], "completed");
+    let output = response["output"].as_array().unwrap();
+    assert_eq!(output.len(), 2);
+    assert_eq!(output[0]["type"], "reasoning");
+    assert_eq!(output[0]["content"][0]["text"], "let me see");
+    assert_eq!(output[1]["type"], "message");
+    assert_eq!(output[1]["content"][0]["type"], "output_text");
+    assert_eq!(output[1]["content"][0]["text"], "yes");
+
+    // responses are not stored
+    let response = responses(serde_json::json!({
+        "model": "foo",
+        "input": "hi",
+        "previous_response_id": "resp_123",
+    }))
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    cancel_token.cancel();
+    task.await.unwrap().unwrap();
+}
@@ -469,3 +469,74 @@ async fn test_multi_turn_with_continuation() {
       insta::assert_snapshot!(formatted_prompt);
     });
 }
+
+/// Text-only content-part arrays render as if the content were a string
+#[tokio::test]
+async fn test_content_parts_without_media() {
+    let mdc = ModelDeploymentCard::from_local_path(
+        "tests/data/sample-models/mock-llama-3.1-8b-instruct",
+        None,
+    )
+    .await
+    .unwrap();
+    let PromptFormatter::OAI(formatter) = PromptFormatter::from_mdc(mdc).await.unwrap();
+
+    let request = |content: serde_json::Value| {
+        let inner = async_openai::types::CreateChatCompletionRequestArgs::default()
+            .model("mock")
+            .messages(vec![serde_json::from_value(serde_json::json!({
+                "role": "user",
+                "content": content
+            }))
+            .unwrap()])
+            .build()
+            .unwrap();
+        NvCreateChatCompletionRequest { inner, nvext: None }
+    };
+
+    let text = formatter
+        .render(&request(serde_json::json!(
+            "What is deep learning?\nExplain briefly."
+        )))
+        .unwrap();
+    let parts = formatter
+        .render(&request(serde_json::json!([
+            { "type": "text", "text": "What is deep learning?" },
+            { "type": "text", "text": "Explain briefly." }
+        ])))
+        .unwrap();
+
+    assert_eq!(text, parts);
+    assert!(parts.contains("What is deep learning?\nExplain briefly."));
+}
+
+/// A chat template override replaces the model's template
+#[tokio::test]
+async fn test_chat_template_override() {
+    let mdc = ModelDeploymentCard::from_local_path(
+        "tests/data/sample-models/mock-llama-3.1-8b-instruct",
+        None,
+    )
+    .await
+    .unwrap();
+    let PromptFormatter::OAI(formatter) = PromptFormatter::from_mdc(mdc).await.unwrap();
+
+    let inner = async_openai::types::CreateChatCompletionRequestArgs::default()
+        .model("mock")
+        .messages(vec![serde_json::from_value(serde_json::json!({
+            "role": "user",
+            "content": "What is deep learning?"
+        }))
+        .unwrap()])
+        .build()
+        .unwrap();
+    let request = NvCreateChatCompletionRequest { inner, nvext: None };
+
+    let prompt = formatter
+        .render_with_template(
+            &request,
+            "{% for message in messages %}<{{ message.role }}>{{ message.content }}{% endfor %}{% if add_generation_prompt %}<assistant>{% endif %}",
+        )
+        .unwrap();
+    assert_eq!(prompt, "<user>What is deep learning?<assistant>");
+}
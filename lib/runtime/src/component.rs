@@ -80,6 +80,28 @@ pub enum TransportType {
 pub struct RegistryInner {
     services: HashMap<String, Service>,
     stats_handlers: HashMap<String, Arc<std::sync::Mutex<HashMap<String, EndpointStatsHandler>>>>,
+    served_endpoints: HashMap<String, ServedEndpoint>,
+}
+
+/// An endpoint served by this process, keyed in the [`Registry`] by its etcd path.
+/// Used to deregister and drain the endpoint before the process shuts down.
+#[derive(Clone)]
+pub(crate) struct ServedEndpoint {
+    pub(crate) drain_token: tokio_util::sync::CancellationToken,
+    pub(crate) inflight: tokio_util::task::TaskTracker,
+}
+
+impl Registry {
+    /// Etcd keys and drain handles of all endpoints currently served by this process
+    pub(crate) async fn served_endpoints(&self) -> Vec<(String, ServedEndpoint)> {
+        self.inner
+            .lock()
+            .await
+            .served_endpoints
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
 }
 
 #[derive(Clone)]
@@ -16,6 +16,7 @@
 use derive_getters::Dissolve;
 
 use super::*;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
 
 pub use async_nats::service::endpoint::Stats as EndpointStats;
 
@@ -84,13 +85,27 @@ impl EndpointConfigBuilder {
 
         drop(registry);
 
-        // insert the stats handler
-        if let Some(stats_handler) = stats_handler {
-            handler_map
-                .lock()
-                .unwrap()
-                .insert(endpoint.subject_to(lease.id()), stats_handler);
-        }
+        // record the built-in endpoint metrics
+        let metrics = endpoint.drt().metrics().endpoint(
+            &endpoint.component.namespace.name,
+            &endpoint.component.name,
+            &endpoint.name,
+        );
+
+        // insert the stats handler; the endpoint metrics are merged into the user's stats
+        let mut stats_handler = stats_handler;
+        let stats_metrics = metrics.clone();
+        let stats_handler: EndpointStatsHandler = Box::new(move |stats| {
+            let data = match stats_handler.as_mut() {
+                Some(handler) => handler(stats),
+                None => serde_json::Value::Null,
+            };
+            stats_metrics.merge_stats(data)
+        });
+        handler_map
+            .lock()
+            .unwrap()
+            .insert(endpoint.subject_to(lease.id()), stats_handler);
 
         // creates an endpoint for the service
         let service_endpoint = group
@@ -99,10 +114,17 @@ impl EndpointConfigBuilder {
             .map_err(|e| anyhow::anyhow!("Failed to start endpoint: {e}"))?;
 
         let cancel_token = lease.child_token();
+        let served = ServedEndpoint {
+            drain_token: CancellationToken::new(),
+            inflight: TaskTracker::new(),
+        };
 
         let push_endpoint = PushEndpoint::builder()
             .service_handler(handler)
             .cancellation_token(cancel_token.clone())
+            .drain_token(served.drain_token.clone())
+            .inflight(served.inflight.clone())
+            .metrics(metrics)
             .build()
             .map_err(|e| anyhow::anyhow!("Failed to build push endpoint: {e}"))?;
 
@@ -121,16 +143,13 @@ impl EndpointConfigBuilder {
         };
 
         let info = serde_json::to_vec_pretty(&info)?;
+        let etcd_path = endpoint.etcd_path_with_id(lease.id());
 
         if let Err(e) = endpoint
             .component
             .drt
             .etcd_client
-            .kv_create(
-                endpoint.etcd_path_with_id(lease.id()),
-                info,
-                Some(lease.id()),
-            )
+            .kv_create(etcd_path.clone(), info, Some(lease.id()))
             .await
         {
             tracing::error!("Failed to register discoverable service: {:?}", e);
@@ -138,7 +157,25 @@ impl EndpointConfigBuilder {
             return Err(error!("Failed to register discoverable service"));
         }
 
-        task.await??;
+        // track the endpoint so it can be drained on shutdown
+        let registry = endpoint.drt().component_registry.clone();
+        registry
+            .inner
+            .lock()
+            .await
+            .served_endpoints
+            .insert(etcd_path.clone(), served);
+
+        let result = task.await;
+
+        registry
+            .inner
+            .lock()
+            .await
+            .served_endpoints
+            .remove(&etcd_path);
+
+        result??;
 
         Ok(())
     }
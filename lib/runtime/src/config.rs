@@ -176,6 +176,31 @@ pub fn use_local_timezone() -> bool {
     env_is_truthy("DYN_LOG_USE_LOCAL_TZ")
 }
 
+/// Base URL of an OTLP/HTTP trace collector, e.g. `http://localhost:4318`
+/// Set the `DYN_OTLP_ENDPOINT` environment variable to enable span export; unset or empty disables it
+pub fn otlp_endpoint() -> Option<String> {
+    std::env::var("DYN_OTLP_ENDPOINT")
+        .ok()
+        .filter(|v| !v.is_empty())
+}
+
+/// Service name reported to the trace collector
+/// Set the `DYN_OTLP_SERVICE_NAME` environment variable; defaults to `dynamo`
+pub fn otlp_service_name() -> String {
+    std::env::var("DYN_OTLP_SERVICE_NAME")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| "dynamo".to_string())
+}
+
+/// Port of the endpoint metrics HTTP listener started by the distributed runtime
+/// Set the `DYN_METRICS_PORT` environment variable to enable it; unset or invalid disables it
+pub fn metrics_port() -> Option<u16> {
+    std::env::var("DYN_METRICS_PORT")
+        .ok()
+        .and_then(|v| v.parse().ok())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
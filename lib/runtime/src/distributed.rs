@@ -17,6 +17,7 @@ pub use crate::component::Component;
 use crate::{
     component::{self, ComponentBuilder, Namespace},
     discovery::DiscoveryClient,
+    metrics,
     service::ServiceClient,
     transports::{etcd, nats, tcp},
     ErrorContext,
@@ -26,11 +27,29 @@ use super::{error, Arc, DistributedRuntime, OnceCell, Result, Runtime, OK};
 
 use derive_getters::Dissolve;
 use figment::error;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{net::SocketAddr, time::Duration};
+
+/// Reply sent by an instance acknowledging a drain request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrainResponse {
+    /// Lease id of the instance being drained
+    pub instance_id: i64,
+
+    /// Number of requests in flight when the drain was requested
+    pub inflight: usize,
+}
+
+/// NATS subject on which an instance listens for drain requests
+fn drain_subject(instance_id: i64) -> String {
+    format!("dynamo.instance.{:x}.drain", instance_id)
+}
 
 impl DistributedRuntime {
     pub async fn new(runtime: Runtime, config: DistributedConfig) -> Result<Self> {
         let secondary = runtime.secondary();
-        let (etcd_config, nats_config) = config.dissolve();
+        let (etcd_config, nats_config, metrics_port) = config.dissolve();
 
         let runtime_clone = runtime.clone();
 
@@ -56,13 +75,22 @@ impl DistributedRuntime {
             })
             .await??;
 
-        Ok(Self {
+        let distributed_runtime = Self {
             runtime,
             etcd_client,
             nats_client,
             tcp_server: Arc::new(OnceCell::new()),
             component_registry: component::Registry::new(),
-        })
+            metrics: metrics::RuntimeMetrics::new()?,
+        };
+
+        if let Some(port) = metrics_port {
+            distributed_runtime
+                .start_metrics_server(SocketAddr::from(([0, 0, 0, 0], port)))
+                .await?;
+        }
+
+        Ok(distributed_runtime)
     }
 
     pub async fn from_settings(runtime: Runtime) -> Result<Self> {
@@ -82,6 +110,140 @@ impl DistributedRuntime {
         self.runtime.shutdown();
     }
 
+    /// Prometheus metrics for the endpoints served by this process
+    pub fn metrics(&self) -> &metrics::RuntimeMetrics {
+        &self.metrics
+    }
+
+    /// Serve the endpoint metrics over HTTP at `GET /metrics` until the runtime shuts down.
+    /// Returns the bound address.
+    pub async fn start_metrics_server(&self, addr: SocketAddr) -> Result<SocketAddr> {
+        self.metrics.serve(addr, self.runtime.primary_token()).await
+    }
+
+    /// Gracefully drain this instance and then shut down the [`Runtime`].
+    ///
+    /// All endpoints served by this instance are removed from etcd so routers stop sending new
+    /// requests, the endpoints stop accepting work, and in-flight requests are given up to `timeout`
+    /// to complete before the primary lease is revoked.
+    pub async fn drain(&self, timeout: Duration) {
+        let served = self.component_registry.served_endpoints().await;
+
+        for (key, _) in &served {
+            if let Err(e) = self.etcd_client.kv_delete(key).await {
+                tracing::warn!(key, "Failed to deregister endpoint: {:?}", e);
+            }
+        }
+
+        for (_, endpoint) in &served {
+            endpoint.drain_token.cancel();
+        }
+
+        let inflight: usize = served.iter().map(|(_, e)| e.inflight.len()).sum();
+        tracing::info!(inflight, "Draining {} endpoint(s)", served.len());
+
+        let wait_all = futures::future::join_all(served.iter().map(|(_, e)| e.inflight.wait()));
+        if tokio::time::timeout(timeout, wait_all).await.is_err() {
+            let remaining: usize = served.iter().map(|(_, e)| e.inflight.len()).sum();
+            tracing::warn!(
+                remaining,
+                "Drain timed out after {:?}; shutting down with requests in flight",
+                timeout
+            );
+        } else {
+            tracing::info!("Drain complete");
+        }
+
+        self.runtime.shutdown();
+    }
+
+    /// Ask the instance with the given lease id to gracefully drain
+    pub async fn request_drain(&self, instance_id: i64) -> Result<DrainResponse> {
+        let response = self
+            .nats_client
+            .client()
+            .request(drain_subject(instance_id), "".into())
+            .await
+            .map_err(|e| error!("Failed to request drain of instance {instance_id:x}: {e}"))?;
+        Ok(serde_json::from_slice(&response.payload)?)
+    }
+
+    /// Opt in to graceful draining: handle [`Runtime::drain`] and remote drain requests for this
+    /// instance by calling [`DistributedRuntime::drain`]. Workers call this before serving their
+    /// endpoints; without it a drain request shuts the [`Runtime`] down immediately.
+    pub async fn enable_drain(&self) -> Result<()> {
+        let runtime = self.runtime.clone();
+        if !runtime.enable_drain() {
+            // already enabled
+            return Ok(());
+        }
+
+        // drain when requested, e.g. by the signal handler
+        let drt = self.clone();
+        let drain_token = runtime.drain_token();
+        let primary_token = runtime.primary_token();
+        runtime.secondary().spawn(async move {
+            tokio::select! {
+                _ = drain_token.cancelled() => {
+                    let timeout = crate::config::WorkerConfig::from_settings().graceful_shutdown_timeout;
+                    drt.drain(Duration::from_secs(timeout)).await;
+                }
+                _ = primary_token.cancelled() => {}
+            }
+        });
+
+        // drain when requested over NATS, e.g. by `llmctl worker drain`
+        let instance_id = self.etcd_client.lease_id();
+        if instance_id == 0 {
+            // without a primary lease there is no instance id to address
+            return Ok(());
+        }
+        let mut subscriber = self
+            .nats_client
+            .client()
+            .subscribe(drain_subject(instance_id))
+            .await?;
+        let nats_client = self.nats_client.client().clone();
+        let registry = self.component_registry.clone();
+        let primary_token = runtime.primary_token();
+        runtime.secondary().spawn(async move {
+            let message = tokio::select! {
+                message = subscriber.next() => message,
+                _ = primary_token.cancelled() => None,
+            };
+            let Some(message) = message else {
+                return;
+            };
+
+            let inflight = registry
+                .served_endpoints()
+                .await
+                .iter()
+                .map(|(_, e)| e.inflight.len())
+                .sum();
+            tracing::info!(inflight, "Drain requested over NATS");
+
+            if let Some(reply) = message.reply {
+                let response = DrainResponse {
+                    instance_id,
+                    inflight,
+                };
+                match serde_json::to_vec(&response) {
+                    Ok(payload) => {
+                        if let Err(e) = nats_client.publish(reply, payload.into()).await {
+                            tracing::warn!("Failed to acknowledge drain request: {:?}", e);
+                        }
+                    }
+                    Err(e) => tracing::warn!("Failed to serialize drain response: {:?}", e),
+                }
+            }
+
+            runtime.drain();
+        });
+
+        Ok(())
+    }
+
     /// Create a [`Namespace`]
     pub fn namespace(&self, name: impl Into<String>) -> Result<Namespace> {
         Namespace::new(self.clone(), name.into())
@@ -132,6 +294,9 @@ impl DistributedRuntime {
 pub struct DistributedConfig {
     pub etcd_config: etcd::ClientOptions,
     pub nats_config: nats::ClientOptions,
+
+    /// Port of the optional endpoint metrics HTTP listener
+    pub metrics_port: Option<u16>,
 }
 
 impl DistributedConfig {
@@ -139,6 +304,7 @@ impl DistributedConfig {
         DistributedConfig {
             etcd_config: etcd::ClientOptions::default(),
             nats_config: nats::ClientOptions::default(),
+            metrics_port: crate::config::metrics_port(),
         }
     }
 
@@ -146,6 +312,7 @@ impl DistributedConfig {
         let mut config = DistributedConfig {
             etcd_config: etcd::ClientOptions::default(),
             nats_config: nats::ClientOptions::default(),
+            metrics_port: None,
         };
 
         config.etcd_config.attach_lease = false;
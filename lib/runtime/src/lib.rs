@@ -33,6 +33,7 @@ pub mod component;
 pub mod discovery;
 pub mod engine;
 pub mod logging;
+pub mod metrics;
 pub mod pipeline;
 pub mod prelude;
 pub mod protocols;
@@ -64,6 +65,8 @@ pub struct Runtime {
     primary: RuntimeType,
     secondary: RuntimeType,
     cancellation_token: CancellationToken,
+    drain_token: CancellationToken,
+    drain_enabled: Arc<std::sync::atomic::AtomicBool>,
 }
 
 /// Distributed [Runtime] which provides access to shared resources across the cluster, this includes
@@ -84,4 +87,7 @@ pub struct DistributedRuntime {
     // a single endpoint watcher for both clients, this keeps the number background tasking watching specific
     // paths in etcd to a minimum.
     component_registry: component::Registry,
+
+    // prometheus metrics for the endpoints served by this process
+    metrics: metrics::RuntimeMetrics,
 }
@@ -37,16 +37,28 @@
 //! "test_logging" = "info"
 //! "test_logging::api" = "trace"
 //! ```
+//!
+//! Spans can additionally be exported to an OpenTelemetry collector over OTLP/HTTP by setting
+//! `DYN_OTLP_ENDPOINT` to the collector base URL (e.g. `http://localhost:4318`). The reported
+//! service name defaults to `dynamo` and can be changed with `DYN_OTLP_SERVICE_NAME`.
+//!
+//! Trace context crosses process boundaries as a W3C [`TraceParent`], which is carried on the
+//! [`crate::pipeline::Context`] and in the request control message of the push transport.
 
 use std::collections::{BTreeMap, HashMap};
-use std::sync::Once;
+use std::sync::{Once, OnceLock};
 
 use figment::{
     providers::{Format, Serialized, Toml},
     Figment,
 };
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource};
 use serde::{Deserialize, Serialize};
 use tracing::{Event, Subscriber};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use tracing_subscriber::fmt::time::FormatTime;
 use tracing_subscriber::fmt::time::LocalTime;
 use tracing_subscriber::fmt::time::SystemTime;
@@ -55,8 +67,8 @@ use tracing_subscriber::fmt::{format::Writer, FormattedFields};
 use tracing_subscriber::fmt::{FmtContext, FormatFields};
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::registry::LookupSpan;
-use tracing_subscriber::EnvFilter;
 use tracing_subscriber::{filter::Directive, fmt};
+use tracing_subscriber::{EnvFilter, Layer};
 
 /// ENV used to set the log level
 const FILTER_ENV: &str = "DYN_LOG";
@@ -70,6 +82,9 @@ const CONFIG_PATH_ENV: &str = "DYN_LOGGING_CONFIG_PATH";
 /// Once instance to ensure the logger is only initialized once
 static INIT: Once = Once::new();
 
+/// Tracer provider backing the OTLP layer; held so buffered spans can be flushed on shutdown
+static TRACER_PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
 #[derive(Serialize, Deserialize, Debug)]
 struct LoggingConfig {
     log_level: String,
@@ -95,44 +110,196 @@ pub fn init() {
     INIT.call_once(|| {
         let config = load_config();
 
-        // Examples to remove noise
-        // .add_directive("rustls=warn".parse()?)
-        // .add_directive("tokio_util::codec=warn".parse()?)
-        let mut filter_layer = EnvFilter::builder()
-            .with_default_directive(config.log_level.parse().unwrap())
-            .with_env_var(FILTER_ENV)
-            .from_env_lossy();
-
-        // apply the log_filters from the config files
-        for (module, level) in config.log_filters {
-            match format!("{module}={level}").parse::<Directive>() {
-                Ok(d) => {
-                    filter_layer = filter_layer.add_directive(d);
-                }
+        let otlp_layer = crate::config::otlp_endpoint().and_then(|endpoint| {
+            match otlp_layer(&endpoint, &crate::config::otlp_service_name()) {
+                Ok(l) => Some(l.with_filter(filters(&config))),
                 Err(e) => {
-                    eprintln!("Failed parsing filter '{level}' for module '{module}': {e}");
+                    eprintln!("Failed to create OTLP exporter for '{endpoint}': {e}");
+                    None
                 }
             }
-        }
+        });
 
         if crate::config::jsonl_logging_enabled() {
             let l = fmt::layer()
                 .with_ansi(false) // ansi terminal escapes and colors always disabled
                 .event_format(CustomJsonFormatter::new())
                 .with_writer(std::io::stderr)
-                .with_filter(filter_layer);
-            tracing_subscriber::registry().with(l).init();
+                .with_filter(filters(&config));
+            tracing_subscriber::registry()
+                .with(otlp_layer)
+                .with(l)
+                .init();
         } else {
             let l = fmt::layer()
                 .with_ansi(!crate::config::disable_ansi_logging())
                 .event_format(fmt::format().compact().with_timer(TimeFormatter::new()))
                 .with_writer(std::io::stderr)
-                .with_filter(filter_layer);
-            tracing_subscriber::registry().with(l).init();
+                .with_filter(filters(&config));
+            tracing_subscriber::registry()
+                .with(otlp_layer)
+                .with(l)
+                .init();
         };
     });
 }
 
+/// Flush and stop the OTLP exporter, if one was configured
+/// Spans are exported in batches, so this should be called before the process exits.
+pub fn shutdown() {
+    if let Some(provider) = TRACER_PROVIDER.get() {
+        if let Err(e) = provider.shutdown() {
+            eprintln!("Failed to flush trace spans: {e}");
+        }
+    }
+}
+
+fn filters(config: &LoggingConfig) -> EnvFilter {
+    // Examples to remove noise
+    // .add_directive("rustls=warn".parse()?)
+    // .add_directive("tokio_util::codec=warn".parse()?)
+    let mut filter_layer = EnvFilter::builder()
+        .with_default_directive(config.log_level.parse().unwrap())
+        .with_env_var(FILTER_ENV)
+        .from_env_lossy();
+
+    // apply the log_filters from the config files
+    for (module, level) in &config.log_filters {
+        match format!("{module}={level}").parse::<Directive>() {
+            Ok(d) => {
+                filter_layer = filter_layer.add_directive(d);
+            }
+            Err(e) => {
+                eprintln!("Failed parsing filter '{level}' for module '{module}': {e}");
+            }
+        }
+    }
+
+    filter_layer
+}
+
+/// Create a layer which exports spans to the OTLP/HTTP collector at `endpoint`
+fn otlp_layer<S>(endpoint: &str, service_name: &str) -> anyhow::Result<impl Layer<S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()?;
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(service_name.to_string())
+                .build(),
+        )
+        .build();
+
+    let tracer = provider.tracer("dynamo");
+    if TRACER_PROVIDER.set(provider).is_err() {
+        anyhow::bail!("OTLP exporter already initialized");
+    }
+
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// W3C trace context (`traceparent` and optional `tracestate`) for a request.
+///
+/// This is extracted from incoming HTTP headers, carried on the [`crate::pipeline::Context`] and
+/// forwarded in the request control message, so the spans on every hop of a request are linked
+/// into a single trace.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceParent {
+    traceparent: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tracestate: Option<String>,
+}
+
+impl TraceParent {
+    /// Create a [`TraceParent`] from the raw header values; returns `None` if `traceparent` is
+    /// not a valid W3C trace context
+    pub fn new(traceparent: &str, tracestate: Option<&str>) -> Option<Self> {
+        let traceparent = traceparent.trim();
+        let parts: Vec<&str> = traceparent.split('-').collect();
+
+        let is_hex = |s: &str| s.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'));
+        let is_zero = |s: &str| s.chars().all(|c| c == '0');
+
+        let valid = parts.len() >= 4
+            && parts[0].len() == 2
+            && parts[0] != "ff"
+            && parts[1].len() == 32
+            && parts[2].len() == 16
+            && parts[3].len() == 2
+            && parts.iter().all(|p| is_hex(p))
+            && !is_zero(parts[1])
+            && !is_zero(parts[2]);
+
+        if !valid {
+            return None;
+        }
+
+        Some(Self {
+            traceparent: traceparent.to_string(),
+            tracestate: tracestate
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+        })
+    }
+
+    /// Trace context of the given span; returns `None` if the span is not being exported
+    pub fn from_span(span: &tracing::Span) -> Option<Self> {
+        let cx = span.context();
+        if !cx.span().span_context().is_valid() {
+            return None;
+        }
+
+        let mut carrier = HashMap::new();
+        TraceContextPropagator::new().inject_context(&cx, &mut carrier);
+        Self::new(
+            carrier.get("traceparent")?,
+            carrier.get("tracestate").map(String::as_str),
+        )
+    }
+
+    /// Trace context of the current span; see [`TraceParent::from_span`]
+    pub fn current() -> Option<Self> {
+        Self::from_span(&tracing::Span::current())
+    }
+
+    /// Make this trace context the remote parent of `span`
+    pub fn attach(&self, span: &tracing::Span) {
+        let mut carrier = HashMap::from([("traceparent".to_string(), self.traceparent.clone())]);
+        if let Some(tracestate) = &self.tracestate {
+            carrier.insert("tracestate".to_string(), tracestate.clone());
+        }
+        span.set_parent(TraceContextPropagator::new().extract(&carrier));
+    }
+
+    /// The 32 hex digit trace id
+    pub fn trace_id(&self) -> &str {
+        &self.traceparent[3..35]
+    }
+
+    /// The 16 hex digit id of the parent span
+    pub fn parent_id(&self) -> &str {
+        &self.traceparent[36..52]
+    }
+
+    /// Value for the `traceparent` header
+    pub fn traceparent(&self) -> &str {
+        &self.traceparent
+    }
+
+    /// Value for the `tracestate` header
+    pub fn tracestate(&self) -> Option<&str> {
+        self.tracestate.as_deref()
+    }
+}
+
 /// Log a message with file and line info
 /// Used by Python wrapper
 pub fn log_message(level: &str, message: &str, module: &str, file: &str, line: u32) {
@@ -336,3 +503,84 @@ impl tracing::field::Visit for JsonVisitor {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn test_trace_parent_parse() {
+        let tp = TraceParent::new(TRACEPARENT, Some("congo=t61rcWkgMzE")).unwrap();
+        assert_eq!(tp.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(tp.parent_id(), "00f067aa0ba902b7");
+        assert_eq!(tp.tracestate(), Some("congo=t61rcWkgMzE"));
+
+        // empty tracestate is dropped
+        let tp = TraceParent::new(TRACEPARENT, Some("")).unwrap();
+        assert_eq!(tp.tracestate(), None);
+
+        // invalid version, all-zero ids, uppercase and truncated values are rejected
+        for invalid in [
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "",
+        ] {
+            assert!(TraceParent::new(invalid, None).is_none(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_trace_parent_serde() {
+        let tp = TraceParent::new(TRACEPARENT, None).unwrap();
+        let json = serde_json::to_string(&tp).unwrap();
+        assert_eq!(json, format!("{{\"traceparent\":\"{TRACEPARENT}\"}}"));
+        assert_eq!(serde_json::from_str::<TraceParent>(&json).unwrap(), tp);
+    }
+
+    /// Spans created under a remote parent are exported to the collector as part of the same trace
+    #[test]
+    fn test_otlp_export_to_collector() {
+        // minimal stand-in for an OTLP/HTTP collector; reports the request line of each export
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { break };
+                let mut buf = vec![0u8; 64 * 1024];
+                let n = stream.read(&mut buf).unwrap_or(0);
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n");
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let _ = tx.send(request.lines().next().unwrap_or_default().to_string());
+            }
+        });
+
+        let remote = TraceParent::new(TRACEPARENT, None).unwrap();
+        let subscriber =
+            tracing_subscriber::registry().with(otlp_layer(&endpoint, "test").unwrap());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("handle_payload");
+            remote.attach(&span);
+            let _guard = span.enter();
+
+            // the local span continues the remote trace with a new span id
+            let local = TraceParent::current().unwrap();
+            assert_eq!(local.trace_id(), remote.trace_id());
+            assert_ne!(local.parent_id(), remote.parent_id());
+        });
+
+        shutdown();
+
+        let request_line = rx
+            .recv_timeout(std::time::Duration::from_secs(10))
+            .expect("collector did not receive any spans");
+        assert_eq!(request_line, "POST /v1/traces HTTP/1.1");
+    }
+}
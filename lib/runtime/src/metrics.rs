@@ -0,0 +1,337 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Built-in Prometheus metrics for the endpoints served by this process.
+//!
+//! Every endpoint started with [`crate::component::Endpoint::endpoint_builder`] records requests,
+//! errors, in-flight requests, handshake latency, stream duration and bytes sent. The metrics are
+//! exposed in the Prometheus text format by the optional metrics listener on
+//! [`crate::DistributedRuntime`] and are merged into the NATS service stats under the
+//! [`RUNTIME_STATS_KEY`] key, so [`crate::component::Component::scrape_stats`] sees them as well.
+//! Stats handlers which do not return a JSON object are reported unchanged.
+
+use std::{net::SocketAddr, time::Duration};
+
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+
+/// Prefix of all runtime endpoint metric names
+pub const METRICS_PREFIX: &str = "dynamo_endpoint";
+
+/// Key under which the [`EndpointMetricsSnapshot`] is inserted into the NATS service stats `data`
+pub const RUNTIME_STATS_KEY: &str = "runtime";
+
+const LABELS: &[&str] = &["namespace", "component", "endpoint"];
+
+/// Registry of the metrics for all endpoints served by this process
+#[derive(Clone)]
+pub struct RuntimeMetrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    errors: IntCounterVec,
+    inflight: IntGaugeVec,
+    handshake: HistogramVec,
+    stream_duration: HistogramVec,
+    bytes_sent: IntCounterVec,
+}
+
+impl RuntimeMetrics {
+    pub fn new() -> Result<Self> {
+        let registry = Registry::new();
+
+        let requests = IntCounterVec::new(
+            Opts::new(
+                format!("{METRICS_PREFIX}_requests_total"),
+                "Total number of requests received by the endpoint",
+            ),
+            LABELS,
+        )?;
+        let errors = IntCounterVec::new(
+            Opts::new(
+                format!("{METRICS_PREFIX}_errors_total"),
+                "Total number of requests which failed before the response stream completed",
+            ),
+            LABELS,
+        )?;
+        let inflight = IntGaugeVec::new(
+            Opts::new(
+                format!("{METRICS_PREFIX}_inflight_requests"),
+                "Number of requests currently being handled by the endpoint",
+            ),
+            LABELS,
+        )?;
+        let handshake = HistogramVec::new(
+            HistogramOpts::new(
+                format!("{METRICS_PREFIX}_handshake_duration_seconds"),
+                "Time from receiving a request to sending the response stream prologue",
+            )
+            .buckets(vec![
+                0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0,
+            ]),
+            LABELS,
+        )?;
+        let stream_duration = HistogramVec::new(
+            HistogramOpts::new(
+                format!("{METRICS_PREFIX}_stream_duration_seconds"),
+                "Time from sending the prologue to the end of the response stream",
+            )
+            .buckets(vec![
+                0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+            ]),
+            LABELS,
+        )?;
+        let bytes_sent = IntCounterVec::new(
+            Opts::new(
+                format!("{METRICS_PREFIX}_response_bytes_total"),
+                "Total number of response bytes sent by the endpoint",
+            ),
+            LABELS,
+        )?;
+
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(errors.clone()))?;
+        registry.register(Box::new(inflight.clone()))?;
+        registry.register(Box::new(handshake.clone()))?;
+        registry.register(Box::new(stream_duration.clone()))?;
+        registry.register(Box::new(bytes_sent.clone()))?;
+
+        Ok(Self {
+            registry,
+            requests,
+            errors,
+            inflight,
+            handshake,
+            stream_duration,
+            bytes_sent,
+        })
+    }
+
+    /// The underlying [`Registry`]; additional process-level collectors may be registered here
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Metrics for a single endpoint
+    pub fn endpoint(&self, namespace: &str, component: &str, endpoint: &str) -> EndpointMetrics {
+        let labels = &[namespace, component, endpoint];
+        EndpointMetrics {
+            requests: self.requests.with_label_values(labels),
+            errors: self.errors.with_label_values(labels),
+            inflight: self.inflight.with_label_values(labels),
+            handshake: self.handshake.with_label_values(labels),
+            stream_duration: self.stream_duration.with_label_values(labels),
+            bytes_sent: self.bytes_sent.with_label_values(labels),
+        }
+    }
+
+    /// Encode all metrics in the Prometheus text format
+    pub fn encode(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        prometheus::TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+
+    /// Serve `GET /metrics` on `addr` until the `cancel_token` is triggered.
+    /// Returns the bound address, which differs from `addr` when binding to port 0.
+    pub async fn serve(
+        &self,
+        addr: SocketAddr,
+        cancel_token: tokio_util::sync::CancellationToken,
+    ) -> Result<SocketAddr> {
+        let metrics = self.clone();
+        let app = axum::Router::new().route(
+            "/metrics",
+            axum::routing::get(move || {
+                let metrics = metrics.clone();
+                async move {
+                    match metrics.encode() {
+                        Ok(body) => (axum::http::StatusCode::OK, body),
+                        Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+                    }
+                }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        tracing::info!("Serving endpoint metrics on http://{local_addr}/metrics");
+
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app)
+                .with_graceful_shutdown(async move { cancel_token.cancelled().await })
+                .await
+            {
+                tracing::error!("Metrics listener failed: {:?}", e);
+            }
+        });
+
+        Ok(local_addr)
+    }
+}
+
+/// Metrics handles for a single served endpoint
+#[derive(Clone)]
+pub struct EndpointMetrics {
+    requests: IntCounter,
+    errors: IntCounter,
+    inflight: IntGauge,
+    handshake: Histogram,
+    stream_duration: Histogram,
+    bytes_sent: IntCounter,
+}
+
+impl EndpointMetrics {
+    /// Count a new request; the request is considered in flight until the guard is dropped
+    pub fn inflight_guard(&self) -> InflightGuard {
+        self.requests.inc();
+        self.inflight.inc();
+        InflightGuard {
+            inflight: self.inflight.clone(),
+        }
+    }
+
+    pub fn inc_errors(&self) {
+        self.errors.inc();
+    }
+
+    pub fn observe_handshake(&self, duration: Duration) {
+        self.handshake.observe(duration.as_secs_f64());
+    }
+
+    pub fn observe_stream_duration(&self, duration: Duration) {
+        self.stream_duration.observe(duration.as_secs_f64());
+    }
+
+    pub fn add_bytes_sent(&self, bytes: usize) {
+        self.bytes_sent.inc_by(bytes as u64);
+    }
+
+    /// Point-in-time values of the endpoint's metrics
+    pub fn snapshot(&self) -> EndpointMetricsSnapshot {
+        EndpointMetricsSnapshot {
+            requests_total: self.requests.get(),
+            errors_total: self.errors.get(),
+            inflight_requests: self.inflight.get(),
+            response_bytes_total: self.bytes_sent.get(),
+            handshake_seconds_sum: self.handshake.get_sample_sum(),
+            stream_duration_seconds_sum: self.stream_duration.get_sample_sum(),
+            streams_total: self.stream_duration.get_sample_count(),
+        }
+    }
+
+    /// Merge the [`EndpointMetricsSnapshot`] into the stats returned by a user stats handler.
+    /// Object values gain a [`RUNTIME_STATS_KEY`] entry and `null` becomes an object holding only
+    /// that entry; any other value is returned unchanged, so its consumers can still decode it.
+    pub fn merge_stats(&self, data: serde_json::Value) -> serde_json::Value {
+        let snapshot = match serde_json::to_value(self.snapshot()) {
+            Ok(snapshot) => snapshot,
+            Err(_) => return data,
+        };
+        match data {
+            serde_json::Value::Object(mut map) => {
+                map.insert(RUNTIME_STATS_KEY.to_string(), snapshot);
+                serde_json::Value::Object(map)
+            }
+            serde_json::Value::Null => serde_json::json!({ RUNTIME_STATS_KEY: snapshot }),
+            data => data,
+        }
+    }
+}
+
+/// Decrements the in-flight gauge when dropped
+pub struct InflightGuard {
+    inflight: IntGauge,
+}
+
+impl Drop for InflightGuard {
+    fn drop(&mut self) {
+        self.inflight.dec();
+    }
+}
+
+/// Endpoint metrics as reported in the NATS service stats
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EndpointMetricsSnapshot {
+    pub requests_total: u64,
+    pub errors_total: u64,
+    pub inflight_requests: i64,
+    pub response_bytes_total: u64,
+    pub handshake_seconds_sum: f64,
+    pub stream_duration_seconds_sum: f64,
+    pub streams_total: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_metrics() -> Result<()> {
+        let metrics = RuntimeMetrics::new()?;
+        let endpoint = metrics.endpoint("ns", "backend", "generate");
+
+        {
+            let _guard = endpoint.inflight_guard();
+            assert_eq!(endpoint.snapshot().inflight_requests, 1);
+            endpoint.observe_handshake(Duration::from_millis(2));
+            endpoint.add_bytes_sent(128);
+            endpoint.observe_stream_duration(Duration::from_millis(50));
+        }
+        {
+            let _guard = endpoint.inflight_guard();
+            endpoint.inc_errors();
+        }
+
+        let snapshot = endpoint.snapshot();
+        assert_eq!(snapshot.requests_total, 2);
+        assert_eq!(snapshot.errors_total, 1);
+        assert_eq!(snapshot.inflight_requests, 0);
+        assert_eq!(snapshot.response_bytes_total, 128);
+        assert_eq!(snapshot.streams_total, 1);
+
+        let text = metrics.encode()?;
+        assert!(text.contains(
+            r#"dynamo_endpoint_requests_total{component="backend",endpoint="generate",namespace="ns"} 2"#
+        ));
+        assert!(text.contains("dynamo_endpoint_handshake_duration_seconds_bucket"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_stats() -> Result<()> {
+        let metrics = RuntimeMetrics::new()?;
+        let endpoint = metrics.endpoint("ns", "backend", "generate");
+        drop(endpoint.inflight_guard());
+
+        let merged = endpoint.merge_stats(serde_json::json!({ "kv_active_blocks": 3 }));
+        assert_eq!(merged["kv_active_blocks"], 3);
+        assert_eq!(merged[RUNTIME_STATS_KEY]["requests_total"], 1);
+
+        let merged = endpoint.merge_stats(serde_json::Value::Null);
+        let snapshot: EndpointMetricsSnapshot =
+            serde_json::from_value(merged[RUNTIME_STATS_KEY].clone())?;
+        assert_eq!(snapshot, endpoint.snapshot());
+
+        let merged = endpoint.merge_stats(serde_json::json!(42));
+        assert_eq!(merged, serde_json::json!(42));
+        Ok(())
+    }
+}
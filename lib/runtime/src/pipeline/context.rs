@@ -29,12 +29,14 @@ use crate::engine::AsyncEngineController;
 use async_trait::async_trait;
 
 use super::registry::Registry;
+use crate::logging::TraceParent;
 
 pub struct Context<T: Data> {
     current: T,
     controller: Arc<Controller>, //todo: hold this as an arc
     registry: Registry,
     stages: Vec<String>,
+    trace_parent: Option<TraceParent>,
 }
 
 impl<T: Send + Sync + 'static> Context<T> {
@@ -45,6 +47,7 @@ impl<T: Send + Sync + 'static> Context<T> {
             controller: Arc::new(Controller::default()),
             registry: Registry::new(),
             stages: Vec::new(),
+            trace_parent: None,
         }
     }
 
@@ -54,6 +57,7 @@ impl<T: Send + Sync + 'static> Context<T> {
             controller: Arc::new(controller),
             registry: Registry::new(),
             stages: Vec::new(),
+            trace_parent: None,
         }
     }
 
@@ -63,6 +67,7 @@ impl<T: Send + Sync + 'static> Context<T> {
             controller: Arc::new(Controller::new(id)),
             registry: Registry::new(),
             stages: Vec::new(),
+            trace_parent: None,
         }
     }
 
@@ -74,6 +79,16 @@ impl<T: Send + Sync + 'static> Context<T> {
         &self.controller
     }
 
+    /// Distributed trace context of the request, if one was provided or created upstream
+    pub fn trace_parent(&self) -> Option<&TraceParent> {
+        self.trace_parent.as_ref()
+    }
+
+    /// Set the distributed trace context which will be propagated to downstream components
+    pub fn set_trace_parent(&mut self, trace_parent: TraceParent) {
+        self.trace_parent = Some(trace_parent);
+    }
+
     /// Insert an object into the registry with a specific key.
     pub fn insert<K: ToString, U: Send + Sync + 'static>(&mut self, key: K, value: U) {
         self.registry.insert_shared(key, value);
@@ -109,6 +124,7 @@ impl<T: Send + Sync + 'static> Context<T> {
                 controller: self.controller,
                 registry: self.registry,
                 stages: self.stages,
+                trace_parent: self.trace_parent,
             },
         )
     }
@@ -453,6 +469,24 @@ mod tests {
         assert_eq!(ctx.current.message, "Processed length: 5");
     }
 
+    #[test]
+    fn test_trace_parent_survives_transfer() {
+        let mut ctx = Context::new(Input {
+            value: "Hello".to_string(),
+        });
+        assert!(ctx.trace_parent().is_none());
+
+        let trace_parent = TraceParent::new(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            None,
+        )
+        .unwrap();
+        ctx.set_trace_parent(trace_parent.clone());
+
+        let ctx: Context<Processed> = ctx.map(|input| input.into());
+        assert_eq!(ctx.trace_parent(), Some(&trace_parent));
+    }
+
     #[test]
     fn test_into_context() {
         let ctx = Context::new(Input {
@@ -32,6 +32,8 @@ use futures::StreamExt;
 use super::{AsyncEngine, AsyncEngineContext, AsyncEngineContextProvider, ResponseStream};
 use serde::{Deserialize, Serialize};
 
+use crate::{logging::TraceParent, metrics::EndpointMetrics};
+
 use super::{
     context, AsyncTransportEngine, Context, Data, Error, ManyOut, PipelineError, PipelineIO,
     SegmentSource, ServiceBackend, ServiceEngine, SingleIn, Source,
@@ -274,6 +276,10 @@ struct RequestControlMessage {
     request_type: RequestType,
     response_type: ResponseType,
     connection_info: ConnectionInfo,
+
+    /// Distributed trace context of the caller, restored as the parent span by the receiver
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trace_parent: Option<TraceParent>,
 }
 
 pub struct Ingress<Req: PipelineIO, Resp: PipelineIO> {
@@ -322,4 +328,15 @@ impl<Req: PipelineIO, Resp: PipelineIO> Ingress<Req, Resp> {
 #[async_trait]
 pub trait PushWorkHandler: Send + Sync {
     async fn handle_payload(&self, payload: Bytes) -> Result<(), PipelineError>;
+
+    /// Handle a payload received by the endpoint which owns `metrics`. A handler served on
+    /// several endpoints records each request under the endpoint which received it. Handlers
+    /// which do not record metrics ignore them.
+    async fn handle_payload_with_metrics(
+        &self,
+        payload: Bytes,
+        _metrics: &EndpointMetrics,
+    ) -> Result<(), PipelineError> {
+        self.handle_payload(payload).await
+    }
 }
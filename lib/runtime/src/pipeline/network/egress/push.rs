@@ -39,6 +39,10 @@ struct RequestControlMessage {
     request_type: RequestType,
     response_type: ResponseType,
     connection_info: ConnectionInfo,
+
+    /// Distributed trace context of the caller, restored as the parent span by the receiver
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trace_parent: Option<TraceParent>,
 }
 
 pub type PushRouter<In, Out> =
@@ -87,6 +91,7 @@ where
 {
     async fn generate(&self, request: SingleIn<AddressedRequest<T>>) -> Result<ManyOut<U>, Error> {
         let request_id = request.context().id().to_string();
+        let trace_parent = request.trace_parent().cloned();
         let (addressed_request, context) = request.transfer(());
         let (request, address) = addressed_request.into_parts();
         let engine_ctx = context.context();
@@ -123,6 +128,7 @@ where
             request_type: RequestType::SingleIn,
             response_type: ResponseType::ManyOut,
             connection_info,
+            trace_parent,
         };
 
         // next build the two part message where we package the connection info and the request into
@@ -17,12 +17,25 @@ use super::*;
 use anyhow::Result;
 use async_nats::service::endpoint::Endpoint;
 use derive_builder::Builder;
-use tokio_util::sync::CancellationToken;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
 
 #[derive(Builder)]
 pub struct PushEndpoint {
     pub service_handler: Arc<dyn PushWorkHandler>,
     pub cancellation_token: CancellationToken,
+
+    /// When triggered, the endpoint stops accepting new requests and waits for the
+    /// in-flight requests to complete before returning
+    #[builder(default)]
+    pub drain_token: CancellationToken,
+
+    /// Tracks the requests currently being handled by this endpoint
+    #[builder(default)]
+    pub inflight: TaskTracker,
+
+    /// Metrics recording the requests received by this endpoint
+    #[builder(default, setter(strip_option))]
+    pub metrics: Option<EndpointMetrics>,
 }
 
 /// version of crate
@@ -53,6 +66,15 @@ impl PushEndpoint {
                     }
                     break;
                 }
+
+                // stop accepting new requests; in-flight requests are allowed to complete
+                _ = self.drain_token.cancelled() => {
+                    tracing::debug!(inflight = self.inflight.len(), "Draining endpoint");
+                    if let Err(e) = endpoint.stop().await {
+                        tracing::warn!("Failed to stop NATS service: {:?}", e);
+                    }
+                    break;
+                }
             };
 
             if let Some(req) = req {
@@ -62,10 +84,17 @@ impl PushEndpoint {
                 }
 
                 let ingress = self.service_handler.clone();
+                let metrics = self.metrics.clone();
                 let worker_id = "".to_string();
-                tokio::spawn(async move {
+                self.inflight.spawn(async move {
                     tracing::trace!(worker_id, "handling new request");
-                    let result = ingress.handle_payload(req.message.payload).await;
+                    let payload = req.message.payload;
+                    let result = match &metrics {
+                        Some(metrics) => {
+                            ingress.handle_payload_with_metrics(payload, metrics).await
+                        }
+                        None => ingress.handle_payload(payload).await,
+                    };
                     match result {
                         Ok(_) => {
                             tracing::trace!(worker_id, "request handled successfully");
@@ -80,6 +109,195 @@ impl PushEndpoint {
             }
         }
 
+        self.inflight.close();
+
+        if self.drain_token.is_cancelled() {
+            tokio::select! {
+                _ = self.inflight.wait() => {
+                    tracing::debug!("Endpoint drained");
+                }
+                _ = self.cancellation_token.cancelled() => {
+                    tracing::warn!(inflight = self.inflight.len(), "Endpoint canceled before drain completed");
+                }
+            }
+        }
+
         Ok(())
     }
 }
+
+#[cfg(feature = "integration")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transports::nats;
+    use async_nats::service::ServiceExt;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+    use tokio::sync::{mpsc, Semaphore};
+
+    /// Holds every request until a permit is released
+    struct BlockingHandler {
+        started: mpsc::UnboundedSender<()>,
+        release: Arc<Semaphore>,
+        handled: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl PushWorkHandler for BlockingHandler {
+        async fn handle_payload(&self, _payload: Bytes) -> Result<(), PipelineError> {
+            let _ = self.started.send(());
+            self.release.acquire().await.unwrap().forget();
+            self.handled.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drain_completes_inflight_requests() {
+        let client = nats::ClientOptions::builder()
+            .build()
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let service = client
+            .client()
+            .service_builder()
+            .start("test_drain_completes", "0.0.1")
+            .await
+            .unwrap();
+        let endpoint = service.endpoint("drain_completes").await.unwrap();
+
+        let (started_tx, mut started) = mpsc::unbounded_channel();
+        let release = Arc::new(Semaphore::new(0));
+        let handled = Arc::new(AtomicUsize::new(0));
+        let handler = BlockingHandler {
+            started: started_tx,
+            release: release.clone(),
+            handled: handled.clone(),
+        };
+        let push_endpoint = PushEndpoint::builder()
+            .service_handler(Arc::new(handler))
+            .cancellation_token(CancellationToken::new())
+            .build()
+            .unwrap();
+        let drain_token = push_endpoint.drain_token.clone();
+        let inflight = push_endpoint.inflight.clone();
+        let server = tokio::spawn(push_endpoint.start(endpoint));
+
+        client
+            .client()
+            .request("drain_completes", "first".into())
+            .await
+            .unwrap();
+        started.recv().await.unwrap();
+        assert_eq!(inflight.len(), 1);
+
+        drain_token.cancel();
+
+        // the endpoint waits for the in-flight request
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!server.is_finished());
+
+        release.add_permits(1);
+        server.await.unwrap().unwrap();
+        assert_eq!(handled.load(Ordering::SeqCst), 1);
+        assert!(inflight.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_drain_rejects_new_requests() {
+        let client = nats::ClientOptions::builder()
+            .build()
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let service = client
+            .client()
+            .service_builder()
+            .start("test_drain_rejects", "0.0.1")
+            .await
+            .unwrap();
+        let endpoint = service.endpoint("drain_rejects").await.unwrap();
+
+        let (started_tx, mut started) = mpsc::unbounded_channel();
+        let release = Arc::new(Semaphore::new(0));
+        let handled = Arc::new(AtomicUsize::new(0));
+        let handler = BlockingHandler {
+            started: started_tx,
+            release: release.clone(),
+            handled: handled.clone(),
+        };
+        let push_endpoint = PushEndpoint::builder()
+            .service_handler(Arc::new(handler))
+            .cancellation_token(CancellationToken::new())
+            .build()
+            .unwrap();
+        let drain_token = push_endpoint.drain_token.clone();
+        let server = tokio::spawn(push_endpoint.start(endpoint));
+
+        drain_token.cancel();
+        server.await.unwrap().unwrap();
+
+        // sent after the endpoint stopped accepting requests
+        let late = client
+            .client()
+            .request("drain_rejects", "late".into())
+            .await;
+        assert!(late.is_err());
+        assert!(started.try_recv().is_err());
+        assert_eq!(handled.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_interrupts_drain() {
+        let client = nats::ClientOptions::builder()
+            .build()
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let service = client
+            .client()
+            .service_builder()
+            .start("test_cancel_interrupts", "0.0.1")
+            .await
+            .unwrap();
+        let endpoint = service.endpoint("cancel_interrupts").await.unwrap();
+
+        let (started_tx, mut started) = mpsc::unbounded_channel();
+        let release = Arc::new(Semaphore::new(0));
+        let handled = Arc::new(AtomicUsize::new(0));
+        let handler = BlockingHandler {
+            started: started_tx,
+            release: release.clone(),
+            handled: handled.clone(),
+        };
+        let push_endpoint = PushEndpoint::builder()
+            .service_handler(Arc::new(handler))
+            .cancellation_token(CancellationToken::new())
+            .build()
+            .unwrap();
+        let drain_token = push_endpoint.drain_token.clone();
+        let cancel_token = push_endpoint.cancellation_token.clone();
+        let server = tokio::spawn(push_endpoint.start(endpoint));
+
+        client
+            .client()
+            .request("cancel_interrupts", "first".into())
+            .await
+            .unwrap();
+        started.recv().await.unwrap();
+
+        drain_token.cancel();
+        cancel_token.cancel();
+
+        // the endpoint returns with the request still in flight
+        server.await.unwrap().unwrap();
+        assert_eq!(handled.load(Ordering::SeqCst), 0);
+    }
+}
@@ -15,6 +15,8 @@
 
 use super::*;
 use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tracing::Instrument;
 
 #[async_trait]
 impl<T: Data, U: Data> PushWorkHandler for Ingress<SingleIn<T>, ManyOut<U>>
@@ -23,6 +25,36 @@ where
     U: Data + Serialize + std::fmt::Debug,
 {
     async fn handle_payload(&self, payload: Bytes) -> Result<(), PipelineError> {
+        self.decode_and_handle(payload, None).await
+    }
+
+    async fn handle_payload_with_metrics(
+        &self,
+        payload: Bytes,
+        metrics: &EndpointMetrics,
+    ) -> Result<(), PipelineError> {
+        let _inflight = metrics.inflight_guard();
+
+        let result = self.decode_and_handle(payload, Some(metrics)).await;
+        if result.is_err() {
+            metrics.inc_errors();
+        }
+        result
+    }
+}
+
+impl<T: Data, U: Data> Ingress<SingleIn<T>, ManyOut<U>>
+where
+    T: Data + for<'de> Deserialize<'de> + std::fmt::Debug,
+    U: Data + Serialize + std::fmt::Debug,
+{
+    async fn decode_and_handle(
+        &self,
+        payload: Bytes,
+        metrics: Option<&EndpointMetrics>,
+    ) -> Result<(), PipelineError> {
+        let received = Instant::now();
+
         // decode the control message and the request
         let msg = TwoPartCodec::default()
             .decode_message(payload)?
@@ -54,10 +86,33 @@ where
             }
         };
 
+        // restore the caller's trace context as the parent of this request's span
+        let span = tracing::info_span!("handle_payload", request_id = %control_msg.id);
+        if let Some(trace_parent) = &control_msg.trace_parent {
+            trace_parent.attach(&span);
+        }
+
+        self.handle_request(control_msg, request, received, metrics)
+            .instrument(span)
+            .await
+    }
+
+    async fn handle_request(
+        &self,
+        control_msg: RequestControlMessage,
+        request: T,
+        received: Instant,
+        metrics: Option<&EndpointMetrics>,
+    ) -> Result<(), PipelineError> {
         // extend request with context
         tracing::trace!("received control message: {:?}", control_msg);
         tracing::trace!("received request: {:?}", request);
-        let request: context::Context<T> = Context::with_id(request, control_msg.id);
+        let mut request: context::Context<T> = Context::with_id(request, control_msg.id);
+
+        // downstream calls made while serving this request are children of this span
+        if let Some(trace_parent) = TraceParent::current().or(control_msg.trace_parent) {
+            request.set_trace_parent(trace_parent);
+        }
 
         // todo - eventually have a handler class which will returned an abstracted object, but for now,
         // we only support tcp here, so we can just unwrap the connection info
@@ -95,19 +150,67 @@ where
             }
         };
 
+        let stream_start = Instant::now();
+        if let Some(metrics) = metrics {
+            metrics.observe_handshake(stream_start - received);
+        }
+
         let context = stream.context();
+        let mut result = Ok(());
 
         while let Some(resp) = stream.next().await {
             tracing::trace!("Sending response: {:?}", resp);
             let resp_bytes = serde_json::to_vec(&resp)
                 .expect("fatal error: invalid response object - this should never happen");
+            let num_bytes = resp_bytes.len();
             if (publisher.send(resp_bytes.into()).await).is_err() {
                 tracing::error!("Failed to publish response for stream {}", context.id());
                 context.stop_generating();
+                result = Err(PipelineError::Generic(format!(
+                    "Failed to publish response for stream {}",
+                    context.id()
+                )));
                 break;
             }
+            if let Some(metrics) = metrics {
+                metrics.add_bytes_sent(num_bytes);
+            }
+        }
+
+        if let Some(metrics) = metrics {
+            metrics.observe_stream_duration(stream_start.elapsed());
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::RuntimeMetrics;
+
+    #[tokio::test]
+    async fn test_metrics_per_endpoint() -> anyhow::Result<()> {
+        let metrics = RuntimeMetrics::new()?;
+        let first = metrics.endpoint("ns", "backend", "generate");
+        let second = metrics.endpoint("ns", "backend", "generate_v2");
+        let ingress = Ingress::<SingleIn<String>, ManyOut<String>>::new();
+
+        // the payloads are too short to be two part messages, so they are counted as errors
+        for endpoint in [&first, &second, &second] {
+            let result = ingress
+                .handle_payload_with_metrics(Bytes::from("invalid"), endpoint)
+                .await;
+            assert!(result.is_err());
         }
 
+        // each request is recorded by the endpoint which received it
+        assert_eq!(first.snapshot().requests_total, 1);
+        assert_eq!(first.snapshot().errors_total, 1);
+        assert_eq!(second.snapshot().requests_total, 2);
+        assert_eq!(second.snapshot().errors_total, 2);
+        assert_eq!(second.snapshot().inflight_requests, 0);
         Ok(())
     }
 }
@@ -30,7 +30,10 @@ use crate::config::{self, RuntimeConfig};
 
 use futures::Future;
 use once_cell::sync::OnceCell;
-use std::sync::{Arc, Mutex};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
 use tokio::{signal, task::JoinHandle};
 
 pub use tokio_util::sync::CancellationToken;
@@ -43,6 +46,9 @@ impl Runtime {
         // create a cancellation token
         let cancellation_token = CancellationToken::new();
 
+        // the drain token is not a child of the cancellation token; draining precedes cancellation
+        let drain_token = CancellationToken::new();
+
         // secondary runtime for background ectd/nats tasks
         let secondary = match secondary {
             Some(secondary) => secondary,
@@ -56,6 +62,8 @@ impl Runtime {
             primary: runtime,
             secondary,
             cancellation_token,
+            drain_token,
+            drain_enabled: Arc::new(AtomicBool::new(false)),
         })
     }
 
@@ -115,6 +123,34 @@ impl Runtime {
     pub fn shutdown(&self) {
         self.cancellation_token.cancel();
     }
+
+    /// Access the drain [`CancellationToken`] for the [`Runtime`].
+    /// This token is triggered when a graceful drain is requested; the component which enabled
+    /// draining is responsible for shutting down the [`Runtime`] once the drain completes.
+    pub fn drain_token(&self) -> CancellationToken {
+        self.drain_token.clone()
+    }
+
+    /// Request a graceful drain of the [`Runtime`].
+    /// If no component has enabled draining, this is equivalent to [`Runtime::shutdown`].
+    pub fn drain(&self) {
+        if self.drain_enabled.load(Ordering::SeqCst) {
+            self.drain_token.cancel();
+        } else {
+            self.shutdown();
+        }
+    }
+
+    /// Returns true if a component has taken responsibility for draining the [`Runtime`]
+    pub fn is_drain_enabled(&self) -> bool {
+        self.drain_enabled.load(Ordering::SeqCst)
+    }
+
+    /// Mark that a component will handle [`Runtime::drain`] requests.
+    /// Returns false if draining was already enabled.
+    pub(crate) fn enable_drain(&self) -> bool {
+        !self.drain_enabled.swap(true, Ordering::SeqCst)
+    }
 }
 
 impl RuntimeType {
@@ -135,3 +171,23 @@ impl std::fmt::Debug for RuntimeType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drain_is_opt_in() {
+        let runtime = Runtime::from_current().unwrap();
+        runtime.drain();
+        assert!(!runtime.drain_token().is_cancelled());
+        assert!(runtime.primary_token().is_cancelled());
+
+        let runtime = Runtime::from_current().unwrap();
+        assert!(runtime.enable_drain());
+        assert!(!runtime.enable_drain());
+        runtime.drain();
+        assert!(runtime.drain_token().is_cancelled());
+        assert!(!runtime.primary_token().is_cancelled());
+    }
+}
@@ -19,7 +19,13 @@
 // we will want to associate the components cancellation token with the
 // component's "service state"
 
-use crate::{error, transports::nats, utils::stream, Result};
+use crate::{
+    error,
+    metrics::{EndpointMetricsSnapshot, RUNTIME_STATS_KEY},
+    transports::nats,
+    utils::stream,
+    Result,
+};
 
 use async_nats::Message;
 use async_stream::try_stream;
@@ -96,6 +102,13 @@ impl Metrics {
     pub fn decode<T: for<'de> Deserialize<'de>>(self) -> Result<T> {
         serde_json::from_value(self.data).map_err(Into::into)
     }
+
+    /// Built-in runtime metrics of the endpoint, if the endpoint reported them
+    pub fn runtime_metrics(&self) -> Option<EndpointMetricsSnapshot> {
+        self.data
+            .get(RUNTIME_STATS_KEY)
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
 }
 
 impl ServiceClient {
@@ -239,4 +252,27 @@ mod tests {
 
         assert_eq!(endpoints.len(), 2);
     }
+
+    #[test]
+    fn test_runtime_metrics() {
+        let mut metrics = Metrics {
+            average_processing_time: 0.1,
+            last_error: "none".to_string(),
+            num_errors: 0,
+            num_requests: 10,
+            processing_time: 100,
+            queue_group: "group1".to_string(),
+            data: serde_json::json!({
+                "key": "value1",
+                "runtime": { "requests_total": 10, "inflight_requests": 2 }
+            }),
+        };
+
+        let runtime = metrics.runtime_metrics().unwrap();
+        assert_eq!(runtime.requests_total, 10);
+        assert_eq!(runtime.inflight_requests, 2);
+
+        metrics.data = serde_json::json!({"key": "value1"});
+        assert!(metrics.runtime_metrics().is_none());
+    }
 }
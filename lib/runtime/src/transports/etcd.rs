@@ -240,6 +240,12 @@ impl Client {
         Ok(())
     }
 
+    /// Delete a single key; returns the number of keys removed
+    pub async fn kv_delete(&self, key: impl AsRef<str>) -> Result<i64> {
+        let response = self.client.kv_client().delete(key.as_ref(), None).await?;
+        Ok(response.deleted())
+    }
+
     pub async fn kv_get_prefix(&self, prefix: impl AsRef<str>) -> Result<Vec<KeyValue>> {
         let mut get_response = self
             .client
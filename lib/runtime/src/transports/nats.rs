@@ -28,13 +28,14 @@
 //! - `NATS_AUTH_CREDENTIALS_FILE`: the path to the credentials file
 //!
 //! Note: `NATS_AUTH_USERNAME` and `NATS_AUTH_PASSWORD` must be used together.
-use crate::Result;
+use crate::{error, pipeline::network::WorkQueueConsumer, Result};
 
 use async_nats::{client, jetstream, Subscriber};
+use async_trait::async_trait;
 use bytes::Bytes;
 use derive_builder::Builder;
 use futures::{StreamExt, TryStreamExt};
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 use tokio::time;
 use validator::{Validate, ValidationError};
 
@@ -108,91 +109,68 @@ impl Client {
         Ok(subscription)
     }
 
-    // /// create a new stream
-    // async fn get_or_create_work_queue_stream(
-    //     &self,
-    //     name: &super::Namespace,
-    // ) -> Result<jetstream::stream::Stream> {
-    //     let stream = self
-    //         .js_ctx
-    //         .get_or_create_stream(async_nats::jetstream::stream::Config {
-    //             name: name.to_string(),
-    //             retention: async_nats::jetstream::stream::RetentionPolicy::WorkQueue,
-    //             subjects: vec![format!("{name}.>")],
-    //             ..Default::default()
-    //         })
-    //         .await?;
-    //     Ok(stream)
-    // }
-
-    // // get work queue
-    // pub async fn get_or_create_work_queue(
-    //     &self,
-    //     namespace: &super::Namespace,
-    //     queue_name: &Slug,
-    // ) -> Result<WorkQueue> {
-    //     let stream = self.get_or_create_work_queue_stream(namespace).await?;
-
-    //     let consumer_name = single_name(namespace, queue_name);
-    //     let subject_name = subject_name(namespace, queue_name);
-    //     let subject_name = format!("{}.*", subject_name);
-
-    //     tracing::trace!(
-    //         durable_name = consumer_name,
-    //         filter_subject = subject_name,
-    //         "get_or_create_work_queue"
-    //     );
-    //     let consumer = stream
-    //         .get_or_create_consumer(
-    //             &consumer_name,
-    //             jetstream::consumer::pull::Config {
-    //                 durable_name: Some(consumer_name.clone()),
-    //                 filter_subject: subject_name,
-    //                 ack_policy: jetstream::consumer::AckPolicy::Explicit,
-    //                 ..Default::default()
-    //             },
-    //         )
-    //         .await?;
-    //     Ok(WorkQueue::new(consumer))
-    // }
+    /// Get or create the durable work queue `name`
+    ///
+    /// The queue is a JetStream stream with work-queue retention, so an item is kept until a
+    /// worker acknowledges it, whether or not the workers or the enqueuing client restart. All
+    /// the workers of a queue share its pull consumer, so each item is handed to a single worker
+    /// at a time.
+    pub async fn get_or_create_work_queue(
+        &self,
+        name: &str,
+        options: WorkQueueOptions,
+    ) -> Result<WorkQueue> {
+        let stream_name = work_queue_stream_name(name);
+        let subject = format!("{stream_name}.queue");
+
+        let stream = self
+            .js_ctx
+            .get_or_create_stream(jetstream::stream::Config {
+                name: stream_name.clone(),
+                retention: jetstream::stream::RetentionPolicy::WorkQueue,
+                subjects: vec![format!("{stream_name}.*")],
+                ..Default::default()
+            })
+            .await?;
 
-    // pub async fn get_or_create_work_queue_publisher(
-    //     &self,
-    //     namespace: &super::Namespace,
-    //     queue_name: &Slug,
-    // ) -> Result<WorkQueuePublisher> {
-    //     let _stream = self.get_or_create_work_queue_stream(namespace).await?;
-    //     let _subject = subject_name(namespace, queue_name);
-    //     Ok(WorkQueuePublisher {
-    //         client: self.clone(),
-    //         namespace: namespace.clone(),
-    //         queue_name: queue_name.clone(),
-    //     })
-    // }
+        log::trace!(
+            stream = stream_name,
+            durable_name = WORK_QUEUE_CONSUMER,
+            filter_subject = subject,
+            "get_or_create_work_queue"
+        );
+        let consumer = stream
+            .get_or_create_consumer(
+                WORK_QUEUE_CONSUMER,
+                jetstream::consumer::pull::Config {
+                    durable_name: Some(WORK_QUEUE_CONSUMER.to_string()),
+                    filter_subject: subject.clone(),
+                    ack_policy: jetstream::consumer::AckPolicy::Explicit,
+                    ack_wait: options.ack_wait,
+                    max_deliver: options.max_deliver,
+                    ..Default::default()
+                },
+            )
+            .await?;
 
-    // pub async fn list_work_queues(
-    //     &self,
-    //     namespace: &super::Namespace,
-    // ) -> Result<Vec<String>> {
-    //     let stream = self.get_stream(namespace.as_ref()).await?;
-    //     let consumers: Vec<String> = stream.consumer_names().try_collect().await?;
-    //     Ok(consumers)
-    // }
+        Ok(WorkQueue {
+            js_ctx: self.js_ctx.clone(),
+            stream_name,
+            subject,
+            consumer,
+            options,
+        })
+    }
 
-    // /// remove a work queue
-    // pub async fn remove_work_queue(
-    //     &self,
-    //     namespace: &super::Namespace,
-    //     queue_name: &Slug,
-    // ) -> Result<()> {
-    //     let stream = self.get_stream(namespace.as_ref()).await?;
-    //     let consumer_name = single_name(namespace, queue_name);
-    //     let consumers = self.list_consumers(namespace.as_ref()).await?;
-    //     if consumers.contains(&consumer_name) {
-    //         stream.delete_consumer(&consumer_name).await?;
-    //     }
-    //     Ok(())
-    // }
+    /// Remove the work queue `name` and the items it holds; does not fail if the queue does not
+    /// exist
+    pub async fn remove_work_queue(&self, name: &str) -> Result<()> {
+        let stream_name = work_queue_stream_name(name);
+        if self.list_streams().await?.contains(&stream_name) {
+            self.js_ctx.delete_stream(&stream_name).await?;
+        }
+        Ok(())
+    }
 
     // /// publish a message to a subject
     // pub async fn publish(&self, subject: String, msg: Vec<u8>) -> Result<()> {
@@ -209,40 +187,6 @@ impl Client {
     //     Ok(sub)
     // }
 
-    // pub async fn enqueue(
-    //     &self,
-    //     namespace: &super::Namespace,
-    //     queue_name: &Slug,
-    //     payload: Bytes,
-    // ) -> Result<String> {
-    //     // let mut headers = HeaderMap::new();
-    //     let subject = subject_name(namespace, queue_name);
-    //     let request_id = uuid::Uuid::new_v4().to_string();
-    //     let subject = format!("{}.{}", subject, request_id);
-
-    //     self.client.publish(subject, payload).await?;
-
-    //     // self.client
-    //     //     .publish_with_headers(subject, headers, payload.into())
-    //     //     .await?;
-
-    //     Ok(request_id)
-    // }
-
-    // pub async fn enqueue_with_id(
-    //     &self,
-    //     namespace: &super::Namespace,
-    //     queue_name: &Slug,
-    //     request_id: &str,
-    //     payload: Vec<u8>,
-    // ) -> Result<()> {
-    //     let subject = subject_name(namespace, queue_name);
-    //     let subject = format!("{}.{}", subject, request_id);
-
-    //     self.client.publish(subject, payload.into()).await?;
-    //     Ok(())
-    // }
-
     // pub fn frontend_client(&self, request_id: String) -> SpecializedClient {
     //     SpecializedClient::new(self.client.clone(), ClientKind::Frontend, request_id)
     // }
@@ -252,6 +196,235 @@ impl Client {
     // }
 }
 
+/// Name of the durable consumer shared by the workers of a work queue
+pub const WORK_QUEUE_CONSUMER: &str = "worker-group";
+
+/// Name of the stream backing the work queue `name`; stream names may not contain `.`, `*`,
+/// `>`, path separators or whitespace
+fn work_queue_stream_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '.' | '*' | '>' | '/' | '\\' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Options of a [`WorkQueue`]
+///
+/// The options of the consumer are set when the queue is first created; a worker attaching to
+/// an existing queue keeps the consumer it finds.
+#[derive(Debug, Clone, Builder)]
+pub struct WorkQueueOptions {
+    /// How long a dequeued item may go unacknowledged before it is redelivered
+    #[builder(default = "Duration::from_secs(30)")]
+    ack_wait: Duration,
+
+    /// Maximum number of deliveries of an item; -1 for no limit
+    #[builder(default = "-1")]
+    max_deliver: i64,
+
+    /// How long [`WorkQueue::dequeue`] waits for an item
+    #[builder(default = "Duration::from_secs(1)")]
+    dequeue_timeout: Duration,
+}
+
+impl WorkQueueOptions {
+    /// Create a new [`WorkQueueOptionsBuilder`]
+    pub fn builder() -> WorkQueueOptionsBuilder {
+        WorkQueueOptionsBuilder::default()
+    }
+}
+
+impl Default for WorkQueueOptions {
+    fn default() -> Self {
+        WorkQueueOptionsBuilder::default()
+            .build()
+            .expect("all options have defaults")
+    }
+}
+
+/// A durable work queue on NATS JetStream
+///
+/// Items are enqueued with an id, pulled by one worker at a time and removed once the worker
+/// acknowledges them. An item that is negatively acknowledged, or that is not acknowledged
+/// within the `ack_wait` of the queue, is redelivered.
+#[derive(Clone)]
+pub struct WorkQueue {
+    js_ctx: jetstream::Context,
+    stream_name: String,
+    subject: String,
+    consumer: jetstream::consumer::PullConsumer,
+    options: WorkQueueOptions,
+}
+
+impl WorkQueue {
+    /// Name of the stream backing the queue
+    pub fn name(&self) -> &str {
+        &self.stream_name
+    }
+
+    /// Enqueue an item under a new unique id; returns the id
+    pub async fn enqueue(&self, payload: Bytes) -> Result<String> {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.enqueue_with_id(&id, payload).await?;
+        Ok(id)
+    }
+
+    /// Enqueue an item under `id`
+    ///
+    /// JetStream drops an item enqueued again under the same id within its duplicate window, so
+    /// an enqueue can safely be retried. Returns false if the item was such a duplicate.
+    pub async fn enqueue_with_id(&self, id: &str, payload: Bytes) -> Result<bool> {
+        let mut headers = async_nats::HeaderMap::new();
+        headers.insert(async_nats::header::NATS_MESSAGE_ID, id);
+
+        let ack = self
+            .js_ctx
+            .publish_with_headers(self.subject.clone(), headers, payload)
+            .await?
+            .await?;
+
+        Ok(!ack.duplicate)
+    }
+
+    /// Pull the next item, waiting up to the `dequeue_timeout` of the queue; None if the queue
+    /// stayed empty
+    pub async fn dequeue(&self) -> Result<Option<WorkItem>> {
+        self.dequeue_with_timeout(self.options.dequeue_timeout)
+            .await
+    }
+
+    /// Pull the next item, waiting up to `timeout`; None if the queue stayed empty
+    ///
+    /// The item must be acknowledged with [`WorkItem::ack`] once processed or it is redelivered.
+    pub async fn dequeue_with_timeout(&self, timeout: Duration) -> Result<Option<WorkItem>> {
+        let mut batch = self
+            .consumer
+            .batch()
+            .max_messages(1)
+            .expires(timeout)
+            .messages()
+            .await?;
+
+        match batch.next().await {
+            Some(Ok(message)) => Ok(Some(WorkItem { message })),
+            Some(Err(e)) => Err(error!("failed to dequeue from {}: {e}", self.stream_name)),
+            None => Ok(None),
+        }
+    }
+
+    /// Number of items waiting, and of items delivered but not yet acknowledged
+    pub async fn stats(&self) -> Result<WorkQueueStats> {
+        let mut consumer = self.consumer.clone();
+        let info = consumer.info().await?;
+        Ok(WorkQueueStats {
+            pending: info.num_pending,
+            ack_pending: info.num_ack_pending as u64,
+            redelivered: info.num_redelivered as u64,
+        })
+    }
+
+    /// Number of items waiting to be dequeued
+    pub async fn depth(&self) -> Result<u64> {
+        Ok(self.stats().await?.pending)
+    }
+}
+
+/// Depth of a [`WorkQueue`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorkQueueStats {
+    /// Items waiting to be dequeued
+    pub pending: u64,
+
+    /// Items dequeued but not yet acknowledged
+    pub ack_pending: u64,
+
+    /// Items delivered more than once and not yet acknowledged
+    pub redelivered: u64,
+}
+
+/// An item pulled from a [`WorkQueue`]
+pub struct WorkItem {
+    message: jetstream::Message,
+}
+
+impl WorkItem {
+    /// Id the item was enqueued under, if it was enqueued with one
+    pub fn id(&self) -> Option<&str> {
+        self.message
+            .headers
+            .as_ref()
+            .and_then(|headers| headers.get(async_nats::header::NATS_MESSAGE_ID))
+            .map(|id| id.as_str())
+    }
+
+    pub fn payload(&self) -> &Bytes {
+        &self.message.payload
+    }
+
+    /// Number of times the item has been delivered, including this delivery
+    pub fn delivered(&self) -> Result<u64> {
+        let info = self
+            .message
+            .info()
+            .map_err(|e| error!("failed to read the delivery info: {e}"))?;
+        Ok(info.delivered as u64)
+    }
+
+    /// Acknowledge the item, removing it from the queue
+    pub async fn ack(self) -> Result<()> {
+        self.message
+            .ack()
+            .await
+            .map_err(|e| error!("failed to ack work item: {e}"))
+    }
+
+    /// Return the item to the queue to be redelivered, after `delay` if any
+    pub async fn nak(self, delay: Option<Duration>) -> Result<()> {
+        self.message
+            .ack_with(jetstream::AckKind::Nak(delay))
+            .await
+            .map_err(|e| error!("failed to nak work item: {e}"))
+    }
+
+    /// Report the item is still being processed, restarting its `ack_wait`
+    pub async fn in_progress(&self) -> Result<()> {
+        self.message
+            .ack_with(jetstream::AckKind::Progress)
+            .await
+            .map_err(|e| error!("failed to report work item progress: {e}"))
+    }
+
+    /// Remove the item from the queue without processing it; it is not redelivered
+    pub async fn term(self) -> Result<()> {
+        self.message
+            .ack_with(jetstream::AckKind::Term)
+            .await
+            .map_err(|e| error!("failed to terminate work item: {e}"))
+    }
+}
+
+#[async_trait]
+impl WorkQueueConsumer for WorkQueue {
+    /// Waits for the next item and acknowledges it as it is handed out; use
+    /// [`WorkQueue::dequeue`] to acknowledge items once they are processed
+    async fn dequeue(&self) -> Result<Bytes, String> {
+        loop {
+            match WorkQueue::dequeue(self).await {
+                Ok(Some(item)) => {
+                    let payload = item.payload().clone();
+                    item.ack().await.map_err(|e| e.to_string())?;
+                    return Ok(payload);
+                }
+                Ok(None) => continue,
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+    }
+}
+
 /// NATS client options
 ///
 /// This object uses the builder pattern with default values that are evaluates
@@ -417,6 +590,15 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_work_queue_stream_name() {
+        assert_eq!(work_queue_stream_name("prefill_queue"), "prefill_queue");
+        assert_eq!(
+            work_queue_stream_name("dynamo/llama 3.1>*"),
+            "dynamo_llama_3_1__"
+        );
+    }
+
     // const TEST_STREAM: &str = "test_async_nats_stream";
 
     // #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -453,108 +635,6 @@ mod tests {
     //         assert_eq!(info.messages, 0, "stream {} not empty", stream);
     //     }
     // }
-
-    // #[tokio::test]
-    // async fn test_workq_pull_and_response_stream() {
-    //     let ns: Namespace = TEST_STREAM.try_into().unwrap();
-    //     let _client = match nats_client().await.ok() {
-    //         Some(client) => client,
-    //         None => {
-    //             println!("Failed to create client; skipping nats tests");
-    //             return;
-    //         }
-    //     };
-
-    //     let client = Client::builder()
-    //         .server("nats://localhost:4222")
-    //         .username("user")
-    //         .password("user")
-    //         .build()
-    //         .await
-    //         .expect("failed to create client");
-
-    //     let _streams = client.list_streams().await.expect("failed to list streams");
-    //     // assert!(!streams.contains(&TEST_STREAM.to_string()));
-
-    //     let _stream = client
-    //         .get_or_create_work_queue_stream(&ns)
-    //         .await
-    //         .expect("failed to create stream");
-
-    //     let model_name: Slug = "foo".try_into().unwrap();
-    //     let request_id = "bar";
-
-    //     let request = Request {
-    //         id: request_id.to_string(),
-    //     };
-
-    //     let request_payload = serde_json::to_vec(&request).expect("failed to serialize request");
-
-    //     //     let request = CompletionRequest {
-    //     //         prompt: CompletionContext::from_prompt("deep learning is".to_string()).into(),
-    //     //         stop_conditions: None,
-    //     //         sampling_options: None,
-    //     //     };
-
-    //     // remove work queue if it exists
-    //     client
-    //         .remove_work_queue(&ns, &model_name)
-    //         .await
-    //         .expect("remove work queue does not fail if queue does not exist");
-
-    //     // get the count of the work queues
-    //     let initial_work_queue_count = client
-    //         .list_work_queues(&ns)
-    //         .await
-    //         .expect("failed to list work queues")
-    //         .len();
-
-    //     // create work queue
-    //     let workq = client
-    //         .get_or_create_work_queue(&ns, &model_name)
-    //         .await
-    //         .expect("failed to get work queue");
-
-    //     // new work queue count
-    //     let work_queue_count = client
-    //         .list_work_queues(&ns)
-    //         .await
-    //         .expect("failed to list work queues")
-    //         .len();
-
-    //     assert_eq!(initial_work_queue_count, work_queue_count - 1);
-
-    //     client
-    //         .enqueue(&ns, &model_name, request_payload.into())
-    //         .await
-    //         .expect("failed to enqueue completion request");
-
-    //     let mut messages = workq
-    //         .pull(1, std::time::Duration::from_secs(1))
-    //         .await
-    //         .expect("failed to pull messages from work queue");
-
-    //     assert_eq!(1, messages.len());
-
-    //     let msg = messages.pop().expect("no message received");
-    //     msg.ack().await.expect("failed to ack");
-
-    //     let request: Request =
-    //         serde_json::from_slice(&msg.payload).expect("failed to deserialize message");
-
-    //     assert_eq!(request.id, request_id);
-
-    //     // clean up and delete nats work queue and stream
-    //     client
-    //         .remove_work_queue(&ns, &model_name)
-    //         .await
-    //         .expect("failed to remove work queue");
-
-    //     // client
-    //     //     .delete_stream(TEST_STREAM)
-    //     //     .await
-    //     //     .expect("failed to delete stream");
-    // }
 }
 // let frontend_client = client.frontend_client("test".to_string());
 
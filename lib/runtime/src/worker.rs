@@ -31,6 +31,10 @@
 //! The default values of [DYN_WORKER_GRACEFUL_SHUTDOWN_TIMEOUT] differ between the development
 //! and release builds. In development, the default is [DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT_DEBUG] and
 //! in release, the default is [DEFAULT_GRACEFUL_SHUTDOWN_TIMEOUT_RELEASE].
+//!
+//! If the application opted in with [crate::DistributedRuntime::enable_drain], the first signal
+//! starts a drain instead: the endpoints are removed from discovery, stop accepting new requests,
+//! and in-flight requests are given the same timeout to complete before the runtime is canceled.
 
 use super::{error, CancellationToken, Result, Runtime, RuntimeConfig};
 
@@ -119,7 +123,10 @@ impl Worker {
 
         INIT.set(Mutex::new(Some(secondary.spawn(async move {
             // start signal handler
-            tokio::spawn(signal_handler(runtime.cancellation_token.clone()));
+            tokio::spawn(signal_handler(
+                runtime.clone(),
+                Duration::from_secs(timeout),
+            ));
 
             let cancel_token = runtime.child_token();
             let (mut app_tx, app_rx) = tokio::sync::oneshot::channel::<()>();
@@ -172,14 +179,25 @@ impl Worker {
             .take()
             .expect("Application initialized; but another thread is awaiting it; Worker.execute() can only be called once");
 
-        secondary.block_on(task)??;
+        let result = secondary.block_on(task);
+
+        // flush any spans still buffered by the trace exporter
+        crate::logging::shutdown();
+
+        result??;
         local_runtime.shutdown();
         Ok(())
     }
 }
 
 /// Catch signals and trigger a shutdown
-async fn signal_handler(cancel_token: CancellationToken) -> Result<()> {
+///
+/// If a component has enabled draining on the [`Runtime`], the first signal starts a graceful drain
+/// and the runtime is canceled once the drain completes or `drain_timeout` elapses. A second
+/// `Ctrl+C` skips the remainder of the drain.
+async fn signal_handler(runtime: Runtime, drain_timeout: Duration) -> Result<()> {
+    let cancel_token = runtime.primary_token();
+
     let ctrl_c = async {
         signal::ctrl_c().await?;
         anyhow::Ok(())
@@ -204,8 +222,105 @@ async fn signal_handler(cancel_token: CancellationToken) -> Result<()> {
         },
     }
 
+    drain_then_shutdown(&runtime, drain_timeout, async {
+        let _ = signal::ctrl_c().await;
+        tracing::info!("Ctrl+C received during drain, shutting down immediately");
+    })
+    .await;
+
+    Ok(())
+}
+
+/// Drain the [`Runtime`] if a component enabled draining, then cancel it. The drain is cut short
+/// after `drain_timeout` or once `abort` completes.
+async fn drain_then_shutdown(
+    runtime: &Runtime,
+    drain_timeout: Duration,
+    abort: impl Future<Output = ()>,
+) {
+    let cancel_token = runtime.primary_token();
+
+    if runtime.is_drain_enabled() && !cancel_token.is_cancelled() {
+        runtime.drain();
+
+        tokio::select! {
+            _ = cancel_token.cancelled() => {
+                tracing::debug!("Drain completed");
+            },
+            _ = tokio::time::sleep(drain_timeout) => {
+                tracing::warn!("Drain did not complete in {:?}; shutting down", drain_timeout);
+            },
+            _ = abort => {},
+        }
+    }
+
     // trigger a shutdown
     cancel_token.cancel();
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    #[tokio::test]
+    async fn test_signal_without_drain() {
+        let runtime = Runtime::from_current().unwrap();
+
+        drain_then_shutdown(&runtime, Duration::from_secs(60), std::future::pending()).await;
+
+        assert!(!runtime.drain_token().is_cancelled());
+        assert!(runtime.primary_token().is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_signal_waits_for_drain() {
+        let runtime = Runtime::from_current().unwrap();
+        assert!(runtime.enable_drain());
+
+        // stands in for the component which enabled draining
+        let drained = Arc::new(AtomicBool::new(false));
+        let drainer = {
+            let runtime = runtime.clone();
+            let drained = drained.clone();
+            tokio::spawn(async move {
+                runtime.drain_token().cancelled().await;
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                drained.store(true, Ordering::SeqCst);
+                runtime.shutdown();
+            })
+        };
+
+        drain_then_shutdown(&runtime, Duration::from_secs(60), std::future::pending()).await;
+
+        assert!(drained.load(Ordering::SeqCst));
+        assert!(runtime.primary_token().is_cancelled());
+        drainer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_signal_drain_timeout() {
+        let runtime = Runtime::from_current().unwrap();
+        assert!(runtime.enable_drain());
+
+        // nothing completes the drain
+        drain_then_shutdown(&runtime, Duration::from_millis(50), std::future::pending()).await;
+
+        assert!(runtime.drain_token().is_cancelled());
+        assert!(runtime.primary_token().is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_signal_aborts_drain() {
+        let runtime = Runtime::from_current().unwrap();
+        assert!(runtime.enable_drain());
+
+        drain_then_shutdown(&runtime, Duration::from_secs(60), std::future::ready(())).await;
+
+        assert!(runtime.drain_token().is_cancelled());
+        assert!(runtime.primary_token().is_cancelled());
+    }
 }
@@ -0,0 +1,219 @@
+// SPDX-FileCopyrightText: Copyright (c) 2024-2025 NVIDIA CORPORATION & AFFILIATES. All rights reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Work queue tests against a local JetStream enabled `nats-server`
+//!
+//! Each test launches its own server on a free port, using the binary named by `NATS_SERVER_BIN`
+//! or `nats-server` on the `PATH`; the tests are skipped if it cannot be launched.
+
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+use bytes::Bytes;
+use dynamo_runtime::pipeline::network::WorkQueueConsumer;
+use dynamo_runtime::transports::nats::{
+    Client, ClientOptions, NatsAuth, WorkQueue, WorkQueueOptions,
+};
+
+const QUEUE: &str = "test_work_queue";
+
+/// A `nats-server` killed, and its store removed, on drop
+struct NatsServer {
+    child: Child,
+    port: u16,
+    store_dir: PathBuf,
+}
+
+impl NatsServer {
+    fn launch() -> Option<Self> {
+        let binary = std::env::var("NATS_SERVER_BIN").unwrap_or_else(|_| "nats-server".into());
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .ok()?
+            .local_addr()
+            .ok()?
+            .port();
+        let store_dir = std::env::temp_dir().join(format!("nats-{}", uuid::Uuid::new_v4()));
+
+        let child = Command::new(binary)
+            .args(["-js", "-a", "127.0.0.1", "-p", &port.to_string()])
+            .args(["--user", "user", "--pass", "user"])
+            .arg("-sd")
+            .arg(&store_dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+
+        match child {
+            Ok(child) => Some(Self {
+                child,
+                port,
+                store_dir,
+            }),
+            Err(e) => {
+                println!("Failed to launch nats-server ({e}); skipping work queue tests");
+                None
+            }
+        }
+    }
+
+    /// Connect to the server, waiting for it to start
+    async fn connect(&self) -> Client {
+        for _ in 0..50 {
+            let client = ClientOptions::builder()
+                .server(format!("nats://127.0.0.1:{}", self.port))
+                .auth(NatsAuth::UserPass("user".into(), "user".into()))
+                .build()
+                .unwrap()
+                .connect()
+                .await;
+            if let Ok(client) = client {
+                return client;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("nats-server did not start on port {}", self.port);
+    }
+}
+
+impl Drop for NatsServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.store_dir);
+    }
+}
+
+async fn work_queue(client: &Client, ack_wait: Duration) -> WorkQueue {
+    let options = WorkQueueOptions::builder()
+        .ack_wait(ack_wait)
+        .dequeue_timeout(Duration::from_millis(500))
+        .build()
+        .unwrap();
+    client
+        .get_or_create_work_queue(QUEUE, options)
+        .await
+        .expect("failed to create work queue")
+}
+
+#[tokio::test]
+async fn test_enqueue_dequeue_ack() {
+    let Some(server) = NatsServer::launch() else {
+        return;
+    };
+    let client = server.connect().await;
+    let queue = work_queue(&client, Duration::from_secs(30)).await;
+
+    assert!(queue.dequeue().await.unwrap().is_none());
+
+    let first = queue.enqueue(Bytes::from_static(b"first")).await.unwrap();
+    assert!(queue
+        .enqueue_with_id("second", Bytes::from_static(b"second"))
+        .await
+        .unwrap());
+    // enqueueing under the same id again is dropped as a duplicate
+    assert!(!queue
+        .enqueue_with_id("second", Bytes::from_static(b"second"))
+        .await
+        .unwrap());
+    assert_eq!(queue.depth().await.unwrap(), 2);
+
+    let item = queue.dequeue().await.unwrap().expect("no item dequeued");
+    assert_eq!(item.id(), Some(first.as_str()));
+    assert_eq!(item.payload().as_ref(), b"first");
+    assert_eq!(item.delivered().unwrap(), 1);
+
+    let stats = queue.stats().await.unwrap();
+    assert_eq!(stats.pending, 1);
+    assert_eq!(stats.ack_pending, 1);
+
+    item.ack().await.unwrap();
+
+    // the trait consumer acknowledges the items it hands out
+    let payload = WorkQueueConsumer::dequeue(&queue).await.unwrap();
+    assert_eq!(payload.as_ref(), b"second");
+
+    let stats = queue.stats().await.unwrap();
+    assert_eq!(stats.pending, 0);
+    assert_eq!(stats.ack_pending, 0);
+
+    client.remove_work_queue(QUEUE).await.unwrap();
+    assert!(!client
+        .list_streams()
+        .await
+        .unwrap()
+        .contains(&QUEUE.to_string()));
+}
+
+#[tokio::test]
+async fn test_redelivery() {
+    let Some(server) = NatsServer::launch() else {
+        return;
+    };
+    let client = server.connect().await;
+    let queue = work_queue(&client, Duration::from_secs(1)).await;
+
+    let id = queue.enqueue(Bytes::from_static(b"work")).await.unwrap();
+
+    // a nak'd item is redelivered
+    let item = queue.dequeue().await.unwrap().expect("no item dequeued");
+    item.nak(None).await.unwrap();
+
+    let item = queue
+        .dequeue()
+        .await
+        .unwrap()
+        .expect("nak'd item not redelivered");
+    assert_eq!(item.id(), Some(id.as_str()));
+    assert_eq!(item.delivered().unwrap(), 2);
+
+    // an item left unacknowledged past the ack wait is redelivered
+    drop(item);
+    assert!(queue.dequeue().await.unwrap().is_none());
+
+    let item = queue
+        .dequeue_with_timeout(Duration::from_secs(3))
+        .await
+        .unwrap()
+        .expect("unacknowledged item not redelivered");
+    assert_eq!(item.id(), Some(id.as_str()));
+    assert_eq!(item.delivered().unwrap(), 3);
+    assert_eq!(queue.stats().await.unwrap().redelivered, 1);
+
+    item.ack().await.unwrap();
+    assert!(queue.dequeue().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_survives_client_restart() {
+    let Some(server) = NatsServer::launch() else {
+        return;
+    };
+
+    let client = server.connect().await;
+    let queue = work_queue(&client, Duration::from_secs(30)).await;
+    let id = queue.enqueue(Bytes::from_static(b"work")).await.unwrap();
+    drop(queue);
+    drop(client);
+
+    // a new worker attaches to the existing queue and finds the item
+    let client = server.connect().await;
+    let queue = work_queue(&client, Duration::from_secs(30)).await;
+    assert_eq!(queue.depth().await.unwrap(), 1);
+
+    let item = queue.dequeue().await.unwrap().expect("item lost");
+    assert_eq!(item.id(), Some(id.as_str()));
+    item.ack().await.unwrap();
+}